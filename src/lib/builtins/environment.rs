@@ -0,0 +1,90 @@
+use super::{command_info::get_command_info, man_pages::{print_man, MAN_ENV}};
+use parser::pipelines::{PipeItem, Pipeline};
+use shell::{pipe_exec::PipelineExecution, status::*, Job, JobKind, Shell};
+use std::{env, ffi::OsString};
+use types::{Identifier, Value};
+
+/// Executes a command with a modified environment, or prints the environment that would be
+/// given to a command if none was supplied. Any changes are reverted once the command exits.
+pub(crate) fn env_(args: &[String], shell: &mut Shell) -> i32 {
+    let mut clear = false;
+    let mut unset = Vec::new();
+    let mut args = args[1..].iter().peekable();
+
+    while let Some(&arg) = args.peek() {
+        match arg.as_str() {
+            "-i" | "--ignore-environment" | "-" => clear = true,
+            "-u" | "--unset" => {
+                args.next();
+                match args.peek() {
+                    Some(&name) => unset.push(name.as_str()),
+                    None => {
+                        eprintln!("ion: env: {} requires a variable name", arg);
+                        return BAD_ARG;
+                    }
+                }
+            }
+            "-h" | "--help" => {
+                print_man(MAN_ENV);
+                return SUCCESS;
+            }
+            "--" => {
+                args.next();
+                break;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("ion: env: invalid option: {}", arg);
+                return BAD_ARG;
+            }
+            _ => break,
+        }
+        args.next();
+    }
+
+    let mut assignments: Vec<(Identifier, Value)> = Vec::new();
+    while let Some(pos) = args.peek().and_then(|arg| arg.find('=')) {
+        let arg = args.next().unwrap();
+        assignments.push((arg[..pos].into(), arg[pos + 1..].into()));
+    }
+
+    let mut command = args.cloned().collect::<Vec<String>>();
+    // Programs must be located before the environment, and therefore `PATH`, is cleared.
+    if clear && !command.is_empty() && !command[0].contains('/') {
+        if let Ok(path) = get_command_info(&command[0], shell) {
+            if path.contains('/') {
+                command[0] = path.into_owned();
+            }
+        }
+    }
+
+    let original = env::vars_os().collect::<Vec<(OsString, OsString)>>();
+    if clear {
+        for &(ref name, _) in &original {
+            env::remove_var(name);
+        }
+    }
+    for name in unset {
+        env::remove_var(name);
+    }
+    let temporaries = shell.variables.assign_temporarily(&assignments);
+
+    let status = if command.is_empty() {
+        for (name, value) in env::vars_os() {
+            println!("{}={}", name.to_string_lossy(), value.to_string_lossy());
+        }
+        SUCCESS
+    } else {
+        let job = Job::new(command.into_iter().collect(), JobKind::Last);
+        let mut pipeline = Pipeline::new();
+        pipeline.items.push(PipeItem::new(job, Vec::new(), Vec::new(), Vec::new()));
+        shell.execute_pipeline(&mut pipeline)
+    };
+
+    shell.variables.restore(temporaries);
+    for (name, value) in original {
+        if env::var_os(&name).is_none() {
+            env::set_var(name, value);
+        }
+    }
+    status
+}
@@ -2,6 +2,8 @@
 use smallstring::SmallString;
 #[cfg(test)]
 use smallvec::SmallVec;
+#[cfg(test)]
+use types::{BTreeMap, Map};
 use std::{fs, os::unix::fs::PermissionsExt};
 
 #[cfg(test)]
@@ -27,6 +29,12 @@ fn evaluate_arguments(arguments: &[String], shell: &Shell) -> Result<bool, Strin
                 Ok(match_option_argument(option, arg, shell))
             })
         }
+        Some(ref s) if s.as_str() == "-m" => {
+            // A map may be followed by the key that it should contain.
+            arguments.get(1).map_or(Ok(true), |map| {
+                Ok(map_contains_key(map, arguments.get(2).map(String::as_str), shell))
+            })
+        }
         Some(ref s) if s.starts_with("-") => {
             // Access the second character in the flag string: this will be type of the
             // flag. If no flag was given, return `SUCCESS`, as this means a
@@ -129,6 +137,15 @@ fn array_var_is_not_empty(arrayvar: &str, shell: &Shell) -> bool {
     }
 }
 
+/// Returns true if the variable is a map which contains the key, or which is not empty if no
+/// key was given
+fn map_contains_key(mapvar: &str, key: Option<&str>, shell: &Shell) -> bool {
+    match shell.variables.get_any_map(mapvar) {
+        Some(map) => key.map_or(!map.is_empty(), |key| map.get(key).is_some()),
+        None => false,
+    }
+}
+
 /// Returns true if the variable is a string and the string is not empty
 fn string_var_is_not_empty(stringvar: &str, shell: &Shell) -> bool {
     match shell.get_var(stringvar) {
@@ -177,6 +194,28 @@ fn test_evaluate_arguments() {
     shell.variables.unset_array("array");
     assert_eq!(evaluate_arguments(&["-a".to_owned(), "array".to_owned()], &shell), Ok(false));
 
+    // check `exists -m`
+    // no argument means we treat it as a string
+    assert_eq!(evaluate_arguments(&["-m".to_owned()], &shell), Ok(true));
+    let mut map = BTreeMap::new();
+    shell.variables.set_map("emptymap", Map::BTree(map.clone()));
+    assert_eq!(evaluate_arguments(&["-m".to_owned(), "emptymap".to_owned()], &shell), Ok(false));
+    map.insert("key".into(), "value".into());
+    shell.variables.set_map("map", Map::BTree(map));
+    assert_eq!(evaluate_arguments(&["-m".to_owned(), "map".to_owned()], &shell), Ok(true));
+    assert_eq!(
+        evaluate_arguments(&["-m".to_owned(), "map".to_owned(), "key".to_owned()], &shell),
+        Ok(true)
+    );
+    assert_eq!(
+        evaluate_arguments(&["-m".to_owned(), "map".to_owned(), "other".to_owned()], &shell),
+        Ok(false)
+    );
+    assert_eq!(
+        evaluate_arguments(&["-m".to_owned(), "array".to_owned(), "key".to_owned()], &shell),
+        Ok(false)
+    );
+
     // check `exists -b`
     // TODO: see test_binary_is_in_path()
     // no argument means we treat it as a string
@@ -249,12 +288,12 @@ fn test_evaluate_arguments() {
         kind: Primitive::Any,
     });
     let mut statements = Vec::new();
-    statements.push(Statement::End);
+    statements.push(Statement::End(None));
     let description = "description".to_owned();
 
     shell.functions.insert(
         name.clone(),
-        Function::new(Some(description), name, args, statements),
+        Function::new(Some(description), name, args, statements, None),
     );
 
     assert_eq!(evaluate_arguments(&["--fn".to_owned(), name_str.to_owned()], &shell), Ok(true));
@@ -419,12 +458,12 @@ fn test_function_is_defined() {
         kind: Primitive::Any,
     });
     let mut statements = Vec::new();
-    statements.push(Statement::End);
+    statements.push(Statement::End(None));
     let description = "description".to_owned();
 
     shell.functions.insert(
         name.clone(),
-        Function::new(Some(description), name, args, statements),
+        Function::new(Some(description), name, args, statements, None),
     );
 
     assert_eq!(function_is_defined(name_str, &shell), true);
@@ -0,0 +1,189 @@
+use json::Json;
+use shell::{status::*, variables::Variables, Shell};
+use std::io::{self, Read};
+use types::{Array, Map, Nested, OrderedMap, Value};
+
+/// Parses a JSON document into a variable with `-p`, or otherwise serializes a variable as a
+/// JSON document.
+pub(crate) fn json(args: &[String], shell: &mut Shell) -> i32 {
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("-p") | Some("--parse") if args.len() > 2 => parse(&args[2], &args[3..], shell),
+        Some(name) if args.len() == 2 && !name.starts_with('-') => serialize(name, shell),
+        _ => {
+            eprintln!("ion: json: expected either '-p NAME [DOCUMENT]' or 'NAME'");
+            BAD_ARG
+        }
+    }
+}
+
+/// The document is read from the standard input if it is not supplied as arguments.
+fn parse(name: &str, document: &[String], shell: &mut Shell) -> i32 {
+    let document = if document.is_empty() {
+        let mut document = String::new();
+        if let Err(why) = io::stdin().read_to_string(&mut document) {
+            eprintln!("ion: json: unable to read the standard input: {}", why);
+            return FAILURE;
+        }
+        document
+    } else {
+        document.join(" ")
+    };
+
+    match Json::parse(&document) {
+        Ok(json) => {
+            assign(shell, name, json);
+            SUCCESS
+        }
+        Err(why) => {
+            eprintln!("ion: json: {}: {}", name, why);
+            FAILURE
+        }
+    }
+}
+
+fn is_scalar(json: &Json) -> bool {
+    match *json {
+        Json::Array(_) | Json::Object(_) => false,
+        _ => true,
+    }
+}
+
+/// Objects are assigned as an `omap`, so that their members retain the order in which they
+/// were written. Only arrays and objects which contain other arrays or objects are nested.
+/// Whichever kind of variable previously had the name is replaced.
+fn assign(shell: &mut Shell, name: &str, json: Json) {
+    shell.variables.unset_all(name);
+    match json {
+        Json::Array(ref values) if values.iter().all(is_scalar) => {
+            let array = values.iter().cloned().map(scalar).collect::<Array>();
+            shell.variables.set_array(name, array);
+        }
+        Json::Object(ref members) if members.iter().all(|&(_, ref value)| is_scalar(value)) => {
+            let mut map = OrderedMap::new();
+            for &(ref key, ref value) in members {
+                map.insert(key.as_str().into(), scalar(value.clone()));
+            }
+            shell.variables.set_map(name, Map::Ordered(map));
+        }
+        Json::Array(_) | Json::Object(_) => shell.variables.set_nested(name, nested(json)),
+        json => shell.set_var(name, &scalar(json)),
+    }
+}
+
+/// A `null` is converted into an empty string, and is therefore serialized as `""`.
+fn scalar(json: Json) -> Value {
+    match json {
+        Json::Null => Value::new(),
+        Json::Bool(boolean) => boolean.to_string(),
+        Json::Number(number) => number,
+        Json::String(string) => string,
+        json => nested(json).to_string(),
+    }
+}
+
+fn nested(json: Json) -> Nested {
+    match json {
+        Json::Array(values) => Nested::Array(values.into_iter().map(nested).collect()),
+        Json::Object(members) => {
+            let mut map = OrderedMap::new();
+            for (key, value) in members {
+                map.insert(key.as_str().into(), nested(value));
+            }
+            Nested::Map(Map::Ordered(map))
+        }
+        json => Nested::Str(scalar(json)),
+    }
+}
+
+fn serialize(name: &str, shell: &Shell) -> i32 {
+    match variable_to_json(name, &shell.variables) {
+        Some(json) => {
+            println!("{}", json);
+            SUCCESS
+        }
+        None => {
+            eprintln!("ion: json: {}: no such variable", name);
+            FAILURE
+        }
+    }
+}
+
+fn variable_to_json(name: &str, variables: &Variables) -> Option<Json> {
+    if let Some(array) = variables.get_array(name) {
+        Some(Json::Array(array.iter().map(|value| value_to_json(value)).collect()))
+    } else if let Some(map) = variables.get_any_map(name) {
+        Some(Json::Object(
+            map.iter()
+                .map(|(key, value)| (key.to_string(), value_to_json(value)))
+                .collect(),
+        ))
+    } else if let Some(nested) = variables.get_nested(name) {
+        Some(nested_to_json(nested))
+    } else {
+        variables.get_var(name).map(|value| value_to_json(&value))
+    }
+}
+
+/// As the types of variables are not retained, values which are written as numbers or booleans
+/// are serialized as such, and every other value is serialized as a string.
+fn value_to_json(value: &str) -> Json {
+    match value {
+        "true" => Json::Bool(true),
+        "false" => Json::Bool(false),
+        _ => match Json::parse(value) {
+            Ok(Json::Number(ref number)) if number == value => Json::Number(value.into()),
+            _ => value.into(),
+        },
+    }
+}
+
+fn nested_to_json(nested: &Nested) -> Json {
+    match *nested {
+        Nested::Str(ref value) => value_to_json(value),
+        Nested::Array(ref values) => Json::Array(values.iter().map(nested_to_json).collect()),
+        Nested::Map(ref map) => Json::Object(
+            map.iter()
+                .map(|(key, value)| (key.to_string(), nested_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell::ShellBuilder;
+
+    #[test]
+    fn values() {
+        assert_eq!(value_to_json("-2.5e3"), Json::Number("-2.5e3".into()));
+        assert_eq!(value_to_json("true"), Json::Bool(true));
+        assert_eq!(value_to_json("007"), Json::String("007".into()));
+        assert_eq!(value_to_json(" 1"), Json::String(" 1".into()));
+        assert_eq!(value_to_json(""), Json::String("".into()));
+    }
+
+    #[test]
+    fn round_trip() {
+        let document = "[{\"name\":\"web\",\"ports\":[80,443],\"tls\":true},{\"name\":\"db\"}]";
+        let value = nested(Json::parse(document).unwrap());
+        assert_eq!(value.to_string(), "[[name=web ports=[80 443] tls=true] [name=db]]");
+        assert_eq!(nested_to_json(&value).to_string(), document);
+        assert_eq!(scalar(Json::Null), "");
+    }
+
+    #[test]
+    fn reassignment() {
+        let mut shell = ShellBuilder::new().as_library();
+        let output = |shell: &mut Shell, document: &str| {
+            assign(shell, "x", Json::parse(document).unwrap());
+            variable_to_json("x", &shell.variables).unwrap().to_string()
+        };
+        assert_eq!(output(&mut shell, "[1,2]"), "[1,2]");
+        assert_eq!(output(&mut shell, "{\"a\":1}"), "{\"a\":1}");
+        assert_eq!(output(&mut shell, "true"), "true");
+        assert_eq!(output(&mut shell, "[[1],[2]]"), "[[1],[2]]");
+        assert_eq!(output(&mut shell, "[3]"), "[3]");
+        assert_eq!(output(&mut shell, "null"), "\"\"");
+    }
+}
@@ -24,6 +24,47 @@ pub(crate) fn check_help(args: &[String], man_page: &'static str) -> bool {
     false
 }
 
+/// Returns the manual page of a builtin, if it has one.
+pub(crate) fn man_page(builtin: &str) -> Option<&'static str> {
+    let page = match builtin {
+        "bg" => MAN_BG,
+        "bool" => MAN_BOOL,
+        "cd" => MAN_CD,
+        "dirs" => MAN_DIRS,
+        "disown" => MAN_DISOWN,
+        "drop" => MAN_DROP,
+        "echo" => MAN_ECHO,
+        "env" => MAN_ENV,
+        "eq" => MAN_EQ,
+        "eval" => MAN_EVAL,
+        "exec" => MAN_EXEC,
+        "exists" => MAN_EXISTS,
+        "exit" => MAN_EXIT,
+        "false" => MAN_FALSE,
+        "fg" => MAN_FG,
+        "history" => MAN_HISTORY,
+        "is" => MAN_IS,
+        "isatty" => MAN_ISATTY,
+        "jobs" => MAN_JOBS,
+        "json" => MAN_JSON,
+        "matches" => MAN_MATCHES,
+        "popd" => MAN_POPD,
+        "pushd" => MAN_PUSHD,
+        "random" => MAN_RANDOM,
+        "read" => MAN_READ,
+        "set" => MAN_SET,
+        "source" => MAN_SOURCE,
+        "status" => MAN_STATUS,
+        "suspend" => MAN_SUSPEND,
+        "test" => MAN_TEST,
+        "trap" => MAN_TRAP,
+        "true" => MAN_TRUE,
+        "which" => MAN_WHICH,
+        _ => return None,
+    };
+    Some(page)
+}
+
 pub(crate) const MAN_STATUS: &'static str = r#"NAME
     status - Evaluates the current runtime status
 
@@ -136,7 +177,7 @@ DESCRIPTION
 // hints, to tell ion to check the type of a functions arguments. An error will
 // occur if an argument supplied to a function is of the wrong type.
 // The supported types in ion are, [], bool, bool[], float, float[], int,
-// int[], str, str[].
+// int[], str, str[], hmap[T], bmap[T], omap[T].
 //
 // Functions are called by typing the function name and then the function
 // arguments, separated by a space.
@@ -162,22 +203,27 @@ pub(crate) const MAN_DROP: &'static str = r#"NAME
     drop - delete some variables or arrays
 
 SYNOPSIS
-    drop [ -a ] VARIABLES...
+    drop [ -a | -m ] VARIABLES...
 
 DESCRIPTION
     Deletes the variables given to it as arguments. The variables name must be supplied.
-    Instead of '$x' use 'x'.
+    Instead of '$x' use 'x'. Within a function, only the variables of that function are
+    deleted, unless the name is prefixed with 'super::' or 'global::'.
 
 OPTIONS
     -a
-        Instead of deleting variables deletes arrays.
+        Instead of deleting variables deletes arrays, including nested arrays.
+
+    -m
+        Instead of deleting variables deletes maps of any kind, including nested maps.
 "#;
 
 pub(crate) const MAN_SET: &'static str = r#"NAME
     set - Set or unset values of shell options and positional parameters.
 
 SYNOPSIS
-    set [ --help ] [-e | +e] [-x | +x] [-o [vi | emacs]] [- | --] [STRING]...
+    set [ --help ] [-e | +e] [-u | +u] [-x | +x] [-o [vi | emacs | huponexit | pipefail | nounset]]
+        [- | --] [STRING]...
 
 DESCRIPTION
     Shell options may be set using the '-' character, and unset using the '+' character.
@@ -185,10 +231,18 @@ DESCRIPTION
 OPTIONS
     -e  Exit immediately if a command exits with a non-zero status.
 
-    -o  Specifies that an argument will follow that sets the key map.
+    -o  Specifies that an argument will follow that sets the key map, or an option.
         The keymap argument may be either `vi` or `emacs`.
+        The `huponexit` option sends a SIGHUP to background jobs when the shell exits.
+        The `pipefail` option gives a pipeline the status of the last command within it
+        that failed, rather than the status of its last command.
+        The `nounset` option is equivalent to `-u`.
 
-    -x  Specifies that commands will be printed as they are executed.
+    -u  Treat references to unset variables as errors, which abort the statement.
+
+    -x  Specifies that commands will be printed as they are executed, along with their location.
+        Each line is preceded by the value of `TRACE_PROMPT`, and is written to the
+        descriptor in `TRACE_FD`, or to standard error.
 
     --  Following arguments will be set as positional arguments in the shell.
         If no argument are supplied, arguments will be unset.
@@ -222,6 +276,25 @@ DESCRIPTION
     all arguments are joined using a space as a separator.
 "#;
 
+pub(crate) const MAN_ENV: &'static str = r#"NAME
+    env - run a command with a modified environment
+
+SYNOPSIS
+    env [-i] [-u NAME]... [NAME=VALUE]... [COMMAND [ARGUMENTS...]]
+
+DESCRIPTION
+    Executes COMMAND, which may be a builtin, a function or an external program, with each
+    NAME assigned to VALUE. The environment of the shell is restored once the command has
+    exited. If no command is given, the resulting environment is printed instead.
+
+OPTIONS
+    -i, --ignore-environment
+        Start with an empty environment.
+
+    -u, --unset NAME
+        Remove NAME from the environment.
+"#;
+
 pub(crate) const MAN_EXEC: &'static str = r#"NAME
     exec - Replace the shell with the given command.
 
@@ -407,6 +480,32 @@ DESCRIPTION
     If two arguments are given the range is [START, END].
 "#;
 
+pub(crate) const MAN_TRAP: &'static str = r#"NAME
+    trap - execute a command when the shell receives a signal or exits
+
+SYNOPSIS
+    trap [-l | -p] [COMMAND | -] [CONDITION...]
+
+DESCRIPTION
+    Binds COMMAND, which is typically the name of a function, to each CONDITION. A condition
+    may be a signal name, with or without the SIG prefix, a signal number, or one of the
+    following pseudo-signals:
+
+        EXIT    Executed just before the shell exits
+        ERR     Executed whenever a pipeline returns a non-zero exit status
+
+    The exit status of the shell is preserved across the execution of a trap. If COMMAND is
+    an empty string, the signal will be ignored. If COMMAND is '-', or omitted, the trap for
+    each CONDITION is removed. With no arguments, all traps which are set will be listed.
+
+OPTIONS
+    -l
+        List the conditions that may be trapped.
+
+    -p
+        List the traps which are set.
+"#;
+
 pub(crate) const MAN_TRUE: &'static str = r#"NAME
     true - does nothing successfully
 
@@ -437,6 +536,35 @@ DESCRIPTION
     Prints a list of all jobs running in the background.
 "#;
 
+pub(crate) const MAN_JSON: &'static str = r#"NAME
+    json - parse and serialize JSON documents
+
+SYNOPSIS
+    json -p NAME [DOCUMENT...]
+    json NAME
+
+DESCRIPTION
+    With -p, parses the JSON DOCUMENT, or the standard input if no DOCUMENT is given, and
+    assigns its value to the variable NAME. Objects are assigned as omap maps, arrays as
+    arrays, and those which contain other arrays or objects as nested variables. Booleans and
+    numbers are assigned as they are written, and null as an empty string, which is serialized
+    as "" rather than null. Any variable of another kind which has the same NAME is replaced.
+    Should the document be invalid, the line and column at which the error was found are
+    reported.
+
+    Otherwise, the variable NAME is written to the standard output as a JSON document. Values
+    which are written as numbers, or as true or false, are serialized as numbers and booleans.
+
+OPTIONS
+    -p, --parse
+        Parse a document into the variable NAME.
+
+EXAMPLES
+    json -p hosts < hosts.json
+    echo $hosts[0][name]
+    json hosts
+"#;
+
 pub(crate) const MAN_BG: &'static str = r#"NAME
     bg - sends jobs to background
 
@@ -537,6 +665,9 @@ OPTIONS
     --fn FUNCTION
         function is defined
 
+    -m MAP [KEY]
+        map var contains the key, or is not empty if no key is given
+
     -s STRING
         string var is not empty
 
@@ -559,6 +690,10 @@ EXAMPLES
         exists -a myArr && echo "myArr exists: @myArr" || echo "myArr does not exist or is empty"
         NOTE: Don't use the '@' sigil, but only the name of the array to check
 
+    Test if a map contains a key
+        exists -m myMap key && echo "key is @myMap[key]" || echo "myMap has no key"
+        NOTE: Don't use the '@' sigil, but only the name of the map to check
+
     Test if a function named 'myFunc' exists
         exists --fn myFunc && myFunc || echo "No function with name myFunc found"
 
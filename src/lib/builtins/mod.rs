@@ -7,26 +7,32 @@ pub mod variables;
 mod command_info;
 mod conditionals;
 mod echo;
+mod environment;
 mod exec;
 mod exists;
 mod ion;
 mod is;
 mod job_control;
+mod json;
 mod man_pages;
 mod set;
 mod status;
 mod test;
+mod trap;
 
 use self::{
-    command_info::*, conditionals::{contains, ends_with, starts_with}, echo::echo, exec::exec,
-    exists::exists, functions::fn_, ion::ion_docs, is::is, man_pages::*, source::source,
-    status::status, test::test, variables::{alias, drop_alias, drop_array, drop_variable},
+    command_info::*, conditionals::{contains, ends_with, starts_with}, echo::echo,
+    environment::env_, exec::exec, exists::exists, functions::fn_, ion::ion_docs, is::is,
+    json::json, man_pages::*, source::source, status::status, test::test, trap::trap,
+    variables::{alias, drop_alias, drop_array, drop_map, drop_variable},
 };
 
 use std::{
     env, error::Error, io::{self, Write},
 };
 
+pub(crate) use self::man_pages::man_page;
+
 use parser::Terminator;
 use shell::{
     self, fork_function::fork_function, job_control::{JobControl, ProcessState}, status::*,
@@ -73,6 +79,7 @@ pub const BUILTINS: &'static BuiltinMap = &map!(
     "drop" => builtin_drop : "Delete a variable",
     "echo" => builtin_echo : "Display a line of text",
     "ends-with" => ends_with : "Evaluates if the supplied argument ends with a given string",
+    "env" => builtin_env : "Run a command with a modified environment",
     "eq" => builtin_eq : "Simple alternative to == and !=",
     "eval" => builtin_eval : "Evaluates the evaluated expression",
     "exec" => builtin_exec : "Replace the shell with the given command.",
@@ -87,6 +94,7 @@ pub const BUILTINS: &'static BuiltinMap = &map!(
     "is" => builtin_is : "Simple alternative to == and !=",
     "isatty" => builtin_isatty : "Returns 0 exit status if the supplied FD is a tty",
     "jobs" => builtin_jobs : "Displays all jobs that are attached to the background",
+    "json" => builtin_json : "Parses JSON into a variable, or serializes a variable as JSON",
     "matches" => builtin_matches : "Checks if a string matches a given regex",
     "popd" => builtin_popd : "Pop a directory from the stack",
     "pushd" => builtin_pushd : "Push a directory to the stack",
@@ -98,6 +106,7 @@ pub const BUILTINS: &'static BuiltinMap = &map!(
     "status" => builtin_status : "Evaluates the current runtime status",
     "suspend" => builtin_suspend : "Suspends the shell with a SIGTSTOP signal",
     "test" => builtin_test : "Performs tests on files and text",
+    "trap" => builtin_trap : "Execute a command when a signal is received or the shell exits",
     "true" => builtin_true : "Do nothing, successfully",
     "type" => builtin_type : "indicates how a command would be interpreted",
     "unalias" => builtin_unalias : "Delete an alias",
@@ -287,6 +296,8 @@ fn builtin_drop(args: &[String], shell: &mut Shell) -> i32 {
     }
     if args.len() >= 2 && args[1] == "-a" {
         drop_array(&mut shell.variables, args)
+    } else if args.len() >= 2 && args[1] == "-m" {
+        drop_map(&mut shell.variables, args)
     } else {
         drop_variable(&mut shell.variables, args)
     }
@@ -330,6 +341,9 @@ fn builtin_eval(args: &[String], shell: &mut Shell) -> i32 {
     }
 }
 
+// Help is handled by `env_`, as the arguments of the command it executes may contain `--help`.
+fn builtin_env(args: &[String], shell: &mut Shell) -> i32 { env_(args, shell) }
+
 fn builtin_history(args: &[String], shell: &mut Shell) -> i32 {
     if check_help(args, MAN_HISTORY) {
         return SUCCESS;
@@ -404,6 +418,20 @@ fn builtin_random(args: &[String], _: &mut Shell) -> i32 {
     }
 }
 
+fn builtin_json(args: &[String], shell: &mut Shell) -> i32 {
+    if check_help(args, MAN_JSON) {
+        return SUCCESS;
+    }
+    json(args, shell)
+}
+
+fn builtin_trap(args: &[String], shell: &mut Shell) -> i32 {
+    if check_help(args, MAN_TRAP) {
+        return SUCCESS;
+    }
+    trap(args, shell)
+}
+
 fn builtin_true(args: &[String], _: &mut Shell) -> i32 {
     check_help(args, MAN_TRUE);
     SUCCESS
@@ -36,6 +36,8 @@ pub(crate) fn set(args: &[String], shell: &mut Shell) -> i32 {
                             context.key_bindings = KeyBindings::Emacs;
                         },
                         Some("huponexit") => shell.flags |= HUPONEXIT,
+                        Some("pipefail") => shell.flags |= PIPE_FAIL,
+                        Some("nounset") => shell.flags |= NO_UNSET,
                         Some(_) => {
                             eprintln!("ion: set: invalid option");
                             return 0;
@@ -45,6 +47,7 @@ pub(crate) fn set(args: &[String], shell: &mut Shell) -> i32 {
                             return 0;
                         }
                     },
+                    b'u' => shell.flags |= NO_UNSET,
                     b'x' => shell.flags |= PRINT_COMMS,
                     _ => return 0,
                 }
@@ -53,9 +56,12 @@ pub(crate) fn set(args: &[String], shell: &mut Shell) -> i32 {
             for flag in arg.bytes().skip(1) {
                 match flag {
                     b'e' => shell.flags &= 255 ^ ERR_EXIT,
+                    b'u' => shell.flags &= 255 ^ NO_UNSET,
                     b'x' => shell.flags &= 255 ^ PRINT_COMMS,
                     b'o' => match args_iter.next().map(|s| s as &str) {
                         Some("huponexit") => shell.flags &= 255 ^ HUPONEXIT,
+                        Some("pipefail") => shell.flags &= 255 ^ PIPE_FAIL,
+                        Some("nounset") => shell.flags &= 255 ^ NO_UNSET,
                         Some(_) => {
                             eprintln!("ion: set: invalid option");
                             return 0;
@@ -92,3 +98,38 @@ pub(crate) fn set(args: &[String], shell: &mut Shell) -> i32 {
 
     0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell::{status::FAILURE, ShellBuilder};
+
+    fn args(args: &[&str]) -> Vec<String> { args.iter().map(|&arg| arg.to_owned()).collect() }
+
+    #[test]
+    fn pipefail() {
+        let mut shell = ShellBuilder::new().as_library();
+        set(&args(&["-o", "pipefail"]), &mut shell);
+        assert_eq!(PIPE_FAIL, shell.flags & PIPE_FAIL);
+        set(&args(&["+o", "pipefail"]), &mut shell);
+        assert_eq!(0, shell.flags & PIPE_FAIL);
+    }
+
+    #[test]
+    fn nounset() {
+        let mut shell = ShellBuilder::new().as_library();
+        set(&args(&["-u"]), &mut shell);
+        assert_eq!(NO_UNSET, shell.flags & NO_UNSET);
+
+        // The statement which referenced the variable is aborted, along with those after it.
+        shell.execute_command("let x = $undefined; let y = 1").unwrap();
+        assert_eq!(FAILURE, shell.previous_status);
+        assert_eq!(None, shell.variables.get_var("x"));
+        assert_eq!(None, shell.variables.get_var("y"));
+
+        set(&args(&["+o", "nounset"]), &mut shell);
+        assert_eq!(0, shell.flags & NO_UNSET);
+        shell.execute_command("let x = $undefined").unwrap();
+        assert_eq!(Some("".into()), shell.variables.get_var("x"));
+    }
+}
@@ -1,5 +1,5 @@
 use shell::{FlowLogic, Shell};
-use std::{fs::File, io::Read};
+use std::{fs::File, io::Read, mem};
 
 /// Evaluates the given file and returns 'SUCCESS' if it succeeds.
 pub(crate) fn source(shell: &mut Shell, arguments: &[String]) -> Result<(), String> {
@@ -10,10 +10,14 @@ pub(crate) fn source(shell: &mut Shell, arguments: &[String]) -> Result<(), Stri
             file.read_to_string(&mut command_list)
                 .map_err(|message| format!("ion: {}: failed to read {}\n", message, argument))
                 .map(|_| {
-                    for command in command_list.lines() {
+                    let script = mem::replace(&mut shell.script, Some(argument.clone()));
+                    let line = shell.line;
+                    for (command, number) in command_list.lines().zip(1..) {
+                        shell.line = number;
                         shell.on_command(command);
                     }
-                    ()
+                    shell.script = script;
+                    shell.line = line;
                 })
         } else {
             Err(format!("ion: failed to open {}\n", argument))
@@ -0,0 +1,84 @@
+use shell::{signals::Trap, status::*, Shell};
+
+/// Binds commands to signals, and the `EXIT` and `ERR` conditions, to be executed when they
+/// are triggered. With no arguments, the currently-set traps are listed.
+pub(crate) fn trap(args: &[String], shell: &mut Shell) -> i32 {
+    let args = &args[1..];
+    match args.first().map(|s| s.as_str()) {
+        None | Some("-p") => {
+            let mut traps = shell.traps.iter().collect::<Vec<_>>();
+            traps.sort_by_key(|&(trap, _)| trap.name());
+            for (trap, command) in traps {
+                println!("trap '{}' {}", command, trap.name());
+            }
+            SUCCESS
+        }
+        Some("-l") => {
+            println!("EXIT ERR SIGHUP SIGINT SIGTERM");
+            SUCCESS
+        }
+        // A single condition resets that condition's trap.
+        Some("-") => remove(&args[1..], shell),
+        Some(_) if args.len() == 1 => remove(args, shell),
+        Some(command) => {
+            let traps = match parse_conditions(&args[1..]) {
+                Ok(traps) => traps,
+                Err(why) => {
+                    eprintln!("{}", why);
+                    return BAD_ARG;
+                }
+            };
+            for trap in traps {
+                shell.traps.insert(trap, command.to_owned());
+            }
+            SUCCESS
+        }
+    }
+}
+
+fn remove(conditions: &[String], shell: &mut Shell) -> i32 {
+    match parse_conditions(conditions) {
+        Ok(traps) => {
+            for trap in traps {
+                shell.traps.remove(&trap);
+            }
+            SUCCESS
+        }
+        Err(why) => {
+            eprintln!("{}", why);
+            BAD_ARG
+        }
+    }
+}
+
+fn parse_conditions(conditions: &[String]) -> Result<Vec<Trap>, String> {
+    if conditions.is_empty() {
+        return Err("ion: trap: a signal name is required".into());
+    }
+
+    conditions
+        .iter()
+        .map(|condition| {
+            Trap::parse(condition)
+                .ok_or_else(|| format!("ion: trap: {}: invalid signal specification", condition))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions() {
+        let conditions = |args: &[&str]| {
+            parse_conditions(&args.iter().map(|&arg| arg.to_owned()).collect::<Vec<String>>())
+        };
+        assert_eq!(Err("ion: trap: a signal name is required".into()), conditions(&[]));
+        assert_eq!(
+            Err("ion: trap: SIGFOO: invalid signal specification".into()),
+            conditions(&["EXIT", "SIGFOO"])
+        );
+        assert_eq!(Ok(2), conditions(&["EXIT", "INT"]).map(|traps| traps.len()));
+    }
+}
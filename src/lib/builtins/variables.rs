@@ -161,8 +161,39 @@ pub(crate) fn drop_array<S: AsRef<str>>(vars: &mut Variables, args: &[S]) -> i32
     }
 
     for array in args.iter().skip(2) {
-        if vars.unset_array(array.as_ref()).is_none() {
-            eprintln!("ion: undefined array: {}", array.as_ref());
+        let array = array.as_ref();
+        let is_array = |nested: &Nested| match *nested {
+            Nested::Array(_) => true,
+            _ => false,
+        };
+        if vars.unset_array(array).is_none() && vars.unset_nested_if(array, is_array).is_none() {
+            eprintln!("ion: undefined array: {}", array);
+            return FAILURE;
+        }
+    }
+    SUCCESS
+}
+
+/// Dropping a map, of any kind, will erase it from the shell.
+pub(crate) fn drop_map<S: AsRef<str>>(vars: &mut Variables, args: &[S]) -> i32 {
+    if args.len() <= 2 {
+        eprintln!("ion: you must specify a map name");
+        return FAILURE;
+    }
+
+    if args[1].as_ref() != "-m" {
+        eprintln!("ion: drop_map must be used with -m option");
+        return FAILURE;
+    }
+
+    for map in args.iter().skip(2) {
+        let map = map.as_ref();
+        let is_map = |nested: &Nested| match *nested {
+            Nested::Map(_) => true,
+            _ => false,
+        };
+        if vars.unset_map(map).is_none() && vars.unset_nested_if(map, is_map).is_none() {
+            eprintln!("ion: undefined map: {}", map);
             return FAILURE;
         }
     }
@@ -256,6 +287,33 @@ mod test {
         assert_eq!("", expanded);
     }
 
+    #[test]
+    fn drop_deletes_nested_array() {
+        let mut variables = Variables::default();
+        variables.set_nested("FOO", Nested::Array(vec![Nested::Str("BAR".into())]));
+        assert_eq!(SUCCESS, drop_array(&mut variables, &["drop", "-a", "FOO"]));
+        assert_eq!(None, variables.get_nested("FOO"));
+    }
+
+    #[test]
+    fn drop_deletes_maps() {
+        let mut variables = Variables::default();
+        variables.set_hashmap_value("FOO", "key", "value");
+        variables.set_map("BAR", Map::Ordered(OrderedMap::new()));
+        variables.set_nested("BAZ", Nested::Map(Map::Hash(HashMap::default())));
+        variables.set_nested("QUX", Nested::Array(Vec::new()));
+        let return_status = drop_map(&mut variables, &["drop", "-m", "FOO", "BAR", "BAZ"]);
+        assert_eq!(SUCCESS, return_status);
+        assert_eq!(None, variables.get_any_map("FOO"));
+        assert_eq!(None, variables.get_any_map("BAR"));
+        assert_eq!(None, variables.get_nested("BAZ"));
+
+        // Arrays are not maps.
+        assert_eq!(FAILURE, drop_map(&mut variables, &["drop", "-m", "QUX"]));
+        assert!(variables.get_nested("QUX").is_some());
+        assert_eq!(FAILURE, drop_map(&mut variables, &["drop", "-m"]));
+    }
+
     #[test]
     fn drop_array_fails_with_no_arguments() {
         let mut variables = Variables::default();
@@ -0,0 +1,434 @@
+//! A minimal JSON document model, along with its parser and serializer.
+
+use std::{
+    char, fmt::{self, Display, Formatter, Write},
+};
+
+/// A JSON value. Numbers are kept as the text that they were written as, so that they are not
+/// altered by a conversion to floating point, and the members of an object are kept in the order
+/// in which they were written.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Creates an object from its members.
+    pub(crate) fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(members.into_iter().map(|(key, value)| (key.to_owned(), value)).collect())
+    }
+
+    /// Parses a JSON document, which must contain a single value.
+    pub(crate) fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser { text, read: 0 };
+        let value = parser.value(0)?;
+        parser.whitespace();
+        if parser.read != text.len() {
+            return Err(parser.error("unexpected characters after the value"));
+        }
+        Ok(value)
+    }
+
+    /// Returns the value of an object's member.
+    pub(crate) fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref members) => {
+                members.iter().find(|&&(ref name, _)| name == key).map(|&(_, ref value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Json::Number(ref number) => number.parse::<usize>().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Json {
+    fn from(string: &'a str) -> Json { Json::String(string.to_owned()) }
+}
+
+impl From<String> for Json {
+    fn from(string: String) -> Json { Json::String(string) }
+}
+
+impl From<bool> for Json {
+    fn from(boolean: bool) -> Json { Json::Bool(boolean) }
+}
+
+impl From<usize> for Json {
+    fn from(number: usize) -> Json { Json::Number(number.to_string()) }
+}
+
+impl From<i32> for Json {
+    fn from(number: i32) -> Json { Json::Number(number.to_string()) }
+}
+
+/// Writes a string as a JSON string literal.
+fn write_string(f: &mut Formatter, string: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for character in string.chars() {
+        match character {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            '\u{8}' => f.write_str("\\b")?,
+            '\u{c}' => f.write_str("\\f")?,
+            character if (character as u32) < 0x20 => write!(f, "\\u{:04x}", character as u32)?,
+            character => f.write_char(character)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Values are written compactly, without any whitespace between their tokens.
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(boolean) => write!(f, "{}", boolean),
+            Json::Number(ref number) => f.write_str(number),
+            Json::String(ref string) => write_string(f, string),
+            Json::Array(ref values) => {
+                f.write_char('[')?;
+                for (id, value) in values.iter().enumerate() {
+                    if id != 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(ref members) => {
+                f.write_char('{')?;
+                for (id, &(ref key, ref value)) in members.iter().enumerate() {
+                    if id != 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// An error in the syntax of a JSON document, which is located by the line and column at which
+/// it was found, each counted from one.
+#[derive(Debug, PartialEq)]
+pub(crate) struct JsonError {
+    pub line:    usize,
+    pub column:  usize,
+    pub message: &'static str,
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Values may be nested no deeper than this, which keeps the recursion of the parser bounded.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    text: &'a str,
+    read: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &'static str) -> JsonError {
+        let before = &self.text[..self.read];
+        let start = before.rfind('\n').map_or(0, |pos| pos + 1);
+        JsonError {
+            line: before.matches('\n').count() + 1,
+            column: before[start..].chars().count() + 1,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> { self.text.as_bytes().get(self.read).cloned() }
+
+    fn whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.read += 1;
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        if self.text[self.read..].starts_with(word) {
+            self.read += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("expected a value"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.whitespace();
+        match self.peek() {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'-') | Some(b'0'...b'9') => self.number(),
+            Some(b'[') | Some(b'{') if depth == MAX_DEPTH => {
+                Err(self.error("values are nested too deeply"))
+            }
+            Some(b'[') => self.array(depth),
+            Some(b'{') => self.object(depth),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.read;
+        while let Some(b'0'...b'9') = self.peek() {
+            self.read += 1;
+        }
+        self.read - start
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.read;
+        if self.peek() == Some(b'-') {
+            self.read += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.read += 1,
+            Some(b'1'...b'9') => {
+                self.digits();
+            }
+            _ => return Err(self.error("expected a digit")),
+        }
+        if self.peek() == Some(b'.') {
+            self.read += 1;
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit after the decimal point"));
+            }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.read += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.read += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit in the exponent"));
+            }
+        }
+        Ok(Json::Number(self.text[start..self.read].to_owned()))
+    }
+
+    fn hex(&mut self) -> Result<u32, JsonError> {
+        let digits = self.text.get(self.read..self.read + 4);
+        match digits.and_then(|digits| u32::from_str_radix(digits, 16).ok()) {
+            Some(code) => {
+                self.read += 4;
+                Ok(code)
+            }
+            None => Err(self.error("expected four hexadecimal digits")),
+        }
+    }
+
+    /// Characters beyond the basic plane are escaped as surrogate pairs, and so a surrogate
+    /// which is not a part of a pair is rejected.
+    fn unicode(&mut self) -> Result<char, JsonError> {
+        let start = self.read;
+        self.read += 1;
+        let mut code = self.hex()?;
+        if code >= 0xD800 && code < 0xDC00 && self.text[self.read..].starts_with("\\u") {
+            self.read += 2;
+            let low = self.hex()?;
+            if low >= 0xDC00 && low < 0xE000 {
+                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+            }
+        }
+        char::from_u32(code).ok_or_else(|| {
+            self.read = start;
+            self.error("invalid unicode escape")
+        })
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        // The opening quote.
+        self.read += 1;
+        let mut string = String::new();
+        loop {
+            let start = self.read;
+            while let Some(byte) = self.peek() {
+                if byte == b'"' || byte == b'\\' || byte < 0x20 {
+                    break;
+                }
+                self.read += 1;
+            }
+            string.push_str(&self.text[start..self.read]);
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.read += 1;
+                    return Ok(string);
+                }
+                Some(b'\\') => {
+                    self.read += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let character = self.unicode()?;
+                            self.read -= 1;
+                            character
+                        }
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    self.read += 1;
+                    string.push(escaped);
+                }
+                Some(_) => return Err(self.error("control characters must be escaped")),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.read += 1;
+        let mut values = Vec::new();
+        self.whitespace();
+        if self.peek() == Some(b']') {
+            self.read += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value(depth + 1)?);
+            self.whitespace();
+            match self.peek() {
+                Some(b',') => self.read += 1,
+                Some(b']') => {
+                    self.read += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.read += 1;
+        let mut members = Vec::new();
+        self.whitespace();
+        if self.peek() == Some(b'}') {
+            self.read += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("expected ':'"));
+            }
+            self.read += 1;
+            let value = self.value(depth + 1)?;
+            // Should a key be repeated, its last value is the one that is kept.
+            match members.iter().position(|&(ref name, _)| *name == key) {
+                Some(pos) => members[pos].1 = value,
+                None => members.push((key, value)),
+            }
+            self.whitespace();
+            match self.peek() {
+                Some(b',') => self.read += 1,
+                Some(b'}') => {
+                    self.read += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let json = Json::parse(
+            " {\"name\": \"ion\", \"tags\": [1, -2.5e3, true, null], \"nested\": \
+             {\"a\\\"\\u00e9\\n\": \"\\ud83d\\ude00\"}, \"name\": \"shell\"} ",
+        );
+        assert_eq!(
+            Ok(Json::object(vec![
+                ("name", "shell".into()),
+                (
+                    "tags",
+                    Json::Array(vec![
+                        Json::Number("1".into()),
+                        Json::Number("-2.5e3".into()),
+                        Json::Bool(true),
+                        Json::Null,
+                    ]),
+                ),
+                ("nested", Json::object(vec![("a\"é\n", "😀".into())])),
+            ])),
+            json
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |line, column, message| Err(JsonError { line, column, message });
+        assert_eq!(error(1, 1, "unexpected end of input"), Json::parse(""));
+        assert_eq!(error(2, 8, "expected ',' or ']'"), Json::parse("[1,\n  true false]"));
+        assert_eq!(error(1, 6, "expected ':'"), Json::parse("{\"a\" 1}"));
+        assert_eq!(error(1, 3, "expected a digit"), Json::parse("[-a]"));
+        assert_eq!(error(1, 4, "invalid escape sequence"), Json::parse("\"é\\x\""));
+        assert_eq!(error(1, 3, "unexpected characters after the value"), Json::parse("1 2"));
+        assert_eq!(error(1, 4, "unterminated string"), Json::parse("\"ab"));
+        assert_eq!(error(1, 3, "invalid unicode escape"), Json::parse("\"\\ud83d\""));
+        assert_eq!(error(1, 3, "invalid unicode escape"), Json::parse("\"\\ude00\\ud83d\""));
+        assert_eq!(error(1, 3, "invalid unicode escape"), Json::parse("\"\\ud83d\\u0041\""));
+    }
+
+    #[test]
+    fn serialize() {
+        let json = Json::object(vec![
+            ("text", "a \"quoted\"\tline\n\u{1}".into()),
+            ("list", Json::Array(vec![1usize.into(), Json::Null, false.into()])),
+            ("empty", Json::Object(Vec::new())),
+        ]);
+        let text = json.to_string();
+        assert_eq!(
+            "{\"text\":\"a \\\"quoted\\\"\\tline\\n\\u0001\",\"list\":[1,null,false],\"empty\":{}}",
+            text
+        );
+        assert_eq!(Ok(json), Json::parse(&text));
+    }
+}
@@ -45,6 +45,7 @@ mod types;
 pub mod parser;
 mod ascii_helpers;
 mod builtins;
+mod json;
 mod shell;
 
 pub use shell::{
@@ -12,7 +12,7 @@ impl<'a> Display for AssignmentError<'a> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match *self {
             AssignmentError::InvalidOperator(op) => write!(f, "invalid operator supplied: {}", op),
-            AssignmentError::InvalidValue(expected, actual) => {
+            AssignmentError::InvalidValue(ref expected, ref actual) => {
                 write!(f, "expected {}, but received {}", expected, actual)
             }
             AssignmentError::TypeError(ref type_err) => write!(f, "{}", type_err),
@@ -95,11 +95,16 @@ impl<'a> Action<'a> {
             | Primitive::BooleanArray
             | Primitive::FloatArray
             | Primitive::IntegerArray
-            | Primitive::StrArray => if is_array(value) {
+            | Primitive::StrArray
+            | Primitive::Array(_) => if is_array(value) {
                 Ok(Action::UpdateArray(var, operator, value))
             } else {
                 Err(AssignmentError::InvalidValue(var.kind, Primitive::Any))
             },
+            // Maps may also be copied from another map with `@map`, which value_check verifies.
+            Primitive::HashMap(_) | Primitive::BTreeMap(_) | Primitive::OrderedMap(_) => {
+                Ok(Action::UpdateArray(var, operator, value))
+            }
             Primitive::Any if is_array(value) => Ok(Action::UpdateArray(var, operator, value)),
             Primitive::Any => Ok(Action::UpdateString(var, operator, value)),
             _ if is_array(value) => {
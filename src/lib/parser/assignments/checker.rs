@@ -1,8 +1,9 @@
 use super::{
-    super::{expand_string, Expander}, Primitive, ReturnValue, TypeError,
+    super::{expand_string, ArgumentSplitter, Expander}, Primitive, ReturnValue, TypeError,
 };
 
 use std::iter::Iterator;
+use types::{BTreeMap, HashMap, Map, Nested, OrderedMap};
 
 #[derive(PartialEq, Clone, Copy)]
 enum IsArrayHelper {
@@ -150,10 +151,113 @@ fn get_array<E: Expander>(shell: &E, value: &str) -> ReturnValue {
     ReturnValue::Vector(expand_string(value, shell, false))
 }
 
+/// Checks that a value satisfies the type of the values of a map, normalizing booleans.
+fn map_value(value: String, expected: &Primitive) -> Result<String, ()> {
+    match *expected {
+        Primitive::Boolean => is_boolean(&value).map(Into::into),
+        Primitive::Integer if value.parse::<i64>().is_err() => Err(()),
+        Primitive::Float if value.parse::<f64>().is_err() => Err(()),
+        _ => Ok(value),
+    }
+}
+
+/// Collects a map from either a `[key=value ...]` literal, or a `@map` reference to an
+/// existing map. Within a literal, a `@map` element inserts each of the pairs of that map.
+fn get_map<E: Expander>(shell: &E, value: &str, expected: &Primitive) -> Result<Map, ()> {
+    let (mut map, kind) = match *expected {
+        Primitive::HashMap(ref kind) => (Map::Hash(HashMap::default()), kind),
+        Primitive::BTreeMap(ref kind) => (Map::BTree(BTreeMap::new()), kind),
+        Primitive::OrderedMap(ref kind) => (Map::Ordered(OrderedMap::new()), kind),
+        _ => unreachable!(),
+    };
+
+    let elements = if is_array(value) {
+        ArgumentSplitter::new(&value[1..value.len() - 1]).collect::<Vec<&str>>()
+    } else {
+        vec![value]
+    };
+
+    for element in elements {
+        if element.starts_with('@') {
+            if let Some(other) = shell.map(&element[1..]) {
+                for (key, value) in other.iter() {
+                    map.insert(key.clone(), map_value(value.clone(), kind)?);
+                }
+                continue;
+            }
+        }
+
+        let pair = expand_string(element, shell, false).join(" ");
+        match pair.find('=') {
+            Some(0) | None => return Err(()),
+            Some(pos) => {
+                let value = map_value(pair[pos + 1..].into(), kind)?;
+                map.insert(pair[..pos].into(), value);
+            }
+        }
+    }
+
+    Ok(map)
+}
+
+/// Collects a value which contains other collections, such as an array of maps, in which each
+/// of the collections is given as a literal of its own: `[[name=web port=80] [name=db]]`.
+fn get_nested<E: Expander>(shell: &E, value: &str, expected: &Primitive) -> Result<Nested, ()> {
+    let (mut map, kind) = match *expected {
+        Primitive::Array(ref kind) if is_array(value) => {
+            return ArgumentSplitter::new(&value[1..value.len() - 1])
+                .map(|element| get_nested(shell, element, kind))
+                .collect::<Result<Vec<Nested>, ()>>()
+                .map(Nested::Array);
+        }
+        Primitive::Array(_) => return Err(()),
+        Primitive::HashMap(ref kind) => (Map::Hash(HashMap::default()), kind),
+        Primitive::BTreeMap(ref kind) => (Map::BTree(BTreeMap::new()), kind),
+        Primitive::OrderedMap(ref kind) => (Map::Ordered(OrderedMap::new()), kind),
+        _ => {
+            return match value_check(shell, value, expected) {
+                Ok(ReturnValue::Str(value)) => Ok(Nested::Str(value)),
+                Ok(ReturnValue::Vector(values)) => {
+                    Ok(Nested::Array(values.into_iter().map(Nested::Str).collect()))
+                }
+                _ => Err(()),
+            };
+        }
+    };
+
+    let elements = if is_array(value) {
+        ArgumentSplitter::new(&value[1..value.len() - 1]).collect::<Vec<&str>>()
+    } else {
+        vec![value]
+    };
+
+    for element in elements {
+        if element.starts_with('@') && !kind.is_collection() {
+            if let Some(other) = shell.map(&element[1..]) {
+                for (key, value) in other.iter() {
+                    map.insert(key.clone(), Nested::Str(map_value(value.clone(), kind)?));
+                }
+                continue;
+            }
+        }
+
+        // The value is split from the key before it is expanded, as it may be a literal.
+        match element.find('=') {
+            Some(0) | None => return Err(()),
+            Some(pos) => {
+                let key = expand_string(&element[..pos], shell, false).join(" ");
+                map.insert(key.into(), get_nested(shell, &element[pos + 1..], kind)?);
+            }
+        }
+    }
+
+    Ok(Nested::Map(map))
+}
+
 pub(crate) fn value_check<'a, E: Expander>(
     shell: &E,
     value: &'a str,
-    expected: Primitive,
+    expected: &Primitive,
 ) -> Result<ReturnValue, TypeError<'a>> {
     macro_rules! string {
         () => {
@@ -166,7 +270,8 @@ pub(crate) fn value_check<'a, E: Expander>(
         };
     }
     let is_array = is_array(value);
-    match expected {
+    let bad_value = || TypeError::BadValue(expected.clone());
+    match *expected {
         Primitive::Any if is_array => Ok(array!()),
         Primitive::Any => Ok(string!()),
         Primitive::AnyArray if is_array => Ok(array!()),
@@ -174,7 +279,7 @@ pub(crate) fn value_check<'a, E: Expander>(
         Primitive::StrArray if is_array => Ok(array!()),
         Primitive::Boolean if !is_array => {
             let value = string!();
-            let value = is_boolean_string(&value).map_err(|_| TypeError::BadValue(expected))?;
+            let value = is_boolean_string(&value).map_err(|_| bad_value())?;
             Ok(ReturnValue::Str(value.to_owned()))
         }
         Primitive::BooleanArray if is_array => {
@@ -182,22 +287,28 @@ pub(crate) fn value_check<'a, E: Expander>(
             if is_boolean_array(&mut values) {
                 Ok(values)
             } else {
-                Err(TypeError::BadValue(expected))
+                Err(bad_value())
             }
         }
         Primitive::Integer if !is_array => {
-            is_integer_string(string!()).map_err(|_| TypeError::BadValue(expected))
+            is_integer_string(string!()).map_err(|_| bad_value())
         }
         Primitive::IntegerArray if is_array => {
-            is_integer_array(array!()).map_err(|_| TypeError::BadValue(expected))
+            is_integer_array(array!()).map_err(|_| bad_value())
         }
         Primitive::Float if !is_array => {
-            is_float_string(string!()).map_err(|_| TypeError::BadValue(expected))
+            is_float_string(string!()).map_err(|_| bad_value())
         }
         Primitive::FloatArray if is_array => {
-            is_float_array(array!()).map_err(|_| TypeError::BadValue(expected))
+            is_float_array(array!()).map_err(|_| bad_value())
+        }
+        _ if expected.is_nested() => {
+            get_nested(shell, value, expected).map(ReturnValue::Nested).map_err(|_| bad_value())
+        }
+        Primitive::HashMap(_) | Primitive::BTreeMap(_) | Primitive::OrderedMap(_) => {
+            get_map(shell, value, expected).map(ReturnValue::Map).map_err(|_| bad_value())
         }
-        _ => Err(TypeError::BadValue(expected)),
+        _ => Err(bad_value()),
     }
 }
 
@@ -224,6 +335,76 @@ mod test {
         assert_eq!(is_boolean("other"), Err(()));
     }
 
+    #[test]
+    fn map_values() {
+        struct Empty;
+        impl Expander for Empty {}
+
+        let kind = Primitive::BTreeMap(Box::new(Primitive::Integer));
+        let mut expected = BTreeMap::new();
+        expected.insert("a".into(), "1".into());
+        expected.insert("b".into(), "2".into());
+        assert_eq!(
+            value_check(&Empty, "[b=2 a=1]", &kind),
+            Ok(ReturnValue::Map(Map::BTree(expected)))
+        );
+        assert_eq!(
+            value_check(&Empty, "[a=one]", &kind),
+            Err(TypeError::BadValue(kind.clone()))
+        );
+        assert_eq!(
+            value_check(&Empty, "[a]", &kind),
+            Err(TypeError::BadValue(kind.clone()))
+        );
+
+        let kind = Primitive::HashMap(Box::new(Primitive::Boolean));
+        match value_check(&Empty, "[yes=y no=0]", &kind) {
+            Ok(ReturnValue::Map(map)) => {
+                assert_eq!(map.get("yes").map(String::as_str), Some("true"));
+                assert_eq!(map.get("no").map(String::as_str), Some("false"));
+            }
+            value => panic!("expected a map, but found {:?}", value),
+        }
+
+        let kind = Primitive::OrderedMap(Box::new(Primitive::Str));
+        match value_check(&Empty, "[b=2 c=3 a=1 b=4]", &kind) {
+            Ok(ReturnValue::Map(map)) => {
+                let pairs = map.iter().map(|(key, value)| format!("{}={}", key, value));
+                assert_eq!(pairs.collect::<Vec<String>>(), vec!["b=4", "c=3", "a=1"]);
+            }
+            value => panic!("expected a map, but found {:?}", value),
+        }
+    }
+
+    #[test]
+    fn nested_values() {
+        struct Empty;
+        impl Expander for Empty {}
+
+        let kind = Primitive::Array(Box::new(Primitive::OrderedMap(Box::new(Primitive::Integer))));
+        match value_check(&Empty, "[[a=1 b=2] [c=3]]", &kind) {
+            Ok(ReturnValue::Nested(nested)) => {
+                assert_eq!(nested.to_string(), "[[a=1 b=2] [c=3]]");
+                assert_eq!(nested.elements(), array!["[a=1 b=2]", "[c=3]"]);
+            }
+            value => panic!("expected a nested value, but found {:?}", value),
+        }
+        assert_eq!(
+            value_check(&Empty, "[[a=1] [b=two]]", &kind),
+            Err(TypeError::BadValue(kind.clone()))
+        );
+
+        let kind = Primitive::BTreeMap(Box::new(Primitive::StrArray));
+        match value_check(&Empty, "[web=[a b] db=[c]]", &kind) {
+            Ok(ReturnValue::Nested(nested)) => assert_eq!(nested.to_string(), "[db=[c] web=[a b]]"),
+            value => panic!("expected a nested value, but found {:?}", value),
+        }
+        assert_eq!(
+            value_check(&Empty, "[web=a]", &kind),
+            Err(TypeError::BadValue(kind.clone()))
+        );
+    }
+
     #[test]
     fn is_integer_array_() {
         let expected = Ok(ReturnValue::Vector(array!["1", "2", "3"]));
@@ -26,7 +26,7 @@ impl<'a> Display for TypeError<'a> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match *self {
             TypeError::Invalid(parm) => write!(f, "invalid type supplied: {}", parm),
-            TypeError::BadValue(expected) => write!(f, "expected {}", expected),
+            TypeError::BadValue(ref expected) => write!(f, "expected {}", expected),
         }
     }
 }
@@ -50,7 +50,7 @@ impl<'a> From<Key<'a>> for KeyBuf {
 }
 
 /// A primitive defines the type that a requested value should satisfy.
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Primitive {
     Any,
     AnyArray,
@@ -62,10 +62,35 @@ pub enum Primitive {
     IntegerArray,
     Float,
     FloatArray,
+    /// A `hmap[T]`, whose values are of the type `T`.
+    HashMap(Box<Primitive>),
+    /// A `bmap[T]`, which is a map that is sorted by its keys.
+    BTreeMap(Box<Primitive>),
+    /// An `omap[T]`, which is a map that retains the order in which its keys were inserted.
+    OrderedMap(Box<Primitive>),
+    /// An array whose elements are themselves collections, such as `hmap[str][]`.
+    Array(Box<Primitive>),
 }
 
 impl Primitive {
     fn parse(data: &str) -> Option<Primitive> {
+        if data.len() > 2 && data.ends_with("[]") {
+            match Primitive::parse(&data[..data.len() - 2]) {
+                Some(kind) if kind.is_collection() => return Some(Primitive::Array(Box::new(kind))),
+                _ => (),
+            }
+        }
+
+        if data.len() > 5 && data.ends_with(']') {
+            let kind = Primitive::parse_value(&data[5..data.len() - 1]).map(Box::new);
+            match data.get(..5) {
+                Some("hmap[") => return kind.map(Primitive::HashMap),
+                Some("bmap[") => return kind.map(Primitive::BTreeMap),
+                Some("omap[") => return kind.map(Primitive::OrderedMap),
+                _ => (),
+            }
+        }
+
         let data = match data {
             "[]" => Primitive::AnyArray,
             "str" => Primitive::Str,
@@ -80,6 +105,39 @@ impl Primitive {
         };
         Some(data)
     }
+
+    /// The values of a map may be of any type, including other arrays and maps, so long as the
+    /// type of their elements is given.
+    fn parse_value(data: &str) -> Option<Primitive> {
+        match Primitive::parse(data) {
+            Some(Primitive::AnyArray) => None,
+            kind => kind,
+        }
+    }
+
+    /// Whether values of this type contain other values.
+    pub(crate) fn is_collection(&self) -> bool {
+        match *self {
+            Primitive::Any
+            | Primitive::Str
+            | Primitive::Boolean
+            | Primitive::Integer
+            | Primitive::Float => false,
+            _ => true,
+        }
+    }
+
+    /// Whether values of this type must be stored as nested values, which is the case for
+    /// arrays of collections, and maps whose values are collections.
+    pub(crate) fn is_nested(&self) -> bool {
+        match *self {
+            Primitive::Array(_) => true,
+            Primitive::HashMap(ref kind)
+            | Primitive::BTreeMap(ref kind)
+            | Primitive::OrderedMap(ref kind) => kind.is_collection(),
+            _ => false,
+        }
+    }
 }
 
 impl Display for Primitive {
@@ -94,6 +152,20 @@ impl Display for Primitive {
             Primitive::Integer => write!(f, "int"),
             Primitive::IntegerArray => write!(f, "int[]"),
             Primitive::StrArray => write!(f, "str[]"),
+            Primitive::HashMap(ref kind) => write!(f, "hmap[{}]", kind),
+            Primitive::BTreeMap(ref kind) => write!(f, "bmap[{}]", kind),
+            Primitive::OrderedMap(ref kind) => write!(f, "omap[{}]", kind),
+            Primitive::Array(ref kind) => write!(f, "{}[]", kind),
+        }
+    }
+}
+
+impl Display for KeyBuf {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.kind {
+            Primitive::Any => write!(f, "{}", self.name),
+            Primitive::AnyArray => write!(f, "{}[]", self.name),
+            ref kind => write!(f, "{}:{}", self.name, kind),
         }
     }
 }
@@ -162,10 +234,14 @@ impl<'a> Iterator for KeyIterator<'a> {
 
     fn next(&mut self) -> Option<Result<Key<'a>, TypeError<'a>>> {
         let mut start = self.read;
+        let mut namespaced = false;
         for byte in self.data.bytes().skip(self.read) {
             self.read += 1;
             match byte {
                 b' ' if start + 1 == self.read => start += 1,
+                // A `::` pattern designates the scope of the variable, not its type.
+                b':' if namespaced => namespaced = false,
+                b':' if self.data.as_bytes().get(self.read) == Some(&b':') => namespaced = true,
                 b' ' => {
                     return Some(Ok(Key {
                         name: &self.data[start..self.read].trim(),
@@ -202,7 +278,10 @@ mod tests {
 
     #[test]
     fn key_parsing() {
-        let mut parser = KeyIterator::new("a:int b[] c:bool d e:int[] d:a");
+        let mut parser = KeyIterator::new(concat!(
+            "a:int b[] c:bool d e:int[] global::f super::g:int ",
+            "h:hmap[int] i:bmap[str] j:omap[float] d:a"
+        ));
         assert_eq!(
             parser.next().unwrap(),
             Ok(Key {
@@ -238,6 +317,65 @@ mod tests {
                 kind: Primitive::IntegerArray,
             },)
         );
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key {
+                name: "global::f",
+                kind: Primitive::Any,
+            },)
+        );
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key {
+                name: "super::g",
+                kind: Primitive::Integer,
+            },)
+        );
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key {
+                name: "h",
+                kind: Primitive::HashMap(Box::new(Primitive::Integer)),
+            },)
+        );
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key {
+                name: "i",
+                kind: Primitive::BTreeMap(Box::new(Primitive::Str)),
+            },)
+        );
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key {
+                name: "j",
+                kind: Primitive::OrderedMap(Box::new(Primitive::Float)),
+            },)
+        );
         assert_eq!(parser.next().unwrap(), Err(TypeError::Invalid("a")));
     }
+
+    #[test]
+    fn nested_types() {
+        let map = |kind| Primitive::HashMap(Box::new(kind));
+        assert_eq!(Primitive::parse("hmap[str[]]"), Some(map(Primitive::StrArray)));
+        assert_eq!(
+            Primitive::parse("hmap[str][]"),
+            Some(Primitive::Array(Box::new(map(Primitive::Str))))
+        );
+        assert_eq!(
+            Primitive::parse("bmap[hmap[int]]"),
+            Some(Primitive::BTreeMap(Box::new(map(Primitive::Integer))))
+        );
+        assert_eq!(
+            Primitive::parse("int[][]"),
+            Some(Primitive::Array(Box::new(Primitive::IntegerArray)))
+        );
+        assert_eq!(Primitive::parse("hmap[[]]"), None);
+        assert_eq!(Primitive::parse("str[][]x"), None);
+
+        assert!(map(Primitive::StrArray).is_nested());
+        assert!(!map(Primitive::Str).is_nested());
+        assert_eq!(Primitive::parse("omap[str][]").unwrap().to_string(), "omap[str][]");
+    }
 }
@@ -10,10 +10,12 @@ pub(crate) use self::{
     keys::{Key, KeyBuf, KeyIterator, TypeError}, operator::Operator, splitter::split_assignment,
 };
 
-use types::{Array, Value};
+use types::{Array, Map, Nested, Value};
 
 #[derive(Debug, PartialEq)]
 pub(crate) enum ReturnValue {
     Str(Value),
     Vector(Array),
+    Map(Map),
+    Nested(Nested),
 }
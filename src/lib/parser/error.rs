@@ -0,0 +1,98 @@
+//! Syntax errors, and the diagnostics which locate them within the source of a script.
+
+use std::{borrow::Cow, ops::Range};
+
+/// An error in the syntax of a statement, along with the text at which it was found. The text
+/// is always a slice of the source that was parsed, which allows the error to be located within
+/// that source when it is reported.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SyntaxError<'a> {
+    pub message: Cow<'static, str>,
+    pub source:  &'a str,
+}
+
+impl<'a> SyntaxError<'a> {
+    pub(crate) fn new<M: Into<Cow<'static, str>>>(message: M, source: &'a str) -> Self {
+        SyntaxError {
+            message: message.into(),
+            source,
+        }
+    }
+
+    /// Returns the byte range of `code` at which the error was found, if it was found within it.
+    pub(crate) fn span(&self, code: &str) -> Option<Range<usize>> {
+        let start = (self.source.as_ptr() as usize).checked_sub(code.as_ptr() as usize)?;
+        let end = start + self.source.len();
+        if end <= code.len() {
+            Some(start..end)
+        } else {
+            None
+        }
+    }
+
+    /// Describes the error that was found within `code`, which begins on the given `line` of
+    /// `script`. The description gives the line and column of the error, followed by the line of
+    /// code on which it occurred, with the erroneous text underlined by carets.
+    pub(crate) fn diagnostic(&self, code: &str, script: &str, line: usize) -> String {
+        let span = self.span(code).unwrap_or(0..0);
+        let line_start = code[..span.start].rfind('\n').map_or(0, |pos| pos + 1);
+        let line_end = code[span.start..].find('\n').map_or(code.len(), |pos| span.start + pos);
+
+        // Tabs are retained in the indentation of the carets, so that they line up with the text.
+        let indent = code[line_start..span.start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let underlined = code[span.start..span.end.min(line_end)].chars().count();
+
+        format!(
+            "{}:{}:{}: syntax error: {}\n    {}\n    {}{}",
+            script,
+            line + code[..line_start].matches('\n').count(),
+            code[line_start..span.start].chars().count() + 1,
+            self.message,
+            &code[line_start..line_end],
+            indent,
+            "^".repeat(underlined.max(1))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans() {
+        let code = "echo one\necho (two)";
+        assert_eq!(Some(14..15), SyntaxError::new("", &code[14..15]).span(code));
+        assert_eq!(Some(9..19), SyntaxError::new("", &code[9..]).span(code));
+        assert_eq!(None, SyntaxError::new("", "(").span(code));
+    }
+
+    #[test]
+    fn diagnostics() {
+        let code = "echo one\n\techo (two) three";
+        let error = SyntaxError::new("'(' is out of place", &code[15..20]);
+        assert_eq!(
+            "script.ion:11:7: syntax error: '(' is out of place\n    \techo (two) three\n    \t     \
+             ^^^^^",
+            error.diagnostic(code, "script.ion", 10)
+        );
+
+        // Errors which span multiple lines are underlined up to the end of the first.
+        let error = SyntaxError::new("unterminated subshell", &code[5..]);
+        assert_eq!(
+            "ion:1:6: syntax error: unterminated subshell\n    echo one\n         ^^^",
+            error.diagnostic(code, "ion", 1)
+        );
+
+        // The end of the code is underlined by a single caret.
+        let error = SyntaxError::new("expected file argument", &code[code.len()..]);
+        assert_eq!(
+            "ion:2:18: syntax error: expected file argument\n    \techo (two) three\n    \t     \
+             \x20          ^",
+            error.diagnostic(code, "ion", 1)
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Heredocs are multi-line inputs, which begin on the line following a `<<TAG` redirection,
+//! and end at the first line that consists solely of the given `TAG`.
+
+/// The delimiter of a heredoc, along with the options that were supplied with it.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Delimiter<'a> {
+    /// The phrase which terminates the heredoc.
+    pub tag: &'a str,
+    /// Set when the tag was quoted, which disables expansions within the body.
+    pub literal: bool,
+    /// Set by `<<-`, which strips the common indentation from each line of the body.
+    pub strip: bool,
+    /// The number of bytes, following the `<<` operator, that the delimiter occupies.
+    pub length: usize,
+}
+
+impl<'a> Delimiter<'a> {
+    /// Parses the delimiter of a heredoc, where `data` begins directly after the `<<` operator.
+    pub(crate) fn parse(data: &'a str) -> Option<Delimiter<'a>> {
+        let bytes = data.as_bytes();
+        let strip = bytes.first() == Some(&b'-');
+        let mut start = if strip { 1 } else { 0 };
+        while bytes.get(start).map_or(false, |&b| b == b' ' || b == b'\t') {
+            start += 1;
+        }
+
+        let (tag, literal, length) = match bytes.get(start) {
+            Some(&quote) if quote == b'\'' || quote == b'"' => {
+                let end = data[start + 1..].find(quote as char)? + start + 1;
+                (&data[start + 1..end], true, end + 1)
+            }
+            _ => {
+                let end = data[start..]
+                    .find(|c: char| c.is_whitespace() || ";&|<>".contains(c))
+                    .map_or(data.len(), |end| end + start);
+                (&data[start..end], false, end)
+            }
+        };
+
+        if tag.is_empty() {
+            None
+        } else {
+            Some(Delimiter {
+                tag,
+                literal,
+                strip,
+                length,
+            })
+        }
+    }
+
+    /// Returns true if the given line, without its newline, terminates the heredoc.
+    pub(crate) fn terminates(&self, line: &str) -> bool { terminates(self.tag, self.strip, line) }
+
+    /// Collects the body of the heredoc from `data`, which begins on the line following the
+    /// heredoc's redirection. On success, the body is returned alongside the number of bytes
+    /// that were consumed, including the terminating line. If the heredoc was not terminated,
+    /// `None` is returned.
+    pub(crate) fn body(&self, data: &str) -> Option<(String, usize)> {
+        let mut lines = Vec::new();
+        let mut consumed = 0;
+        loop {
+            let remaining = &data[consumed..];
+            let (line, length) = match remaining.find('\n') {
+                Some(pos) => (&remaining[..pos], pos + 1),
+                None if remaining.is_empty() => return None,
+                None => (remaining, remaining.len()),
+            };
+            consumed += length;
+            if self.terminates(line) {
+                break;
+            }
+            lines.push(line);
+        }
+
+        let indent = if self.strip {
+            lines
+                .iter()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.len() - line.trim_left().len())
+                .min()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        let mut body = String::new();
+        for line in lines {
+            body.push_str(line.get(indent..).unwrap_or(""));
+            body.push('\n');
+        }
+        Some((body, consumed))
+    }
+}
+
+/// Returns true if the line consists solely of the tag. Only the terminating line of a `<<-`
+/// heredoc may be indented.
+pub(crate) fn terminates(tag: &str, strip: bool, line: &str) -> bool {
+    (if strip { line.trim_left() } else { line }) == tag
+}
+
+/// Quotes the body of a heredoc, so that it may be expanded as a double-quoted word. A backslash
+/// only escapes a `$` or another backslash, and quotes within the body are retained.
+pub(crate) fn quote(body: &str) -> String {
+    let mut quoted = String::with_capacity(body.len() + 2);
+    quoted.push('"');
+    let mut chars = body.chars().peekable();
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' if chars.peek().map_or(false, |&next| next == '$' || next == '\\') => {
+                quoted.push(character);
+                quoted.extend(chars.next());
+            }
+            '\\' | '"' => {
+                quoted.push('\\');
+                quoted.push(character);
+            }
+            _ => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delimiters() {
+        let expected = Delimiter { tag: "EOF", literal: false, strip: false, length: 4 };
+        assert_eq!(Delimiter::parse(" EOF | tr a b"), Some(expected));
+        let expected = Delimiter { tag: "EOF", literal: true, strip: false, length: 5 };
+        assert_eq!(Delimiter::parse("'EOF'"), Some(expected));
+        let expected = Delimiter { tag: "END", literal: true, strip: true, length: 7 };
+        assert_eq!(Delimiter::parse("- \"END\" > file"), Some(expected));
+        assert_eq!(Delimiter::parse(" "), None);
+        assert_eq!(Delimiter::parse("'EOF"), None);
+    }
+
+    #[test]
+    fn bodies() {
+        let delimiter = Delimiter::parse("EOF").unwrap();
+        let data = "one\n  two\nEOF\necho three";
+        assert_eq!(delimiter.body(data), Some(("one\n  two\n".into(), 14)));
+        assert_eq!(delimiter.body("one\ntwo"), None);
+        let data = "  EOF\nEOF \n\tEOF\nEOF";
+        assert_eq!(delimiter.body(data), Some(("  EOF\nEOF \n\tEOF\n".into(), data.len())));
+
+        let delimiter = Delimiter::parse("-EOF").unwrap();
+        let data = "    one\n      two\n\n    three\n    EOF";
+        assert_eq!(
+            delimiter.body(data),
+            Some(("one\n  two\n\nthree\n".into(), data.len()))
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("say \"hi\"\n"), "\"say \\\"hi\\\"\n\"");
+        assert_eq!(quote("C:\\dir \\n $HOME"), "\"C:\\\\dir \\\\n $HOME\"");
+        assert_eq!(quote("\\$HOME \\\\ end\\"), "\"\\$HOME \\\\ end\\\\\"");
+    }
+}
@@ -1,5 +1,7 @@
 mod arguments;
 pub(crate) mod assignments;
+mod error;
+pub(crate) mod heredoc;
 mod loops;
 pub(crate) mod pipelines;
 mod quotes;
@@ -8,6 +10,9 @@ mod statement;
 
 pub use self::{arguments::ArgumentSplitter, assignments::Primitive, quotes::Terminator};
 pub(crate) use self::{
-    loops::for_grammar::ForExpression, shell_expand::{expand_string, Expander, Select},
-    statement::{parse_and_validate, StatementSplitter},
+    error::SyntaxError, loops::for_grammar::ForExpression,
+    shell_expand::{
+        expand_string, Expander, Index, Select, SelectWithSize, ARRAY_METHODS, STRING_METHODS,
+    },
+    statement::{parse_and_validate, StatementSplitter, StatementVariant},
 };
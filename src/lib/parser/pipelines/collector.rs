@@ -2,8 +2,12 @@
 
 use std::{collections::HashSet, iter::Peekable};
 
-use super::{Input, PipeItem, Pipeline, RedirectFrom, Redirection};
+use super::{
+    super::{heredoc::Delimiter, SyntaxError}, FdRedirection, FileMode, Input, PipeItem, Pipeline,
+    RedirectFrom, Redirection,
+};
 use shell::{Job, JobKind};
+use std::os::unix::io::RawFd;
 use types::*;
 
 pub(crate) struct Collector<'a> {
@@ -12,16 +16,20 @@ pub(crate) struct Collector<'a> {
 
 lazy_static! {
     /// The set of bytes that will always indicate an end of an arg
-    static ref FOLLOW_ARGS: HashSet<u8> = b"&|<> \t".into_iter().map(|b| *b).collect();
+    static ref FOLLOW_ARGS: HashSet<u8> = b"&|<> \t\n".into_iter().map(|b| *b).collect();
 }
 
 impl<'a> Collector<'a> {
-    pub(crate) fn parse(&self) -> Result<Pipeline, &'static str> {
+    pub(crate) fn parse(&self) -> Result<Pipeline, SyntaxError<'a>> {
         let mut bytes = self.data.bytes().enumerate().peekable();
         let mut args = Array::new();
         let mut pipeline = Pipeline::new();
         let mut outputs: Option<Vec<Redirection>> = None;
         let mut inputs: Option<Vec<Input>> = None;
+        let mut descriptors: Vec<FdRedirection> = Vec::new();
+        let mut heredoc_end: Option<usize> = None;
+        // The position which follows the most recent subshell or group.
+        let mut block_end = 0;
 
         /// Add a new argument that is re
         macro_rules! push_arg {
@@ -34,7 +42,7 @@ impl<'a> Collector<'a> {
 
         /// Attempt to add a redirection
         macro_rules! try_redir_out {
-            ($from:expr) => {{
+            ($from:expr, $start:expr) => {{
                 if let None = outputs {
                     outputs = Some(Vec::new());
                 }
@@ -45,15 +53,22 @@ impl<'a> Collector<'a> {
                     false
                 };
                 if let Some(file) = self.arg(&mut bytes)? {
-                    outputs.as_mut().map(|o| {
-                        o.push(Redirection {
-                            from: $from,
-                            file: file.into(),
-                            append,
-                        })
-                    });
+                    let redirection = Redirection {
+                        from: $from,
+                        file: file.into(),
+                        append,
+                    };
+                    if descriptors.is_empty() {
+                        outputs.as_mut().map(|o| o.push(redirection));
+                    } else {
+                        // Following a numbered redirection, the order of redirections matters.
+                        descriptors.extend(redirection.descriptors());
+                    }
                 } else {
-                    return Err("expected file argument after redirection for output");
+                    return Err(SyntaxError::new(
+                        "expected file argument after redirection for output",
+                        self.operator($start),
+                    ));
                 }
             }};
         };
@@ -62,7 +77,16 @@ impl<'a> Collector<'a> {
         macro_rules! try_add_item {
             ($job_kind:expr) => {{
                 if !args.is_empty() {
-                    let job = Job::new(args.clone(), $job_kind);
+                    let env = assignments(&mut args);
+                    if args.len() > 3 && (args[0] == "(" || args[0] == "{") {
+                        let arguments = self.data[block_end..].split('|').next().unwrap().trim();
+                        return Err(SyntaxError::new(
+                            "unexpected argument following a subshell or group",
+                            arguments,
+                        ));
+                    }
+                    let mut job = Job::new(args.clone(), $job_kind);
+                    job.env = env;
                     args.clear();
                     let item_out = if let Some(out_tmp) = outputs.take() {
                         out_tmp
@@ -74,7 +98,8 @@ impl<'a> Collector<'a> {
                     } else {
                         Vec::new()
                     };
-                    pipeline.items.push(PipeItem::new(job, item_out, item_in));
+                    let item_fds = descriptors.drain(..).collect();
+                    pipeline.items.push(PipeItem::new(job, item_out, item_in, item_fds));
                 }
             }};
         }
@@ -89,7 +114,7 @@ impl<'a> Collector<'a> {
                         Some(&(_, b'>')) => {
                             // And this byte
                             bytes.next();
-                            try_redir_out!(RedirectFrom::Both);
+                            try_redir_out!(RedirectFrom::Both, i);
                         }
                         Some(&(_, b'|')) => {
                             bytes.next();
@@ -108,10 +133,14 @@ impl<'a> Collector<'a> {
                     // We do not immediately consume this byte as it could just be the start of
                     // a new argument
                     match self.peek(i + 1) {
+                        Some(b'>') if self.peek(i + 2) == Some(b'&') => {
+                            bytes.next();
+                            descriptors.push(self.fd_redirection(&mut bytes, 2)?);
+                        }
                         Some(b'>') => {
                             bytes.next();
                             bytes.next();
-                            try_redir_out!(RedirectFrom::Stderr);
+                            try_redir_out!(RedirectFrom::Stderr, i);
                         }
                         Some(b'|') => {
                             bytes.next();
@@ -125,9 +154,27 @@ impl<'a> Collector<'a> {
                     bytes.next();
                     try_add_item!(JobKind::Pipe(RedirectFrom::Stdout));
                 }
+                // Process substitutions are arguments, rather than redirections.
+                b'<' | b'>' if self.peek(i + 1) == Some(b'(') => push_arg!(),
+                // A number which directly precedes a redirection names the descriptor.
+                b'0'...b'9' => match self.descriptor(i) {
+                    Some((fd, length)) => {
+                        for _ in 0..length {
+                            bytes.next();
+                        }
+                        descriptors.push(self.fd_redirection(&mut bytes, fd)?);
+                    }
+                    None => push_arg!(),
+                },
+                b'>' if self.peek(i + 1) == Some(b'&') => {
+                    descriptors.push(self.fd_redirection(&mut bytes, 1)?);
+                }
+                b'<' if self.peek(i + 1) == Some(b'&') || self.peek(i + 1) == Some(b'>') => {
+                    descriptors.push(self.fd_redirection(&mut bytes, 0)?);
+                }
                 b'>' => {
                     bytes.next();
-                    try_redir_out!(RedirectFrom::Stdout);
+                    try_redir_out!(RedirectFrom::Stdout, i);
                 }
                 b'<' => {
                     if let None = inputs {
@@ -145,39 +192,108 @@ impl<'a> Collector<'a> {
                                     .as_mut()
                                     .map(|x| x.push(Input::HereString(cmd.into())));
                             } else {
-                                return Err("expected string argument after '<<<'");
+                                return Err(SyntaxError::new(
+                                    "expected string argument after '<<<'",
+                                    self.operator(i),
+                                ));
                             }
                         } else {
                             // Otherwise, what we have is not a herestring, but a heredoc.
                             bytes.next();
-                            // Collect the rest of the byte iterator and then trim the result
-                            // in order to get the EOF phrase that will be used to terminate
-                            // the heredoc.
-                            let heredoc = {
-                                let mut buffer = Vec::new();
-                                while let Some((_, byte)) = bytes.next() {
-                                    buffer.push(byte);
+                            let delimiter = match Delimiter::parse(&self.data[i + 2..]) {
+                                Some(delimiter) => delimiter,
+                                None => {
+                                    return Err(SyntaxError::new(
+                                        "expected delimiter after '<<'",
+                                        self.operator(i),
+                                    ))
                                 }
-                                unsafe { String::from_utf8_unchecked(buffer) }
                             };
-                            let heredoc = heredoc.lines().skip(1).collect::<Vec<&str>>();
-                            if heredoc.len() > 1 {
-                                let herestring =
-                                    Input::HereString(heredoc[..heredoc.len() - 1].join("\n"));
-                                inputs.as_mut().map(|x| x.push(herestring.clone()));
+                            for _ in 0..delimiter.length {
+                                bytes.next();
                             }
+
+                            // The body begins on the line following the redirection, or after
+                            // the body of the previous heredoc on that line. A statement which
+                            // shares its line with others is supplied its bodies afterwards.
+                            let start = match heredoc_end {
+                                Some(end) => end,
+                                None => match self.data[i..].find('\n') {
+                                    Some(pos) => i + pos + 1,
+                                    None => {
+                                        inputs.as_mut().map(|x| {
+                                            x.push(Input::HereDoc {
+                                                body:   String::new(),
+                                                expand: !delimiter.literal,
+                                            })
+                                        });
+                                        continue;
+                                    }
+                                },
+                            };
+                            let (body, consumed) = match delimiter.body(&self.data[start..]) {
+                                Some(body) => body,
+                                None => {
+                                    return Err(SyntaxError::new(
+                                        "heredoc was not terminated",
+                                        &self.data[i..i + 2 + delimiter.length],
+                                    ))
+                                }
+                            };
+                            heredoc_end = Some(start + consumed);
+                            inputs.as_mut().map(|x| {
+                                x.push(Input::HereDoc {
+                                    body,
+                                    expand: !delimiter.literal,
+                                })
+                            });
                         }
                     } else if let Some(file) = self.arg(&mut bytes)? {
                         // Otherwise interpret it as stdin redirection
-                        inputs.as_mut().map(|x| x.push(Input::File(file.into())));
+                        if descriptors.is_empty() {
+                            inputs.as_mut().map(|x| x.push(Input::File(file.into())));
+                        } else {
+                            descriptors.push(FdRedirection::Open {
+                                fd:   0,
+                                file: file.into(),
+                                mode: FileMode::Read,
+                            });
+                        }
                     } else {
-                        return Err("expected file argument after redirection for input");
+                        return Err(SyntaxError::new(
+                            "expected file argument after redirection for input",
+                            self.operator(i),
+                        ));
                     }
                 }
+                // Skip over the bodies of any heredocs that have been collected.
+                b'\n' => match heredoc_end {
+                    Some(end) => while bytes.peek().map_or(false, |&(i, _)| i < end) {
+                        bytes.next();
+                    },
+                    None => {
+                        bytes.next();
+                    }
+                },
                 // Skip over whitespace between jobs
                 b' ' | b'\t' => {
                     bytes.next();
                 }
+                // Subshells and groups may only begin where a command would, which includes
+                // after the assignments of a command. The job of a block consists of its
+                // delimiters and the unparsed body between them.
+                b'(' if args.iter().all(|arg| is_assignment(arg)) => {
+                    let body = self.block(&mut bytes, b'(', b')', "unterminated subshell")?;
+                    args.extend(["(", body, ")"].iter().map(|&arg| arg.into()));
+                    block_end = i + body.len() + 2;
+                }
+                b'{' if args.iter().all(|arg| is_assignment(arg))
+                    && self.peek(i + 1).map_or(false, |b| b.is_ascii_whitespace()) =>
+                {
+                    let body = self.block(&mut bytes, b'{', b'}', "unterminated group")?;
+                    args.extend(["{", body, "}"].iter().map(|&arg| arg.into()));
+                    block_end = i + body.len() + 2;
+                }
                 // Assume that the next character starts an argument and parse that argument
                 _ => push_arg!(),
             }
@@ -190,7 +306,7 @@ impl<'a> Collector<'a> {
         Ok(pipeline)
     }
 
-    fn arg<I>(&self, bytes: &mut Peekable<I>) -> Result<Option<&'a str>, &'static str>
+    fn arg<I>(&self, bytes: &mut Peekable<I>) -> Result<Option<&'a str>, SyntaxError<'a>>
     where
         I: Iterator<Item = (usize, u8)>,
     {
@@ -282,6 +398,10 @@ impl<'a> Collector<'a> {
                     bytes.next();
                     bytes.next();
                 }
+                // The start of a process substitution, which is completed by the paren.
+                b'<' | b'>' if self.peek(i + 1) == Some(b'(') => {
+                    bytes.next();
+                }
                 // If we see a byte from the follow set, we've definitely reached the end of
                 // the arguments
                 c if FOLLOW_ARGS.contains(&c) && is_toplevel!() => {
@@ -294,20 +414,24 @@ impl<'a> Collector<'a> {
                 }
             }
         }
+        let error = |message| {
+            let arg = &self.data[start.unwrap_or(0)..end.unwrap_or(self.data.len())];
+            Err(SyntaxError::new(message, arg))
+        };
         if proc_level > 0 {
-            return Err("ion: syntax error: unmatched left paren");
+            return error("unmatched left paren");
         }
         if array_level > 0 {
-            return Err("ion: syntax error: unmatched left bracket");
+            return error("unmatched left bracket");
         }
         if brace_level > 0 {
-            return Err("ion: syntax error: unmatched left brace");
+            return error("unmatched left brace");
         }
         if proc_level < 0 {
-            return Err("ion: syntax error: extra right paren(s)");
+            return error("extra right paren(s)");
         }
         if array_level < 0 {
-            return Err("ion: syntax error: extra right bracket(s)");
+            return error("extra right bracket(s)");
         }
         match (start, end) {
             (Some(i), Some(j)) if i < j => Ok(Some(&self.data[i..j])),
@@ -316,11 +440,132 @@ impl<'a> Collector<'a> {
         }
     }
 
+    /// Collects the body of a subshell or group, which is delimited by `open` and `close`.
+    fn block<I>(
+        &self,
+        bytes: &mut Peekable<I>,
+        open: u8,
+        close: u8,
+        unterminated: &'static str,
+    ) -> Result<&'a str, SyntaxError<'a>>
+    where
+        I: Iterator<Item = (usize, u8)>,
+    {
+        let start = match bytes.next() {
+            Some((i, _)) => i + 1,
+            None => return Err(SyntaxError::new(unterminated, &self.data[self.data.len()..])),
+        };
+        let mut level = 0;
+        while let Some(&(i, b)) = bytes.peek() {
+            match b {
+                b'"' => {
+                    bytes.next();
+                    self.double_quoted(bytes, i)?;
+                    continue;
+                }
+                b'\'' => {
+                    bytes.next();
+                    self.single_quoted(bytes, i)?;
+                    continue;
+                }
+                b'\\' => {
+                    bytes.next();
+                }
+                _ if b == open => level += 1,
+                _ if b == close && level == 0 => {
+                    bytes.next();
+                    return Ok(&self.data[start..i]);
+                }
+                _ if b == close => level -= 1,
+                _ => (),
+            }
+            bytes.next();
+        }
+        Err(SyntaxError::new(unterminated, &self.data[start - 1..]))
+    }
+
+    /// Determines whether the digits at `start` name the descriptor of a redirection, such as
+    /// the `2` in `2>&1`, returning the descriptor and the number of digits if so.
+    fn descriptor(&self, start: usize) -> Option<(RawFd, usize)> {
+        let length = self.data[start..].bytes().take_while(u8::is_ascii_digit).count();
+        match (self.peek(start + length), self.peek(start + length + 1)) {
+            // Process substitutions, heredocs and herestrings are excluded.
+            (Some(b'<'), Some(b'(')) | (Some(b'>'), Some(b'(')) | (Some(b'<'), Some(b'<')) => None,
+            (Some(b'<'), _) | (Some(b'>'), _) => self.data[start..start + length]
+                .parse::<RawFd>()
+                .ok()
+                .map(|fd| (fd, length)),
+            _ => None,
+        }
+    }
+
+    /// Parses the remainder of a redirection upon the descriptor `fd`, beginning with the
+    /// `<` or `>` operator.
+    fn fd_redirection<I>(
+        &self,
+        bytes: &mut Peekable<I>,
+        fd: RawFd,
+    ) -> Result<FdRedirection, SyntaxError<'a>>
+    where
+        I: Iterator<Item = (usize, u8)>,
+    {
+        let (operator, input) = match bytes.next() {
+            Some((i, b)) => (self.operator(i), b == b'<'),
+            None => (&self.data[self.data.len()..], false),
+        };
+        let expected_descriptor = || {
+            SyntaxError::new("expected file descriptor or '-' after '>&' or '<&'", operator)
+        };
+        let mode = match (input, bytes.peek().map(|&(_, b)| b)) {
+            (_, Some(b'&')) => {
+                bytes.next();
+                if let Some(&(_, b'-')) = bytes.peek() {
+                    bytes.next();
+                    return Ok(FdRedirection::Close(fd));
+                }
+                let start = bytes.peek().map_or(self.data.len(), |&(i, _)| i);
+                // The descriptor may also be given by an expansion, such as `>&$fd`.
+                if let Some(b'$') | Some(b'@') = self.peek(start) {
+                    return match self.arg(bytes)? {
+                        Some(source) => Ok(FdRedirection::DuplicateWord {
+                            fd,
+                            source: source.into(),
+                        }),
+                        None => Err(expected_descriptor()),
+                    };
+                }
+                let length = self.data[start..].bytes().take_while(u8::is_ascii_digit).count();
+                for _ in 0..length {
+                    bytes.next();
+                }
+                return self.data[start..start + length]
+                    .parse::<RawFd>()
+                    .map(|source| FdRedirection::Duplicate { fd, source })
+                    .map_err(|_| expected_descriptor());
+            }
+            (true, Some(b'>')) => {
+                bytes.next();
+                FileMode::ReadWrite
+            }
+            (false, Some(b'>')) => {
+                bytes.next();
+                FileMode::Append
+            }
+            (true, _) => FileMode::Read,
+            (false, _) => FileMode::Write,
+        };
+
+        match self.arg(bytes)? {
+            Some(file) => Ok(FdRedirection::Open { fd, file: file.into(), mode }),
+            None => Err(SyntaxError::new("expected file argument after redirection", operator)),
+        }
+    }
+
     fn double_quoted<I>(
         &self,
         bytes: &mut Peekable<I>,
         start: usize,
-    ) -> Result<&'a str, &'static str>
+    ) -> Result<&'a str, SyntaxError<'a>>
     where
         I: Iterator<Item = (usize, u8)>,
     {
@@ -338,14 +583,14 @@ impl<'a> Collector<'a> {
             }
             bytes.next();
         }
-        Err("ion: syntax error: unterminated quote")
+        Err(SyntaxError::new("unterminated quote", &self.data[start..]))
     }
 
     fn single_quoted<I>(
         &self,
         bytes: &mut Peekable<I>,
         start: usize,
-    ) -> Result<&'a str, &'static str>
+    ) -> Result<&'a str, SyntaxError<'a>>
     where
         I: Iterator<Item = (usize, u8)>,
     {
@@ -360,7 +605,7 @@ impl<'a> Collector<'a> {
             }
             bytes.next();
         }
-        Err("ion: syntax error: unterminated single quote")
+        Err(SyntaxError::new("unterminated single quote", &self.data[start..]))
     }
 
     fn peek(&self, index: usize) -> Option<u8> {
@@ -371,24 +616,68 @@ impl<'a> Collector<'a> {
         }
     }
 
-    pub(crate) fn run(data: &'a str) -> Result<Pipeline, &'static str> {
+    /// Returns the redirection operator which begins at `start`, which errors refer to.
+    fn operator(&self, start: usize) -> &'a str {
+        let length = self.data[start..].bytes().take_while(|b| b"&^<>".contains(b)).count();
+        &self.data[start..start + length]
+    }
+
+    pub(crate) fn run(data: &'a str) -> Result<Pipeline, SyntaxError<'a>> {
         Collector::new(data).parse()
     }
 
     pub(crate) fn new(data: &'a str) -> Self { Collector { data } }
 }
 
+/// Whether the word is a `NAME=value` assignment that may precede a command.
+pub(crate) fn is_assignment(arg: &str) -> bool {
+    match arg.find('=') {
+        Some(pos) if pos > 0 => {
+            let name = &arg[..pos];
+            !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Removes the `NAME=value` words which precede a command, returning them as the variables
+/// that are to be assigned for the duration of that command.
+fn assignments(args: &mut Array) -> Vec<(Identifier, Value)> {
+    let count = args.iter().take_while(|arg| is_assignment(arg)).count();
+    // Assignments which aren't followed by a command are left to be reported as a command.
+    if count == 0 || count == args.len() {
+        return Vec::new();
+    }
+
+    let mut words = args.drain().collect::<Vec<_>>();
+    let command = words.split_off(count);
+    args.extend(command);
+    words
+        .into_iter()
+        .map(|word| {
+            let pos = word.find('=').unwrap();
+            (word[..pos].into(), word[pos + 1..].into())
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use parser::{
-        pipelines::{Input, PipeItem, Pipeline, RedirectFrom, Redirection}, statement::parse,
+        pipelines::{
+            Collector, FdRedirection, FileMode, Input, PipeItem, Pipeline, RedirectFrom,
+            Redirection,
+        },
+        statement::parse, SyntaxError,
     };
     use shell::{flow_control::Statement, Job, JobKind};
     use types::Array;
 
     #[test]
     fn stderr_redirection() {
-        if let Statement::Pipeline(pipeline) = parse("git rev-parse --abbrev-ref HEAD ^> /dev/null")
+        if let Statement::Pipeline(pipeline) =
+            parse("git rev-parse --abbrev-ref HEAD ^> /dev/null").unwrap()
         {
             assert_eq!("git", pipeline.items[0].job.args[0]);
             assert_eq!("rev-parse", pipeline.items[0].job.args[1]);
@@ -409,7 +698,7 @@ mod tests {
 
     #[test]
     fn braces() {
-        if let Statement::Pipeline(pipeline) = parse("echo {a b} {a {b c}}") {
+        if let Statement::Pipeline(pipeline) = parse("echo {a b} {a {b c}}").unwrap() {
             let items = pipeline.items;
             assert_eq!("{a b}", items[0].job.args[1]);
             assert_eq!("{a {b c}}", items[0].job.args[2]);
@@ -420,7 +709,9 @@ mod tests {
 
     #[test]
     fn methods() {
-        if let Statement::Pipeline(pipeline) = parse("echo @split(var, ', ') $join(array, ',')") {
+        if let Statement::Pipeline(pipeline) =
+            parse("echo @split(var, ', ') $join(array, ',')").unwrap()
+        {
             let items = pipeline.items;
             assert_eq!("echo", items[0].job.args[0]);
             assert_eq!("@split(var, ', ')", items[0].job.args[1]);
@@ -432,7 +723,9 @@ mod tests {
 
     #[test]
     fn nested_process() {
-        if let Statement::Pipeline(pipeline) = parse("echo $(echo one $(echo two) three)") {
+        if let Statement::Pipeline(pipeline) =
+            parse("echo $(echo one $(echo two) three)").unwrap()
+        {
             let items = pipeline.items;
             assert_eq!("echo", items[0].job.args[0]);
             assert_eq!("$(echo one $(echo two) three)", items[0].job.args[1]);
@@ -443,7 +736,9 @@ mod tests {
 
     #[test]
     fn nested_array_process() {
-        if let Statement::Pipeline(pipeline) = parse("echo @(echo one @(echo two) three)") {
+        if let Statement::Pipeline(pipeline) =
+            parse("echo @(echo one @(echo two) three)").unwrap()
+        {
             let items = pipeline.items;
             assert_eq!("echo", items[0].job.args[0]);
             assert_eq!("@(echo one @(echo two) three)", items[0].job.args[1]);
@@ -454,7 +749,7 @@ mod tests {
 
     #[test]
     fn quoted_process() {
-        if let Statement::Pipeline(pipeline) = parse("echo \"$(seq 1 10)\"") {
+        if let Statement::Pipeline(pipeline) = parse("echo \"$(seq 1 10)\"").unwrap() {
             let items = pipeline.items;
             assert_eq!("echo", items[0].job.args[0]);
             assert_eq!("\"$(seq 1 10)\"", items[0].job.args[1]);
@@ -466,7 +761,7 @@ mod tests {
 
     #[test]
     fn process() {
-        if let Statement::Pipeline(pipeline) = parse("echo $(seq 1 10 | head -1)") {
+        if let Statement::Pipeline(pipeline) = parse("echo $(seq 1 10 | head -1)").unwrap() {
             let items = pipeline.items;
             assert_eq!("echo", items[0].job.args[0]);
             assert_eq!("$(seq 1 10 | head -1)", items[0].job.args[1]);
@@ -478,7 +773,7 @@ mod tests {
 
     #[test]
     fn array_process() {
-        if let Statement::Pipeline(pipeline) = parse("echo @(seq 1 10 | head -1)") {
+        if let Statement::Pipeline(pipeline) = parse("echo @(seq 1 10 | head -1)").unwrap() {
             let items = pipeline.items;
             assert_eq!("echo", items[0].job.args[0]);
             assert_eq!("@(seq 1 10 | head -1)", items[0].job.args[1]);
@@ -490,7 +785,7 @@ mod tests {
 
     #[test]
     fn single_job_no_args() {
-        if let Statement::Pipeline(pipeline) = parse("cat") {
+        if let Statement::Pipeline(pipeline) = parse("cat").unwrap() {
             let items = pipeline.items;
             assert_eq!(1, items.len());
             assert_eq!("cat", items[0].job.command);
@@ -502,7 +797,7 @@ mod tests {
 
     #[test]
     fn single_job_with_single_character_arguments() {
-        if let Statement::Pipeline(pipeline) = parse("echo a b c") {
+        if let Statement::Pipeline(pipeline) = parse("echo a b c").unwrap() {
             let items = pipeline.items;
             assert_eq!(1, items.len());
             assert_eq!("echo", items[0].job.args[0]);
@@ -517,7 +812,7 @@ mod tests {
 
     #[test]
     fn job_with_args() {
-        if let Statement::Pipeline(pipeline) = parse("ls -al dir") {
+        if let Statement::Pipeline(pipeline) = parse("ls -al dir").unwrap() {
             let items = pipeline.items;
             assert_eq!(1, items.len());
             assert_eq!("ls", items[0].job.command);
@@ -530,7 +825,7 @@ mod tests {
 
     #[test]
     fn parse_empty_string() {
-        if let Statement::Default = parse("") {
+        if let Statement::Default = parse("").unwrap() {
             ()
         } else {
             assert!(false);
@@ -539,7 +834,7 @@ mod tests {
 
     #[test]
     fn multiple_white_space_between_words() {
-        if let Statement::Pipeline(pipeline) = parse("ls \t -al\t\tdir") {
+        if let Statement::Pipeline(pipeline) = parse("ls \t -al\t\tdir").unwrap() {
             let items = pipeline.items;
             assert_eq!(1, items.len());
             assert_eq!("ls", items[0].job.command);
@@ -552,7 +847,7 @@ mod tests {
 
     #[test]
     fn trailing_whitespace() {
-        if let Statement::Pipeline(pipeline) = parse("ls -al\t ") {
+        if let Statement::Pipeline(pipeline) = parse("ls -al\t ").unwrap() {
             assert_eq!(1, pipeline.items.len());
             assert_eq!("ls", pipeline.items[0].job.command);
             assert_eq!("-al", pipeline.items[0].job.args[1]);
@@ -563,7 +858,7 @@ mod tests {
 
     #[test]
     fn double_quoting() {
-        if let Statement::Pipeline(pipeline) = parse("echo \"a > 10\" \"a < 10\"") {
+        if let Statement::Pipeline(pipeline) = parse("echo \"a > 10\" \"a < 10\"").unwrap() {
             let items = pipeline.items;
             assert_eq!("\"a > 10\"", items[0].job.args[1]);
             assert_eq!("\"a < 10\"", items[0].job.args[2]);
@@ -575,7 +870,7 @@ mod tests {
 
     #[test]
     fn double_quoting_contains_single() {
-        if let Statement::Pipeline(pipeline) = parse("echo \"Hello 'Rusty' World\"") {
+        if let Statement::Pipeline(pipeline) = parse("echo \"Hello 'Rusty' World\"").unwrap() {
             let items = pipeline.items;
             assert_eq!(2, items[0].job.args.len());
             assert_eq!("\"Hello \'Rusty\' World\"", items[0].job.args[1]);
@@ -586,7 +881,7 @@ mod tests {
 
     #[test]
     fn multi_quotes() {
-        if let Statement::Pipeline(pipeline) = parse("echo \"Hello \"Rusty\" World\"") {
+        if let Statement::Pipeline(pipeline) = parse("echo \"Hello \"Rusty\" World\"").unwrap() {
             let items = pipeline.items;
             assert_eq!(2, items[0].job.args.len());
             assert_eq!("\"Hello \"Rusty\" World\"", items[0].job.args[1]);
@@ -594,7 +889,7 @@ mod tests {
             assert!(false)
         }
 
-        if let Statement::Pipeline(pipeline) = parse("echo \'Hello \'Rusty\' World\'") {
+        if let Statement::Pipeline(pipeline) = parse("echo \'Hello \'Rusty\' World\'").unwrap() {
             let items = pipeline.items;
             assert_eq!(2, items[0].job.args.len());
             assert_eq!("\'Hello \'Rusty\' World\'", items[0].job.args[1]);
@@ -605,7 +900,7 @@ mod tests {
 
     #[test]
     fn all_whitespace() {
-        if let Statement::Default = parse("  \t ") {
+        if let Statement::Default = parse("  \t ").unwrap() {
             ()
         } else {
             assert!(false);
@@ -614,7 +909,7 @@ mod tests {
 
     #[test]
     fn not_background_job() {
-        if let Statement::Pipeline(pipeline) = parse("echo hello world") {
+        if let Statement::Pipeline(pipeline) = parse("echo hello world").unwrap() {
             let items = pipeline.items;
             assert_eq!(JobKind::Last, items[0].job.kind);
         } else {
@@ -624,14 +919,14 @@ mod tests {
 
     #[test]
     fn background_job() {
-        if let Statement::Pipeline(pipeline) = parse("echo hello world&") {
+        if let Statement::Pipeline(pipeline) = parse("echo hello world&").unwrap() {
             let items = pipeline.items;
             assert_eq!(JobKind::Background, items[0].job.kind);
         } else {
             assert!(false);
         }
 
-        if let Statement::Pipeline(pipeline) = parse("echo hello world &") {
+        if let Statement::Pipeline(pipeline) = parse("echo hello world &").unwrap() {
             let items = pipeline.items;
             assert_eq!(JobKind::Background, items[0].job.kind);
         } else {
@@ -641,7 +936,7 @@ mod tests {
 
     #[test]
     fn disown_job() {
-        if let Statement::Pipeline(pipeline) = parse("echo hello world&!") {
+        if let Statement::Pipeline(pipeline) = parse("echo hello world&!").unwrap() {
             let items = pipeline.items;
             assert_eq!(JobKind::Disown, items[0].job.kind);
         } else {
@@ -651,7 +946,7 @@ mod tests {
 
     #[test]
     fn lone_comment() {
-        if let Statement::Default = parse("# ; \t as!!+dfa") {
+        if let Statement::Default = parse("# ; \t as!!+dfa").unwrap() {
             ()
         } else {
             assert!(false);
@@ -660,7 +955,7 @@ mod tests {
 
     #[test]
     fn leading_whitespace() {
-        if let Statement::Pipeline(pipeline) = parse("    \techo") {
+        if let Statement::Pipeline(pipeline) = parse("    \techo").unwrap() {
             let items = pipeline.items;
             assert_eq!(1, items.len());
             assert_eq!("echo", items[0].job.command);
@@ -671,7 +966,7 @@ mod tests {
 
     #[test]
     fn single_quoting() {
-        if let Statement::Pipeline(pipeline) = parse("echo '#!!;\"\\'") {
+        if let Statement::Pipeline(pipeline) = parse("echo '#!!;\"\\'").unwrap() {
             let items = pipeline.items;
             assert_eq!("'#!!;\"\\'", items[0].job.args[1]);
         } else {
@@ -682,7 +977,7 @@ mod tests {
     #[test]
     fn mixed_quoted_and_unquoted() {
         if let Statement::Pipeline(pipeline) =
-            parse("echo 123 456 \"ABC 'DEF' GHI\" 789 one'  'two")
+            parse("echo 123 456 \"ABC 'DEF' GHI\" 789 one'  'two").unwrap()
         {
             let items = pipeline.items;
             assert_eq!("123", items[0].job.args[1]);
@@ -697,7 +992,7 @@ mod tests {
 
     #[test]
     fn several_blank_lines() {
-        if let Statement::Default = parse("\n\n\n") {
+        if let Statement::Default = parse("\n\n\n").unwrap() {
             ()
         } else {
             assert!(false);
@@ -709,7 +1004,7 @@ mod tests {
     // the input redirection shoud be associated with.
     fn pipeline_with_redirection() {
         let input = "cat | echo hello | cat < stuff > other";
-        if let Statement::Pipeline(pipeline) = parse(input) {
+        if let Statement::Pipeline(pipeline) = parse(input).unwrap() {
             assert_eq!(3, pipeline.items.len());
             assert_eq!("cat", &pipeline.clone().items[0].job.args[0]);
             assert_eq!("echo", &pipeline.clone().items[1].job.args[0]);
@@ -728,7 +1023,9 @@ mod tests {
     // FIXME: May need updating after resolution of which part of the pipe
     // the input redirection shoud be associated with.
     fn pipeline_with_redirection_append() {
-        if let Statement::Pipeline(pipeline) = parse("cat | echo hello | cat < stuff >> other") {
+        if let Statement::Pipeline(pipeline) =
+            parse("cat | echo hello | cat < stuff >> other").unwrap()
+        {
             assert_eq!(3, pipeline.items.len());
             assert_eq!(Input::File("stuff".into()), pipeline.items[2].inputs[0]);
             assert_eq!("other", pipeline.items[2].outputs[0].file);
@@ -746,17 +1043,18 @@ mod tests {
         let expected = Pipeline {
             items: vec![
                 PipeItem {
-                    job:     Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
-                    inputs:  vec![
+                    job:         Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
+                    inputs:      vec![
                         Input::File("file1".into()),
                         Input::HereString("\"herestring\"".into()),
                     ],
-                    outputs: Vec::new(),
+                    outputs:     Vec::new(),
+                    descriptors: Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(array!["tr", "'x'", "'y'"], JobKind::Last),
-                    inputs:  Vec::new(),
-                    outputs: vec![
+                    job:         Job::new(array!["tr", "'x'", "'y'"], JobKind::Last),
+                    inputs:      Vec::new(),
+                    outputs:     vec![
                         Redirection {
                             from:   RedirectFrom::Stderr,
                             file:   "err".into(),
@@ -773,10 +1071,11 @@ mod tests {
                             append: false,
                         },
                     ],
+                    descriptors: Vec::new(),
                 },
             ],
         };
-        assert_eq!(parse(input), Statement::Pipeline(expected));
+        assert_eq!(parse(input).unwrap(), Statement::Pipeline(expected));
     }
 
     #[test]
@@ -787,27 +1086,33 @@ mod tests {
         let expected = Pipeline {
             items: vec![
                 PipeItem {
-                    job:     Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
-                    inputs:  Vec::new(),
-                    outputs: Vec::new(),
+                    job:         Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
+                    inputs:      Vec::new(),
+                    outputs:     Vec::new(),
+                    descriptors: Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(array!["echo", "hello"], JobKind::Pipe(RedirectFrom::Stdout)),
-                    inputs:  Vec::new(),
-                    outputs: Vec::new(),
+                    job:         Job::new(
+                        array!["echo", "hello"],
+                        JobKind::Pipe(RedirectFrom::Stdout),
+                    ),
+                    inputs:      Vec::new(),
+                    outputs:     Vec::new(),
+                    descriptors: Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(array!["cat"], JobKind::Last),
-                    inputs:  vec![Input::File("stuff".into())],
-                    outputs: vec![Redirection {
+                    job:         Job::new(array!["cat"], JobKind::Last),
+                    inputs:      vec![Input::File("stuff".into())],
+                    outputs:     vec![Redirection {
                         from:   RedirectFrom::Stderr,
                         file:   "other".into(),
                         append: true,
                     }],
+                    descriptors: Vec::new(),
                 },
             ],
         };
-        assert_eq!(parse(input), Statement::Pipeline(expected));
+        assert_eq!(parse(input).unwrap(), Statement::Pipeline(expected));
     }
 
     #[test]
@@ -818,34 +1123,42 @@ mod tests {
         let expected = Pipeline {
             items: vec![
                 PipeItem {
-                    job:     Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
-                    inputs:  Vec::new(),
-                    outputs: Vec::new(),
+                    job:         Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
+                    inputs:      Vec::new(),
+                    outputs:     Vec::new(),
+                    descriptors: Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(array!["echo", "hello"], JobKind::Pipe(RedirectFrom::Stdout)),
-                    inputs:  Vec::new(),
-                    outputs: Vec::new(),
+                    job:         Job::new(
+                        array!["echo", "hello"],
+                        JobKind::Pipe(RedirectFrom::Stdout),
+                    ),
+                    inputs:      Vec::new(),
+                    outputs:     Vec::new(),
+                    descriptors: Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(array!["cat"], JobKind::Last),
-                    inputs:  vec![Input::File("stuff".into())],
-                    outputs: vec![Redirection {
+                    job:         Job::new(array!["cat"], JobKind::Last),
+                    inputs:      vec![Input::File("stuff".into())],
+                    outputs:     vec![Redirection {
                         from:   RedirectFrom::Both,
                         file:   "other".into(),
                         append: true,
                     }],
+                    descriptors: Vec::new(),
                 },
             ],
         };
-        assert_eq!(parse(input), Statement::Pipeline(expected));
+        assert_eq!(parse(input).unwrap(), Statement::Pipeline(expected));
     }
 
     #[test]
     // FIXME: May need updating after resolution of which part of the pipe
     // the input redirection shoud be associated with.
     fn pipeline_with_redirection_reverse_order() {
-        if let Statement::Pipeline(pipeline) = parse("cat | echo hello | cat > stuff < other") {
+        if let Statement::Pipeline(pipeline) =
+            parse("cat | echo hello | cat > stuff < other").unwrap()
+        {
             assert_eq!(3, pipeline.items.len());
             assert_eq!(vec![Input::File("other".into())], pipeline.items[2].inputs);
             assert_eq!("stuff", pipeline.items[2].outputs[0].file);
@@ -856,7 +1169,7 @@ mod tests {
 
     #[test]
     fn var_meets_quote() {
-        if let Statement::Pipeline(pipeline) = parse("echo $x '{()}' test") {
+        if let Statement::Pipeline(pipeline) = parse("echo $x '{()}' test").unwrap() {
             assert_eq!(1, pipeline.items.len());
             assert_eq!("echo", &pipeline.clone().items[0].job.args[0]);
             assert_eq!("$x", &pipeline.clone().items[0].job.args[1]);
@@ -866,7 +1179,7 @@ mod tests {
             assert!(false);
         }
 
-        if let Statement::Pipeline(pipeline) = parse("echo $x'{()}' test") {
+        if let Statement::Pipeline(pipeline) = parse("echo $x'{()}' test").unwrap() {
             assert_eq!(1, pipeline.items.len());
             assert_eq!("echo", &pipeline.clone().items[0].job.args[0]);
             assert_eq!("$x'{()}'", &pipeline.clone().items[0].job.args[1]);
@@ -876,17 +1189,211 @@ mod tests {
         }
     }
 
+    #[test]
+    fn process_substitutions() {
+        if let Statement::Pipeline(pipeline) = parse("diff <(ls a) >(cat)>out").unwrap() {
+            let items = pipeline.items;
+            assert_eq!(1, items.len());
+            assert_eq!(array!["diff", "<(ls a)", ">(cat)"], items[0].job.args);
+            assert!(items[0].inputs.is_empty());
+            assert_eq!("out", items[0].outputs[0].file);
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn subshells_and_groups() {
+        if let Statement::Pipeline(pipeline) =
+            parse("(cd a; echo \"(\" $(ls)) | { read x; } &").unwrap()
+        {
+            let items = pipeline.items;
+            assert_eq!(2, items.len());
+            assert_eq!(array!["(", "cd a; echo \"(\" $(ls)", ")"], items[0].job.args);
+            assert_eq!(Some(("cd a; echo \"(\" $(ls)", true)), items[0].job.block());
+            assert_eq!(array!["{", " read x; ", "}"], items[1].job.args);
+            assert_eq!(Some((" read x; ", false)), items[1].job.block());
+            assert_eq!(JobKind::Background, items[1].job.kind);
+        } else {
+            assert!(false);
+        }
+
+        // Braces which are not followed by whitespace are brace expansions.
+        if let Statement::Pipeline(pipeline) = parse("{echo,ls} (x)").unwrap() {
+            assert_eq!(array!["{echo,ls}", "(x)"], pipeline.items[0].job.args);
+            assert_eq!(None, pipeline.items[0].job.block());
+        } else {
+            assert!(false);
+        }
+
+        let error = SyntaxError::new("unexpected argument following a subshell or group", "two");
+        assert_eq!(Err(error), Collector::run("(echo one) two"));
+        let error = SyntaxError::new("unterminated subshell", "(echo one");
+        assert_eq!(Err(error), Collector::run("(echo one"));
+        let error = SyntaxError::new("unterminated group", "{ echo one");
+        assert_eq!(Err(error), Collector::run("{ echo one"));
+    }
+
+    #[test]
+    fn environment_assignments() {
+        if let Statement::Pipeline(pipeline) =
+            parse("FOO=bar BAZ=\"a b\" cmd X=y | echo").unwrap()
+        {
+            let items = pipeline.items;
+            assert_eq!(array!["cmd", "X=y"], items[0].job.args);
+            assert_eq!("cmd", items[0].job.command.as_ref());
+            let env = vec![
+                ("FOO".into(), "bar".into()),
+                ("BAZ".into(), "\"a b\"".into()),
+            ];
+            assert_eq!(env, items[0].job.env);
+            assert!(items[1].job.env.is_empty());
+        } else {
+            assert!(false);
+        }
+
+        // Subshells and groups may be given assignments as well.
+        if let Statement::Pipeline(pipeline) = parse("FOO=1 (echo $FOO) | X=y { cat; }").unwrap() {
+            let items = pipeline.items;
+            assert_eq!(Some(("echo $FOO", true)), items[0].job.block());
+            assert_eq!(vec![("FOO".into(), "1".into())], items[0].job.env);
+            assert_eq!(Some((" cat; ", false)), items[1].job.block());
+            assert_eq!(vec![("X".into(), "y".into())], items[1].job.env);
+        } else {
+            assert!(false);
+        }
+        let error = SyntaxError::new("unexpected argument following a subshell or group", "two");
+        assert_eq!(Err(error), Collector::run("FOO=1 (echo one) two"));
+
+        // Without a command, or with an invalid name, the words are not assignments.
+        for input in &["FOO=bar", "1X=y cmd", "=x cmd", "a-b=c cmd"] {
+            if let Statement::Pipeline(pipeline) = parse(input).unwrap() {
+                assert!(pipeline.items[0].job.env.is_empty());
+            } else {
+                assert!(false);
+            }
+        }
+    }
+
+    #[test]
+    fn fd_redirections() {
+        if let Statement::Pipeline(pipeline) =
+            parse("cmd 3>log 2>&1 4<>rw >&- <&3 ^>&1 arg 3>>x").unwrap()
+        {
+            let item = &pipeline.items[0];
+            assert_eq!(array!["cmd", "arg"], item.job.args);
+            assert_eq!(
+                vec![
+                    FdRedirection::Open {
+                        fd:   3,
+                        file: "log".into(),
+                        mode: FileMode::Write,
+                    },
+                    FdRedirection::Duplicate { fd: 2, source: 1 },
+                    FdRedirection::Open {
+                        fd:   4,
+                        file: "rw".into(),
+                        mode: FileMode::ReadWrite,
+                    },
+                    FdRedirection::Close(1),
+                    FdRedirection::Duplicate { fd: 0, source: 3 },
+                    FdRedirection::Duplicate { fd: 2, source: 1 },
+                    FdRedirection::Open {
+                        fd:   3,
+                        file: "x".into(),
+                        mode: FileMode::Append,
+                    },
+                ],
+                item.descriptors
+            );
+        } else {
+            assert!(false);
+        }
+
+        // Descriptors may be given by expansions, which are resolved as the command executes.
+        if let Statement::Pipeline(pipeline) = parse("cmd >&$fd 3<&@fds[0]").unwrap() {
+            assert_eq!(
+                vec![
+                    FdRedirection::DuplicateWord {
+                        fd:     1,
+                        source: "$fd".into(),
+                    },
+                    FdRedirection::DuplicateWord {
+                        fd:     3,
+                        source: "@fds[0]".into(),
+                    },
+                ],
+                pipeline.items[0].descriptors
+            );
+        } else {
+            assert!(false);
+        }
+
+        // Numbers which don't directly precede a redirection are arguments.
+        if let Statement::Pipeline(pipeline) = parse("echo 2 1a>out 3<(cat)").unwrap() {
+            let item = &pipeline.items[0];
+            assert_eq!(array!["echo", "2", "1a", "3<(cat)"], item.job.args);
+            assert_eq!("out", item.outputs[0].file);
+            assert!(item.descriptors.is_empty());
+        } else {
+            assert!(false);
+        }
+
+        let error = SyntaxError::new("expected file descriptor or '-' after '>&' or '<&'", ">&");
+        assert_eq!(Err(error), Collector::run("echo >&x"));
+    }
+
+    #[test]
+    fn fd_redirection_ordering() {
+        // Redirections of the standard streams which follow a numbered redirection are applied
+        // in order, rather than being teed.
+        if let Statement::Pipeline(pipeline) =
+            parse("cmd 2>&1 > out &> both < in | tr a b").unwrap()
+        {
+            let item = &pipeline.items[0];
+            assert!(item.outputs.is_empty());
+            assert!(item.inputs.is_empty());
+            assert_eq!(
+                vec![
+                    FdRedirection::Duplicate { fd: 2, source: 1 },
+                    FdRedirection::Open {
+                        fd:   1,
+                        file: "out".into(),
+                        mode: FileMode::Write,
+                    },
+                    FdRedirection::Open {
+                        fd:   1,
+                        file: "both".into(),
+                        mode: FileMode::Write,
+                    },
+                    FdRedirection::Duplicate { fd: 2, source: 1 },
+                    FdRedirection::Open {
+                        fd:   0,
+                        file: "in".into(),
+                        mode: FileMode::Read,
+                    },
+                ],
+                item.descriptors
+            );
+            assert!(pipeline.items[1].descriptors.is_empty());
+            assert_eq!("cmd 2>&1 > out > both 2>&1 < in | tr a b", pipeline.to_string());
+        } else {
+            assert!(false);
+        }
+    }
+
     #[test]
     fn herestring() {
         let input = "calc <<< $(cat math.txt)";
         let expected = Pipeline {
             items: vec![PipeItem {
-                job:     Job::new(array!["calc"], JobKind::Last),
-                inputs:  vec![Input::HereString("$(cat math.txt)".into())],
-                outputs: vec![],
+                job:         Job::new(array!["calc"], JobKind::Last),
+                inputs:      vec![Input::HereString("$(cat math.txt)".into())],
+                outputs:     vec![],
+                descriptors: Vec::new(),
             }],
         };
-        assert_eq!(Statement::Pipeline(expected), parse(input));
+        assert_eq!(Statement::Pipeline(expected), parse(input).unwrap());
     }
 
     #[test]
@@ -894,12 +1401,45 @@ mod tests {
         let input = "calc << EOF\n1 + 2\n3 + 4\nEOF";
         let expected = Pipeline {
             items: vec![PipeItem {
-                job:     Job::new(array!["calc"], JobKind::Last),
-                inputs:  vec![Input::HereString("1 + 2\n3 + 4".into())],
-                outputs: vec![],
+                job:         Job::new(array!["calc"], JobKind::Last),
+                inputs:      vec![Input::HereDoc {
+                    body:   "1 + 2\n3 + 4\n".into(),
+                    expand: true,
+                }],
+                outputs:     vec![],
+                descriptors: Vec::new(),
             }],
         };
-        assert_eq!(Statement::Pipeline(expected), parse(input));
+        assert_eq!(Statement::Pipeline(expected), parse(input).unwrap());
+    }
+
+    #[test]
+    fn heredoc_with_redirections() {
+        let input = "cat <<-'EOF' | tr a b > out\n    $one\n      two\n    EOF";
+        let expected = Pipeline {
+            items: vec![
+                PipeItem {
+                    job:         Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
+                    inputs:      vec![Input::HereDoc {
+                        body:   "$one\n  two\n".into(),
+                        expand: false,
+                    }],
+                    outputs:     vec![],
+                    descriptors: Vec::new(),
+                },
+                PipeItem {
+                    job:         Job::new(array!["tr", "a", "b"], JobKind::Last),
+                    inputs:      vec![],
+                    outputs:     vec![Redirection {
+                        from:   RedirectFrom::Stdout,
+                        file:   "out".into(),
+                        append: false,
+                    }],
+                    descriptors: Vec::new(),
+                },
+            ],
+        };
+        assert_eq!(Statement::Pipeline(expected), parse(input).unwrap());
     }
 
     #[test]
@@ -910,27 +1450,31 @@ mod tests {
         let expected = Pipeline {
             items: vec![
                 PipeItem {
-                    job:     Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
-                    inputs:  Vec::new(),
-                    outputs: Vec::new(),
+                    job:         Job::new(array!["cat"], JobKind::Pipe(RedirectFrom::Stdout)),
+                    inputs:      Vec::new(),
+                    outputs:     Vec::new(),
+                    descriptors: Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(array!["tr", "'o'", "'x'"], JobKind::Last),
-                    inputs:  vec![Input::HereString("$VAR".into())],
-                    outputs: vec![Redirection {
+                    job:         Job::new(array!["tr", "'o'", "'x'"], JobKind::Last),
+                    inputs:      vec![Input::HereString("$VAR".into())],
+                    outputs:     vec![Redirection {
                         from:   RedirectFrom::Stdout,
                         file:   "out.log".into(),
                         append: false,
                     }],
+                    descriptors: Vec::new(),
                 },
             ],
         };
-        assert_eq!(Statement::Pipeline(expected), parse(input));
+        assert_eq!(Statement::Pipeline(expected), parse(input).unwrap());
     }
 
     #[test]
     fn awk_tests() {
-        if let Statement::Pipeline(pipeline) = parse("awk -v x=$x '{ if (1) print $1 }' myfile") {
+        if let Statement::Pipeline(pipeline) =
+            parse("awk -v x=$x '{ if (1) print $1 }' myfile").unwrap()
+        {
             assert_eq!(1, pipeline.items.len());
             assert_eq!("awk", &pipeline.clone().items[0].job.args[0]);
             assert_eq!("-v", &pipeline.clone().items[0].job.args[1]);
@@ -950,16 +1494,17 @@ mod tests {
         let input = "echo zardoz >> foo\\'bar";
         let expected = Pipeline {
             items: vec![PipeItem {
-                job:     Job::new(array!["echo", "zardoz"], JobKind::Last),
-                inputs:  Vec::new(),
-                outputs: vec![Redirection {
+                job:         Job::new(array!["echo", "zardoz"], JobKind::Last),
+                inputs:      Vec::new(),
+                outputs:     vec![Redirection {
                     from:   RedirectFrom::Stdout,
                     file:   "foo\\'bar".into(),
                     append: true,
                 }],
+                descriptors: Vec::new(),
             }],
         };
-        assert_eq!(parse(input), Statement::Pipeline(expected));
+        assert_eq!(parse(input).unwrap(), Statement::Pipeline(expected));
     }
 
 }
@@ -2,9 +2,9 @@ mod collector;
 
 pub(crate) use self::collector::*;
 
-use super::expand_string;
+use super::{expand_string, heredoc};
 use shell::{Job, JobKind, Shell};
-use std::fmt;
+use std::{fmt, os::unix::io::RawFd};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum RedirectFrom {
@@ -20,6 +20,85 @@ pub(crate) struct Redirection {
     pub append: bool,
 }
 
+impl Redirection {
+    /// The equivalent redirections of numbered descriptors, which are applied in order.
+    pub(crate) fn descriptors(&self) -> Vec<FdRedirection> {
+        let mode = if self.append { FileMode::Append } else { FileMode::Write };
+        let fd = if self.from == RedirectFrom::Stderr { 2 } else { 1 };
+        let open = FdRedirection::Open { fd, file: self.file.clone(), mode };
+        if self.from == RedirectFrom::Both {
+            vec![open, FdRedirection::Duplicate { fd: 2, source: 1 }]
+        } else {
+            vec![open]
+        }
+    }
+}
+
+/// The manner in which a file is opened upon a numbered file descriptor.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum FileMode {
+    Read,
+    Write,
+    Append,
+    ReadWrite,
+}
+
+/// A redirection upon a numbered file descriptor. Unlike the redirections of the standard
+/// streams, which may be teed, these are applied one after another in the order given.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum FdRedirection {
+    /// `N>file`, `N>>file`, `N<file` and `N<>file` open the file upon descriptor `N`.
+    Open { fd: RawFd, file: String, mode: FileMode },
+    /// `N>&M` and `N<&M` make descriptor `N` a copy of descriptor `M`.
+    Duplicate { fd: RawFd, source: RawFd },
+    /// `N>&$var` and `N<&$var` duplicate the descriptor that the word expands to.
+    DuplicateWord { fd: RawFd, source: String },
+    /// `N>&-` and `N<&-` close descriptor `N`.
+    Close(RawFd),
+}
+
+impl FdRedirection {
+    /// The descriptor that is modified by this redirection.
+    pub(crate) fn fd(&self) -> RawFd {
+        match *self {
+            FdRedirection::Open { fd, .. }
+            | FdRedirection::Duplicate { fd, .. }
+            | FdRedirection::DuplicateWord { fd, .. }
+            | FdRedirection::Close(fd) => fd,
+        }
+    }
+}
+
+impl fmt::Display for FdRedirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Descriptors are omitted when they are the default for the operator.
+        match *self {
+            FdRedirection::Open { fd, ref file, mode } => {
+                let operator = match mode {
+                    FileMode::Read => "<",
+                    FileMode::Write => ">",
+                    FileMode::Append => ">>",
+                    FileMode::ReadWrite => "<>",
+                };
+                match (fd, mode) {
+                    (0, FileMode::Read)
+                    | (0, FileMode::ReadWrite)
+                    | (1, FileMode::Write)
+                    | (1, FileMode::Append) => write!(f, "{} {}", operator, file),
+                    _ => write!(f, "{}{} {}", fd, operator, file),
+                }
+            }
+            FdRedirection::Duplicate { fd: 0, source } => write!(f, "<&{}", source),
+            FdRedirection::Duplicate { fd: 1, source } => write!(f, ">&{}", source),
+            FdRedirection::Duplicate { fd, source } => write!(f, "{}>&{}", fd, source),
+            FdRedirection::DuplicateWord { fd: 0, ref source } => write!(f, "<&{}", source),
+            FdRedirection::DuplicateWord { fd: 1, ref source } => write!(f, ">&{}", source),
+            FdRedirection::DuplicateWord { fd, ref source } => write!(f, "{}>&{}", fd, source),
+            FdRedirection::Close(fd) => write!(f, "{}>&-", fd),
+        }
+    }
+}
+
 /// Represents input that a process could initially receive from `stdin`
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum Input {
@@ -27,8 +106,10 @@ pub(crate) enum Input {
     /// process
     File(String),
     /// A string literal that is written to the `stdin` of a process.
-    /// If there is a second string, that second string is the EOF phrase for the heredoc.
     HereString(String),
+    /// The body of a heredoc, which is written to the `stdin` of a process. Expansions are
+    /// performed on the body, as if it were double-quoted, unless its delimiter was quoted.
+    HereDoc { body: String, expand: bool },
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -38,14 +119,19 @@ pub(crate) struct Pipeline {
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct PipeItem {
-    pub job:     Job,
-    pub outputs: Vec<Redirection>,
-    pub inputs:  Vec<Input>,
+    pub job:         Job,
+    pub outputs:     Vec<Redirection>,
+    pub inputs:      Vec<Input>,
+    pub descriptors: Vec<FdRedirection>,
 }
 
 impl PipeItem {
     pub(crate) fn expand(&mut self, shell: &Shell) {
-        self.job.expand(shell);
+        // The arguments of a function are expanded when they are checked, as it is called.
+        if !shell.functions.contains_key(&self.job.command) {
+            self.job.expand(shell);
+        }
+        self.job.expand_environment(shell);
 
         for input in self.inputs.iter_mut() {
             *input = match input {
@@ -53,19 +139,44 @@ impl PipeItem {
                 &mut Input::HereString(ref s) => {
                     Input::HereString(expand_string(s, shell, true).join(" "))
                 }
+                // The expanded body is marked as literal so that it won't be expanded twice.
+                &mut Input::HereDoc { ref body, expand: true } => {
+                    let quoted = heredoc::quote(body);
+                    Input::HereDoc {
+                        body:   expand_string(&quoted, shell, false).join(" "),
+                        expand: false,
+                    }
+                }
+                &mut Input::HereDoc { .. } => continue,
             };
         }
 
         for output in self.outputs.iter_mut() {
             output.file = expand_string(output.file.as_str(), shell, false).join(" ");
         }
+
+        for descriptor in self.descriptors.iter_mut() {
+            match *descriptor {
+                FdRedirection::Open { file: ref mut word, .. }
+                | FdRedirection::DuplicateWord { source: ref mut word, .. } => {
+                    *word = expand_string(word.as_str(), shell, false).join(" ");
+                }
+                _ => (),
+            }
+        }
     }
 
-    pub(crate) fn new(job: Job, outputs: Vec<Redirection>, inputs: Vec<Input>) -> Self {
+    pub(crate) fn new(
+        job: Job,
+        outputs: Vec<Redirection>,
+        inputs: Vec<Input>,
+        descriptors: Vec<FdRedirection>,
+    ) -> Self {
         PipeItem {
             job,
             outputs,
             inputs,
+            descriptors,
         }
     }
 }
@@ -75,6 +186,7 @@ impl Pipeline {
         self.items.len() > 1
             || self.items.iter().any(|it| it.outputs.len() > 0)
             || self.items.iter().any(|it| it.inputs.len() > 0)
+            || self.items.iter().any(|it| it.descriptors.len() > 0)
             || self.items.last().unwrap().job.kind == JobKind::Background
             || self.items.last().unwrap().job.kind == JobKind::Disown
     }
@@ -94,6 +206,9 @@ impl fmt::Display for Pipeline {
             let kind = job.kind;
             let inputs = &item.inputs;
             let outputs = &item.outputs;
+            for &(ref name, ref value) in &job.env {
+                tokens.push([name.as_ref(), "=", value].concat());
+            }
             tokens.extend(item.job.args.clone().into_iter());
             for input in inputs {
                 match input {
@@ -101,7 +216,7 @@ impl fmt::Display for Pipeline {
                         tokens.push("<".into());
                         tokens.push(file.clone());
                     }
-                    &Input::HereString(ref string) => {
+                    &Input::HereString(ref string) | &Input::HereDoc { body: ref string, .. } => {
                         tokens.push("<<<".into());
                         tokens.push(string.clone());
                     }
@@ -121,6 +236,7 @@ impl fmt::Display for Pipeline {
                 }
                 tokens.push(output.file.clone());
             }
+            tokens.extend(item.descriptors.iter().map(|descriptor| descriptor.to_string()));
             match kind {
                 JobKind::Last => (),
                 JobKind::Background => tokens.push("&".into()),
@@ -1,4 +1,5 @@
-use std::str;
+use super::heredoc::{self, Delimiter};
+use std::collections::VecDeque;
 
 bitflags! {
     pub struct Flags : u8 {
@@ -20,9 +21,11 @@ bitflags! {
 /// will only be submitted for execution once a terminated command is supplied.
 pub struct Terminator {
     buffer:     String,
-    eof:        Option<String>,
+    eof:        VecDeque<(String, bool)>,
     eof_buffer: String,
     array:      usize,
+    paren:      usize,
+    brace:      usize,
     read:       usize,
     flags:      Flags,
 }
@@ -39,13 +42,17 @@ impl Terminator {
     /// Consumes the `Terminator`, and returns the underlying `String`.
     pub fn consume(self) -> String { self.buffer }
 
+    /// Returns true if the terminator is collecting the body of a heredoc, in which case
+    /// comments should not be stripped from the lines that are appended.
+    pub fn is_heredoc(&self) -> bool { !self.eof.is_empty() }
+
     pub fn is_terminated(&mut self) -> bool {
         let mut eof_line = None;
-        let eof = self.eof.clone();
-        let status = if let Some(ref eof) = eof {
+        let eof = self.eof.front().cloned();
+        let mut status = if let Some((ref tag, strip)) = eof {
             let line = &self.eof_buffer;
             eof_line = Some([&line, "\n"].concat());
-            line.trim() == eof
+            heredoc::terminates(tag, strip, line)
         } else {
             {
                 let mut instance = Flags::empty();
@@ -63,17 +70,41 @@ impl Terminator {
                             b'"' if !self.flags.intersects(Flags::SQUOTE) => {
                                 self.flags ^= Flags::DQUOTE
                             }
-                            b'<' if !self.flags.contains(Flags::SQUOTE | Flags::DQUOTE) => {
-                                let as_bytes = self.buffer.as_bytes();
-                                if Some(&b'<') == as_bytes.get(self.read) {
+                            b'(' if !self.flags.intersects(Flags::SQUOTE | Flags::DQUOTE) => {
+                                self.paren += 1;
+                            }
+                            b')' if !self.flags.intersects(Flags::SQUOTE | Flags::DQUOTE) => {
+                                self.paren = self.paren.saturating_sub(1);
+                            }
+                            b'{' if !self.flags.intersects(Flags::SQUOTE | Flags::DQUOTE) => {
+                                self.brace += 1;
+                            }
+                            b'}' if !self.flags.intersects(Flags::SQUOTE | Flags::DQUOTE) => {
+                                self.brace = self.brace.saturating_sub(1);
+                            }
+                            // Heredocs are not supported within subshells, groups and arithmetic.
+                            b'<' if !self.flags.intersects(Flags::SQUOTE | Flags::DQUOTE)
+                                && self.paren == 0
+                                && self.brace == 0 =>
+                            {
+                                if Some(&b'<') == self.buffer.as_bytes().get(self.read) {
+                                    let _ = bytes.next();
                                     self.read += 1;
-                                    if Some(&b'<') != as_bytes.get(self.read) {
-                                        let eof_phrase = unsafe {
-                                            str::from_utf8_unchecked(&as_bytes[self.read..])
-                                        };
-                                        self.eof = Some(eof_phrase.trim().to_owned());
+                                    if Some(&b'<') == self.buffer.as_bytes().get(self.read) {
+                                        let _ = bytes.next();
+                                        self.read += 1;
+                                    } else if let Some(delimiter) =
+                                        Delimiter::parse(&self.buffer[self.read..])
+                                    {
+                                        // The bodies are collected in the order that their
+                                        // heredocs were written.
+                                        for _ in 0..delimiter.length {
+                                            let _ = bytes.next();
+                                        }
+                                        self.read += delimiter.length;
+                                        let tag = delimiter.tag.to_owned();
+                                        self.eof.push_back((tag, delimiter.strip));
                                         instance |= Flags::EOF;
-                                        break;
                                     }
                                 }
                             }
@@ -114,14 +145,25 @@ impl Terminator {
                     self.buffer.clear();
                     self.buffer.push('\n');
                     return true;
-                } else if instance.contains(Flags::EOF) {
+                }
+
+                if instance.contains(Flags::COMM) {
+                    self.buffer.truncate(self.read - 1);
+                    if !instance.contains(Flags::EOF) {
+                        let quoted = self
+                            .flags
+                            .intersects(Flags::SQUOTE | Flags::DQUOTE | Flags::ARRAY);
+                        if !quoted && self.paren + self.brace != 0 {
+                            self.buffer.push('\n');
+                            return false;
+                        }
+                        return !quoted;
+                    }
+                }
+
+                if instance.contains(Flags::EOF) {
                     self.buffer.push('\n');
                     return false;
-                } else if instance.contains(Flags::COMM) {
-                    self.buffer.truncate(self.read - 1);
-                    return !self
-                        .flags
-                        .intersects(Flags::SQUOTE | Flags::DQUOTE | Flags::ARRAY);
                 }
             }
 
@@ -148,6 +190,11 @@ impl Terminator {
                     self.read -= 1;
                     self.flags |= Flags::TRIM;
                     false
+                } else if self.paren + self.brace != 0 {
+                    // The bodies of subshells and groups may span multiple lines.
+                    self.read += 1;
+                    self.buffer.push('\n');
+                    false
                 } else {
                     // If the last two bytes are either '&&' or '||', we aren't terminated yet.
                     let bytes = self.buffer.as_bytes();
@@ -164,10 +211,11 @@ impl Terminator {
         if let Some(line) = eof_line {
             self.buffer.push_str(&line);
         }
-        if self.eof.is_some() {
+        if !self.eof.is_empty() {
             self.eof_buffer.clear();
             if status {
-                self.eof = None;
+                let _ = self.eof.pop_front();
+                status = self.eof.is_empty();
             }
         }
         status
@@ -175,7 +223,7 @@ impl Terminator {
 
     /// Appends a string to the internal buffer.
     pub fn append(&mut self, input: &str) {
-        if self.eof.is_none() {
+        if self.eof.is_empty() {
             self.buffer.push_str(if self.flags.contains(Flags::TRIM) {
                 input.trim()
             } else {
@@ -189,9 +237,11 @@ impl Terminator {
     pub fn new(input: String) -> Terminator {
         Terminator {
             buffer:     input,
-            eof:        None,
+            eof:        VecDeque::new(),
             eof_buffer: String::new(),
             array:      0,
+            paren:      0,
+            brace:      0,
             read:       0,
             flags:      Flags::empty(),
         }
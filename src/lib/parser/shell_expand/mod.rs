@@ -6,7 +6,10 @@ mod braces;
 mod ranges;
 mod words;
 
-pub(crate) use self::words::{Index, Range, Select, WordIterator, WordToken};
+pub(crate) use self::words::{
+    Index, Range, Select, SelectWithSize, WordIterator, WordToken, ARRAY_METHODS,
+    STRING_METHODS,
+};
 use self::{braces::BraceToken, ranges::parse_range};
 use glob::glob;
 use std::{ptr, str};
@@ -30,10 +33,19 @@ pub(crate) trait Expander {
     fn tilde(&self, &str) -> Option<String> { None }
     /// Expand an array variable with some selection
     fn array(&self, &str, Select) -> Option<Array> { None }
+    /// Obtain a copy of a map variable
+    fn map(&self, &str) -> Option<Map> { None }
+    /// Expand a nested variable, where each selection is applied to the result of the last
+    fn nested(&self, &str, &[Select]) -> Option<Array> { None }
     /// Expand a string variable given if its quoted / unquoted
     fn variable(&self, &str, bool) -> Option<Value> { None }
     /// Expand a subshell expression
     fn command(&self, &str) -> Option<Value> { None }
+    /// Expand a process substitution into the path of its pipe, where the boolean is set if
+    /// the process will read from that path
+    fn substitution(&self, &str, bool) -> Option<Value> { None }
+    /// Notes a reference to a variable or array which expanded to nothing
+    fn unbound(&self, &str) {}
 }
 
 fn expand_process<E: Expander>(
@@ -188,6 +200,24 @@ fn slice<S: AsRef<str>>(output: &mut String, expanded: S, selection: Select) {
     }
 }
 
+/// Expands a string variable, or otherwise a nested variable, whose selected elements are
+/// joined by spaces.
+fn expand_variable<E: Expander>(
+    output: &mut String,
+    expand_func: &E,
+    name: &str,
+    quoted: bool,
+    selection: &Select,
+) {
+    match expand_func.variable(name, quoted) {
+        Some(value) => slice(output, value, selection.clone()),
+        None => match expand_func.nested(name, &[selection.clone()]) {
+            Some(array) => output.push_str(&array.join(" ")),
+            None => expand_func.unbound(name),
+        },
+    }
+}
+
 /// Performs shell expansions to an input string, efficiently returning the final
 /// expanded form. Shells must provide their own batteries for expanding tilde
 /// and variable words.
@@ -235,8 +265,16 @@ fn expand_braces<E: Expander>(
                 output.push_str(&array_expand(elements, expand_func, index.clone()).join(" "));
             }
             WordToken::ArrayVariable(array, _, ref index) => {
-                if let Some(array) = expand_func.array(array, index.clone()) {
-                    output.push_str(&array.join(" "));
+                match expand_func.array(array, index.clone()) {
+                    Some(array) => output.push_str(&array.join(" ")),
+                    None => expand_func.unbound(array),
+                }
+            }
+            WordToken::NestedVariable(name, _, ref selections)
+            | WordToken::NestedArrayVariable(name, _, ref selections) => {
+                match expand_func.nested(name, selections) {
+                    Some(array) => output.push_str(&array.join(" ")),
+                    None => expand_func.unbound(name),
                 }
             }
             WordToken::ArrayProcess(command, _, ref index) => match *index {
@@ -291,14 +329,14 @@ fn expand_braces<E: Expander>(
                 let quoted = if reverse_quoting { !quoted } else { quoted };
                 expand_process(&mut output, command, index.clone(), expand_func, quoted);
             }
+            WordToken::Substitution(command, writable) => {
+                if let Some(path) = expand_func.substitution(command, writable) {
+                    output.push_str(&path);
+                }
+            }
             WordToken::Variable(text, quoted, ref index) => {
                 let quoted = if reverse_quoting { !quoted } else { quoted };
-                let expanded = match expand_func.variable(text, quoted) {
-                    Some(var) => var,
-                    None => continue,
-                };
-
-                slice(&mut output, expanded, index.clone());
+                expand_variable(&mut output, expand_func, text, quoted, index);
             }
             WordToken::Normal(text, do_glob, tilde) => {
                 expand(
@@ -343,7 +381,20 @@ fn expand_single_array_token<E: Expander>(token: &WordToken, expand_func: &E) ->
             match expand_func.array(array, index.clone()) {
                 Some(ref array) if quoted => Some(array.join(" ").into()).into_iter().collect(),
                 Some(array) => Some(array),
-                None => Some(Array::new()),
+                None => {
+                    expand_func.unbound(array);
+                    Some(Array::new())
+                }
+            }
+        }
+        WordToken::NestedArrayVariable(name, quoted, ref selections) => {
+            match expand_func.nested(name, selections) {
+                Some(ref array) if quoted => Some(array.join(" ").into()).into_iter().collect(),
+                Some(array) => Some(array),
+                None => {
+                    expand_func.unbound(name);
+                    Some(Array::new())
+                }
             }
         }
         WordToken::ArrayProcess(command, _, ref index) => match *index {
@@ -422,19 +473,20 @@ fn expand_single_string_token<E: Expander>(
             let quoted = if reverse_quoting { !quoted } else { quoted };
             expand_process(&mut output, command, index.clone(), expand_func, quoted);
         }
+        WordToken::Substitution(command, writable) => {
+            if let Some(path) = expand_func.substitution(command, writable) {
+                output.push_str(&path);
+            }
+        }
         WordToken::Variable(text, quoted, ref index) => {
             let quoted = if reverse_quoting { !quoted } else { quoted };
-            let expanded = match expand_func.variable(text, quoted) {
-                Some(var) => var,
-                None => {
-                    if output != "" {
-                        expanded_words.push(output.into());
-                    }
-                    return expanded_words;
-                }
-            };
-
-            slice(&mut output, expanded, index.clone());
+            expand_variable(&mut output, expand_func, text, quoted, index);
+        }
+        WordToken::NestedVariable(name, _, ref selections) => {
+            match expand_func.nested(name, selections) {
+                Some(array) => output.push_str(&array.join(" ")),
+                None => expand_func.unbound(name),
+            }
         }
         WordToken::Arithmetic(s) => expand_arithmetic(&mut output, s, expand_func),
         _ => unreachable!(),
@@ -508,8 +560,16 @@ pub(crate) fn expand_tokens<E: Expander>(
                     output.push_str(&array_expand(elements, expand_func, index.clone()).join(" "));
                 }
                 WordToken::ArrayVariable(array, _, ref index) => {
-                    if let Some(array) = expand_func.array(array, index.clone()) {
-                        output.push_str(&array.join(" "));
+                    match expand_func.array(array, index.clone()) {
+                        Some(array) => output.push_str(&array.join(" ")),
+                        None => expand_func.unbound(array),
+                    }
+                }
+                WordToken::NestedVariable(name, _, ref selections)
+                | WordToken::NestedArrayVariable(name, _, ref selections) => {
+                    match expand_func.nested(name, selections) {
+                        Some(array) => output.push_str(&array.join(" ")),
+                        None => expand_func.unbound(name),
                     }
                 }
                 WordToken::ArrayProcess(command, _, ref index) => match index.clone() {
@@ -569,14 +629,14 @@ pub(crate) fn expand_tokens<E: Expander>(
                     let quoted = if reverse_quoting { !quoted } else { quoted };
                     expand_process(&mut output, command, index.clone(), expand_func, quoted);
                 }
+                WordToken::Substitution(command, writable) => {
+                    if let Some(path) = expand_func.substitution(command, writable) {
+                        output.push_str(&path);
+                    }
+                }
                 WordToken::Variable(text, quoted, ref index) => {
                     let quoted = if reverse_quoting { !quoted } else { quoted };
-                    let expanded = match expand_func.variable(text, quoted) {
-                        Some(var) => var,
-                        None => continue,
-                    };
-
-                    slice(&mut output, expanded, index.clone());
+                    expand_variable(&mut output, expand_func, text, quoted, index);
                 }
                 WordToken::Arithmetic(s) => expand_arithmetic(&mut output, s, expand_func),
             }
@@ -658,6 +718,20 @@ mod test {
 
     impl Expander for CommandExpander {
         fn command(&self, cmd: &str) -> Option<Value> { Some(cmd.to_owned()) }
+
+        fn substitution(&self, cmd: &str, writable: bool) -> Option<Value> {
+            Some(format!("/dev/fd/{}{}", cmd, if writable { "w" } else { "r" }))
+        }
+    }
+
+    #[test]
+    fn expand_process_substitutions() {
+        let expanded = expand_string("<(a)", &CommandExpander, false);
+        assert_eq!(array!["/dev/fd/ar"], expanded);
+        let expanded = expand_string(">(a)", &CommandExpander, false);
+        assert_eq!(array!["/dev/fd/aw"], expanded);
+        let expanded = expand_string("'<(a)'", &CommandExpander, false);
+        assert_eq!(array!["<(a)"], expanded);
     }
 
     #[test]
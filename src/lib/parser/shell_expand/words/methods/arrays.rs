@@ -6,7 +6,7 @@ use super::{
 };
 use smallstring::SmallString;
 use std::char;
-use types::Array;
+use types::{Array, Map};
 use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -157,6 +157,35 @@ impl<'a> ArrayMethod<'a> {
         Ok(res)
     }
 
+    fn keys<E: Expander>(&self, expand_func: &E) -> Result<Array, &'static str> {
+        let map = self.resolve_map(expand_func)?;
+        let keys = map.iter()
+            .map(|(key, _)| key.to_string())
+            .select(self.selection.clone(), map.len());
+        Ok(keys)
+    }
+
+    fn values<E: Expander>(&self, expand_func: &E) -> Result<Array, &'static str> {
+        let map = self.resolve_map(expand_func)?;
+        let values = map.iter()
+            .map(|(_, value)| value.clone())
+            .select(self.selection.clone(), map.len());
+        Ok(values)
+    }
+
+    fn pairs<E: Expander>(&self, expand_func: &E) -> Result<Array, &'static str> {
+        let map = self.resolve_map(expand_func)?;
+        let pairs = map.iter()
+            .flat_map(|(key, value)| array![key.to_string(), value.clone()])
+            .select(self.selection.clone(), map.len() * 2);
+        Ok(pairs)
+    }
+
+    #[inline]
+    fn resolve_map<E: Expander>(&self, expand_func: &E) -> Result<Map, &'static str> {
+        expand_func.map(self.variable).ok_or("requires the name of a map")
+    }
+
     #[inline]
     fn resolve_array<E: Expander>(&self, expand_func: &E) -> Array {
         if let Some(array) = expand_func.array(self.variable, Select::All) {
@@ -188,6 +217,9 @@ impl<'a> ArrayMethod<'a> {
             "chars" => self.chars(expand_func),
             "lines" => self.lines(expand_func),
             "reverse" => self.reverse(expand_func),
+            "keys" => self.keys(expand_func),
+            "values" => self.values(expand_func),
+            "pairs" => self.pairs(expand_func),
             _ => Err("invalid array method"),
         };
 
@@ -214,7 +246,7 @@ mod test {
     use super::{
         super::{super::Range, Key}, *,
     };
-    use types::Value;
+    use types::{OrderedMap, Value};
 
     struct VariableExpander;
 
@@ -226,6 +258,18 @@ mod test {
             }
         }
 
+        fn map(&self, variable: &str) -> Option<Map> {
+            match variable {
+                "MAP" => {
+                    let mut map = OrderedMap::new();
+                    map.insert("b".into(), "1".into());
+                    map.insert("a".into(), "2".into());
+                    Some(Map::Ordered(map))
+                }
+                _ => None,
+            }
+        }
+
         fn variable(&self, variable: &str, _: bool) -> Option<Value> {
             match variable {
                 "FOO" => Some("FOOBAR".to_owned()),
@@ -482,4 +526,40 @@ mod test {
             array!["c", "b", "a"]
         );
     }
+
+    #[test]
+    fn test_keys() {
+        let method = ArrayMethod {
+            method:    "keys",
+            variable:  "MAP",
+            pattern:   Pattern::Whitespace,
+            selection: Select::All,
+        };
+        assert_eq!(method.handle_as_array(&VariableExpander), array!["b", "a"]);
+    }
+
+    #[test]
+    fn test_values() {
+        let method = ArrayMethod {
+            method:    "values",
+            variable:  "MAP",
+            pattern:   Pattern::Whitespace,
+            selection: Select::Index(Index::Backward(0)),
+        };
+        assert_eq!(method.handle_as_array(&VariableExpander), array!["2"]);
+    }
+
+    #[test]
+    fn test_pairs() {
+        let method = ArrayMethod {
+            method:    "pairs",
+            variable:  "MAP",
+            pattern:   Pattern::Whitespace,
+            selection: Select::All,
+        };
+        assert_eq!(
+            method.handle_as_array(&VariableExpander),
+            array!["b", "1", "a", "2"]
+        );
+    }
 }
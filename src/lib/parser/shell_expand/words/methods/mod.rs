@@ -3,6 +3,44 @@ mod strings;
 
 pub(crate) use self::{arrays::ArrayMethod, strings::StringMethod};
 
+/// The names of the methods which expand to a string, such as `$len(array)`.
+pub(crate) const STRING_METHODS: &[&str] = &[
+    "basename",
+    "contains",
+    "ends_with",
+    "escape",
+    "extension",
+    "filename",
+    "find",
+    "join",
+    "len",
+    "len_bytes",
+    "parent",
+    "regex_replace",
+    "repeat",
+    "replace",
+    "replacen",
+    "reverse",
+    "starts_with",
+    "to_lowercase",
+    "to_uppercase",
+    "unescape",
+];
+
+/// The names of the methods which expand to an array, such as `@split(string)`.
+pub(crate) const ARRAY_METHODS: &[&str] = &[
+    "bytes",
+    "chars",
+    "graphemes",
+    "keys",
+    "lines",
+    "pairs",
+    "reverse",
+    "split",
+    "split_at",
+    "values",
+];
+
 use self::strings::unescape;
 use super::{super::super::ArgumentSplitter, expand_string, Expander};
 
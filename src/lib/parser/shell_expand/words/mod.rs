@@ -8,7 +8,8 @@ mod tests;
 #[cfg(test)]
 pub(crate) use self::methods::Key;
 pub(crate) use self::{
-    index::Index, methods::{ArrayMethod, Pattern, StringMethod}, range::Range,
+    index::Index,
+    methods::{ArrayMethod, Pattern, StringMethod, ARRAY_METHODS, STRING_METHODS}, range::Range,
     select::{Select, SelectWithSize},
 };
 use super::{super::ArgumentSplitter, expand_string, Expander};
@@ -39,8 +40,15 @@ pub(crate) enum WordToken<'a> {
     Array(Vec<&'a str>, Select),
     Variable(&'a str, bool, Select),
     ArrayVariable(&'a str, bool, Select),
+    /// A variable which is followed by a chain of selections, as in `$hosts[0][name]`
+    NestedVariable(&'a str, bool, Vec<Select>),
+    /// An array variable which is followed by a chain of selections, as in `@hosts[0][ports]`
+    NestedArrayVariable(&'a str, bool, Vec<Select>),
     ArrayProcess(&'a str, bool, Select),
     Process(&'a str, bool, Select),
+    /// A process substitution, where the boolean is set for the `>(cmd)` form, in which the
+    /// process reads from the substituted path
+    Substitution(&'a str, bool),
     StringMethod(StringMethod<'a>),
     ArrayMethod(ArrayMethod<'a>),
     Arithmetic(&'a str), // Glob(&'a str)
@@ -205,7 +213,7 @@ impl<'a, E: Expander + 'a> WordIterator<'a, E> {
                 b'\\' => self.flags ^= Flags::BACKSL,
                 b'\'' if !self.flags.contains(Flags::DQUOTE) => self.flags ^= Flags::SQUOTE,
                 b'"' if !self.flags.contains(Flags::SQUOTE) => self.flags ^= Flags::DQUOTE,
-                b'@' if !self.flags.contains(Flags::SQUOTE) => {
+                b'@' | b'<' | b'>' if !self.flags.contains(Flags::SQUOTE) => {
                     if self.data.as_bytes()[self.read + 1] == b'(' {
                         level += 1;
                     }
@@ -252,7 +260,7 @@ impl<'a, E: Expander + 'a> WordIterator<'a, E> {
                 b'\\' => self.flags ^= Flags::BACKSL,
                 b'\'' if !self.flags.contains(Flags::DQUOTE) => self.flags ^= Flags::SQUOTE,
                 b'"' if !self.flags.contains(Flags::SQUOTE) => self.flags ^= Flags::DQUOTE,
-                b'$' if !self.flags.contains(Flags::SQUOTE) => {
+                b'$' | b'<' | b'>' if !self.flags.contains(Flags::SQUOTE) => {
                     if self.data.as_bytes()[self.read + 1] == b'(' {
                         // Pop out the '(' char
                         iterator.next();
@@ -293,6 +301,17 @@ impl<'a, E: Expander + 'a> WordIterator<'a, E> {
         panic!("ion: fatal error with syntax validation: unterminated process");
     }
 
+    /// Contains the logic for parsing process substitution syntax.
+    fn substitution<I>(&mut self, iterator: &mut I, writable: bool) -> WordToken<'a>
+    where
+        I: Iterator<Item = u8>,
+    {
+        match self.process(iterator) {
+            WordToken::Process(command, ..) => WordToken::Substitution(command, writable),
+            token => token,
+        }
+    }
+
     fn braced_array_variable<I>(&mut self, iterator: &mut I) -> WordToken<'a>
     where
         I: Iterator<Item = u8>,
@@ -422,11 +441,19 @@ impl<'a, E: Expander + 'a> WordIterator<'a, E> {
                     panic!("ion: fatal error with syntax validation parsing: unterminated method");
                 }
                 b'[' => {
-                    return WordToken::ArrayVariable(
-                        &self.data[start..self.read],
-                        self.flags.contains(Flags::DQUOTE),
-                        self.read_selection(iterator),
-                    )
+                    let array = &self.data[start..self.read];
+                    let quoted = self.flags.contains(Flags::DQUOTE);
+                    let mut selections = self.read_selections(iterator);
+                    return if selections.len() == 1 {
+                        WordToken::ArrayVariable(array, quoted, selections.remove(0))
+                    } else {
+                        WordToken::NestedArrayVariable(array, quoted, selections)
+                    };
+                }
+                // A `::` pattern designates the namespace of the variable.
+                b':' if self.data.as_bytes().get(self.read + 1) == Some(&b':') => {
+                    let _ = iterator.next();
+                    self.read += 1;
                 }
                 // Only alphanumerical and underscores are allowed in variable names
                 0...47 | 58...64 | 91...94 | 96 | 123...127 => {
@@ -471,6 +498,19 @@ impl<'a, E: Expander + 'a> WordIterator<'a, E> {
         panic!()
     }
 
+    /// Reads a selection, along with each of the selections which are chained after it.
+    fn read_selections<I>(&mut self, iterator: &mut I) -> Vec<Select>
+    where
+        I: Iterator<Item = u8>,
+    {
+        let mut selections = vec![self.read_selection(iterator)];
+        while let Some(&b'[') = self.data.as_bytes().get(self.read) {
+            let _ = iterator.next();
+            selections.push(self.read_selection(iterator));
+        }
+        selections
+    }
+
     /// Contains the logic for parsing variable syntax
     fn variable<I>(&mut self, iterator: &mut I) -> WordToken<'a>
     where
@@ -557,16 +597,23 @@ impl<'a, E: Expander + 'a> WordIterator<'a, E> {
 
                     panic!("ion: fatal error with syntax validation parsing: unterminated method");
                 }
+                // A `::` pattern designates the namespace of the variable.
+                b':' if self.data.as_bytes().get(self.read + 1) == Some(&b':') => {
+                    let _ = iterator.next();
+                    self.read += 1;
+                }
                 // Only alphanumerical and underscores are allowed in variable names
                 0...47 | 58...64 | 91...94 | 96 | 123...127 => {
                     let variable = &self.data[start..self.read];
 
                     return if character == b'[' {
-                        WordToken::Variable(
-                            variable,
-                            self.flags.contains(Flags::DQUOTE),
-                            self.read_selection(iterator),
-                        )
+                        let quoted = self.flags.contains(Flags::DQUOTE);
+                        let mut selections = self.read_selections(iterator);
+                        if selections.len() == 1 {
+                            WordToken::Variable(variable, quoted, selections.remove(0))
+                        } else {
+                            WordToken::NestedVariable(variable, quoted, selections)
+                        }
                     } else {
                         WordToken::Variable(
                             variable,
@@ -656,7 +703,12 @@ impl<'a, E: Expander + 'a> Iterator for WordIterator<'a, E> {
                         break;
                     }
                     b'\\' => {
-                        if !self.flags.intersects(Flags::DQUOTE | Flags::SQUOTE) {
+                        // Within double quotes, a backslash is only removed when it escapes.
+                        let escapes = self.flags.contains(Flags::DQUOTE)
+                            && self.data.as_bytes().get(self.read + 1).map_or(false, |&next| {
+                                next == b'$' || next == b'\\' || next == b'"'
+                            });
+                        if escapes || !self.flags.intersects(Flags::DQUOTE | Flags::SQUOTE) {
                             start += 1;
                         }
                         self.read += 1;
@@ -745,6 +797,13 @@ impl<'a, E: Expander + 'a> Iterator for WordIterator<'a, E> {
                             }
                         }
                     }
+                    b'<' | b'>' if !self.flags.intersects(Flags::DQUOTE | Flags::SQUOTE)
+                        && self.data.as_bytes().get(self.read + 1) == Some(&b'(') =>
+                    {
+                        let _ = iterator.next();
+                        self.read += 2;
+                        return Some(self.substitution(&mut iterator, character == b'>'));
+                    }
                     b'*' | b'?' => {
                         self.read += 1;
                         glob = true;
@@ -83,6 +83,20 @@ fn array_variables() {
     compare(input, expected);
 }
 
+#[test]
+fn namespaced_variables() {
+    let input = "$global::foo @super::bar[0] $foo:bar";
+    let expected = vec![
+        WordToken::Variable("global::foo", false, Select::All),
+        WordToken::Whitespace(" "),
+        WordToken::ArrayVariable("super::bar", false, Select::Index(Index::new(0))),
+        WordToken::Whitespace(" "),
+        WordToken::Variable("foo", false, Select::All),
+        WordToken::Normal(":bar", false, false),
+    ];
+    compare(input, expected);
+}
+
 #[test]
 fn array_processes() {
     let input = "@(echo one two three) @(echo one two three)[0]";
@@ -148,6 +162,31 @@ fn string_keys() {
     compare(input, expected);
 }
 
+#[test]
+fn chained_selections() {
+    let input = "$hosts[0][name] @hosts[-1][ports][1..] $hosts[0]";
+    let expected = vec![
+        WordToken::NestedVariable(
+            "hosts",
+            false,
+            vec![Select::Index(Index::new(0)), Select::Key(Key::new("name"))],
+        ),
+        WordToken::Whitespace(" "),
+        WordToken::NestedArrayVariable(
+            "hosts",
+            false,
+            vec![
+                Select::Index(Index::new(-1)),
+                Select::Key(Key::new("ports")),
+                Select::Range(Range::from(Index::new(1))),
+            ],
+        ),
+        WordToken::Whitespace(" "),
+        WordToken::Variable("hosts", false, Select::Index(Index::new(0))),
+    ];
+    compare(input, expected);
+}
+
 #[test]
 fn nested_processes() {
     let input = "echo $(echo $(echo one)) $(echo one $(echo two) three)";
@@ -161,6 +200,21 @@ fn nested_processes() {
     compare(input, expected);
 }
 
+#[test]
+fn process_substitutions() {
+    let input = "diff <(sort $(echo a)) >(cat) \"<(quoted)\"";
+    let expected = vec![
+        WordToken::Normal("diff", false, false),
+        WordToken::Whitespace(" "),
+        WordToken::Substitution("sort $(echo a)", false),
+        WordToken::Whitespace(" "),
+        WordToken::Substitution("cat", true),
+        WordToken::Whitespace(" "),
+        WordToken::Normal("<(quoted)", false, false),
+    ];
+    compare(input, expected);
+}
+
 #[test]
 fn words_process_with_quotes() {
     let input = "echo $(git branch | rg '[*]' | awk '{print $2}')";
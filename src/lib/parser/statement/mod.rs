@@ -4,20 +4,50 @@ mod parse;
 mod splitter;
 
 pub(crate) use self::{
-    parse::parse, splitter::{StatementVariant, StatementError, StatementSplitter},
+    parse::parse, splitter::{StatementVariant, StatementSplitter},
 };
+use super::{pipelines::{Input, Pipeline}, SyntaxError};
 use shell::flow_control::Statement;
+use std::vec;
 
 /// Parses a given statement string and return's the corresponding mapped
 /// `Statement`
-pub(crate) fn parse_and_validate<'a>(statement: Result<StatementVariant, StatementError>) -> Statement {
-    match statement {
-        Ok(StatementVariant::And(statement)) => Statement::And(Box::new(parse(statement))),
-        Ok(StatementVariant::Or(statement)) => Statement::Or(Box::new(parse(statement))),
-        Ok(StatementVariant::Default(statement)) => parse(statement),
-        Err(err) => {
-            eprintln!("ion: {}", err);
-            Statement::Error(-1)
+pub(crate) fn parse_and_validate<'a>(
+    statement: Result<StatementVariant<'a>, SyntaxError<'a>>,
+) -> Result<Statement, SyntaxError<'a>> {
+    match statement? {
+        StatementVariant::And(statement) => Ok(Statement::And(Box::new(parse(statement)?))),
+        StatementVariant::Or(statement) => Ok(Statement::Or(Box::new(parse(statement)?))),
+        StatementVariant::Default(statement) => parse(statement),
+        StatementVariant::Heredocs(statement, bodies) => {
+            let mut statement = parse_and_validate(Ok(*statement))?;
+            supply_heredocs(&mut statement, &mut bodies.into_iter());
+            Ok(statement)
+        }
+    }
+}
+
+/// Supplies the bodies of the heredocs of a statement, in the order that they were written.
+fn supply_heredocs(statement: &mut Statement, bodies: &mut vec::IntoIter<String>) {
+    let pipeline: &mut Pipeline = match *statement {
+        Statement::Pipeline(ref mut pipeline)
+        | Statement::If { expression: ref mut pipeline, .. }
+        | Statement::While { expression: ref mut pipeline, .. }
+        | Statement::End(Some(ref mut pipeline))
+        | Statement::Coproc { pipeline: Some(ref mut pipeline), .. } => pipeline,
+        Statement::ElseIf(ref mut elseif) => &mut elseif.expression,
+        Statement::Time(ref mut statement)
+        | Statement::And(ref mut statement)
+        | Statement::Or(ref mut statement)
+        | Statement::Not(ref mut statement) => return supply_heredocs(statement, bodies),
+        _ => return,
+    };
+
+    for item in &mut pipeline.items {
+        for input in &mut item.inputs {
+            if let Input::HereDoc { ref mut body, .. } = *input {
+                *body = bodies.next().unwrap_or_default();
+            }
         }
     }
 }
@@ -1,39 +1,91 @@
 use super::{
     super::{
         assignments::{split_assignment, Operator}, pipelines::{self, Pipeline}, ArgumentSplitter,
+        SyntaxError,
     },
     case, functions::{collect_arguments, parse_function},
 };
-use shell::flow_control::{Case, ElseIf, ExportAction, LocalAction, Statement};
+use shell::{
+    flow_control::{Case, ElseIf, ExportAction, LocalAction, Statement}, JobKind,
+};
 use std::char;
 
-fn collect<F>(arguments: &str, statement: F) -> Statement
+fn collect<F>(arguments: &str, statement: F) -> Result<Statement, SyntaxError>
 where
     F: Fn(Pipeline) -> Statement,
 {
-    match pipelines::Collector::run(arguments) {
-        Ok(pipeline) => statement(pipeline),
-        Err(err) => {
-            eprintln!("ion: syntax error: {}", err);
-            return Statement::Default;
+    pipelines::Collector::run(arguments).map(statement)
+}
+
+/// Parses the redirections and pipes that follow the `end` of a block. The block itself is
+/// represented by the first item of the pipeline.
+fn parse_block_redirection(cmd: &str) -> Result<Pipeline, SyntaxError> {
+    let pipeline = pipelines::Collector::run(cmd)?;
+    let block = &pipeline.items[0];
+    if block.job.args.len() != 1 {
+        let arguments = cmd[3..].split(|c| "|&^<>".contains(c)).next().unwrap().trim();
+        Err(SyntaxError::new("unexpected arguments after end", arguments))
+    } else if block.job.kind == JobKind::Background || block.job.kind == JobKind::Disown {
+        let background = cmd.rfind('&').map_or(cmd, |pos| &cmd[pos..]);
+        Err(SyntaxError::new("blocks may not be sent to the background", background))
+    } else {
+        Ok(pipeline)
+    }
+}
+
+/// Parses the arguments of `coproc`, which are `-c [NAME]` to close the input of a coprocess,
+/// or an optional name followed by the pipeline to execute. As with other shells, a name may
+/// only be given when the pipeline is a subshell or group, and otherwise defaults to `COPROC`.
+fn parse_coproc(args: &str) -> Result<Statement, SyntaxError> {
+    if args == "-c" || args.starts_with("-c ") {
+        let name = args[2..].trim();
+        let name = if name.is_empty() { "COPROC" } else { name };
+        if !is_valid_name(name) {
+            return Err(SyntaxError::new(
+                format!("'{}' is not a valid coprocess name", name),
+                name,
+            ));
         }
+        return Ok(Statement::Coproc {
+            name:     name.into(),
+            pipeline: None,
+        });
     }
+
+    let (name, command) = match args.find(char::is_whitespace) {
+        Some(pos) if is_valid_name(&args[..pos]) => {
+            let command = args[pos..].trim_left();
+            if command.starts_with('(')
+                || (command.starts_with('{') && command[1..].starts_with(char::is_whitespace))
+            {
+                (&args[..pos], command)
+            } else {
+                ("COPROC", args)
+            }
+        }
+        _ => ("COPROC", args),
+    };
+
+    collect(command, |pipeline| Statement::Coproc {
+        name:     name.into(),
+        pipeline: Some(pipeline),
+    })
 }
 
 fn is_valid_name(name: &str) -> bool { !name.chars().any(|c| !(c.is_alphanumeric() || c == '_')) }
 
-pub(crate) fn parse(code: &str) -> Statement {
+pub(crate) fn parse(code: &str) -> Result<Statement, SyntaxError> {
     let cmd = code.trim();
     match cmd {
-        "end" => return Statement::End,
-        "break" => return Statement::Break,
-        "continue" => return Statement::Continue,
+        "end" => return Ok(Statement::End(None)),
+        "break" => return Ok(Statement::Break),
+        "continue" => return Ok(Statement::Continue),
+        "return" => return Ok(Statement::Return(None)),
         "for" | "match" | "case" => {
-            eprintln!("ion: syntax error: incomplete control flow statement");
-            return Statement::Default;
+            return Err(SyntaxError::new("incomplete control flow statement", cmd));
         }
         "let" => {
-            return Statement::Let(LocalAction::List);
+            return Ok(Statement::Let(LocalAction::List));
         }
         _ if cmd.starts_with("let ") => {
             // Split the let expression and ensure that the statement is valid.
@@ -44,12 +96,12 @@ pub(crate) fn parse(code: &str) -> Statement {
                     (keys.unwrap().into(), op.unwrap(), vals.into())
                 }
                 None => {
-                    if op.is_none() {
-                        eprintln!("ion: assignment error: no operator supplied.");
+                    let message = if op.is_none() {
+                        "no operator was supplied to the assignment"
                     } else {
-                        eprintln!("ion: assignment error: no values supplied.")
-                    }
-                    return Statement::Default;
+                        "no values were supplied to the assignment"
+                    };
+                    return Err(SyntaxError::new(message, cmd));
                 }
             };
 
@@ -57,16 +109,13 @@ pub(crate) fn parse(code: &str) -> Statement {
             // statement.
             match Operator::parse(op) {
                 Ok(operator) => {
-                    return Statement::Let(LocalAction::Assign(keys, operator, values));
-                }
-                Err(why) => {
-                    eprintln!("ion: assignment error: {}", why);
-                    return Statement::Default;
+                    return Ok(Statement::Let(LocalAction::Assign(keys, operator, values)));
                 }
+                Err(why) => return Err(SyntaxError::new(why.to_string(), op)),
             }
         }
         "export" => {
-            return Statement::Export(ExportAction::List);
+            return Ok(Statement::Export(ExportAction::List));
         }
         _ if cmd.starts_with("export ") => {
             // Split the let expression and ensure that the statement is valid.
@@ -77,14 +126,15 @@ pub(crate) fn parse(code: &str) -> Statement {
                     (keys.unwrap().into(), op.unwrap(), vals.into())
                 }
                 None => {
-                    if keys.is_none() {
-                        eprintln!("ion: assignment error: no keys supplied.")
+                    let message = if keys.is_none() {
+                        "no keys were supplied to the assignment"
                     } else if op.is_some() {
-                        eprintln!("ion: assignment error: no values supplied.")
+                        "no values were supplied to the assignment"
                     } else {
-                        return Statement::Export(ExportAction::LocalExport(keys.unwrap().into()));
-                    }
-                    return Statement::Default;
+                        let keys = keys.unwrap().into();
+                        return Ok(Statement::Export(ExportAction::LocalExport(keys)));
+                    };
+                    return Err(SyntaxError::new(message, cmd));
                 }
             };
 
@@ -92,14 +142,20 @@ pub(crate) fn parse(code: &str) -> Statement {
             // statement.
             match Operator::parse(op) {
                 Ok(operator) => {
-                    return Statement::Export(ExportAction::Assign(keys, operator, values));
-                }
-                Err(why) => {
-                    eprintln!("ion: assignment error: {}", why);
-                    return Statement::Default;
+                    return Ok(Statement::Export(ExportAction::Assign(keys, operator, values)));
                 }
+                Err(why) => return Err(SyntaxError::new(why.to_string(), op)),
             }
         }
+        _ if cmd.starts_with("return ") => {
+            return Ok(Statement::Return(Some(cmd[7..].trim_left().into())))
+        }
+        // Redirections and pipes which follow the end of a block apply to the entire block.
+        _ if cmd.starts_with("end")
+            && cmd[3..].starts_with(|c: char| c.is_whitespace() || "|&^<>".contains(c)) =>
+        {
+            return parse_block_redirection(cmd).map(|pipeline| Statement::End(Some(pipeline)))
+        }
         _ if cmd.starts_with("if ") => {
             return collect(cmd[3..].trim_left(), |pipeline| Statement::If {
                 expression: pipeline,
@@ -108,11 +164,11 @@ pub(crate) fn parse(code: &str) -> Statement {
                 failure:    Vec::new(),
             })
         }
-        "else" => return Statement::Else,
+        "else" => return Ok(Statement::Else),
         _ if cmd.starts_with("else") => {
             let cmd = cmd[4..].trim_left();
             if cmd.len() == 0 {
-                return Statement::Else;
+                return Ok(Statement::Else);
             } else if cmd.starts_with("if ") {
                 return collect(cmd[3..].trim_left(), |pipeline| {
                     Statement::ElseIf(ElseIf {
@@ -129,30 +185,34 @@ pub(crate) fn parse(code: &str) -> Statement {
             })
         }
         _ if cmd.starts_with("for ") => {
+            // Every word that precedes the `in` keyword is a loop variable.
             let mut cmd = cmd[4..].trim_left();
-            let pos = match cmd.find(char::is_whitespace) {
-                Some(pos) => pos,
-                None => {
-                    eprintln!("ion: syntax error: incorrect for loop syntax");
-                    return Statement::Default;
-                }
-            };
-
-            let variable = &cmd[..pos];
-            cmd = &cmd[pos..].trim_left();
+            let mut variables = Vec::new();
+            while variables.is_empty() || !cmd.starts_with("in ") {
+                let pos = match cmd.find(char::is_whitespace) {
+                    Some(pos) => pos,
+                    None if variables.is_empty() => {
+                        return Err(SyntaxError::new("incorrect for loop syntax", cmd))
+                    }
+                    None => {
+                        return Err(SyntaxError::new(
+                            "expected 'in' after the for loop variables",
+                            cmd,
+                        ))
+                    }
+                };
 
-            if !cmd.starts_with("in ") {
-                eprintln!("ion: syntax error: incorrect for loop syntax");
-                return Statement::Default;
+                variables.push(cmd[..pos].into());
+                cmd = &cmd[pos..].trim_left();
             }
 
-            return Statement::For {
-                variable:   variable.into(),
+            return Ok(Statement::For {
+                variables,
                 values:     ArgumentSplitter::new(cmd[3..].trim_left())
                     .map(String::from)
                     .collect(),
                 statements: Vec::new(),
-            };
+            });
         }
         _ if cmd.starts_with("case ") => {
             let (value, binding, conditional) = match cmd[5..].trim_left() {
@@ -160,10 +220,7 @@ pub(crate) fn parse(code: &str) -> Statement {
                 value @ _ => {
                     let (value, binding, conditional) = match case::parse_case(value) {
                         Ok(values) => values,
-                        Err(why) => {
-                            eprintln!("ion: case error: {}", why);
-                            return Statement::Default;
-                        }
+                        Err(why) => return Err(SyntaxError::new(why.to_string(), value)),
                     };
                     let binding = binding.map(Into::into);
                     match value {
@@ -174,67 +231,70 @@ pub(crate) fn parse(code: &str) -> Statement {
                 }
             };
 
-            return Statement::Case(Case {
+            return Ok(Statement::Case(Case {
                 value,
                 binding,
                 conditional,
                 statements: Vec::new(),
-            });
+            }));
         }
         _ if cmd.starts_with("match ") => {
-            return Statement::Match {
+            return Ok(Statement::Match {
                 expression: cmd[6..].trim_left().into(),
                 cases:      Vec::new(),
-            }
+            })
         }
         _ if cmd.starts_with("fn ") => {
             let cmd = cmd[3..].trim_left();
             let pos = cmd.find(char::is_whitespace).unwrap_or(cmd.len());
             let name = &cmd[..pos];
             if !is_valid_name(name) {
-                eprintln!(
-                    "ion: syntax error: '{}' is not a valid function name\n     Function names \
-                     may only contain alphanumeric characters",
-                    name
-                );
-                return Statement::Default;
+                return Err(SyntaxError::new(
+                    format!(
+                        "'{}' is not a valid function name, as function names may only contain \
+                         alphanumeric characters",
+                        name
+                    ),
+                    name,
+                ));
             }
 
             let (args, description) = parse_function(&cmd[pos..]);
             match collect_arguments(args) {
                 Ok(args) => {
-                    return Statement::Function {
+                    return Ok(Statement::Function {
                         description: description.map(String::from),
                         name: name.into(),
                         args,
                         statements: Vec::new(),
-                    }
-                }
-                Err(why) => {
-                    eprintln!("ion: function argument error: {}", why);
-                    return Statement::Default;
+                    })
                 }
+                Err(why) => return Err(SyntaxError::new(why.to_string(), cmd[pos..].trim())),
             }
         }
+        _ if cmd.starts_with("coproc ") => return parse_coproc(cmd[6..].trim_left()),
+        _ if cmd.eq("coproc") => return Err(SyntaxError::new("coproc requires a command", cmd)),
         _ if cmd.starts_with("time ") => {
-            return Statement::Time(Box::new(parse(cmd[4..].trim_left())))
+            return Ok(Statement::Time(Box::new(parse(cmd[4..].trim_left())?)))
         }
-        _ if cmd.eq("time") => return Statement::Time(Box::new(Statement::Default)),
+        _ if cmd.eq("time") => return Ok(Statement::Time(Box::new(Statement::Default))),
         _ if cmd.starts_with("and ") => {
-            return Statement::And(Box::new(parse(cmd[3..].trim_left())))
+            return Ok(Statement::And(Box::new(parse(cmd[3..].trim_left())?)))
+        }
+        _ if cmd.eq("and") => return Ok(Statement::And(Box::new(Statement::Default))),
+        _ if cmd.starts_with("or ") => {
+            return Ok(Statement::Or(Box::new(parse(cmd[2..].trim_left())?)))
         }
-        _ if cmd.eq("and") => return Statement::And(Box::new(Statement::Default)),
-        _ if cmd.starts_with("or ") => return Statement::Or(Box::new(parse(cmd[2..].trim_left()))),
-        _ if cmd.eq("or") => return Statement::Or(Box::new(Statement::Default)),
+        _ if cmd.eq("or") => return Ok(Statement::Or(Box::new(Statement::Default))),
         _ if cmd.starts_with("not ") => {
-            return Statement::Not(Box::new(parse(cmd[3..].trim_left())))
+            return Ok(Statement::Not(Box::new(parse(cmd[3..].trim_left())?)))
         }
-        _ if cmd.eq("not") => return Statement::Not(Box::new(Statement::Default)),
+        _ if cmd.eq("not") => return Ok(Statement::Not(Box::new(Statement::Default))),
         _ => (),
     }
 
     if cmd.is_empty() || cmd.starts_with('#') {
-        Statement::Default
+        Ok(Statement::Default)
     } else {
         collect(cmd, Statement::Pipeline)
     }
@@ -242,19 +302,20 @@ pub(crate) fn parse(code: &str) -> Statement {
 
 #[cfg(test)]
 mod tests {
-    use self::pipelines::PipeItem;
+    use self::pipelines::{FdRedirection, FileMode, PipeItem, RedirectFrom};
     use super::*;
     use parser::assignments::{KeyBuf, Primitive};
-    use shell::{flow_control::Statement, Job, JobKind};
+    use shell::{flow_control::Statement, Job};
+    use types::Array;
 
     #[test]
     fn parsing_ifs() {
         // Default case where spaced normally
-        let parsed_if = parse("if test 1 -eq 2");
+        let parsed_if = parse("if test 1 -eq 2").unwrap();
         let correct_parse = Statement::If {
             expression: Pipeline {
                 items: vec![PipeItem {
-                    job:     Job::new(
+                    job:         Job::new(
                         vec![
                             "test".to_owned(),
                             "1".to_owned(),
@@ -264,8 +325,9 @@ mod tests {
                             .collect(),
                         JobKind::Last,
                     ),
-                    outputs: Vec::new(),
-                    inputs:  Vec::new(),
+                    outputs:     Vec::new(),
+                    inputs:      Vec::new(),
+                    descriptors: Vec::new(),
                 }],
             },
             success:    vec![],
@@ -275,48 +337,132 @@ mod tests {
         assert_eq!(correct_parse, parsed_if);
 
         // Trailing spaces after final value
-        let parsed_if = parse("if test 1 -eq 2         ");
+        let parsed_if = parse("if test 1 -eq 2         ").unwrap();
         assert_eq!(correct_parse, parsed_if);
     }
 
     #[test]
     fn parsing_elses() {
         // Default case where spaced normally
-        let mut parsed_if = parse("else");
+        let mut parsed_if = parse("else").unwrap();
         let correct_parse = Statement::Else;
         assert_eq!(correct_parse, parsed_if);
 
         // Trailing spaces after final value
-        parsed_if = parse("else         ");
+        parsed_if = parse("else         ").unwrap();
         assert_eq!(correct_parse, parsed_if);
 
         // Leading spaces after final value
-        parsed_if = parse("         else");
+        parsed_if = parse("         else").unwrap();
         assert_eq!(correct_parse, parsed_if);
     }
 
+    #[test]
+    fn parsing_returns() {
+        assert_eq!(Statement::Return(None), parse("return").unwrap());
+        assert_eq!(Statement::Return(None), parse("   return   ").unwrap());
+        assert_eq!(Statement::Return(Some("1".into())), parse("return 1").unwrap());
+        assert_eq!(Statement::Return(Some("$?".into())), parse("return   $? ").unwrap());
+    }
+
+    #[test]
+    fn parsing_fors() {
+        let correct_parse = Statement::For {
+            variables:  vec!["key".into(), "value".into()],
+            values:     vec!["@pairs(map)".into()],
+            statements: Vec::new(),
+        };
+        assert_eq!(correct_parse, parse("for key value in @pairs(map)").unwrap());
+        assert_eq!(correct_parse, parse("  for  key   value  in  @pairs(map)").unwrap());
+        assert!(parse("for key").is_err());
+        assert!(parse("for key value").is_err());
+    }
+
     #[test]
     fn parsing_ends() {
         // Default case where spaced normally
-        let parsed_if = parse("end");
-        let correct_parse = Statement::End;
+        let parsed_if = parse("end").unwrap();
+        let correct_parse = Statement::End(None);
         assert_eq!(correct_parse, parsed_if);
 
         // Trailing spaces after final value
-        let parsed_if = parse("end         ");
-        let correct_parse = Statement::End;
+        let parsed_if = parse("end         ").unwrap();
+        let correct_parse = Statement::End(None);
         assert_eq!(correct_parse, parsed_if);
 
         // Leading spaces after final value
-        let parsed_if = parse("         end");
-        let correct_parse = Statement::End;
+        let parsed_if = parse("         end").unwrap();
+        let correct_parse = Statement::End(None);
         assert_eq!(correct_parse, parsed_if);
     }
 
+    #[test]
+    fn parsing_redirected_ends() {
+        if let Statement::End(Some(pipeline)) = parse("end 2>&1 >> report | sort").unwrap() {
+            assert_eq!(2, pipeline.items.len());
+            assert_eq!(array!["end"], pipeline.items[0].job.args);
+            assert_eq!(JobKind::Pipe(RedirectFrom::Stdout), pipeline.items[0].job.kind);
+            assert_eq!(
+                vec![
+                    FdRedirection::Duplicate { fd: 2, source: 1 },
+                    FdRedirection::Open {
+                        fd:   1,
+                        file: "report".into(),
+                        mode: FileMode::Append,
+                    },
+                ],
+                pipeline.items[0].descriptors
+            );
+            assert_eq!(array!["sort"], pipeline.items[1].job.args);
+        } else {
+            assert!(false);
+        }
+
+        let error = parse("end extra > file").unwrap_err();
+        assert_eq!(("unexpected arguments after end", "extra"), (&*error.message, error.source));
+        let error = parse("end > file &").unwrap_err();
+        assert_eq!("blocks may not be sent to the background", error.message);
+        assert_eq!("&", error.source);
+        match parse("endless > file").unwrap() {
+            Statement::Pipeline(_) => (),
+            statement => panic!("expected a pipeline, found {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn parsing_coprocesses() {
+        let coproc = |name: &str, args: Array| Statement::Coproc {
+            name:     name.into(),
+            pipeline: Some(Pipeline {
+                items: vec![PipeItem {
+                    job:         Job::new(args, JobKind::Last),
+                    outputs:     Vec::new(),
+                    inputs:      Vec::new(),
+                    descriptors: Vec::new(),
+                }],
+            }),
+        };
+        assert_eq!(coproc("COPROC", array!["bc", "-q"]), parse("coproc bc -q").unwrap());
+        assert_eq!(coproc("COPROC", array!["cat", "file"]), parse("coproc cat file").unwrap());
+        assert_eq!(
+            coproc("calc", array!["{", " bc; ", "}"]),
+            parse("coproc calc { bc; }").unwrap()
+        );
+        assert_eq!(
+            Statement::Coproc {
+                name:     "calc".into(),
+                pipeline: None,
+            },
+            parse("coproc -c calc").unwrap()
+        );
+        let code = "coproc -c not-a-name";
+        assert_eq!(Some(10..20), parse(code).unwrap_err().span(code));
+    }
+
     #[test]
     fn parsing_functions() {
         // Default case where spaced normally
-        let parsed_if = parse("fn bob");
+        let parsed_if = parse("fn bob").unwrap();
         let correct_parse = Statement::Function {
             description: None,
             name:        "bob".into(),
@@ -326,15 +472,15 @@ mod tests {
         assert_eq!(correct_parse, parsed_if);
 
         // Trailing spaces after final value
-        let parsed_if = parse("fn bob        ");
+        let parsed_if = parse("fn bob        ").unwrap();
         assert_eq!(correct_parse, parsed_if);
 
         // Leading spaces after final value
-        let parsed_if = parse("         fn bob");
+        let parsed_if = parse("         fn bob").unwrap();
         assert_eq!(correct_parse, parsed_if);
 
         // Default case where spaced normally
-        let parsed_if = parse("fn bob a b");
+        let parsed_if = parse("fn bob a b").unwrap();
         let correct_parse = Statement::Function {
             description: None,
             name:        "bob".into(),
@@ -353,10 +499,10 @@ mod tests {
         assert_eq!(correct_parse, parsed_if);
 
         // Trailing spaces after final value
-        let parsed_if = parse("fn bob a b       ");
+        let parsed_if = parse("fn bob a b       ").unwrap();
         assert_eq!(correct_parse, parsed_if);
 
-        let parsed_if = parse("fn bob a b --bob is a nice function");
+        let parsed_if = parse("fn bob a b --bob is a nice function").unwrap();
         let correct_parse = Statement::Function {
             description: Some("bob is a nice function".to_string()),
             name:        "bob".into(),
@@ -373,9 +519,9 @@ mod tests {
             statements:  vec![],
         };
         assert_eq!(correct_parse, parsed_if);
-        let parsed_if = parse("fn bob a b --          bob is a nice function");
+        let parsed_if = parse("fn bob a b --          bob is a nice function").unwrap();
         assert_eq!(correct_parse, parsed_if);
-        let parsed_if = parse("fn bob a b      --bob is a nice function");
+        let parsed_if = parse("fn bob a b      --bob is a nice function").unwrap();
         assert_eq!(correct_parse, parsed_if);
     }
 }
@@ -2,6 +2,7 @@
 // - Rewrite this in the same style as shell_expand::words.
 // - Validate syntax in methods
 
+use super::super::{heredoc::Delimiter, pipelines::is_assignment, SyntaxError};
 use std::{
     fmt::{self, Display, Formatter}, u16,
 };
@@ -26,7 +27,7 @@ bitflags! {
 #[derive(Debug, PartialEq)]
 pub(crate) enum StatementError {
     IllegalCommandName(String),
-    InvalidCharacter(char, usize),
+    InvalidCharacter(char),
     UnterminatedSubshell,
     UnterminatedBracedVar,
     UnterminatedBrace,
@@ -39,26 +40,20 @@ impl Display for StatementError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match *self {
             StatementError::IllegalCommandName(ref command) => {
-                writeln!(f, "illegal command name: {}", command)
+                write!(f, "illegal command name: {}", command)
             }
-            StatementError::InvalidCharacter(character, position) => writeln!(
-                f,
-                "syntax error: '{}' at position {} is out of place",
-                character, position
-            ),
-            StatementError::UnterminatedSubshell => {
-                writeln!(f, "syntax error: unterminated subshell")
+            StatementError::InvalidCharacter(character) => {
+                write!(f, "'{}' is out of place", character)
             }
-            StatementError::UnterminatedBrace => writeln!(f, "syntax error: unterminated brace"),
-            StatementError::UnterminatedBracedVar => {
-                writeln!(f, "syntax error: unterminated braced var")
-            }
-            StatementError::UnterminatedMethod => writeln!(f, "syntax error: unterminated method"),
+            StatementError::UnterminatedSubshell => write!(f, "unterminated subshell"),
+            StatementError::UnterminatedBrace => write!(f, "unterminated brace"),
+            StatementError::UnterminatedBracedVar => write!(f, "unterminated braced var"),
+            StatementError::UnterminatedMethod => write!(f, "unterminated method"),
             StatementError::UnterminatedArithmetic => {
-                writeln!(f, "syntax error: unterminated arithmetic subexpression")
+                write!(f, "unterminated arithmetic subexpression")
             }
             StatementError::ExpectedCommandButFound(element) => {
-                writeln!(f, "expected command, but found {}", element)
+                write!(f, "expected command, but found {}", element)
             }
         }
     }
@@ -78,6 +73,9 @@ pub(crate) enum StatementVariant<'a> {
     And(&'a str),
     Or(&'a str),
     Default(&'a str),
+    /// A statement which shares its line with other statements, and so can't be followed by the
+    /// bodies of its heredocs. The bodies are collected in the order that they were written.
+    Heredocs(Box<StatementVariant<'a>>, Vec<String>),
 }
 
 pub(crate) struct StatementSplitter<'a> {
@@ -88,6 +86,9 @@ pub(crate) struct StatementSplitter<'a> {
     paren_level:      u8,
     brace_level:      u8,
     math_paren_level: i8,
+    /// The position at which the next heredoc body begins, once a statement which is followed
+    /// by other statements on its line has collected the bodies of its heredocs.
+    heredoc_end:      Option<usize>,
 }
 
 impl<'a> StatementSplitter<'a> {
@@ -114,6 +115,7 @@ impl<'a> StatementSplitter<'a> {
             paren_level: 0,
             brace_level: 0,
             math_paren_level: 0,
+            heredoc_end: None,
         }
     }
 
@@ -131,6 +133,36 @@ impl<'a> StatementSplitter<'a> {
         }
     }
 
+    /// Returns true if a command may begin at the current position, which is the case at the
+    /// start of a statement, after a pipe or the opening of another subshell or group, or after
+    /// a keyword which precedes a command. Assignments to a command do not change this.
+    fn command_position(&self) -> bool {
+        let mut preceding =
+            self.data[self.start..self.read - 1].trim_right_matches(|c| c == ' ' || c == '\t');
+        let mut word;
+        loop {
+            word = preceding
+                .rfind(|c: char| c.is_whitespace() || "|&;({".contains(c))
+                .map_or(preceding, |pos| &preceding[pos + 1..]);
+            if !is_assignment(word) {
+                break;
+            }
+            preceding = preceding[..preceding.len() - word.len()]
+                .trim_right_matches(|c| c == ' ' || c == '\t');
+        }
+        // A named coprocess, such as `coproc NAME (...)`, precedes its command with a name.
+        let named_coproc = || {
+            preceding[..preceding.len() - word.len()]
+                .trim_right()
+                .rsplit(|c: char| c.is_whitespace() || "|&;".contains(c))
+                .next() == Some("coproc")
+        };
+        preceding.is_empty() || preceding.ends_with(|c| "|&;({\n".contains(c)) || match word {
+            "if" | "while" | "and" | "or" | "not" | "time" | "coproc" => true,
+            _ => named_coproc(),
+        }
+    }
+
     fn get_statement_from(&mut self, input: &'a str) -> StatementVariant<'a> {
         if self.flags.contains(Flags::AND) {
             self.flags -= Flags::AND;
@@ -142,10 +174,71 @@ impl<'a> StatementSplitter<'a> {
             StatementVariant::Default(input)
         }
     }
+
+    /// Collects the bodies of the given heredocs, which follow the current line, or the bodies
+    /// that were collected by the statements before them on that line.
+    fn heredoc_bodies(
+        &mut self,
+        heredocs: &[Delimiter<'a>],
+    ) -> Result<Vec<String>, SyntaxError<'a>> {
+        let data = self.data;
+        let mut position = match self.heredoc_end {
+            Some(end) => end,
+            None => data[self.read..]
+                .find('\n')
+                .map_or(data.len(), |pos| self.read + pos + 1),
+        };
+        let mut bodies = Vec::with_capacity(heredocs.len());
+        for delimiter in heredocs {
+            match delimiter.body(&data[position..]) {
+                Some((body, consumed)) => {
+                    bodies.push(body);
+                    position += consumed;
+                }
+                None => {
+                    self.heredoc_end = Some(data.len());
+                    return Err(SyntaxError::new("heredoc was not terminated", delimiter.tag));
+                }
+            }
+        }
+        self.heredoc_end = Some(position);
+        Ok(bodies)
+    }
+
+    /// Ends a statement on a line which has heredocs, where the statement isn't directly
+    /// followed by its heredoc bodies.
+    fn heredoc_statement(
+        &mut self,
+        statement: StatementVariant<'a>,
+        heredocs: &[Delimiter<'a>],
+        error: Option<SyntaxError<'a>>,
+    ) -> Result<StatementVariant<'a>, SyntaxError<'a>> {
+        let bodies = if heredocs.is_empty() {
+            Ok(Vec::new())
+        } else {
+            self.heredoc_bodies(heredocs)
+        };
+        match (error, bodies) {
+            (Some(error), _) | (None, Err(error)) => Err(error),
+            (None, Ok(ref bodies)) if bodies.is_empty() => Ok(statement),
+            (None, Ok(bodies)) => Ok(StatementVariant::Heredocs(Box::new(statement), bodies)),
+        }
+    }
+
+    /// Creates an error for the text between `start` and `end`.
+    fn error(&self, kind: StatementError, start: usize, end: usize) -> SyntaxError<'a> {
+        SyntaxError::new(kind.to_string(), &self.data[start..end])
+    }
+
+    /// Creates an error for the entire statement, which is used when the statement ends before
+    /// a subshell, brace or expansion that it contains was terminated.
+    fn statement_error(&self, kind: StatementError) -> SyntaxError<'a> {
+        SyntaxError::new(kind.to_string(), self.data[self.start..].trim())
+    }
 }
 
 impl<'a> Iterator for StatementSplitter<'a> {
-    type Item = Result<StatementVariant<'a>, StatementError>;
+    type Item = Result<StatementVariant<'a>, SyntaxError<'a>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.start = self.read;
@@ -153,6 +246,7 @@ impl<'a> Iterator for StatementSplitter<'a> {
         let mut else_found = false;
         let mut else_pos = 0;
         let mut error = None;
+        let mut heredocs = Vec::new();
         let mut bytes = self.data.bytes().skip(self.read).peekable();
         while let Some(character) = bytes.next() {
             self.read += 1;
@@ -170,8 +264,9 @@ impl<'a> Iterator for StatementSplitter<'a> {
                 {
                     // If we are just ending the braced section continue as normal
                     if error.is_none() {
-                        error = Some(StatementError::InvalidCharacter(
-                            character as char,
+                        error = Some(self.error(
+                            StatementError::InvalidCharacter(character as char),
+                            self.read - 1,
                             self.read,
                         ))
                     }
@@ -198,8 +293,9 @@ impl<'a> Iterator for StatementSplitter<'a> {
                 b'}' if self.flags.contains(Flags::VBRACE) => self.flags.toggle(Flags::VBRACE),
                 b'}' if !self.flags.contains(Flags::DQUOTE) => if self.brace_level == 0 {
                     if error.is_none() {
-                        error = Some(StatementError::InvalidCharacter(
-                            character as char,
+                        error = Some(self.error(
+                            StatementError::InvalidCharacter(character as char),
+                            self.read - 1,
                             self.read,
                         ))
                     }
@@ -209,13 +305,21 @@ impl<'a> Iterator for StatementSplitter<'a> {
                 b'(' if self.flags.contains(Flags::MATHEXPR) => {
                     self.math_paren_level += 1;
                 }
+                // A subshell, which may only begin where a command would.
+                b'(' if !self.flags.intersects(
+                    Flags::DQUOTE | Flags::COMM_1 | Flags::COMM_2 | Flags::VARIAB | Flags::ARRAY,
+                ) && self.command_position() =>
+                {
+                    self.paren_level += 1;
+                }
                 b'(' if !self
                     .flags
                     .intersects(Flags::COMM_1 | Flags::VARIAB | Flags::ARRAY) =>
                 {
                     if error.is_none() && !self.flags.contains(Flags::DQUOTE) {
-                        error = Some(StatementError::InvalidCharacter(
-                            character as char,
+                        error = Some(self.error(
+                            StatementError::InvalidCharacter(character as char),
+                            self.read - 1,
                             self.read,
                         ))
                     }
@@ -239,15 +343,20 @@ impl<'a> Iterator for StatementSplitter<'a> {
                 b')' if self.flags.contains(Flags::MATHEXPR) => if self.math_paren_level == 0 {
                     if self.data.as_bytes().len() <= self.read {
                         if error.is_none() {
-                            error = Some(StatementError::UnterminatedArithmetic)
+                            error = Some(self.statement_error(
+                                StatementError::UnterminatedArithmetic,
+                            ))
                         }
                     } else {
-                        let next_character = self.data.as_bytes()[self.read] as char;
+                        let next_character = self.data[self.read..].chars().next().unwrap();
                         if next_character == ')' {
                             self.flags = (self.flags - Flags::MATHEXPR) | Flags::POST_MATHEXPR;
                         } else if error.is_none() {
-                            error =
-                                Some(StatementError::InvalidCharacter(next_character, self.read));
+                            error = Some(self.error(
+                                StatementError::InvalidCharacter(next_character),
+                                self.read,
+                                self.read + next_character.len_utf8(),
+                            ));
                         }
                     }
                 } else {
@@ -258,52 +367,122 @@ impl<'a> Iterator for StatementSplitter<'a> {
                 }
                 b')' if self.paren_level == 0 => {
                     if error.is_none() && !self.flags.contains(Flags::DQUOTE) {
-                        error = Some(StatementError::InvalidCharacter(
-                            character as char,
+                        error = Some(self.error(
+                            StatementError::InvalidCharacter(character as char),
+                            self.read - 1,
                             self.read,
                         ))
                     }
                 }
                 b')' => self.paren_level -= 1,
-                b';' if !self.flags.contains(Flags::DQUOTE) && self.paren_level == 0 =>
+                b';' if !self.flags.contains(Flags::DQUOTE)
+                    && self.paren_level == 0
+                    && self.brace_level == 0 =>
                 {
                     let statement = self.get_statement(Flags::empty());
-                    return match error {
-                        Some(error) => Some(Err(error)),
-                        None => Some(Ok(statement)),
-                    }
+                    return Some(self.heredoc_statement(statement, &heredocs, error));
                 }
-                b'&' if !self.flags.contains(Flags::DQUOTE) && self.paren_level == 0 =>
+                b'&' if !self.flags.contains(Flags::DQUOTE)
+                    && self.paren_level == 0
+                    && self.brace_level == 0 =>
                 {
                     if bytes.peek() == Some(&b'&') { // Detecting if there is a 2nd `&` character
                         let statement = self.get_statement(Flags::AND);
                         self.read += 1; // Have `read` skip the 2nd `&` character after reading
-                        return match error {
-                            Some(error) => Some(Err(error)),
-                            None => Some(Ok(statement)),
-                        };
+                        return Some(self.heredoc_statement(statement, &heredocs, error));
                     }
                 }
-                b'|' if !self.flags.contains(Flags::DQUOTE) && self.paren_level == 0 =>
+                b'|' if !self.flags.contains(Flags::DQUOTE)
+                    && self.paren_level == 0
+                    && self.brace_level == 0 =>
                 {
                     if bytes.peek() == Some(&b'|') { // Detecting if there is a 2nd `|` character
                         let statement = self.get_statement(Flags::OR);
                         self.read += 1; // Have `read` skip the 2nd `|` character after reading
+                        return Some(self.heredoc_statement(statement, &heredocs, error));
+                    }
+                }
+
+                // Process substitutions, such as `<(cmd)`, are parsed like subshells.
+                b'<' | b'>' if !self.flags.contains(Flags::DQUOTE) && bytes.peek() == Some(&b'(') =>
+                {
+                    self.flags = (self.flags - Flags::COMM_2) | Flags::COMM_1;
+                    continue;
+                }
+                b'<' if !self.flags.intersects(Flags::DQUOTE | Flags::MATHEXPR)
+                    && self.paren_level == 0
+                    && self.brace_level == 0 =>
+                {
+                    if bytes.peek() == Some(&b'<') {
+                        let _ = bytes.next();
+                        self.read += 1;
+                        if bytes.peek() == Some(&b'<') {
+                            let _ = bytes.next();
+                            self.read += 1;
+                        } else if let Some(delimiter) = Delimiter::parse(&self.data[self.read..]) {
+                            for _ in 0..delimiter.length {
+                                let _ = bytes.next();
+                            }
+                            self.read += delimiter.length;
+                            heredocs.push(delimiter);
+                        }
+                    }
+                }
+                // The bodies of heredocs begin on the following line, and are not parsed. The
+                // statement ends with the line that terminates its last heredoc.
+                b'\n' if self.heredoc_end.is_some() => {
+                    let statement = self.get_statement(Flags::empty());
+                    let statement = self.heredoc_statement(statement, &heredocs, error);
+                    self.read = self.heredoc_end.take().unwrap_or(self.read);
+                    return Some(statement);
+                }
+                b'\n' if !heredocs.is_empty() => {
+                    let data = self.data;
+                    let mut consumed = 0;
+                    for delimiter in heredocs.drain(..) {
+                        match delimiter.body(&data[self.read + consumed..]) {
+                            Some((_, length)) => consumed += length,
+                            None => {
+                                consumed = data.len() - self.read;
+                                break;
+                            }
+                        }
+                    }
+                    for _ in 0..consumed {
+                        let _ = bytes.next();
+                    }
+                    self.read += consumed;
+                    if self.read < data.len() {
+                        let statement = self.get_statement(Flags::empty());
                         return match error {
                             Some(error) => Some(Err(error)),
                             None => Some(Ok(statement)),
                         };
                     }
                 }
-
+                // Statements are also terminated by newlines, outside of subshells and groups.
+                b'\n' if !self.flags.intersects(Flags::DQUOTE | Flags::MATHEXPR)
+                    && self.paren_level == 0
+                    && self.brace_level == 0 =>
+                {
+                    let statement = self.get_statement(Flags::empty());
+                    return match error {
+                        Some(error) => Some(Err(error)),
+                        None => Some(Ok(statement)),
+                    };
+                }
                 b'#' if self.read == 1 || (!self.flags.contains(Flags::DQUOTE) && self.paren_level == 0
+                        && self.brace_level == 0
                         && match self.data.as_bytes()[self.read - 2] {
-                            b' ' | b'\t' => true,
+                            b' ' | b'\t' | b'\n' => true,
                             _ => false,
                         }) =>
                 {
                     let statement = self.get_statement(Flags::empty());
-                    self.read = self.data.len();
+                    // Comments end with the line that they are on.
+                    self.read = self.data[self.read..]
+                        .find('\n')
+                        .map_or(self.data.len(), |pos| self.read + pos + 1);
                     return match error {
                         Some(error) => Some(Err(error)),
                         None => Some(Ok(statement)),
@@ -351,32 +530,50 @@ impl<'a> Iterator for StatementSplitter<'a> {
             match error {
                 Some(error) => Some(Err(error)),
                 None if self.paren_level != 0 => {
-                    Some(Err(StatementError::UnterminatedSubshell))
+                    Some(Err(self.statement_error(StatementError::UnterminatedSubshell)))
                 }
                 None if self.flags.contains(Flags::METHOD) => {
-                    Some(Err(StatementError::UnterminatedMethod))
+                    Some(Err(self.statement_error(StatementError::UnterminatedMethod)))
                 }
                 None if self.flags.contains(Flags::VBRACE) => {
-                    Some(Err(StatementError::UnterminatedBracedVar))
+                    Some(Err(self.statement_error(StatementError::UnterminatedBracedVar)))
+                }
+                None if self.brace_level != 0 => {
+                    Some(Err(self.statement_error(StatementError::UnterminatedBrace)))
                 }
-                None if self.brace_level != 0 => Some(Err(StatementError::UnterminatedBrace)),
                 None if self.flags.contains(Flags::MATHEXPR) => {
-                    Some(Err(StatementError::UnterminatedArithmetic))
+                    Some(Err(self.statement_error(StatementError::UnterminatedArithmetic)))
                 }
+                None if !heredocs.is_empty() => Some(Err(SyntaxError::new(
+                    "expected heredoc body after '<<'",
+                    heredocs[0].tag,
+                ))),
                 None => {
                     let output = self.data[self.start..].trim();
                     if output.is_empty() {
                         return Some(Ok(self.get_statement_from(output)));
                     }
                     match output.as_bytes()[0] {
-                        b'>' | b'<' | b'^' => {
-                            Some(Err(StatementError::ExpectedCommandButFound("redirection")))
+                        b'>' | b'<' | b'^' => Some(Err(SyntaxError::new(
+                            StatementError::ExpectedCommandButFound("redirection").to_string(),
+                            output,
+                        ))),
+                        b'|' => Some(Err(SyntaxError::new(
+                            StatementError::ExpectedCommandButFound("pipe").to_string(),
+                            output,
+                        ))),
+                        b'&' => Some(Err(SyntaxError::new(
+                            StatementError::ExpectedCommandButFound("&").to_string(),
+                            output,
+                        ))),
+                        // A brace group, as opposed to a brace expansion.
+                        b'{' if output.as_bytes().get(1).map_or(false, u8::is_ascii_whitespace) => {
+                            Some(Ok(self.get_statement_from(output)))
                         }
-                        b'|' => Some(Err(StatementError::ExpectedCommandButFound("pipe"))),
-                        b'&' => Some(Err(StatementError::ExpectedCommandButFound("&"))),
-                        b'*' | b'%' | b'?' | b'{' | b'}' => Some(Err(
-                            StatementError::IllegalCommandName(String::from(output)),
-                        )),
+                        b'*' | b'%' | b'?' | b'{' | b'}' => Some(Err(SyntaxError::new(
+                            StatementError::IllegalCommandName(String::from(output)).to_string(),
+                            output,
+                        ))),
                         _ => Some(Ok(self.get_statement_from(output))),
                     }
                 }
@@ -387,26 +584,37 @@ impl<'a> Iterator for StatementSplitter<'a> {
 
 #[test]
 fn syntax_errors() {
-    let command = "echo (echo one); echo $( (echo one); echo ) two; echo $(echo one";
-    let results = StatementSplitter::new(command)
-        .collect::<Vec<_>>();
-    assert_eq!(results[0], Err(StatementError::InvalidCharacter('(', 6)));
-    assert_eq!(results[1], Err(StatementError::InvalidCharacter('(', 26)));
-    assert_eq!(results[2], Err(StatementError::InvalidCharacter(')', 43)));
-    assert_eq!(results[3], Err(StatementError::UnterminatedSubshell));
-    assert_eq!(results.len(), 4);
-
-    let command = ">echo";
-    let results = StatementSplitter::new(command).collect::<Vec<_>>();
-    assert_eq!(
-        results[0],
-        Err(StatementError::ExpectedCommandButFound("redirection"))
-    );
+    // Errors are compared by their message and the span of the command at which they occur.
+    fn errors(command: &str) -> Vec<(String, Option<::std::ops::Range<usize>>)> {
+        StatementSplitter::new(command)
+            .map(|result| {
+                let error = result.unwrap_err();
+                let span = error.span(command);
+                (error.message.into_owned(), span)
+            })
+            .collect()
+    }
+
+    let results = errors("echo (echo one); echo one) two; echo $(echo one");
+    assert_eq!(results[0], ("'(' is out of place".into(), Some(5..6)));
+    assert_eq!(results[1], ("')' is out of place".into(), Some(25..26)));
+    assert_eq!(results[2], ("unterminated subshell".into(), Some(32..47)));
+    assert_eq!(results.len(), 3);
+
+    let results = errors("FOO=1 echo (x)");
+    assert_eq!(results[0], ("'(' is out of place".into(), Some(11..12)));
     assert_eq!(results.len(), 1);
 
-    let command = "echo $((foo bar baz)";
-    let results = StatementSplitter::new(command).collect::<Vec<_>>();
-    assert_eq!(results[0], Err(StatementError::UnterminatedArithmetic));
+    // Assignments to a subshell precede it where a command would begin.
+    let statements = StatementSplitter::new("FOO=1 (x)").collect::<Vec<_>>();
+    assert_eq!(statements, vec![Ok(StatementVariant::Default("FOO=1 (x)"))]);
+
+    let results = errors(">echo");
+    assert_eq!(results[0], ("expected command, but found redirection".into(), Some(0..5)));
+    assert_eq!(results.len(), 1);
+
+    let results = errors("echo $((foo bar baz)");
+    assert_eq!(results[0], ("unterminated arithmetic subexpression".into(), Some(0..20)));
     assert_eq!(results.len(), 1);
 }
 
@@ -506,3 +714,83 @@ fn variants() {
     assert_eq!(results[3], Ok(StatementVariant::Or(r#"echo "Helping by making sure your code works right.""#)));
     assert_eq!(results[4], Ok(StatementVariant::Default(r#"echo "Have a good day!""#)));
 }
+
+#[test]
+fn heredocs() {
+    let command = "cat <<EOF | tr a b\none; two && three # four\nEOF\necho five";
+    let statements = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(
+        statements[0],
+        Ok(StatementVariant::Default("cat <<EOF | tr a b\none; two && three # four\nEOF"))
+    );
+    assert_eq!(statements[1], Ok(StatementVariant::Default("echo five")));
+    assert_eq!(statements.len(), 2);
+
+    let command = "cat <<A; cat <<-B && echo\none\nA\n  two\n  B\necho three";
+    let statements = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(
+        statements[0],
+        Ok(StatementVariant::Heredocs(
+            Box::new(StatementVariant::Default("cat <<A")),
+            vec!["one\n".into()]
+        ))
+    );
+    assert_eq!(
+        statements[1],
+        Ok(StatementVariant::Heredocs(
+            Box::new(StatementVariant::Default("cat <<-B")),
+            vec!["two\n".into()]
+        ))
+    );
+    assert_eq!(statements[2], Ok(StatementVariant::And("echo")));
+    assert_eq!(statements[3], Ok(StatementVariant::Default("echo three")));
+    assert_eq!(statements.len(), 4);
+
+    let command = "cat <<A <<B\none\nA\ntwo\nB\necho three";
+    let statements = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(statements[0], Ok(StatementVariant::Default("cat <<A <<B\none\nA\ntwo\nB")));
+    assert_eq!(statements[1], Ok(StatementVariant::Default("echo three")));
+    assert_eq!(statements.len(), 2);
+
+    let command = "echo $((1 << 2))\necho three";
+    let statements = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(statements[0], Ok(StatementVariant::Default("echo $((1 << 2))")));
+    assert_eq!(statements[1], Ok(StatementVariant::Default("echo three")));
+    assert_eq!(statements.len(), 2);
+}
+
+#[test]
+fn process_substitutions() {
+    let command = "diff <(sort a; echo b) >(cat); echo done";
+    let statements = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(statements[0], Ok(StatementVariant::Default("diff <(sort a; echo b) >(cat)")));
+    assert_eq!(statements[1], Ok(StatementVariant::Default("echo done")));
+    assert_eq!(statements.len(), 2);
+}
+
+#[test]
+fn subshells_and_groups() {
+    let command = "(cd /tmp; ls) | wc -l; { echo one; echo two; } > file; echo $( (echo three) )";
+    let statements = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(statements[0], Ok(StatementVariant::Default("(cd /tmp; ls) | wc -l")));
+    assert_eq!(statements[1], Ok(StatementVariant::Default("{ echo one; echo two; } > file")));
+    assert_eq!(statements[2], Ok(StatementVariant::Default("echo $( (echo three) )")));
+    assert_eq!(statements.len(), 3);
+
+    let command = "(\n    echo one\n    echo two\n) && echo three\nif (true); echo four; end";
+    let statements = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(
+        statements[0],
+        Ok(StatementVariant::Default("(\n    echo one\n    echo two\n)"))
+    );
+    assert_eq!(statements[1], Ok(StatementVariant::And("echo three")));
+    assert_eq!(statements[2], Ok(StatementVariant::Default("if (true)")));
+    assert_eq!(statements[3], Ok(StatementVariant::Default("echo four")));
+    assert_eq!(statements[4], Ok(StatementVariant::Default("end")));
+    assert_eq!(statements.len(), 5);
+    let command = "coproc calc (bc; echo done); coproc (cat; cat)";
+    let statements = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(statements[0], Ok(StatementVariant::Default("coproc calc (bc; echo done)")));
+    assert_eq!(statements[1], Ok(StatementVariant::Default("coproc (cat; cat)")));
+    assert_eq!(statements.len(), 2);
+}
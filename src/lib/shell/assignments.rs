@@ -1,5 +1,5 @@
 use super::{
-    flow_control::{ExportAction, LocalAction}, status::*, Shell,
+    flags::NO_UNSET, flow_control::{ExportAction, LocalAction}, status::*, Shell,
 };
 use itoa;
 use parser::assignments::*;
@@ -32,7 +32,7 @@ fn list_vars(shell: &Shell) {
 
     // Write all the string variables to the buffer.
     let _ = buffer.write(b"# String Variables\n");
-    shell.variables.variables.iter().for_each(|(key, val)| {
+    shell.variables.visible_strings().for_each(|(key, val)| {
         let _ = buffer.write([key, " = ", val.as_str(), "\n"].concat().as_bytes());
     });
 
@@ -40,9 +40,20 @@ fn list_vars(shell: &Shell) {
     let _ = buffer.write(b"\n# Array Variables\n");
     shell
         .variables
-        .arrays
-        .iter()
+        .visible_arrays()
         .for_each(|(key, val)| print_array(&mut buffer, &key, &val));
+
+    // Followed by the map variables, in the same form with which they are declared.
+    let _ = buffer.write(b"\n# Map Variables\n");
+    shell.variables.visible_maps().for_each(|(key, val)| {
+        let _ = buffer.write([key, " = ", &val.to_string(), "\n"].concat().as_bytes());
+    });
+
+    // And lastly, the nested arrays and maps.
+    let _ = buffer.write(b"\n# Nested Variables\n");
+    shell.variables.visible_nested().for_each(|(key, val)| {
+        let _ = buffer.write([key, " = ", &val.to_string(), "\n"].concat().as_bytes());
+    });
 }
 
 /// Represents: A variable store capable of setting local variables or
@@ -56,8 +67,8 @@ pub(crate) trait VariableStore {
 
 impl VariableStore for Shell {
     fn export(&mut self, action: ExportAction) -> i32 {
-        let actions = match action {
-            ExportAction::Assign(ref keys, op, ref vals) => AssignmentActions::new(keys, op, vals),
+        let (keys, op, vals) = match action {
+            ExportAction::Assign(ref keys, op, ref vals) => (keys, op, vals),
             ExportAction::LocalExport(ref key) => match self.get_var(key) {
                 Some(var) => {
                     env::set_var(key, &var);
@@ -78,11 +89,22 @@ impl VariableStore for Shell {
             }
         };
 
-        for action in actions {
+        for action in AssignmentActions::new(keys, op, vals) {
             match action {
                 Ok(Action::UpdateArray(key, Operator::Equal, expression)) => {
-                    match value_check(self, &expression, key.kind) {
+                    let value = value_check(self, &expression, &key.kind);
+                    if unbound(self, "let", keys, op, vals) {
+                        return FAILURE;
+                    }
+                    match value {
                         Ok(ReturnValue::Vector(values)) => env::set_var(key.name, values.join(" ")),
+                        Ok(ReturnValue::Map(_)) | Ok(ReturnValue::Nested(_)) => {
+                            eprintln!(
+                                "ion: assignment error: {}: {} values cannot be exported",
+                                key.name, key.kind
+                            );
+                            return FAILURE;
+                        }
                         Err(why) => {
                             eprintln!("ion: assignment error: {}: {}", key.name, why);
                             return FAILURE;
@@ -97,17 +119,20 @@ impl VariableStore for Shell {
                     return FAILURE;
                 }
                 Ok(Action::UpdateString(key, operator, expression)) => {
-                    match value_check(self, &expression, key.kind) {
+                    let value = value_check(self, &expression, &key.kind);
+                    if unbound(self, "let", keys, op, vals) {
+                        return FAILURE;
+                    }
+                    match value {
                         Ok(ReturnValue::Str(value)) => {
                             let key_name: &str = &key.name;
                             let lhs = self
                                 .variables
-                                .variables
-                                .get(key_name)
+                                .get_str(key_name)
                                 .map(|x| x.as_str())
                                 .unwrap_or("0");
 
-                            let result = math(&lhs, key.kind, operator, &value, |value| {
+                            let result = math(&lhs, &key.kind, operator, &value, |value| {
                                 env::set_var(key_name, &OsStr::from_bytes(value))
                             });
 
@@ -134,17 +159,21 @@ impl VariableStore for Shell {
     }
 
     fn local(&mut self, action: LocalAction) -> i32 {
-        let actions = match action {
+        let (keys, op, vals) = match action {
             LocalAction::List => {
                 list_vars(&self);
                 return SUCCESS;
             }
-            LocalAction::Assign(ref keys, op, ref vals) => AssignmentActions::new(keys, op, vals),
+            LocalAction::Assign(ref keys, op, ref vals) => (keys, op, vals),
         };
-        for action in actions {
+        for action in AssignmentActions::new(keys, op, vals) {
             match action {
                 Ok(Action::UpdateArray(key, Operator::Equal, expression)) => {
-                    match value_check(self, &expression, key.kind) {
+                    let value = value_check(self, &expression, &key.kind);
+                    if unbound(self, "let", keys, op, vals) {
+                        return FAILURE;
+                    }
+                    match value {
                         Ok(ReturnValue::Vector(values)) => {
                             // When we changed the HISTORY_IGNORE variable, update the
                             // ignore patterns. This happens first because `set_array`
@@ -154,6 +183,10 @@ impl VariableStore for Shell {
                             }
                             self.variables.set_array(key.name, values)
                         }
+                        Ok(ReturnValue::Map(map)) => self.variables.set_map(key.name, map),
+                        Ok(ReturnValue::Nested(nested)) => {
+                            self.variables.set_nested(key.name, nested)
+                        }
                         Err(why) => {
                             eprintln!("ion: assignment error: {}: {}", key.name, why);
                             return FAILURE;
@@ -173,18 +206,21 @@ impl VariableStore for Shell {
                         return FAILURE;
                     }
 
-                    match value_check(self, &expression, key.kind) {
+                    let value = value_check(self, &expression, &key.kind);
+                    if unbound(self, "let", keys, op, vals) {
+                        return FAILURE;
+                    }
+                    match value {
                         Ok(ReturnValue::Str(value)) => {
                             let key_name: &str = &key.name;
                             let lhs = self
                                 .variables
-                                .variables
-                                .get(key_name)
+                                .get_str(key_name)
                                 .map(|x| x.as_str())
                                 .unwrap_or("0") as *const str;
 
                             let result =
-                                math(unsafe { &*lhs }, key.kind, operator, &value, |value| {
+                                math(unsafe { &*lhs }, &key.kind, operator, &value, |value| {
                                     self.set_var(key_name, unsafe {
                                         str::from_utf8_unchecked(value)
                                     })
@@ -213,6 +249,12 @@ impl VariableStore for Shell {
     }
 }
 
+/// Checks whether the expansions of an assignment referenced an unbound variable.
+fn unbound(shell: &mut Shell, keyword: &str, keys: &str, op: Operator, vals: &str) -> bool {
+    shell.flags & NO_UNSET != 0
+        && shell.check_unbound(&[keyword, " ", keys, " ", &op.to_string(), " ", vals].concat())
+}
+
 enum MathError {
     RHS,
     LHS,
@@ -257,25 +299,25 @@ fn write_integer<F: FnMut(&[u8])>(integer: i64, mut func: F) {
 
 fn math<'a, F: FnMut(&[u8])>(
     lhs: &str,
-    key: Primitive,
+    key: &Primitive,
     operator: Operator,
     value: &'a str,
     mut writefn: F,
 ) -> Result<(), MathError> {
     match operator {
-        Operator::Add => if Primitive::Any == key || Primitive::Float == key {
+        Operator::Add => if Primitive::Any == *key || Primitive::Float == *key {
             writefn(
                 parse_f64(lhs, value, |lhs, rhs| lhs + rhs)?
                     .to_string()
                     .as_bytes(),
             );
-        } else if let Primitive::Integer = key {
+        } else if let Primitive::Integer = *key {
             write_integer(parse_i64(lhs, value, |lhs, rhs| lhs + rhs)?, writefn);
         } else {
             return Err(MathError::Unsupported);
         },
         Operator::Divide => {
-            if Primitive::Any == key || Primitive::Float == key || Primitive::Integer == key {
+            if Primitive::Any == *key || Primitive::Float == *key || Primitive::Integer == *key {
                 writefn(
                     parse_f64(lhs, value, |lhs, rhs| lhs / rhs)?
                         .to_string()
@@ -285,40 +327,40 @@ fn math<'a, F: FnMut(&[u8])>(
                 return Err(MathError::Unsupported);
             }
         }
-        Operator::IntegerDivide => if Primitive::Any == key || Primitive::Float == key {
+        Operator::IntegerDivide => if Primitive::Any == *key || Primitive::Float == *key {
             write_integer(parse_i64(lhs, value, |lhs, rhs| lhs / rhs)?, writefn);
         } else {
             return Err(MathError::Unsupported);
         },
-        Operator::Subtract => if Primitive::Any == key || Primitive::Float == key {
+        Operator::Subtract => if Primitive::Any == *key || Primitive::Float == *key {
             writefn(
                 parse_f64(lhs, value, |lhs, rhs| lhs - rhs)?
                     .to_string()
                     .as_bytes(),
             );
-        } else if let Primitive::Integer = key {
+        } else if let Primitive::Integer = *key {
             write_integer(parse_i64(lhs, value, |lhs, rhs| lhs - rhs)?, writefn);
         } else {
             return Err(MathError::Unsupported);
         },
-        Operator::Multiply => if Primitive::Any == key || Primitive::Float == key {
+        Operator::Multiply => if Primitive::Any == *key || Primitive::Float == *key {
             writefn(
                 parse_f64(lhs, value, |lhs, rhs| lhs * rhs)?
                     .to_string()
                     .as_bytes(),
             );
-        } else if let Primitive::Integer = key {
+        } else if let Primitive::Integer = *key {
             write_integer(parse_i64(lhs, value, |lhs, rhs| lhs * rhs)?, writefn);
         } else {
             return Err(MathError::Unsupported);
         },
-        Operator::Exponent => if Primitive::Any == key || Primitive::Float == key {
+        Operator::Exponent => if Primitive::Any == *key || Primitive::Float == *key {
             writefn(
                 parse_f64(lhs, value, |lhs, rhs| lhs.powf(rhs))?
                     .to_string()
                     .as_bytes(),
             );
-        } else if let Primitive::Integer = key {
+        } else if let Primitive::Integer = *key {
             write_integer(
                 parse_i64(lhs, value, |lhs, rhs| lhs.pow(rhs as u32))?,
                 writefn,
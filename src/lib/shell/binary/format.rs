@@ -0,0 +1,264 @@
+//! A formatter which normalizes the layout of scripts, without changing what they do.
+
+use super::terminate::terminate_lines;
+use parser::{parse_and_validate, StatementSplitter, StatementVariant};
+use shell::flow_control::Statement;
+
+/// The number of spaces by which each level of a block is indented.
+const INDENT: usize = 4;
+
+/// The kinds of blocks that may be open at a line, which determine its indentation.
+#[derive(Debug, PartialEq)]
+enum Block {
+    Statement,
+    Match,
+    Case,
+}
+
+/// Parses the statements of a command, or returns `None` if it contains a syntax error. The
+/// statements are returned with the length of the code that they were parsed from, as any text
+/// which follows that code is a comment.
+fn parse(command: &str) -> Option<(Vec<Statement>, usize)> {
+    let mut statements = Vec::new();
+    let mut length = 0;
+    for statement in StatementSplitter::new(command) {
+        if let Ok(StatementVariant::And(text))
+        | Ok(StatementVariant::Or(text))
+        | Ok(StatementVariant::Default(text)) = statement
+        {
+            length = length.max(text.as_ptr() as usize - command.as_ptr() as usize + text.len());
+        }
+        match parse_and_validate(statement) {
+            Ok(Statement::Default) => (),
+            Ok(statement) => statements.push(statement),
+            Err(_) => return None,
+        }
+    }
+    Some((statements, length))
+}
+
+/// Writes each of the statements of a command on a single line. Statements which are only run
+/// depending on the status of the statement before them are chained to that statement.
+fn render(statements: &[Statement]) -> String {
+    let mut line = String::new();
+    for statement in statements {
+        match *statement {
+            Statement::And(ref statement) if !line.is_empty() => {
+                line.push_str(" && ");
+                line.push_str(&statement.to_string());
+            }
+            Statement::Or(ref statement) if !line.is_empty() => {
+                line.push_str(" || ");
+                line.push_str(&statement.to_string());
+            }
+            ref statement => {
+                if !line.is_empty() {
+                    line.push_str("; ");
+                }
+                line.push_str(&statement.to_string());
+            }
+        }
+    }
+    line
+}
+
+/// Returns the depth at which a line that contains the given statements is indented, and
+/// updates the blocks that are open according to those statements.
+fn indentation(blocks: &mut Vec<Block>, statements: &[Statement]) -> usize {
+    let mut depth = None;
+    for statement in statements {
+        let mut statement = statement;
+        while let Statement::And(ref inner)
+        | Statement::Or(ref inner)
+        | Statement::Not(ref inner)
+        | Statement::Time(ref inner) = *statement
+        {
+            statement = inner;
+        }
+
+        // The body of a case is ended by the next case, or by the end of its match.
+        match *statement {
+            Statement::End(_) => {
+                if blocks.last() == Some(&Block::Case) {
+                    blocks.pop();
+                }
+                blocks.pop();
+            }
+            Statement::Case(_) if blocks.last() == Some(&Block::Case) => {
+                blocks.pop();
+            }
+            _ => (),
+        }
+
+        let current = match *statement {
+            Statement::Else | Statement::ElseIf(_) => blocks.len().saturating_sub(1),
+            _ => blocks.len(),
+        };
+        depth = depth.or(Some(current));
+
+        match *statement {
+            Statement::If { .. }
+            | Statement::For { .. }
+            | Statement::While { .. }
+            | Statement::Function { .. } => blocks.push(Block::Statement),
+            Statement::Match { .. } => blocks.push(Block::Match),
+            Statement::Case(_) => blocks.push(Block::Case),
+            _ => (),
+        }
+    }
+    depth.unwrap_or_else(|| blocks.len())
+}
+
+/// Formats the source of a script. The statements of each block are indented by a level, and
+/// statements written on a single line are normalized, provided that they would be parsed
+/// exactly as before. Comments are retained, and consecutive blank lines are collapsed.
+///
+/// Commands that span multiple lines are only indented, as the text of quotes and heredocs
+/// must be left as it was, although lines continued by a backslash are indented by a further
+/// level. A script which ends within a quote or heredoc can't be formatted.
+pub(crate) fn format(source: &str) -> Result<String, String> {
+    let lines = source.lines().collect::<Vec<&str>>();
+    let mut commands = Vec::new();
+    terminate_lines(source.lines().map(String::from), |number, command| {
+        commands.push((number - 1, command))
+    }).map_err(|(number, kind)| format!("{}: unterminated {}", number, kind))?;
+
+    let mut output = String::with_capacity(source.len());
+    let mut blocks = Vec::new();
+    // Blank lines at the beginning of the script are removed.
+    let mut blank = true;
+    for (id, &(start, ref command)) in commands.iter().enumerate() {
+        let end = commands.get(id + 1).map_or(lines.len(), |&(next, _)| next);
+        let line = lines[start];
+        if line.trim().is_empty() && end == start + 1 {
+            if !blank {
+                output.push('\n');
+            }
+            blank = true;
+            continue;
+        }
+        blank = false;
+
+        let parsed = parse(command);
+        let depth = indentation(&mut blocks, parsed.as_ref().map_or(&[], |p| p.0.as_slice()));
+        let indent = " ".repeat(depth * INDENT);
+
+        if end != start + 1 {
+            output.push_str(&indent);
+            output.push_str(line.trim_left());
+            output.push('\n');
+            let heredoc = command.contains("<<");
+            for number in start + 1..end {
+                if !heredoc && lines[number - 1].ends_with('\\') {
+                    output.push_str(&indent);
+                    output.push_str(&" ".repeat(INDENT));
+                    output.push_str(lines[number].trim_left());
+                } else {
+                    output.push_str(lines[number]);
+                }
+                output.push('\n');
+            }
+            continue;
+        }
+
+        // The comment which follows a command is not a part of the command.
+        let (code, length) = match parsed {
+            Some((ref statements, length)) => {
+                let rendered = render(statements);
+                match parse(&rendered) {
+                    Some((ref reparsed, _)) if reparsed == statements => (rendered, length),
+                    _ => (command[..length].trim().to_owned(), length),
+                }
+            }
+            None => (command.trim().to_owned(), command.len()),
+        };
+        let comment = match line.get(length..).map(str::trim) {
+            Some(comment) if line.starts_with(command.as_str()) && comment.starts_with('#') => {
+                comment
+            }
+            _ => "",
+        };
+
+        output.push_str(&indent);
+        output.push_str(&code);
+        if !comment.is_empty() {
+            if !code.is_empty() {
+                output.push(' ');
+            }
+            output.push_str(comment);
+        }
+        output.push('\n');
+    }
+
+    while output.ends_with("\n\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indentation() {
+        let source = "\n\nfn greet name:str -- says hello\nif test $name = world\necho   \
+                      hello\n  else if true\n echo hi # greeting\nelse\nfor x in 1 2;  echo $x;  \
+                      end\nend\nend\n\n\n\nmatch $x\ncase 1\necho one\ncase _\n# anything \
+                      else\necho other\nend\n\n";
+        let expected = "fn greet name:str -- says hello
+    if test $name = world
+        echo hello
+    else if true
+        echo hi # greeting
+    else
+        for x in 1 2; echo $x; end
+    end
+end
+
+match $x
+    case 1
+        echo one
+    case _
+        # anything else
+        echo other
+end
+";
+        assert_eq!(Ok(expected.to_owned()), format(source));
+    }
+
+    #[test]
+    fn normalization() {
+        let source = "let  a:int   =  1;\nFOO=bar   cmd  ^>  err | tee  log &&   true ||  \
+                      false\nnot  test -n \"a  b\"\necho 'a     b'   2>&1\nwhile true; break; \
+                      end >  out";
+        let expected = "let a:int = 1
+FOO=bar cmd ^> err | tee log && true || false
+not test -n \"a  b\"
+echo 'a     b' 2>&1
+while true; break; end > out
+";
+        assert_eq!(Ok(expected.to_owned()), format(source));
+    }
+
+    #[test]
+    fn multiple_lines() {
+        // The bodies of quotes and heredocs are left as they were.
+        let source = "for x in 1\necho \"one\n  two\"\ncat <<EOF\n  body \\\nmore\nEOF\necho one \
+                      \\\n        two \\\nthree\nend";
+        let expected = "for x in 1
+    echo \"one
+  two\"
+    cat <<EOF
+  body \\
+more
+EOF
+    echo one \\
+        two \\
+        three
+end
+";
+        assert_eq!(Ok(expected.to_owned()), format(source));
+        assert_eq!(Err("1: unterminated quote".into()), format("echo 'one"));
+    }
+}
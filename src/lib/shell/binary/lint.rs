@@ -0,0 +1,958 @@
+//! A static analysis of scripts, which reports likely mistakes without executing anything.
+
+use super::terminate::terminate_lines;
+use fnv::{FnvHashMap, FnvHashSet};
+use parser::{
+    assignments::*, expand_string, heredoc, parse_and_validate,
+    pipelines::{FdRedirection, Input, Pipeline}, Expander, Select, StatementSplitter,
+};
+use shell::{
+    flow_control::{collect_cases, collect_if, collect_loops, ExportAction, LocalAction, Statement},
+    variables::Variables, Shell,
+};
+use std::{
+    cell::RefCell, env, fmt::{self, Display, Formatter}, fs::{File, OpenOptions},
+    io::{self, Read}, mem, ops::Range, os::unix::io::AsRawFd, path::{Path, PathBuf}, slice,
+};
+use sys;
+use types::{Array, HashMap, Map, Value};
+
+/// The kinds of problems which the linter reports.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum LintKind {
+    SyntaxError,
+    UndefinedVariable,
+    UndefinedFunction,
+    ArgumentCount,
+    TypeMismatch,
+    UnreachableCode,
+    UnusedParameter,
+}
+
+impl Display for LintKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let code = match *self {
+            LintKind::SyntaxError => "syntax-error",
+            LintKind::UndefinedVariable => "undefined-variable",
+            LintKind::UndefinedFunction => "undefined-function",
+            LintKind::ArgumentCount => "argument-count",
+            LintKind::TypeMismatch => "type-mismatch",
+            LintKind::UnreachableCode => "unreachable-code",
+            LintKind::UnusedParameter => "unused-parameter",
+        };
+        f.write_str(code)
+    }
+}
+
+/// A problem that was found within a script, along with the line on which it was found.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Lint {
+    pub line:    usize,
+    /// The bytes of the line at which a syntax error was found, when they are known.
+    pub columns: Option<Range<usize>>,
+    pub kind:    LintKind,
+    pub message: String,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.line, self.kind, self.message)
+    }
+}
+
+/// Records the variables and commands that are referred to by a word as it is expanded.
+#[derive(Default)]
+struct References {
+    variables: RefCell<Vec<String>>,
+    commands:  RefCell<Vec<String>>,
+}
+
+impl References {
+    /// Records the variable, if the name is that of a variable rather than an expression.
+    fn record(&self, name: &str) -> bool {
+        let variable = name.trim_left_matches("global::").trim_left_matches("super::");
+        let is_variable = !variable.is_empty()
+            && Variables::is_valid_variable_name(variable)
+            && !variable.bytes().all(|byte| byte.is_ascii_digit());
+        if is_variable {
+            self.variables.borrow_mut().push(name.into());
+        }
+        is_variable
+    }
+}
+
+impl Expander for References {
+    fn array(&self, name: &str, _: Select) -> Option<Array> {
+        if self.record(name) {
+            Some(array!["1"])
+        } else {
+            None
+        }
+    }
+
+    fn map(&self, name: &str) -> Option<Map> {
+        if self.record(name) {
+            Some(Map::Hash(HashMap::default()))
+        } else {
+            None
+        }
+    }
+
+    fn nested(&self, name: &str, _: &[Select]) -> Option<Array> {
+        if self.record(name) {
+            Some(array!["1"])
+        } else {
+            None
+        }
+    }
+
+    fn variable(&self, name: &str, _: bool) -> Option<Value> {
+        if self.record(name) {
+            Some("1".into())
+        } else {
+            None
+        }
+    }
+
+    fn command(&self, command: &str) -> Option<Value> {
+        self.commands.borrow_mut().push(command.into());
+        Some(Value::new())
+    }
+
+    fn substitution(&self, command: &str, _: bool) -> Option<Value> {
+        self.commands.borrow_mut().push(command.into());
+        Some("/dev/null".into())
+    }
+}
+
+/// The body of a function, which is checked once every statement of the script has been
+/// seen, as the body may refer to anything that the script defines before calling it.
+struct FunctionBody {
+    line:       usize,
+    name:       String,
+    args:       Vec<KeyBuf>,
+    statements: Vec<Statement>,
+}
+
+/// Walks the statements of a script in the order in which they would be executed, tracking
+/// which variables and functions have been defined at each point.
+pub(crate) struct Linter<'a> {
+    shell:     &'a Shell,
+    /// The directory of the script, against which sourced scripts are also located.
+    directory: Option<PathBuf>,
+    lints:     Vec<Lint>,
+    line:      usize,
+    /// Variables that have been assigned, where the first scope holds the global variables.
+    scopes:    Vec<FnvHashSet<String>>,
+    /// Variables that have been referred to by the body of the current function.
+    used:      FnvHashSet<String>,
+    /// The parameters of every function that the script defines.
+    declared:  FnvHashMap<String, Vec<KeyBuf>>,
+    /// The functions which have been defined by the statements seen so far.
+    defined:   FnvHashSet<String>,
+    aliases:   FnvHashSet<String>,
+    /// Whether each command that was searched for was found in the `PATH`.
+    commands:  FnvHashMap<String, bool>,
+    bodies:    Vec<FunctionBody>,
+    /// The coprocesses that each function starts, whose variables are global, and so are
+    /// defined wherever the function is called.
+    coprocs:   FnvHashMap<String, Vec<String>>,
+    /// Set while the body of a function is being checked.
+    function:  Option<String>,
+    /// The scripts that are being sourced, which are checked without reporting problems.
+    sourced:   Vec<PathBuf>,
+    /// Set once a script whose definitions are unknown is sourced.
+    opaque:    bool,
+}
+
+impl<'a> Linter<'a> {
+    pub(crate) fn new(shell: &'a Shell, script: Option<&Path>) -> Linter<'a> {
+        let mut globals = FnvHashSet::default();
+        for &name in &["?", "args", "PIPESTATUS"] {
+            globals.insert(name.into());
+        }
+
+        Linter {
+            shell,
+            directory: script.and_then(Path::parent).map(Path::to_path_buf),
+            lints: Vec::new(),
+            line: 1,
+            scopes: vec![globals],
+            used: FnvHashSet::default(),
+            declared: FnvHashMap::default(),
+            defined: FnvHashSet::default(),
+            aliases: FnvHashSet::default(),
+            commands: FnvHashMap::default(),
+            bodies: Vec::new(),
+            coprocs: FnvHashMap::default(),
+            function: None,
+            sourced: Vec::new(),
+            opaque: false,
+        }
+    }
+
+    /// Checks the source of a script, returning the problems that were found, ordered by the
+    /// line on which they were found.
+    pub(crate) fn lint(mut self, source: &str) -> Vec<Lint> {
+        self.check_script(source);
+
+        let globals = self.scopes[0].clone();
+        while !self.bodies.is_empty() {
+            for body in mem::replace(&mut self.bodies, Vec::new()) {
+                self.check_function(body, &globals);
+            }
+        }
+
+        self.lints.sort_by_key(|lint| lint.line);
+        self.lints
+    }
+
+    fn report<M: Into<String>>(&mut self, kind: LintKind, message: M) {
+        let line = self.line;
+        self.report_at(line, kind, message);
+    }
+
+    fn report_at<M: Into<String>>(&mut self, line: usize, kind: LintKind, message: M) {
+        self.report_within(line, None, kind, message);
+    }
+
+    fn report_within<M: Into<String>>(
+        &mut self,
+        line: usize,
+        columns: Option<Range<usize>>,
+        kind: LintKind,
+        message: M,
+    ) {
+        // Problems within sourced scripts belong to those scripts, rather than this one.
+        if self.sourced.is_empty() {
+            self.lints.push(Lint {
+                line,
+                columns,
+                kind,
+                message: message.into(),
+            });
+        }
+    }
+
+    fn check_script(&mut self, source: &str) {
+        let mut statements = Vec::new();
+        let result = terminate_lines(source.lines().map(String::from), |number, command| {
+            statements.extend(self.parse(&command, number, Some(source)));
+        });
+        if let Err((number, kind)) = result {
+            self.report_at(number, LintKind::SyntaxError, format!("unterminated {}", kind));
+        }
+
+        // Calls to functions that are defined later in the script are told apart from calls
+        // to functions that are never defined.
+        for statement in &statements {
+            if let Statement::Function { ref name, ref args, .. } = *statement {
+                self.declared.insert(name.to_string(), args.clone());
+            }
+        }
+
+        self.check_statements(statements);
+    }
+
+    /// Parses a command into its statements, which are preceded by the line of the command.
+    /// The columns of syntax errors are reported when the line on which an error was found
+    /// appears in the `source` of the script as it does within the command.
+    fn parse(&mut self, command: &str, line: usize, source: Option<&str>) -> Vec<Statement> {
+        let mut statements = vec![Statement::Line(line)];
+        for statement in StatementSplitter::new(command) {
+            match parse_and_validate(statement) {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    let (offset, columns) = error
+                        .span(command)
+                        .map_or((0, None), |span| locate(command, span, source, line));
+                    let message = error.message.into_owned();
+                    self.report_within(line + offset, columns, LintKind::SyntaxError, message);
+                }
+            }
+        }
+        statements
+    }
+
+    /// Checks a command which runs within a subshell, whose assignments are not retained.
+    fn check_subshell(&mut self, command: &str) {
+        let scopes = self.scopes.clone();
+        let line = self.line;
+        let statements = self.parse(command, line, None);
+        self.check_statements(statements);
+        self.scopes = scopes;
+        self.line = line;
+    }
+
+    fn check_function(&mut self, body: FunctionBody, globals: &FnvHashSet<String>) {
+        let parameters = body.args.iter().map(|arg| arg.name.clone()).collect();
+        let scopes = mem::replace(&mut self.scopes, vec![globals.clone(), parameters]);
+        self.used.clear();
+        self.function = Some(body.name.clone());
+        self.line = body.line;
+
+        self.check_statements(body.statements);
+
+        // Parameters which begin with an underscore are intentionally unused.
+        for arg in &body.args {
+            if !arg.name.starts_with('_') && !self.used.contains(&arg.name) {
+                let message =
+                    format!("the '{}' parameter of '{}' is never used", arg.name, body.name);
+                self.report_at(body.line, LintKind::UnusedParameter, message);
+            }
+        }
+
+        self.function = None;
+        self.scopes = scopes;
+    }
+
+    /// Checks each statement of a block, reporting the first statement which follows a
+    /// statement that unconditionally leaves the block.
+    fn check_statements(&mut self, statements: Vec<Statement>) {
+        let mut iterator = statements.into_iter();
+        let mut exited = None;
+        let mut reported = false;
+        while let Some(statement) = iterator.next() {
+            match statement {
+                Statement::Line(line) => {
+                    self.line = line;
+                    continue;
+                }
+                Statement::Error(_) | Statement::Default => continue,
+                _ => (),
+            }
+
+            if let Some(keyword) = exited {
+                if !reported {
+                    let message = format!("this statement follows `{}`, and is never run", keyword);
+                    self.report(LintKind::UnreachableCode, message);
+                    reported = true;
+                }
+            }
+
+            if let Some(keyword) = self.check_statement(&mut iterator, statement) {
+                exited = exited.or(Some(keyword));
+            }
+        }
+    }
+
+    /// Checks a statement, collecting the body of a block from the statements which follow
+    /// it. If the statement unconditionally leaves the block, its keyword is returned.
+    fn check_statement<I>(&mut self, iterator: &mut I, statement: Statement) -> Option<&'static str>
+    where
+        I: Iterator<Item = Statement>,
+    {
+        let line = self.line;
+        match statement {
+            Statement::Let(LocalAction::Assign(keys, operator, values))
+            | Statement::Export(ExportAction::Assign(keys, operator, values)) => {
+                self.check_assignment(&keys, operator, &values)
+            }
+            Statement::Export(ExportAction::LocalExport(name)) => self.reference(&name),
+            Statement::While {
+                expression,
+                mut statements,
+            } => {
+                let mut level = 1;
+                let redirection = collect_loops(iterator, &mut statements, &mut level);
+                self.check_end(level, "while", line);
+                self.check_pipeline(&expression, false);
+                self.check_redirection(redirection);
+                self.check_statements(statements);
+            }
+            Statement::For {
+                variables,
+                values,
+                mut statements,
+            } => {
+                let mut level = 1;
+                let redirection = collect_loops(iterator, &mut statements, &mut level);
+                self.check_end(level, "for", line);
+                for value in &values {
+                    self.expand(value);
+                }
+                self.check_redirection(redirection);
+                for variable in &variables {
+                    self.define(variable);
+                }
+                self.check_statements(statements);
+            }
+            Statement::If {
+                expression,
+                mut success,
+                mut else_if,
+                mut failure,
+            } => {
+                let mut level = 1;
+                match collect_if(iterator, &mut success, &mut else_if, &mut failure, &mut level, 0)
+                {
+                    Ok((_, redirection)) => {
+                        self.check_end(level, "if", line);
+                        self.check_redirection(redirection);
+                    }
+                    Err(why) => {
+                        let message = why.trim_left_matches("ion: syntax error: ");
+                        self.report(LintKind::SyntaxError, message);
+                    }
+                }
+                self.check_pipeline(&expression, false);
+                self.check_statements(success);
+                for branch in else_if {
+                    self.check_pipeline(&branch.expression, false);
+                    self.check_statements(branch.success);
+                }
+                self.check_statements(failure);
+            }
+            Statement::Match {
+                expression,
+                mut cases,
+            } => {
+                let mut level = 1;
+                match collect_cases(iterator, &mut cases, &mut level) {
+                    Ok(redirection) => {
+                        self.check_end(level, "match", line);
+                        self.check_redirection(redirection);
+                    }
+                    Err(why) => {
+                        let message = why.trim_left_matches("ion: syntax error: ");
+                        self.report(LintKind::SyntaxError, message);
+                    }
+                }
+                self.expand(&expression);
+                for case in cases {
+                    if let Some(ref value) = case.value {
+                        self.expand(value);
+                    }
+                    if let Some(ref binding) = case.binding {
+                        self.define(binding);
+                    }
+                    if let Some(ref conditional) = case.conditional {
+                        let statements = self.parse(conditional, self.line, None);
+                        self.check_statements(statements);
+                    }
+                    self.check_statements(case.statements);
+                }
+            }
+            Statement::Function {
+                name,
+                args,
+                mut statements,
+                ..
+            } => {
+                let mut level = 1;
+                if collect_loops(iterator, &mut statements, &mut level).is_some() {
+                    let message = "redirections are not supported on function definitions";
+                    self.report(LintKind::SyntaxError, message);
+                }
+                self.check_end(level, "fn", line);
+                self.declared.insert(name.to_string(), args.clone());
+                self.defined.insert(name.to_string());
+                let mut names = Vec::new();
+                coprocesses(&statements, &mut names);
+                self.coprocs.insert(name.to_string(), names);
+                if self.sourced.is_empty() {
+                    self.bodies.push(FunctionBody {
+                        line,
+                        name: name.to_string(),
+                        args,
+                        statements,
+                    });
+                }
+            }
+            Statement::Pipeline(pipeline) => {
+                self.check_pipeline(&pipeline, false);
+                let is_exit = pipeline.items.len() == 1
+                    && pipeline.items[0].job.command.as_ref() as &str == "exit";
+                if is_exit {
+                    return Some("exit");
+                }
+            }
+            Statement::Coproc { name, pipeline } => {
+                if let Some(pipeline) = pipeline {
+                    self.check_pipeline(&pipeline, false);
+                }
+                self.define_coprocess(&name);
+            }
+            Statement::Time(statement) => return self.check_statement(iterator, *statement),
+            Statement::And(statement) | Statement::Or(statement) | Statement::Not(statement) => {
+                self.check_statement(iterator, *statement);
+            }
+            Statement::Break => return Some("break"),
+            Statement::Continue => return Some("continue"),
+            Statement::Return(expression) => {
+                if let Some(expression) = expression {
+                    self.expand(&expression);
+                }
+                return Some("return");
+            }
+            Statement::Else | Statement::ElseIf(_) => {
+                self.report(LintKind::SyntaxError, "not an if statement")
+            }
+            Statement::End(_) => self.report(LintKind::SyntaxError, "no block to end"),
+            Statement::Case(_) => self.report(LintKind::SyntaxError, "case is not within a match"),
+            _ => (),
+        }
+        None
+    }
+
+    /// Reports a block which was never ended, once its body has been collected.
+    fn check_end(&mut self, level: usize, keyword: &str, line: usize) {
+        if level != 0 {
+            let message = format!("expected an end to the `{}` block", keyword);
+            self.report_at(line, LintKind::SyntaxError, message);
+        }
+    }
+
+    fn check_assignment(&mut self, keys: &str, operator: Operator, values: &str) {
+        for action in AssignmentActions::new(keys, operator, values) {
+            let (key, value) = match action {
+                Ok(Action::UpdateString(key, _, value))
+                | Ok(Action::UpdateArray(key, _, value)) => (key, value),
+                Err(why) => {
+                    self.report(LintKind::TypeMismatch, format!("{}", why));
+                    continue;
+                }
+            };
+
+            self.expand(value);
+            // Arithmetic assignments also refer to the value that is being modified.
+            if operator != Operator::Equal {
+                self.reference(key.name);
+            }
+
+            let is_literal = !value.contains('$') && !value.contains('@');
+            if is_literal && key.kind != Primitive::Any {
+                if let Err(why) = value_check(&References::default(), value, &key.kind) {
+                    let name = key.name.trim_left_matches("global::");
+                    let message = format!("cannot assign '{}' to '{}': {}", value, name, why);
+                    self.report(LintKind::TypeMismatch, message);
+                }
+            }
+
+            self.define(key.name);
+        }
+    }
+
+    /// Checks the redirection of a block, where the first item represents the block itself.
+    fn check_redirection(&mut self, redirection: Option<Pipeline>) {
+        if let Some(pipeline) = redirection {
+            self.check_pipeline(&pipeline, true);
+        }
+    }
+
+    fn check_pipeline(&mut self, pipeline: &Pipeline, is_block: bool) {
+        for (id, item) in pipeline.items.iter().enumerate() {
+            if !is_block || id != 0 {
+                match item.job.block() {
+                    Some((body, true)) => self.check_subshell(body),
+                    Some((body, false)) => {
+                        let statements = self.parse(body, self.line, None);
+                        self.check_statements(statements);
+                    }
+                    None => {
+                        for &(_, ref value) in &item.job.env {
+                            self.expand(value);
+                        }
+                        for arg in item.job.args.iter() {
+                            self.expand(arg);
+                        }
+                        self.check_command(&item.job.args);
+                    }
+                }
+            }
+
+            for input in &item.inputs {
+                match *input {
+                    Input::File(ref word) | Input::HereString(ref word) => self.expand(word),
+                    Input::HereDoc { ref body, expand: true } => self.expand(&heredoc::quote(body)),
+                    Input::HereDoc { .. } => (),
+                }
+            }
+
+            for output in &item.outputs {
+                self.expand(&output.file);
+            }
+
+            for descriptor in &item.descriptors {
+                match *descriptor {
+                    FdRedirection::Open { file: ref word, .. }
+                    | FdRedirection::DuplicateWord { source: ref word, .. } => self.expand(word),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    fn check_command(&mut self, args: &[String]) {
+        let command = match args.first() {
+            Some(command) => command.as_str(),
+            None => return,
+        };
+
+        match command {
+            "read" => for name in args[1..].iter().filter(|arg| !arg.starts_with('-')) {
+                self.define(name);
+            },
+            "alias" => if let Some(name) = args.get(1) {
+                self.aliases.insert(name.split('=').next().unwrap_or("").into());
+            },
+            "source" => if let Some(script) = args.get(1) {
+                self.source(script);
+            },
+            "json" => match (args.get(1).map(String::as_str), args.get(2)) {
+                (Some("-p"), Some(name)) | (Some("--parse"), Some(name)) => self.define(name),
+                _ => (),
+            },
+            _ => (),
+        }
+
+        let args = &args[1..];
+        if let Some(parameters) = self.declared.get(command).cloned() {
+            if self.function.is_none() && !self.defined.contains(command) && !self.opaque {
+                let message = format!("'{}' is called before it is defined", command);
+                self.report(LintKind::UndefinedFunction, message);
+            }
+            self.check_arguments(command, &parameters, args);
+            for name in self.coprocs.get(command).cloned().unwrap_or_default() {
+                self.define_coprocess(&name);
+            }
+        } else if !self.is_command(command) && !self.opaque {
+            let message = format!("'{}' is not a function, builtin or command", command);
+            self.report(LintKind::UndefinedFunction, message);
+        }
+    }
+
+    fn check_arguments(&mut self, function: &str, parameters: &[KeyBuf], args: &[String]) {
+        // Arrays, braces and globs may expand into any number of arguments.
+        let is_variadic = |arg: &String| arg.contains(|c| c == '@' || c == '{' || c == '*');
+        if args.iter().any(is_variadic) {
+            return;
+        }
+
+        if args.len() != parameters.len() {
+            let message = format!(
+                "'{}' takes {} argument{}, but {} {} given",
+                function,
+                parameters.len(),
+                if parameters.len() == 1 { "" } else { "s" },
+                args.len(),
+                if args.len() == 1 { "was" } else { "were" }
+            );
+            self.report(LintKind::ArgumentCount, message);
+            return;
+        }
+
+        for (parameter, arg) in parameters.iter().zip(args) {
+            if parameter.kind != Primitive::Any && !arg.contains('$') {
+                if let Err(why) = value_check(&References::default(), arg, &parameter.kind) {
+                    let message = format!(
+                        "cannot pass '{}' to the '{}' parameter of '{}': {}",
+                        arg, parameter.name, function, why
+                    );
+                    self.report(LintKind::TypeMismatch, message);
+                }
+            }
+        }
+    }
+
+    /// Whether the command is a builtin, an alias, or a program within the `PATH`. Commands
+    /// that are given by a path, or by an expansion, are assumed to exist.
+    fn is_command(&mut self, command: &str) -> bool {
+        if command.is_empty()
+            || command.contains(|c| "$@/\\'\"~".contains(c))
+            || self.shell.builtins.contains_key(command)
+            || self.shell.variables.aliases.contains_key(command)
+            || self.aliases.contains(command)
+        {
+            return true;
+        }
+
+        if let Some(&found) = self.commands.get(command) {
+            return found;
+        }
+
+        let found = env::var("PATH")
+            .unwrap_or_else(|_| "/bin".into())
+            .split(sys::PATH_SEPARATOR)
+            .any(|directory| Path::new(directory).join(command).is_file());
+        self.commands.insert(command.into(), found);
+        found
+    }
+
+    /// Checks a sourced script, so that its definitions are known to the statements which
+    /// follow. If the script can't be found, nothing can be assumed about what is defined.
+    fn source(&mut self, script: &str) {
+        let mut candidates = vec![PathBuf::from(script)];
+        if let Some(ref directory) = self.directory {
+            candidates.push(directory.join(script));
+        }
+
+        let is_literal = !script.contains(|c| c == '$' || c == '@');
+        let found = candidates.into_iter().filter(|_| is_literal).find(|path| path.is_file());
+        let path = match found {
+            Some(path) => path,
+            None => {
+                self.opaque = true;
+                return;
+            }
+        };
+
+        if self.sourced.contains(&path) {
+            return;
+        }
+
+        let mut source = String::new();
+        match File::open(&path).and_then(|mut file| file.read_to_string(&mut source)) {
+            Ok(_) => {
+                let line = self.line;
+                self.sourced.push(path);
+                self.check_script(&source);
+                self.sourced.pop();
+                self.line = line;
+            }
+            Err(_) => self.opaque = true,
+        }
+    }
+
+    /// Expands a word, checking the variables and commands that it refers to.
+    fn expand(&mut self, word: &str) {
+        let references = References::default();
+        // Variables expand to placeholders rather than their values, so the errors which an
+        // expansion reports, such as an index being out of bounds, are meaningless.
+        silenced(|| expand_string(word, &references, false));
+        for name in references.variables.into_inner() {
+            self.reference(&name);
+        }
+        for command in references.commands.into_inner() {
+            self.check_subshell(&command);
+        }
+    }
+
+    fn reference(&mut self, name: &str) {
+        // The scope of a caller is unknown, so its variables are assumed to exist.
+        let (name, is_super) = match name.trim_left_matches("global::") {
+            name if name.starts_with("super::") => (name.trim_left_matches("super::"), true),
+            name => (name, false),
+        };
+        if name.contains("::") {
+            return;
+        }
+        self.used.insert(name.into());
+        if is_super {
+            return;
+        }
+
+        let is_defined = self.scopes.iter().any(|scope| scope.contains(name))
+            || self.shell.variables.get_array(name).is_some()
+            || self.shell.variables.get_any_map(name).is_some()
+            || self.shell.variables.get_nested(name).is_some()
+            || self.shell.variables.get_var(name).is_some();
+        if !is_defined && !self.opaque {
+            let message = format!("'{}' is used before it is assigned", name);
+            self.report(LintKind::UndefinedVariable, message);
+            // The variable is reported only where it is first used.
+            self.define(name);
+        }
+    }
+
+    fn define(&mut self, name: &str) {
+        let scope = if name.starts_with("global::") || name.starts_with("super::") {
+            0
+        } else {
+            self.scopes.len() - 1
+        };
+        let name = name.trim_left_matches("global::").trim_left_matches("super::");
+        self.scopes[scope].insert(name.into());
+    }
+
+    /// The variables of a coprocess are global, even when it is started within a function.
+    fn define_coprocess(&mut self, name: &str) {
+        self.define(&["global::", name].concat());
+        self.define(&["global::", name, "_PID"].concat());
+    }
+}
+
+/// Returns the line of the command on which the span begins, counting from zero, along with the
+/// bytes of that line which the span covers. The bytes are only given if the line appears in the
+/// `source` of the script, which begins the command at `line`, as it does within the command.
+fn locate(
+    command: &str,
+    span: Range<usize>,
+    source: Option<&str>,
+    line: usize,
+) -> (usize, Option<Range<usize>>) {
+    let start = command[..span.start].rfind('\n').map_or(0, |pos| pos + 1);
+    let end = command[span.start..].find('\n').map_or(command.len(), |pos| span.start + pos);
+    let offset = command[..start].matches('\n').count();
+    let text = source.and_then(|source| source.lines().nth(line + offset - 1));
+    if text == Some(&command[start..end]) {
+        (offset, Some(span.start - start..span.end.min(end) - start))
+    } else {
+        (offset, None)
+    }
+}
+
+/// Collects the names of the coprocesses that the statements start.
+fn coprocesses(statements: &[Statement], names: &mut Vec<String>) {
+    for statement in statements {
+        match *statement {
+            Statement::Coproc { ref name, pipeline: Some(_) } => names.push(name.to_string()),
+            Statement::If { ref success, ref else_if, ref failure, .. } => {
+                coprocesses(success, names);
+                for branch in else_if {
+                    coprocesses(&branch.success, names);
+                }
+                coprocesses(failure, names);
+            }
+            Statement::For { ref statements, .. } | Statement::While { ref statements, .. } => {
+                coprocesses(statements, names)
+            }
+            Statement::Match { ref cases, .. } => for case in cases {
+                coprocesses(&case.statements, names);
+            },
+            Statement::Time(ref statement)
+            | Statement::And(ref statement)
+            | Statement::Or(ref statement)
+            | Statement::Not(ref statement) => coprocesses(slice::from_ref(&**statement), names),
+            _ => (),
+        }
+    }
+}
+
+/// Executes the function with its standard error discarded.
+fn silenced<T, F: FnOnce() -> T>(function: F) -> T {
+    let null = match OpenOptions::new().write(true).open(sys::NULL_PATH) {
+        Ok(null) => null,
+        Err(_) => return function(),
+    };
+    let stderr = match sys::dup(sys::STDERR_FILENO) {
+        Ok(stderr) => stderr,
+        Err(_) => return function(),
+    };
+    let _ = sys::dup2(null.as_raw_fd(), sys::STDERR_FILENO);
+    let result = function();
+    let _ = sys::dup2(stderr, sys::STDERR_FILENO);
+    let _ = sys::close(stderr);
+    result
+}
+
+/// Checks a script for problems without executing it.
+pub(crate) fn lint_script(shell: &Shell, script: &str) -> io::Result<Vec<Lint>> {
+    let mut source = String::new();
+    File::open(script)?.read_to_string(&mut source)?;
+    Ok(Linter::new(shell, Some(Path::new(script))).lint(&source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell::ShellBuilder;
+
+    fn lint(source: &str) -> Vec<String> {
+        let shell = ShellBuilder::new().as_library();
+        Linter::new(&shell, None)
+            .lint(source)
+            .into_iter()
+            .map(|lint| lint.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn variables() {
+        let source = "echo $first\nlet first = 1\necho $first @second\nfor item in 1 2\n    echo \
+                      $item\nend\necho $(echo $third)";
+        assert_eq!(
+            lint(source),
+            vec![
+                "1: undefined-variable: 'first' is used before it is assigned",
+                "3: undefined-variable: 'second' is used before it is assigned",
+                "7: undefined-variable: 'third' is used before it is assigned",
+            ]
+        );
+
+        // Function bodies may refer to any global that the script assigns.
+        let source =
+            "fn show\n    echo $global::value $local $super::value\nend\nlet value = 1\nshow";
+        assert_eq!(
+            lint(source),
+            vec!["2: undefined-variable: 'local' is used before it is assigned"]
+        );
+
+        // Subshells don't assign the variables of the shell.
+        assert_eq!(
+            lint("(let inner = 1)\necho $inner"),
+            vec!["2: undefined-variable: 'inner' is used before it is assigned"]
+        );
+        assert!(lint("read name\necho $name $? @PIPESTATUS $HOME $len(name)").is_empty());
+        assert!(lint("json -p doc '{}'\necho @keys(doc)\necho $split_at(HOME, 99)").is_empty());
+        // The variables of coprocesses which are started by functions are global.
+        let source = "coproc (cat)\necho @COPROC $COPROC_PID\nfn start\n    if true\n        \
+                      coproc up (cat)\n    end\nend\necho @up\nstart\necho @up[1] $up_PID";
+        assert_eq!(
+            lint(source),
+            vec!["8: undefined-variable: 'up' is used before it is assigned"]
+        );
+
+        // Heredocs are quoted as they would be when executed.
+        assert_eq!(
+            lint("cat <<EOF\n\\\" it's $third\nEOF"),
+            vec!["1: undefined-variable: 'third' is used before it is assigned"]
+        );
+    }
+
+    #[test]
+    fn functions() {
+        let source = "early\nfn early\nend\nfn pair a b:int\n    echo $a $b\nend\npair 1\npair 1 \
+                      two\npair @values\nnonexistent_command_for_ion_lint";
+        assert_eq!(
+            lint(source),
+            vec![
+                "1: undefined-function: 'early' is called before it is defined",
+                "7: argument-count: 'pair' takes 2 arguments, but 1 was given",
+                "8: type-mismatch: cannot pass 'two' to the 'b' parameter of 'pair': expected int",
+                "9: undefined-variable: 'values' is used before it is assigned",
+                "10: undefined-function: 'nonexistent_command_for_ion_lint' is not a function, \
+                 builtin or command",
+            ]
+        );
+
+        assert_eq!(
+            lint("fn show used unused _ignored\n    echo $used\nend"),
+            vec!["1: unused-parameter: the 'unused' parameter of 'show' is never used"]
+        );
+    }
+
+    #[test]
+    fn assignments() {
+        assert_eq!(
+            lint("let a:int = one\nlet b:bool[] = [true 2]\nlet c:int = [1]\nlet d:float = 1.5"),
+            vec![
+                "1: type-mismatch: cannot assign 'one' to 'a': expected int",
+                "2: type-mismatch: cannot assign '[true 2]' to 'b': expected bool[]",
+                "3: type-mismatch: expected int, but received []",
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        let source = "for x in 1 2\n    break\n    echo $x\n    echo twice\nend\nif true\n    \
+                      exit 1\nelse\n    echo reachable\nend\necho reachable";
+        assert_eq!(
+            lint(source),
+            vec!["3: unreachable-code: this statement follows `break`, and is never run"]
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            lint("echo one\nend\nfor x in 1 2\n    echo 'two"),
+            vec![
+                "2: syntax-error: no block to end",
+                "3: syntax-error: expected an end to the `for` block",
+                "4: syntax-error: unterminated quote",
+            ]
+        );
+    }
+}
@@ -0,0 +1,664 @@
+//! A language server for Ion scripts, which speaks the Language Server Protocol over the
+//! standard input and output of the shell.
+
+use super::{
+    lint::{Lint, LintKind, Linter}, terminate::terminate_lines,
+};
+use builtins::{man_page, BUILTINS};
+use fnv::FnvHashMap;
+use json::Json;
+use parser::{
+    assignments::{KeyBuf, KeyIterator}, parse_and_validate, StatementSplitter, ARRAY_METHODS,
+    STRING_METHODS,
+};
+use shell::{
+    flow_control::{ExportAction, LocalAction, Statement}, status::*, Shell,
+};
+use std::{
+    io::{self, BufRead, ErrorKind, Read, Write}, path::Path,
+};
+
+// The codes of the errors which a request may fail with.
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+// The kinds of completion items, as they are numbered by the protocol.
+const METHOD: usize = 2;
+const FUNCTION: usize = 3;
+const VARIABLE: usize = 6;
+
+/// Reads the next message from the client, which is preceded by a header that gives its
+/// length. `None` is returned once the client has closed its end of the stream.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_right();
+        if header.is_empty() {
+            break;
+        }
+        let mut fields = header.splitn(2, ':');
+        let name = fields.next().unwrap_or("");
+        if name.eq_ignore_ascii_case("content-length") {
+            length = fields.next().and_then(|value| value.trim().parse::<usize>().ok());
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "message has no Content-Length"))?;
+    let mut body = Vec::with_capacity(length);
+    input.take(length as u64).read_to_end(&mut body)?;
+    if body.len() != length {
+        return Err(io::Error::new(ErrorKind::UnexpectedEof, "message was cut short"));
+    }
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "message is not valid UTF-8"))
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn response(id: Json, result: Json) -> Json {
+    Json::object(vec![("jsonrpc", "2.0".into()), ("id", id), ("result", result)])
+}
+
+fn error(id: Json, code: i32, message: String) -> Json {
+    let error = Json::object(vec![("code", code.into()), ("message", message.into())]);
+    Json::object(vec![("jsonrpc", "2.0".into()), ("id", id), ("error", error)])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+}
+
+fn range(line: usize, start: usize, end: usize) -> Json {
+    let position = |character: usize| {
+        Json::object(vec![("line", line.into()), ("character", character.into())])
+    };
+    Json::object(vec![("start", position(start)), ("end", position(end))])
+}
+
+/// Returns the length of some text in UTF-16 code units, by which columns are measured.
+fn utf16_length(text: &str) -> usize { text.chars().map(char::len_utf16).sum() }
+
+/// Converts a column, which is measured in UTF-16 code units, to a byte offset within a line.
+fn byte_offset(line: &str, column: usize) -> usize {
+    let mut units = 0;
+    for (pos, character) in line.char_indices() {
+        if units >= column {
+            return pos;
+        }
+        units += character.len_utf16();
+    }
+    line.len()
+}
+
+fn is_word(character: char) -> bool {
+    character.is_alphanumeric() || character == '_' || character == '-' || character == ':'
+}
+
+/// A function which is defined by a document.
+struct Definition {
+    name:        String,
+    args:        Vec<KeyBuf>,
+    description: Option<String>,
+    /// The line on which the function is defined, and the column at which its name begins.
+    line:        usize,
+    column:      usize,
+}
+
+impl Definition {
+    fn signature(&self) -> String {
+        let mut signature = ["fn ", &self.name].concat();
+        for arg in &self.args {
+            signature.push(' ');
+            signature.push_str(&arg.to_string());
+        }
+        signature
+    }
+}
+
+/// The functions and variables which are defined by a document.
+#[derive(Default)]
+struct Symbols {
+    functions: Vec<Definition>,
+    variables: Vec<String>,
+}
+
+impl Symbols {
+    fn new(text: &str) -> Symbols {
+        let lines = text.lines().collect::<Vec<&str>>();
+        let mut symbols = Symbols::default();
+        // A document which ends within a quote still defines what precedes that quote.
+        let _ = terminate_lines(text.lines().map(String::from), |number, command| {
+            for statement in StatementSplitter::new(&command) {
+                if let Ok(statement) = parse_and_validate(statement) {
+                    symbols.add(statement, number - 1, lines[number - 1]);
+                }
+            }
+        });
+        symbols.variables.sort();
+        symbols.variables.dedup();
+        symbols
+    }
+
+    fn add(&mut self, statement: Statement, line: usize, text: &str) {
+        match statement {
+            Statement::Function {
+                name,
+                args,
+                description,
+                ..
+            } => {
+                let column = text
+                    .find("fn ")
+                    .and_then(|pos| text[pos + 3..].find(&*name).map(|name| pos + 3 + name))
+                    .map_or(0, |pos| utf16_length(&text[..pos]));
+                self.variables.extend(args.iter().map(|arg| arg.name.clone()));
+                self.functions.push(Definition {
+                    name: name.to_string(),
+                    args,
+                    description,
+                    line,
+                    column,
+                });
+            }
+            Statement::Let(LocalAction::Assign(keys, ..))
+            | Statement::Export(ExportAction::Assign(keys, ..)) => {
+                for key in KeyIterator::new(&keys).filter_map(Result::ok) {
+                    let name = key.name.trim_left_matches("global::").trim_left_matches("super::");
+                    self.variables.push(name.to_owned());
+                }
+            }
+            Statement::For { variables, .. } => {
+                self.variables.extend(variables.iter().map(ToString::to_string))
+            }
+            Statement::And(statement)
+            | Statement::Or(statement)
+            | Statement::Not(statement)
+            | Statement::Time(statement) => self.add(*statement, line, text),
+            _ => (),
+        }
+    }
+
+    /// Finds the definition of a function which is in effect at the given line.
+    fn function(&self, name: &str, line: usize) -> Option<&Definition> {
+        let mut functions = self.functions.iter().filter(|function| function.name == name);
+        let first = functions.next()?;
+        Some(functions.filter(|function| function.line <= line).last().unwrap_or(first))
+    }
+}
+
+/// The state of the server, which holds the documents that the client has opened.
+pub(crate) struct Server<'a> {
+    shell:     &'a Shell,
+    documents: FnvHashMap<String, String>,
+    shutdown:  bool,
+    /// Set to the status that the shell exits with, once the client asks the server to exit.
+    exit:      Option<i32>,
+}
+
+impl<'a> Server<'a> {
+    pub(crate) fn new(shell: &'a Shell) -> Server<'a> {
+        Server {
+            shell,
+            documents: FnvHashMap::default(),
+            shutdown: false,
+            exit: None,
+        }
+    }
+
+    /// Handles a message from the client, returning the messages that are sent in reply.
+    pub(crate) fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = match message.get("method").and_then(Json::as_str) {
+            Some(method) => method,
+            // The client has replied to a request, but the server never sends any.
+            None => return Vec::new(),
+        };
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return self.notification(method, params),
+        };
+
+        let result = if self.shutdown {
+            Err((INVALID_REQUEST, "the server has been shut down".into()))
+        } else {
+            self.request(method, params)
+        };
+        vec![match result {
+            Ok(result) => response(id, result),
+            Err((code, message)) => error(id, code, message),
+        }]
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let document = params.get("textDocument");
+        let uri = document.and_then(|document| document.get("uri")).and_then(Json::as_str);
+        match (method, uri) {
+            ("exit", _) => {
+                self.exit = Some(if self.shutdown { SUCCESS } else { FAILURE });
+                Vec::new()
+            }
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = document.and_then(|document| document.get("text"));
+                let text = text.and_then(Json::as_str).unwrap_or("");
+                self.documents.insert(uri.to_owned(), text.to_owned());
+                vec![self.diagnostics(uri)]
+            }
+            // Documents are synchronized by sending their full text after each change.
+            ("textDocument/didChange", Some(uri)) => {
+                let changes = match params.get("contentChanges") {
+                    Some(&Json::Array(ref changes)) => changes,
+                    _ => return Vec::new(),
+                };
+                let text = changes.last().and_then(|change| change.get("text"));
+                match text.and_then(Json::as_str) {
+                    Some(text) => {
+                        self.documents.insert(uri.to_owned(), text.to_owned());
+                        vec![self.diagnostics(uri)]
+                    }
+                    None => Vec::new(),
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(uri);
+                let params = Json::object(vec![
+                    ("uri", uri.into()),
+                    ("diagnostics", Json::Array(Vec::new())),
+                ]);
+                vec![notification("textDocument/publishDiagnostics", params)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i32, String)> {
+        match method {
+            "initialize" => {
+                let triggers = Json::Array(vec!["$".into(), "@".into()]);
+                let completion = Json::object(vec![("triggerCharacters", triggers)]);
+                let capabilities = Json::object(vec![
+                    ("textDocumentSync", 1usize.into()),
+                    ("completionProvider", completion),
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                ]);
+                let info = Json::object(vec![("name", "ion".into())]);
+                Ok(Json::object(vec![("capabilities", capabilities), ("serverInfo", info)]))
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/completion" => {
+                let (uri, line, column) = position(params)?;
+                Ok(completion(self.document(uri)?, line, column))
+            }
+            "textDocument/hover" => {
+                let (uri, line, column) = position(params)?;
+                Ok(hover(self.document(uri)?, line, column))
+            }
+            "textDocument/definition" => {
+                let (uri, line, column) = position(params)?;
+                Ok(definition(uri, self.document(uri)?, line, column))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method: {}", method))),
+        }
+    }
+
+    fn document(&self, uri: &str) -> Result<&str, (i32, String)> {
+        match self.documents.get(uri) {
+            Some(text) => Ok(text),
+            None => Err((INVALID_PARAMS, format!("{} has not been opened", uri))),
+        }
+    }
+
+    /// Lints a document, and publishes the problems that were found as its diagnostics.
+    fn diagnostics(&self, uri: &str) -> Json {
+        let text = &self.documents[uri];
+        let lines = text.lines().collect::<Vec<&str>>();
+        let script = if uri.starts_with("file://") { Some(Path::new(&uri[7..])) } else { None };
+        let diagnostics = Linter::new(self.shell, script)
+            .lint(text)
+            .into_iter()
+            .map(|lint| diagnostic(lint, &lines))
+            .collect();
+
+        let params =
+            Json::object(vec![("uri", uri.into()), ("diagnostics", Json::Array(diagnostics))]);
+        notification("textDocument/publishDiagnostics", params)
+    }
+}
+
+/// Converts a lint into a diagnostic, which covers the text at which a syntax error was found,
+/// or otherwise the whole of the line on which the problem was found.
+fn diagnostic(lint: Lint, lines: &[&str]) -> Json {
+    let line = lint.line.saturating_sub(1);
+    let text = lines.get(line).cloned().unwrap_or("");
+    let (start, end) = match lint.columns {
+        Some(ref columns) if text.get(columns.clone()).is_some() => {
+            (utf16_length(&text[..columns.start]), utf16_length(&text[..columns.end]))
+        }
+        _ => (0, utf16_length(text)),
+    };
+    let severity = if lint.kind == LintKind::SyntaxError { 1 } else { 2 };
+    Json::object(vec![
+        ("range", range(line, start, end)),
+        ("severity", Json::from(severity)),
+        ("code", lint.kind.to_string().into()),
+        ("source", "ion".into()),
+        ("message", lint.message.into()),
+    ])
+}
+
+/// Returns the document and the position within it that a request refers to.
+fn position(params: &Json) -> Result<(&str, usize, usize), (i32, String)> {
+    let uri = params.get("textDocument").and_then(|document| document.get("uri"));
+    let position = params.get("position");
+    let line = position.and_then(|position| position.get("line")).and_then(Json::as_usize);
+    let column = position.and_then(|position| position.get("character")).and_then(Json::as_usize);
+    match (uri.and_then(Json::as_str), line, column) {
+        (Some(uri), Some(line), Some(column)) => Ok((uri, line, column)),
+        _ => Err((INVALID_PARAMS, "expected a document and a position".into())),
+    }
+}
+
+/// Returns the word which contains the given column, and its range of bytes within the line,
+/// along with the character that precedes it.
+fn word_at(line: &str, column: usize) -> (&str, usize, usize, Option<char>) {
+    let offset = byte_offset(line, column);
+    let start = line[..offset]
+        .char_indices()
+        .rev()
+        .find(|&(_, character)| !is_word(character))
+        .map_or(0, |(pos, character)| pos + character.len_utf8());
+    let end = line[offset..]
+        .find(|character| !is_word(character))
+        .map_or(line.len(), |pos| offset + pos);
+    (&line[start..end], start, end, line[..start].chars().next_back())
+}
+
+fn item(label: &str, kind: usize, detail: &str, documentation: Option<&str>) -> Json {
+    let mut item = Json::object(vec![
+        ("label", label.into()),
+        ("kind", kind.into()),
+        ("detail", detail.into()),
+    ]);
+    if let (Some(documentation), &mut Json::Object(ref mut members)) = (documentation, &mut item) {
+        members.push(("documentation".into(), documentation.into()));
+    }
+    item
+}
+
+/// Completes the word which ends at the given position. Variables and methods are offered
+/// after a sigil, and builtins and functions otherwise.
+fn completion(text: &str, line: usize, column: usize) -> Json {
+    let current = text.lines().nth(line).unwrap_or("");
+    let offset = byte_offset(current, column);
+    let (_, start, _, sigil) = word_at(&current[..offset], column);
+    let prefix = &current[start..offset];
+    let symbols = Symbols::new(text);
+
+    let mut items = Vec::new();
+    match sigil {
+        Some(sigil) if sigil == '$' || sigil == '@' => {
+            let (methods, detail) = if sigil == '$' {
+                (STRING_METHODS, "string method")
+            } else {
+                (ARRAY_METHODS, "array method")
+            };
+            for variable in symbols.variables.iter().filter(|name| name.starts_with(prefix)) {
+                items.push(item(variable, VARIABLE, "variable", None));
+            }
+            for method in methods.iter().filter(|name| name.starts_with(prefix)) {
+                items.push(item(method, METHOD, detail, None));
+            }
+        }
+        _ => {
+            for &name in BUILTINS.keys().iter().filter(|name| name.starts_with(prefix)) {
+                let help = BUILTINS.get(name).map_or("", |builtin| builtin.help);
+                items.push(item(name, FUNCTION, "builtin", Some(help)));
+            }
+            for function in symbols.functions.iter().filter(|f| f.name.starts_with(prefix)) {
+                let signature = function.signature();
+                let description = function.description.as_ref().map(String::as_str);
+                items.push(item(&function.name, FUNCTION, &signature, description));
+            }
+        }
+    }
+    Json::Array(items)
+}
+
+/// Describes the function or builtin at the given position, using the description of the
+/// function or the manual page of the builtin.
+fn hover(text: &str, line: usize, column: usize) -> Json {
+    let current = text.lines().nth(line).unwrap_or("");
+    let (word, start, end, sigil) = word_at(current, column);
+    if word.is_empty() || sigil == Some('$') || sigil == Some('@') {
+        return Json::Null;
+    }
+
+    let symbols = Symbols::new(text);
+    let contents = match symbols.function(word, line) {
+        Some(function) => match function.description {
+            Some(ref description) => [&function.signature(), "\n\n", description].concat(),
+            None => function.signature(),
+        },
+        None => match BUILTINS.get(word) {
+            Some(builtin) => man_page(word).unwrap_or(builtin.help).to_owned(),
+            None => return Json::Null,
+        },
+    };
+
+    let contents = Json::object(vec![("kind", "plaintext".into()), ("value", contents.into())]);
+    let range = range(line, utf16_length(&current[..start]), utf16_length(&current[..end]));
+    Json::object(vec![("contents", contents), ("range", range)])
+}
+
+/// Locates the definition of the function at the given position.
+fn definition(uri: &str, text: &str, line: usize, column: usize) -> Json {
+    let current = text.lines().nth(line).unwrap_or("");
+    let (word, ..) = word_at(current, column);
+    match Symbols::new(text).function(word, line) {
+        Some(function) => {
+            let end = function.column + utf16_length(&function.name);
+            Json::object(vec![
+                ("uri", uri.into()),
+                ("range", range(function.line, function.column, end)),
+            ])
+        }
+        None => Json::Null,
+    }
+}
+
+/// Serves the requests which are read from `input`, until the client asks the server to exit,
+/// returning the status with which the shell should exit.
+pub(crate) fn serve<R: BufRead, W: Write>(shell: &Shell, mut input: R, mut output: W) -> i32 {
+    let mut server = Server::new(shell);
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            // The client went away without asking the server to exit.
+            Ok(None) => return FAILURE,
+            Err(why) => {
+                eprintln!("ion: --lsp: {}", why);
+                return FAILURE;
+            }
+        };
+
+        let replies = match Json::parse(&message) {
+            Ok(message) => server.handle(&message),
+            Err(why) => vec![error(Json::Null, PARSE_ERROR, why.to_string())],
+        };
+        for reply in replies {
+            if let Err(why) = write_message(&mut output, &reply) {
+                eprintln!("ion: --lsp: {}", why);
+                return FAILURE;
+            }
+        }
+
+        if let Some(status) = server.exit {
+            return status;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell::ShellBuilder;
+    use std::io::Cursor;
+
+    const URI: &str = "file:///script.ion";
+    const SCRIPT: &str = "fn greet name -- Says hello\n    echo hello $name\nend\nlet target = \
+                          world\ngreet $target\nundefined_function_for_ion_lsp";
+
+    /// Runs a session with the server, returning the messages which it replied with.
+    fn session(messages: &[&str]) -> (i32, Vec<Json>) {
+        let mut input = Vec::new();
+        for message in messages {
+            write!(input, "Content-Length: {}\r\n\r\n{}", message.len(), message).unwrap();
+        }
+        let mut output = Vec::new();
+        let shell = ShellBuilder::new().as_library();
+        let status = serve(&shell, Cursor::new(input), &mut output);
+
+        let mut output = Cursor::new(output);
+        let mut replies = Vec::new();
+        while let Some(reply) = read_message(&mut output).unwrap() {
+            replies.push(Json::parse(&reply).unwrap());
+        }
+        (status, replies)
+    }
+
+    fn request(id: usize, method: &str, params: Json) -> String {
+        let mut request = notification(method, params);
+        if let Json::Object(ref mut members) = request {
+            members.insert(1, ("id".into(), id.into()));
+        }
+        request.to_string()
+    }
+
+    fn at(id: usize, method: &str, line: usize, character: usize) -> String {
+        let position = Json::object(vec![("line", line.into()), ("character", character.into())]);
+        let params = Json::object(vec![
+            ("textDocument", Json::object(vec![("uri", URI.into())])),
+            ("position", position),
+        ]);
+        request(id, method, params)
+    }
+
+    fn labels(reply: &Json) -> Vec<&str> {
+        match reply.get("result") {
+            Some(&Json::Array(ref items)) => {
+                items.iter().filter_map(|item| item.get("label").and_then(Json::as_str)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn session_lifecycle() {
+        let document = Json::object(vec![("uri", URI.into()), ("text", SCRIPT.into())]);
+        let open = Json::object(vec![("textDocument", document)]);
+        let messages = [
+            request(1, "initialize", Json::Object(Vec::new())),
+            notification("initialized", Json::Object(Vec::new())).to_string(),
+            notification("textDocument/didOpen", open).to_string(),
+            at(2, "textDocument/completion", 4, 2),
+            at(3, "textDocument/completion", 4, 9),
+            at(4, "textDocument/hover", 4, 1),
+            at(5, "textDocument/definition", 4, 3),
+            at(6, "textDocument/hover", 1, 5),
+            request(7, "textDocument/rename", Json::Object(Vec::new())),
+            "{not json".to_owned(),
+            request(8, "shutdown", Json::Null),
+            notification("exit", Json::Null).to_string(),
+        ];
+        let messages = messages.iter().map(String::as_str).collect::<Vec<&str>>();
+        let (status, replies) = session(&messages);
+        assert_eq!(SUCCESS, status);
+        assert_eq!(10, replies.len());
+
+        let capabilities = replies[0].get("result").and_then(|result| result.get("capabilities"));
+        assert_eq!(Some(&Json::Bool(true)), capabilities.and_then(|c| c.get("hoverProvider")));
+
+        // Opening the document publishes the problems that were found within it.
+        let diagnostics = replies[1].get("params").and_then(|params| params.get("diagnostics"));
+        let diagnostic = match diagnostics {
+            Some(&Json::Array(ref diagnostics)) if diagnostics.len() == 1 => &diagnostics[0],
+            _ => panic!("expected a single diagnostic: {:?}", diagnostics),
+        };
+        assert_eq!(Some(&range(5, 0, 30)), diagnostic.get("range"));
+        assert_eq!(Some("undefined-function"), diagnostic.get("code").and_then(Json::as_str));
+
+        assert_eq!(vec!["greet"], labels(&replies[2]));
+        assert_eq!(vec!["target"], labels(&replies[3]));
+
+        let hover = replies[4].get("result").and_then(|result| result.get("contents"));
+        let hover = hover.and_then(|contents| contents.get("value")).and_then(Json::as_str);
+        assert_eq!(Some("fn greet name\n\nSays hello"), hover);
+
+        let location = replies[5].get("result");
+        assert_eq!(Some(&range(0, 3, 8)), location.and_then(|location| location.get("range")));
+
+        let hover = replies[6].get("result").and_then(|result| result.get("contents"));
+        let hover = hover.and_then(|contents| contents.get("value")).and_then(Json::as_str);
+        assert!(hover.map_or(false, |hover| hover.contains("echo - display a line of text")));
+
+        let code = |reply: &Json| {
+            reply.get("error").and_then(|error| error.get("code")).cloned()
+        };
+        assert_eq!(Some(Json::from(METHOD_NOT_FOUND)), code(&replies[7]));
+        assert_eq!(Some(Json::from(PARSE_ERROR)), code(&replies[8]));
+        assert_eq!(Some(&Json::Null), replies[9].get("result"));
+    }
+
+    #[test]
+    fn completions() {
+        let text = "let files = [a b]\nfor file in @files\n    echo $len(@fi) $fi\nend\necho @sp";
+        let labels = |line, column| match completion(text, line, column) {
+            Json::Array(items) => items
+                .iter()
+                .filter_map(|item| item.get("label").and_then(Json::as_str).map(String::from))
+                .collect::<Vec<String>>(),
+            _ => Vec::new(),
+        };
+        assert_eq!(vec!["len", "len_bytes"], labels(2, 12));
+        assert_eq!(vec!["file", "files"], labels(2, 17));
+        assert_eq!(vec!["file", "files", "filename", "find"], labels(2, 22));
+        assert_eq!(vec!["split", "split_at"], labels(4, 8));
+    }
+
+    #[test]
+    fn diagnostic_ranges() {
+        let shell = ShellBuilder::new().as_library();
+        let text = "echo ok\n    echo é (two)\nlet x = [1";
+        let lines = text.lines().collect::<Vec<&str>>();
+        let ranges = Linter::new(&shell, None)
+            .lint(text)
+            .into_iter()
+            .map(|lint| diagnostic(lint, &lines).get("range").cloned())
+            .collect::<Vec<Option<Json>>>();
+        // The column of a syntax error is given, but unterminated quotes cover the whole line.
+        assert_eq!(vec![Some(range(1, 11, 12)), Some(range(2, 0, 10))], ranges);
+    }
+
+    #[test]
+    fn exit_without_shutdown() {
+        let (status, replies) = session(&[r#"{"jsonrpc":"2.0","method":"exit"}"#]);
+        assert_eq!(FAILURE, status);
+        assert!(replies.is_empty());
+        assert_eq!(FAILURE, session(&[]).0);
+    }
+}
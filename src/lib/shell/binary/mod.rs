@@ -1,22 +1,32 @@
 //! Contains the binary logic of Ion.
 mod designators;
+mod format;
+mod lint;
+mod lsp;
 mod prompt;
 mod readln;
 mod terminate;
 
 use self::{
-    prompt::{prompt, prompt_fn}, readln::readln,
+    format::format, lint::lint_script, lsp::serve, prompt::{prompt, prompt_fn}, readln::readln,
     terminate::{terminate_quotes, terminate_script_quotes},
 };
-use super::{flow_control::Statement, status::*, FlowLogic, Shell, ShellHistory};
+use super::{
+    debugger::Debugger, flow_control::Statement, profiler::Profiler, status::*, FlowLogic, Shell,
+    ShellHistory,
+};
 use liner::{Buffer, Context};
-use std::{env, fs::File, io::ErrorKind, iter, path::Path, process};
+use std::{
+    cell::RefCell, env, fs::{self, File}, io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    iter, path::Path, process,
+};
 
 pub const MAN_ION: &'static str = r#"NAME
     ion - ion shell
 
 SYNOPSIS
-    ion [ -h | --help ] [-c] [-n] [-v]
+    ion [ -h | --help ] [-c] [-n] [-v] [--lint] [--fmt [--check]] [--lsp]
+        [--debug SCRIPT [ARGS...]] [--profile [--folded FILE] SCRIPT [ARGS...]]
 
 DESCRIPTION
     ion is a commandline shell created to be a faster and easier to use alternative to the
@@ -27,15 +37,54 @@ OPTIONS
         evaulates given commands instead of reading from the commandline.
 
     -n or --no-execute
-        do not execute any commands, just do syntax checking.
+        do not execute any commands, just do syntax checking. Errors are reported with their
+        location, and the exit status is 1 if any were found.
 
     -v or --version
         prints the version, platform and revision of ion then exits.
+
+    --lint SCRIPT...
+        checks each script for likely mistakes without executing it, printing one problem per
+        line as `script:line: kind: message`. The exit status is 1 if any were found.
+
+    --fmt [--check] [SCRIPT...]
+        formats each script in place, indenting the bodies of blocks and normalizing the layout
+        of statements while keeping comments. With --check, the scripts are left as they are,
+        and those which would be changed are printed, with an exit status of 1 if there were
+        any. Without scripts, standard input is formatted to standard output.
+
+    --lsp
+        runs a language server for ion scripts, which speaks the Language Server Protocol over
+        standard input and output. It publishes the problems found by --lint as diagnostics,
+        and provides completion, hover documentation and the definitions of functions.
+
+    --debug SCRIPT [ARGS...]
+        executes the script within a debugger, which pauses before its first statement and
+        reads commands from the terminal. Breakpoints may be set on lines or functions, and
+        at each pause the script may be stepped through, its variables printed or assigned,
+        and the functions being called listed. Enter `help` at a pause for the commands.
+
+    --profile [--folded FILE] SCRIPT [ARGS...]
+        executes the script, and then writes a report to standard error of the time spent by
+        each statement, function, external command and command substitution, along with how
+        many times each was executed, with the slowest first. With --folded, the time spent
+        within each stack of functions is also written to the file, in the folded format
+        which flamegraph tools accept.
 "#;
 
 pub trait Binary {
     /// Parses and executes the arguments that were supplied to the shell.
     fn execute_arguments<A: Iterator<Item = String>>(&mut self, args: A);
+    /// Checks each script for problems without executing it, and prints those that were found.
+    fn lint_scripts<A: Iterator<Item = String>>(&mut self, scripts: A) -> i32;
+    /// Formats each script in place, or only reports those which need formatting.
+    fn format_scripts<A: Iterator<Item = String>>(&mut self, args: A) -> i32;
+    /// Serves the Language Server Protocol over standard input and output.
+    fn language_server(&mut self) -> i32;
+    /// Executes a script with its arguments, pausing for the commands of a debugger.
+    fn debug_script<A: Iterator<Item = String>>(&mut self, args: A);
+    /// Executes a script with its arguments, reporting where the time was spent on exit.
+    fn profile_script<A: Iterator<Item = String>>(&mut self, args: A);
     /// Creates an interactive session that reads from a prompt provided by
     /// Liner.
     fn execute_interactive(self);
@@ -136,6 +185,7 @@ impl Binary for Shell {
                         let cmd: &str = &designators::expand_designators(&self, command.trim());
                         self.on_command(&cmd);
                         self.save_command(&cmd);
+                        self.line += 1;
                     } else {
                         self.reset_flow();
                     }
@@ -171,6 +221,136 @@ impl Binary for Shell {
         }
     }
 
+    fn lint_scripts<A: Iterator<Item = String>>(&mut self, scripts: A) -> i32 {
+        let mut status = SUCCESS;
+        let mut checked = false;
+        for script in scripts {
+            checked = true;
+            match lint_script(self, &script) {
+                Ok(lints) => for lint in lints {
+                    println!("{}:{}", script, lint);
+                    status = FAILURE;
+                },
+                Err(why) => {
+                    eprintln!("ion: {}: {}", script, why);
+                    status = FAILURE;
+                }
+            }
+        }
+
+        if !checked {
+            eprintln!("ion: --lint requires a script");
+            return FAILURE;
+        }
+        status
+    }
+
+    fn format_scripts<A: Iterator<Item = String>>(&mut self, args: A) -> i32 {
+        let mut check = false;
+        let mut scripts = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--check" => check = true,
+                _ => scripts.push(arg),
+            }
+        }
+
+        if scripts.is_empty() {
+            let mut source = String::new();
+            if let Err(why) = io::stdin().read_to_string(&mut source) {
+                eprintln!("ion: failed to read from stdin: {}", why);
+                return FAILURE;
+            }
+            return match format(&source) {
+                Ok(ref formatted) if check && formatted != &source => FAILURE,
+                Ok(_) if check => SUCCESS,
+                Ok(formatted) => {
+                    let _ = io::stdout().write_all(formatted.as_bytes());
+                    SUCCESS
+                }
+                Err(why) => {
+                    eprintln!("ion: stdin:{}", why);
+                    FAILURE
+                }
+            };
+        }
+
+        let mut status = SUCCESS;
+        for script in scripts {
+            let source = match fs::read_to_string(&script) {
+                Ok(source) => source,
+                Err(why) => {
+                    eprintln!("ion: {}: {}", script, why);
+                    status = FAILURE;
+                    continue;
+                }
+            };
+            match format(&source) {
+                Ok(ref formatted) if formatted == &source => (),
+                Ok(_) if check => {
+                    println!("{}", script);
+                    status = FAILURE;
+                }
+                Ok(formatted) => if let Err(why) = fs::write(&script, formatted) {
+                    eprintln!("ion: {}: {}", script, why);
+                    status = FAILURE;
+                },
+                Err(why) => {
+                    eprintln!("ion: {}:{}", script, why);
+                    status = FAILURE;
+                }
+            }
+        }
+        status
+    }
+
+    fn language_server(&mut self) -> i32 {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        serve(self, stdin.lock(), stdout.lock())
+    }
+
+    fn debug_script<A: Iterator<Item = String>>(&mut self, mut args: A) {
+        let script = match args.next() {
+            Some(script) => script,
+            None => {
+                eprintln!("ion: --debug requires a script");
+                self.exit(FAILURE);
+            }
+        };
+
+        // Commands are read from the terminal, as standard input belongs to the script.
+        let input: Box<BufRead> = match File::open("/dev/tty") {
+            Ok(terminal) => Box::new(BufReader::new(terminal)),
+            Err(_) => Box::new(BufReader::new(io::stdin())),
+        };
+        self.debugger = Some(Box::new(Debugger::new(input, Box::new(io::stderr()))));
+        execute_script_with_args(self, script, args);
+    }
+
+    fn profile_script<A: Iterator<Item = String>>(&mut self, mut args: A) {
+        let mut folded = None;
+        let script = loop {
+            match args.next() {
+                Some(ref arg) if arg == "--folded" && folded.is_none() => match args.next() {
+                    Some(path) => folded = Some(path),
+                    None => {
+                        eprintln!("ion: --folded requires a file");
+                        self.exit(FAILURE);
+                    }
+                },
+                Some(script) => break script,
+                None => {
+                    eprintln!("ion: --profile requires a script");
+                    self.exit(FAILURE);
+                }
+            }
+        };
+
+        self.profiler = Some(RefCell::new(Profiler::new(&script, folded)));
+        execute_script_with_args(self, script, args);
+    }
+
     fn terminate_quotes(&mut self, command: String) -> Result<String, ()> {
         terminate_quotes(self, command)
     }
@@ -241,6 +421,18 @@ where
     }
 }
 
+/// Executes a script, with the script and its arguments as the `args` array.
+fn execute_script_with_args<A>(shell: &mut Shell, script: String, args: A)
+where
+    A: Iterator<Item = String>,
+{
+    let array = iter::once(script.clone()).chain(args).map(Into::into).collect();
+    shell.variables.set_array("args", array);
+    if let Err(err) = shell.execute_script(&script) {
+        eprintln!("ion: {}: {}", script, err);
+    }
+}
+
 fn word_divide<'a>(buf: &'a Buffer) -> Vec<(usize, usize)> {
     // -> impl Iterator<Item = (usize, usize)> + 'a
     WordDivide {
@@ -1,40 +1,66 @@
-use super::super::{status::*, Binary, FlowLogic, Shell};
+use super::super::{flags::NO_EXEC, status::*, Binary, FlowLogic, Shell};
 use parser::Terminator;
 
-pub(crate) fn terminate_script_quotes<I: Iterator<Item = String>>(
-    shell: &mut Shell,
-    mut lines: I,
-) -> i32 {
-    while let Some(command) = lines.next() {
-        let mut buffer = Terminator::new(command);
+/// Joins the lines of a script into complete commands, which are handed to `command` along
+/// with the line on which they begin. Should the script end within a quote or a heredoc, the
+/// line of the unterminated command is returned, along with the kind of terminator it lacks.
+pub(crate) fn terminate_lines<I, F>(lines: I, mut command: F) -> Result<(), (usize, &'static str)>
+where
+    I: Iterator<Item = String>,
+    F: FnMut(usize, String),
+{
+    // Lines are numbered from one, and a command is located by the line on which it begins.
+    let mut lines = lines.zip(1..);
+    while let Some((line, number)) = lines.next() {
+        let mut buffer = Terminator::new(line);
         while !buffer.is_terminated() {
             loop {
-                if let Some(command) = lines.next() {
-                    if !command.starts_with('#') {
+                if let Some((line, _)) = lines.next() {
+                    if buffer.is_heredoc() {
+                        buffer.append(&line);
+                        break;
+                    } else if !line.starts_with('#') {
                         let mut start = 0;
                         let cmd: &str = loop {
-                            if start >= command.len() {
-                                break &command;
+                            if start >= line.len() {
+                                break &line;
                             }
 
-                            match command[start..].find('#').map(|x| x + start) {
-                                Some(pos) if command.as_bytes()[pos - 1] != b' ' => {
+                            match line[start..].find('#').map(|x| x + start) {
+                                Some(pos) if line.as_bytes()[pos - 1] != b' ' => {
                                     start = pos + 1;
                                 }
-                                Some(pos) => break &command[..pos],
-                                None => break &command,
+                                Some(pos) => break &line[..pos],
+                                None => break &line,
                             }
                         };
                         buffer.append(cmd);
                         break;
                     }
                 } else {
-                    eprintln!("ion: unterminated quote in script");
-                    return FAILURE;
+                    let kind = if buffer.is_heredoc() { "heredoc" } else { "quote" };
+                    return Err((number, kind));
                 }
             }
         }
-        shell.on_command(&buffer.consume());
+        command(number, buffer.consume());
+    }
+    Ok(())
+}
+
+pub(crate) fn terminate_script_quotes<I: Iterator<Item = String>>(
+    shell: &mut Shell,
+    lines: I,
+) -> i32 {
+    let result = terminate_lines(lines, |number, command| {
+        shell.line = number;
+        shell.on_command(&command);
+    });
+
+    if let Err((number, kind)) = result {
+        let script = shell.script.as_ref().map_or("ion", String::as_str);
+        eprintln!("ion: {}:{}: unterminated {} in script", script, number, kind);
+        return FAILURE;
     }
 
     // The flow control level being non zero means that we have a statement that has
@@ -47,6 +73,11 @@ pub(crate) fn terminate_script_quotes<I: Iterator<Item = String>>(
         return FAILURE;
     }
 
+    // A script which is only being checked with `-n` fails if it contains syntax errors.
+    if shell.flags & NO_EXEC != 0 && shell.syntax_errors.get() != 0 {
+        return FAILURE;
+    }
+
     SUCCESS
 }
 
@@ -55,7 +86,7 @@ pub(crate) fn terminate_quotes(shell: &mut Shell, command: String) -> Result<Str
     shell.flow_control.level += 1;
     while !buffer.is_terminated() {
         if let Some(command) = shell.readln() {
-            if !command.starts_with('#') {
+            if buffer.is_heredoc() || !command.starts_with('#') {
                 buffer.append(&command);
             }
         } else {
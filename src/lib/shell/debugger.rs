@@ -0,0 +1,463 @@
+//! An interactive debugger for scripts, which pauses the shell before statements are executed.
+
+use super::{assignments::VariableStore, flow_control::Statement, Shell};
+use parser::{expand_string, parse_and_validate, StatementSplitter};
+use std::{
+    fmt::{self, Display, Formatter}, fs, io::{BufRead, Write}, process,
+};
+use types::Identifier;
+
+const HELP: &str = "step (s)                   execute the next statement, entering functions
+next (n)                   execute the next statement, stepping over functions
+finish (f)                 continue until the current function returns
+continue (c)               continue until a breakpoint is reached
+break (b) [[SCRIPT:]LINE]  pause before the statements of a line
+break (b) FUNCTION         pause when a function is called
+break (b)                  list the breakpoints
+delete (d) [N]             delete a breakpoint, or all of them
+print (p) WORDS...         expand and print the words, such as $string or @array
+info (i) NAME...           print the value of a string, array or map variable
+let ...                    assign to variables, as the let builtin would
+backtrace (bt)             print the functions which are being called
+list (l)                   print the lines of the script around the current line
+quit (q)                   exit the shell
+help (h)                   print this help
+An empty line repeats the previous command.";
+
+/// The lines which are listed before and after the current line.
+const CONTEXT: usize = 5;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Breakpoint {
+    /// A line of a script, given by its path, or by the end of its path.
+    Line(String, usize),
+    /// The first statement of a function.
+    Function(String),
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Breakpoint::Line(ref script, line) => write!(f, "{}:{}", script, line),
+            Breakpoint::Function(ref name) => write!(f, "function {}", name),
+        }
+    }
+}
+
+/// Determines when the debugger next pauses, besides at breakpoints. The number of functions
+/// which were being called when the mode was chosen is recorded by those that depend on it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Mode {
+    Step,
+    Next(usize),
+    Finish(usize),
+    Continue,
+}
+
+/// A function which is being called, and where it was called from.
+struct Frame {
+    function: Identifier,
+    script:   Option<String>,
+    line:     usize,
+}
+
+pub(crate) struct Debugger {
+    input:       Box<BufRead>,
+    output:      Box<Write>,
+    breakpoints: Vec<Breakpoint>,
+    mode:        Mode,
+    frames:      Vec<Frame>,
+    /// Set when a line is started, as a breakpoint on a line pauses at its first statement.
+    new_line:    bool,
+    /// The command that is repeated when an empty line is entered.
+    previous:    String,
+    /// The process which the debugger belongs to. Forks of the shell, such as subshells and
+    /// the functions of pipelines, run without the debugger, as they can't share the terminal.
+    pid:         u32,
+}
+
+impl Debugger {
+    /// Creates a debugger which reads commands from the input, and writes to the output. The
+    /// debugger pauses at the first statement that is executed.
+    pub(crate) fn new(input: Box<BufRead>, output: Box<Write>) -> Debugger {
+        Debugger {
+            input,
+            output,
+            breakpoints: Vec::new(),
+            mode: Mode::Step,
+            frames: Vec::new(),
+            new_line: true,
+            previous: String::new(),
+            pid: process::id(),
+        }
+    }
+
+    /// Records that a function is being called from the current line of the shell. If a
+    /// breakpoint was set on the function, the debugger pauses at its first statement.
+    pub(crate) fn enter(&mut self, function: &Identifier, script: Option<String>, line: usize) {
+        let breakpoint = Breakpoint::Function(function.to_string());
+        if let Some(id) = self.breakpoints.iter().position(|b| b == &breakpoint) {
+            let _ = writeln!(self.output, "breakpoint {}, {}", id + 1, breakpoint);
+            self.mode = Mode::Step;
+        }
+        self.frames.push(Frame { function: function.clone(), script, line });
+    }
+
+    /// Records that the function which was called last has returned.
+    pub(crate) fn leave(&mut self) { self.frames.pop(); }
+
+    fn breakpoint(&self, shell: &Shell) -> Option<usize> {
+        let script = shell.script.as_ref()?;
+        self.breakpoints.iter().position(|breakpoint| match *breakpoint {
+            Breakpoint::Line(ref path, line) => {
+                line == shell.line
+                    && (script == path || script.ends_with(&["/", path.as_str()].concat()))
+            }
+            Breakpoint::Function(_) => false,
+        })
+    }
+
+    fn pause(&mut self, shell: &mut Shell, statement: &Statement) {
+        let _ = writeln!(self.output, "{}: {}", location(&shell.script, shell.line), statement);
+        loop {
+            let _ = write!(self.output, "(ion-debug) ");
+            let _ = self.output.flush();
+            let mut command = String::new();
+            match self.input.read_line(&mut command) {
+                Ok(0) | Err(_) => {
+                    // Without any further commands, the script runs to completion.
+                    let _ = writeln!(self.output);
+                    self.breakpoints.clear();
+                    self.mode = Mode::Continue;
+                    return;
+                }
+                Ok(_) => (),
+            }
+
+            let command = match command.trim() {
+                "" => self.previous.clone(),
+                command => command.to_owned(),
+            };
+            self.previous = command.clone();
+            if self.execute(shell, &command) {
+                return;
+            }
+        }
+    }
+
+    /// Executes a command of the debugger, returning `true` if the shell should resume.
+    fn execute(&mut self, shell: &mut Shell, command: &str) -> bool {
+        let (name, args) = match command.find(char::is_whitespace) {
+            Some(pos) => (&command[..pos], command[pos..].trim()),
+            None => (command, ""),
+        };
+        let depth = self.frames.len();
+        match name {
+            "s" | "step" => self.mode = Mode::Step,
+            "n" | "next" => self.mode = Mode::Next(depth),
+            "f" | "finish" if depth == 0 => {
+                let _ = writeln!(self.output, "not within a function");
+                return false;
+            }
+            "f" | "finish" => self.mode = Mode::Finish(depth),
+            "c" | "continue" => self.mode = Mode::Continue,
+            "b" | "break" => self.set_breakpoint(shell, args),
+            "d" | "delete" => self.delete_breakpoint(args),
+            "p" | "print" => {
+                let words = expand_string(args, shell, false).join(" ");
+                let _ = writeln!(self.output, "{}", words);
+            }
+            "i" | "info" => for name in args.split_whitespace() {
+                self.info(shell, name);
+            },
+            "let" => self.assign(shell, command),
+            "bt" | "backtrace" => self.backtrace(shell),
+            "l" | "list" => self.list(shell),
+            "q" | "quit" => {
+                let status = shell.previous_status;
+                shell.exit(status);
+            }
+            "h" | "help" => {
+                let _ = writeln!(self.output, "{}", HELP);
+            }
+            "" => (),
+            _ => {
+                let _ = writeln!(self.output, "unknown command: {}; try help", name);
+            }
+        }
+        match name {
+            "s" | "step" | "n" | "next" | "f" | "finish" | "c" | "continue" => true,
+            _ => false,
+        }
+    }
+
+    fn set_breakpoint(&mut self, shell: &Shell, args: &str) {
+        if args.is_empty() {
+            for (id, breakpoint) in self.breakpoints.iter().enumerate() {
+                let _ = writeln!(self.output, "{}: {}", id + 1, breakpoint);
+            }
+            return;
+        }
+
+        let breakpoint = match args.rfind(':') {
+            Some(pos) => match args[pos + 1..].parse::<usize>() {
+                Ok(line) => Breakpoint::Line(args[..pos].to_owned(), line),
+                Err(_) => {
+                    let _ = writeln!(self.output, "invalid line: {}", &args[pos + 1..]);
+                    return;
+                }
+            },
+            None => match args.parse::<usize>() {
+                Ok(line) => match shell.script {
+                    Some(ref script) => Breakpoint::Line(script.clone(), line),
+                    None => {
+                        let _ = writeln!(self.output, "not within a script");
+                        return;
+                    }
+                },
+                Err(_) => Breakpoint::Function(args.to_owned()),
+            },
+        };
+
+        let id = match self.breakpoints.iter().position(|b| b == &breakpoint) {
+            Some(id) => id,
+            None => {
+                self.breakpoints.push(breakpoint);
+                self.breakpoints.len() - 1
+            }
+        };
+        let _ = writeln!(self.output, "breakpoint {}: {}", id + 1, self.breakpoints[id]);
+    }
+
+    fn delete_breakpoint(&mut self, args: &str) {
+        if args.is_empty() {
+            self.breakpoints.clear();
+            return;
+        }
+        match args.parse::<usize>() {
+            Ok(id) if id != 0 && id <= self.breakpoints.len() => {
+                self.breakpoints.remove(id - 1);
+            }
+            _ => {
+                let _ = writeln!(self.output, "no such breakpoint: {}", args);
+            }
+        }
+    }
+
+    fn info(&mut self, shell: &Shell, name: &str) {
+        let variables = &shell.variables;
+        if let Some(value) = variables.get_var(name) {
+            let _ = writeln!(self.output, "{} = {}", name, value);
+        } else if let Some(array) = variables.get_array(name) {
+            let _ = writeln!(self.output, "{} = [{}]", name, array.join(" "));
+        } else if let Some(map) = variables.get_any_map(name) {
+            let _ = writeln!(self.output, "{} = {}", name, map);
+        } else if let Some(nested) = variables.get_nested(name) {
+            let _ = writeln!(self.output, "{} = {}", name, nested);
+        } else {
+            let _ = writeln!(self.output, "{} is not defined", name);
+        }
+    }
+
+    fn assign(&mut self, shell: &mut Shell, command: &str) {
+        for statement in StatementSplitter::new(command) {
+            match parse_and_validate(statement) {
+                Ok(Statement::Let(action)) => {
+                    shell.previous_status = shell.local(action);
+                }
+                Ok(Statement::Default) => (),
+                Ok(statement) => {
+                    let _ = writeln!(self.output, "not an assignment: {}", statement);
+                }
+                Err(why) => {
+                    let _ = writeln!(self.output, "syntax error: {}", why.message);
+                }
+            }
+        }
+    }
+
+    /// Prints the function which is being executed, followed by those which called it.
+    fn backtrace(&mut self, shell: &Shell) {
+        let mut current = location(&shell.script, shell.line);
+        for (id, frame) in self.frames.iter().rev().enumerate() {
+            let _ = writeln!(self.output, "#{} {} at {}", id, frame.function, current);
+            current = location(&frame.script, frame.line);
+        }
+        let _ = writeln!(self.output, "#{} main at {}", self.frames.len(), current);
+    }
+
+    fn list(&mut self, shell: &Shell) {
+        let source = match shell.script.as_ref().map(fs::read_to_string) {
+            Some(Ok(source)) => source,
+            Some(Err(why)) => {
+                let _ = writeln!(self.output, "unable to read the script: {}", why);
+                return;
+            }
+            None => {
+                let _ = writeln!(self.output, "not within a script");
+                return;
+            }
+        };
+
+        let first = shell.line.saturating_sub(CONTEXT).max(1);
+        for (number, line) in source.lines().enumerate().skip(first - 1).take(CONTEXT * 2 + 1) {
+            let marker = if number + 1 == shell.line { "->" } else { "  " };
+            let _ = writeln!(self.output, "{} {:4} {}", marker, number + 1, line);
+        }
+    }
+}
+
+fn location(script: &Option<String>, line: usize) -> String {
+    format!("{}:{}", script.as_ref().map_or("ion", String::as_str), line)
+}
+
+/// Gives the debugger of the shell, if it has one, the chance to pause before the statement is
+/// executed. Statements which only mark a line, or continue a block, are never paused at; nor
+/// are keywords which apply to another statement, as the debugger pauses at that statement.
+pub(crate) fn debug(shell: &mut Shell, statement: &Statement) {
+    let mut debugger = match shell.debugger.take() {
+        Some(debugger) => debugger,
+        None => return,
+    };
+    if debugger.pid != process::id() {
+        return;
+    }
+
+    match *statement {
+        Statement::Line(_) => debugger.new_line = true,
+        Statement::Error(_)
+        | Statement::Default
+        | Statement::End(_)
+        | Statement::Else
+        | Statement::ElseIf(_)
+        | Statement::Case(_)
+        | Statement::Time(_)
+        | Statement::And(_)
+        | Statement::Or(_)
+        | Statement::Not(_) => (),
+        _ => {
+            let new_line = debugger.new_line;
+            debugger.new_line = false;
+            let depth = debugger.frames.len();
+            let breakpoint = if new_line { debugger.breakpoint(shell) } else { None };
+            if let Some(id) = breakpoint {
+                let breakpoint = &debugger.breakpoints[id];
+                let _ = writeln!(debugger.output, "breakpoint {}, {}", id + 1, breakpoint);
+            }
+            let pause = breakpoint.is_some() || match debugger.mode {
+                Mode::Step => true,
+                Mode::Next(from) => depth <= from,
+                Mode::Finish(from) => depth < from,
+                Mode::Continue => false,
+            };
+            if pause {
+                debugger.pause(shell, statement);
+            }
+        }
+    }
+    shell.debugger = Some(debugger);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell::{Binary, ShellBuilder};
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+    use types::Array;
+
+    const SCRIPT: &str = "fn double n\n    let result = $((n * 2))\n    test $result -eq 2\nend\n\
+                          let x = 1\nlet list = [a b]\ndouble $x\nlet y = $x";
+
+    /// A writer whose contents remain available once the debugger is dropped.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> { Ok(()) }
+    }
+
+    /// Runs the script with the debugger, returning the shell and what the debugger wrote.
+    fn debug_script(commands: &str) -> (Shell, String) {
+        let output = Output::default();
+        let mut shell = ShellBuilder::new().as_library();
+        let input = Cursor::new(commands.as_bytes().to_vec());
+        shell.debugger = Some(Box::new(Debugger::new(Box::new(input), Box::new(output.clone()))));
+        shell.script = Some("/tmp/script.ion".into());
+        shell.terminate_script_quotes(SCRIPT.lines().map(String::from));
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        (shell, output.replace("(ion-debug) ", ""))
+    }
+
+    #[test]
+    fn stepping() {
+        let (_, output) = debug_script("s\n\nn\nn\nc\n");
+        let expected = "/tmp/script.ion:1: fn double n
+/tmp/script.ion:5: let x = 1
+/tmp/script.ion:6: let list = [a b]
+/tmp/script.ion:7: double $x
+/tmp/script.ion:8: let y = $x
+";
+        assert_eq!(expected, output);
+
+        let (_, output) = debug_script("b 7\nc\ns\ns\nbt\nfinish\n");
+        let expected = "/tmp/script.ion:1: fn double n
+breakpoint 1: /tmp/script.ion:7
+breakpoint 1, /tmp/script.ion:7
+/tmp/script.ion:7: double $x
+/tmp/script.ion:2: let result = $((n * 2))
+/tmp/script.ion:3: test $result -eq 2
+#0 double at /tmp/script.ion:3
+#1 main at /tmp/script.ion:7
+/tmp/script.ion:8: let y = $x
+
+";
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let (_, output) = debug_script("b double\nb script.ion:8\nb\nd 1\nb\nd 3\nc\nd\nc\n");
+        let expected = "/tmp/script.ion:1: fn double n
+breakpoint 1: function double
+breakpoint 2: script.ion:8
+1: function double
+2: script.ion:8
+1: script.ion:8
+no such breakpoint: 3
+breakpoint 1, script.ion:8
+/tmp/script.ion:8: let y = $x
+";
+        assert_eq!(expected, output);
+
+        let (_, output) = debug_script("break double\nc\nc\n");
+        let expected = "/tmp/script.ion:1: fn double n
+breakpoint 1: function double
+breakpoint 1, function double
+/tmp/script.ion:2: let result = $((n * 2))
+";
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn variables() {
+        let commands = "b 8\nc\np $x @list\ni x list undefined\nlet x = 5\nlet list = [@list \
+                        c]\nc\n";
+        let (shell, output) = debug_script(commands);
+        let expected = "/tmp/script.ion:1: fn double n
+breakpoint 1: /tmp/script.ion:8
+breakpoint 1, /tmp/script.ion:8
+/tmp/script.ion:8: let y = $x
+1 a b
+x = 1
+list = [a b]
+undefined is not defined
+";
+        assert_eq!(expected, output);
+        assert_eq!(Some("5".into()), shell.variables.get_var("y"));
+        assert_eq!(Some(&array!["a", "b", "c"]), shell.variables.get_array("list"));
+    }
+}
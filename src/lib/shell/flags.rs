@@ -2,3 +2,5 @@ pub const ERR_EXIT: u8 = 1;
 pub const PRINT_COMMS: u8 = 2;
 pub const NO_EXEC: u8 = 4;
 pub const HUPONEXIT: u8 = 8;
+pub const PIPE_FAIL: u8 = 16;
+pub const NO_UNSET: u8 = 32;
@@ -1,23 +1,95 @@
 use super::{
-    flags::*,
+    debugger::debug, flags::*,
     flow_control::{collect_cases, collect_if, collect_loops, Case, ElseIf, Function, Statement},
-    job_control::JobControl, status::*, Shell,
+    job_control::JobControl,
+    pipe_exec::{
+        coprocess, prepare_child,
+        streams::{duplicate_streams, redir, redirect_shell, redirect_streams},
+    },
+    profiler::profile, signals::Trap, status::*, Shell,
 };
 use parser::{
-    assignments::{is_array, ReturnValue}, expand_string, parse_and_validate, pipelines::Pipeline,
-    ForExpression, StatementSplitter,
+    assignments::{is_array, ReturnValue}, expand_string, parse_and_validate,
+    pipelines::{Pipeline, RedirectFrom}, ForExpression, StatementSplitter,
 };
-use shell::assignments::VariableStore;
+use shell::{assignments::VariableStore, JobKind};
 use std::{
-    io::{stdout, Write}, iter, mem,
+    io::{stderr, stdout, Write}, iter::{self, Peekable}, mem,
 };
-use types::Array;
+use sys;
+use types::{Array, Identifier, Value};
+
+/// Executes a pipeline as a statement. If the pipeline fails outside of a condition, the `ERR`
+/// trap is executed, and the shell exits when `set -e` is in effect.
+fn execute_pipeline_statement(shell: &mut Shell, mut pipeline: Pipeline) {
+    let source = if shell.flags & ERR_EXIT != 0 { pipeline.to_string() } else { String::new() };
+    shell.run_pipeline(&mut pipeline);
+    check_status(shell, &source);
+}
+
+/// Executes a `let` or `export` statement, which is subject to `set -e` like a pipeline.
+fn execute_assignment(shell: &mut Shell, statement: Statement) {
+    let source = if shell.flags & ERR_EXIT != 0 { statement.to_string() } else { String::new() };
+    shell.previous_status = match statement {
+        Statement::Let(action) => shell.local(action),
+        Statement::Export(action) => shell.export(action),
+        _ => unreachable!(),
+    };
+    check_status(shell, &source);
+}
+
+/// Executes the `ERR` trap, and exits the shell when `set -e` is in effect, if the statement
+/// that was just executed failed outside of a condition.
+fn check_status(shell: &mut Shell, source: &str) {
+    let status = shell.previous_status;
+    if status == SUCCESS || shell.condition_depth != 0 {
+        return;
+    }
+    // Like other shells, the ERR trap is not inherited by function bodies; a failed function
+    // call will trigger it from the caller instead.
+    if shell.variables.scope_depth() == 0 {
+        shell.run_trap(Trap::Err);
+    }
+    if shell.flags & ERR_EXIT != 0 {
+        eprintln!("ion: exiting because `{}` failed with status {}", source.trim(), status);
+        shell.exit(status);
+    }
+}
+
+/// Traces an iteration of a for loop, if `set -x` is in effect. Each iteration begins on the
+/// line of the loop.
+fn trace_iteration(shell: &mut Shell, line: usize, variables: &[Identifier], values: &[Value]) {
+    shell.line = line;
+    if shell.flags & PRINT_COMMS != 0 {
+        let variables = variables.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+        shell.trace(&["for ", &variables.join(" "), " in ", &values.join(" ")].concat());
+    }
+}
+
+/// Evaluates a condition, within which failures neither trigger the `ERR` trap nor cause
+/// `set -e` to exit the shell. This includes any functions which the condition calls.
+fn as_condition<T, F: FnOnce(&mut Shell) -> T>(shell: &mut Shell, condition: F) -> T {
+    shell.condition_depth += 1;
+    let result = condition(shell);
+    shell.condition_depth -= 1;
+    result
+}
+
+/// Determines whether the next statement continues an `&&` or `||` chain, in which case the
+/// status of the current statement is examined by the chain.
+fn is_chained<I: Iterator<Item = Statement>>(iterator: &mut Peekable<I>) -> bool {
+    match iterator.peek() {
+        Some(&Statement::And(_)) | Some(&Statement::Or(_)) => true,
+        _ => false,
+    }
+}
 
 pub(crate) enum Condition {
     Continue,
     Break,
     NoOp,
     SigInt,
+    Return,
 }
 
 pub(crate) trait FlowLogic {
@@ -42,7 +114,7 @@ pub(crate) trait FlowLogic {
     /// specified in the range.
     fn execute_for(
         &mut self,
-        variable: &str,
+        variables: &[Identifier],
         values: &[String],
         statements: Vec<Statement>,
     ) -> Condition;
@@ -68,6 +140,13 @@ pub(crate) trait FlowLogic {
     /// Expand an expression and run a branch based on the value of the
     /// expanded expression
     fn execute_match(&mut self, expression: String, cases: Vec<Case>) -> Condition;
+
+    /// Executes a block with the redirections and pipes that followed its `end`, if any.
+    /// Redirections are applied to the shell for the duration of the block, whereas a
+    /// piped block is executed within a forked child of the shell.
+    fn execute_redirected<F>(&mut self, redirection: Option<Pipeline>, block: F) -> Condition
+    where
+        F: FnOnce(&mut Self) -> Condition;
 }
 
 impl FlowLogic for Shell {
@@ -79,14 +158,13 @@ impl FlowLogic for Shell {
     where
         I: Iterator<Item = Statement>,
     {
+        debug(self, &statement);
         match statement {
             Statement::Error(number) => self.previous_status = number,
+            Statement::Line(line) => self.line = line,
             // Execute a Let Statement
-            Statement::Let(action) => {
-                self.previous_status = self.local(action);
-            }
-            Statement::Export(action) => {
-                self.previous_status = self.export(action);
+            statement @ Statement::Let(_) | statement @ Statement::Export(_) => {
+                execute_assignment(self, statement)
             }
             // Collect the statements for the while loop, and if the loop is complete,
             // execute the while loop with the provided expression.
@@ -97,11 +175,14 @@ impl FlowLogic for Shell {
                 self.flow_control.level += 1;
 
                 // Collect all of the statements contained within the while block.
-                collect_loops(iterator, &mut statements, &mut self.flow_control.level);
+                let redirection =
+                    collect_loops(iterator, &mut statements, &mut self.flow_control.level);
 
                 if self.flow_control.level == 0 {
                     // All blocks were read, thus we can immediately execute now
-                    self.execute_while(expression, statements);
+                    self.execute_redirected(redirection, |shell| {
+                        shell.execute_while(expression, statements)
+                    });
                 } else {
                     // Store the partial `Statement::While` to memory
                     self.flow_control.current_statement = Statement::While {
@@ -113,22 +194,25 @@ impl FlowLogic for Shell {
             // Collect the statements for the for loop, and if the loop is complete,
             // execute the for loop with the provided expression.
             Statement::For {
-                variable,
+                variables,
                 values,
                 mut statements,
             } => {
                 self.flow_control.level += 1;
 
                 // Collect all of the statements contained within the for block.
-                collect_loops(iterator, &mut statements, &mut self.flow_control.level);
+                let redirection =
+                    collect_loops(iterator, &mut statements, &mut self.flow_control.level);
 
                 if self.flow_control.level == 0 {
                     // All blocks were read, thus we can immediately execute now
-                    self.execute_for(&variable, &values, statements);
+                    self.execute_redirected(redirection, |shell| {
+                        shell.execute_for(&variables, &values, statements)
+                    });
                 } else {
                     // Store the partial `Statement::For` to memory
                     self.flow_control.current_statement = Statement::For {
-                        variable,
+                        variables,
                         values,
                         statements,
                     }
@@ -147,7 +231,7 @@ impl FlowLogic for Shell {
                 // Collect all of the success and failure statements within the if condition.
                 // The `mode` value will let us know whether the collector ended while
                 // collecting the success block or the failure block.
-                let mode = collect_if(
+                let (mode, redirection) = collect_if(
                     iterator,
                     &mut success,
                     &mut else_if,
@@ -158,7 +242,9 @@ impl FlowLogic for Shell {
 
                 if self.flow_control.level == 0 {
                     // All blocks were read, thus we can immediately execute now
-                    self.execute_if(expression, success, else_if, failure);
+                    self.execute_redirected(redirection, |shell| {
+                        shell.execute_if(expression, success, else_if, failure)
+                    });
                 } else {
                     // Set the mode and partial if statement in memory.
                     self.flow_control.current_if_mode = mode;
@@ -181,13 +267,16 @@ impl FlowLogic for Shell {
                 self.flow_control.level += 1;
 
                 // The same logic that applies to loops, also applies here.
-                collect_loops(iterator, &mut statements, &mut self.flow_control.level);
+                if collect_loops(iterator, &mut statements, &mut self.flow_control.level).is_some()
+                {
+                    eprintln!("ion: redirections are not supported on function definitions");
+                }
 
                 if self.flow_control.level == 0 {
                     // All blocks were read, thus we can add it to the list
                     self.functions.insert(
                         name.clone(),
-                        Function::new(description, name, args, statements),
+                        Function::new(description, name, args, statements, self.script.clone()),
                     );
                 } else {
                     // Store the partial function declaration in memory.
@@ -200,12 +289,14 @@ impl FlowLogic for Shell {
                 }
             }
             // Simply executes a provided pipeline, immediately.
-            Statement::Pipeline(mut pipeline) => {
-                self.run_pipeline(&mut pipeline);
-                if self.flags & ERR_EXIT != 0 && self.previous_status != SUCCESS {
-                    let status = self.previous_status;
-                    self.exit(status);
-                }
+            Statement::Pipeline(pipeline) => execute_pipeline_statement(self, pipeline),
+            Statement::Coproc { name, pipeline } => {
+                self.previous_status = match pipeline {
+                    Some(pipeline) => coprocess::spawn(self, &name, pipeline),
+                    None => coprocess::close_input(self, &name),
+                };
+                let status = self.previous_status.to_string();
+                self.set_var("?", &status);
             }
             Statement::Time(box_statement) => {
                 let time = ::std::time::Instant::now();
@@ -282,7 +373,10 @@ impl FlowLogic for Shell {
             }
             Statement::Not(box_statement) => {
                 if self.flow_control.level == 0 {
-                    if let Err(why) = self.execute_toplevel(iterator, *box_statement) {
+                    let result = as_condition(self, |shell| {
+                        shell.execute_toplevel(iterator, *box_statement)
+                    });
+                    if let Err(why) = result {
                         eprintln!("{}", why);
                         self.flow_control.level = 0;
                         self.flow_control.current_if_mode = 0;
@@ -303,11 +397,21 @@ impl FlowLogic for Shell {
             }
             // At this level, else and else if keywords are forbidden.
             Statement::ElseIf { .. } | Statement::Else => {
-                eprintln!("ion: syntax error: not an if statement");
+                let script = self.script.as_ref().map_or("ion", String::as_str);
+                eprintln!("ion: {}:{}: syntax error: not an if statement", script, self.line);
+                self.syntax_errors.set(self.syntax_errors.get() + 1);
             }
             // Likewise to else and else if, the end keyword does nothing here.
-            Statement::End => {
-                eprintln!("ion: syntax error: no block to end");
+            Statement::End(_) => {
+                let script = self.script.as_ref().map_or("ion", String::as_str);
+                eprintln!("ion: {}:{}: syntax error: no block to end", script, self.line);
+                self.syntax_errors.set(self.syntax_errors.get() + 1);
+            }
+            // Functions are never executed at this level.
+            Statement::Return(_) => {
+                eprintln!("ion: return: not within a function");
+                self.previous_status = FAILURE;
+                self.set_var("?", &FAILURE.to_string());
             }
             // Collect all cases that are being used by a match construct
             Statement::Match {
@@ -315,13 +419,19 @@ impl FlowLogic for Shell {
                 mut cases,
             } => {
                 self.flow_control.level += 1;
-                if let Err(why) = collect_cases(iterator, &mut cases, &mut self.flow_control.level)
-                {
-                    eprintln!("{}", why);
-                }
+                let redirection =
+                    match collect_cases(iterator, &mut cases, &mut self.flow_control.level) {
+                        Ok(redirection) => redirection,
+                        Err(why) => {
+                            eprintln!("{}", why);
+                            None
+                        }
+                    };
                 if self.flow_control.level == 0 {
                     // If all blocks were read we execute the statement
-                    self.execute_match(expression, cases);
+                    self.execute_redirected(redirection, |shell| {
+                        shell.execute_match(expression, cases)
+                    });
                 } else {
                     // Store the partial function declaration in memory.
                     self.flow_control.current_statement = Statement::Match { expression, cases };
@@ -344,78 +454,89 @@ impl FlowLogic for Shell {
             .into_iter()
             .map(|cond| (cond.expression, cond.success));
 
-        for (mut condition, statements) in first_condition.chain(else_conditions) {
-            if self.run_pipeline(&mut condition) == Some(SUCCESS) {
+        for (branch, (mut condition, statements)) in
+            first_condition.chain(else_conditions).enumerate()
+        {
+            if as_condition(self, |shell| shell.run_pipeline(&mut condition)) == Some(SUCCESS) {
+                if self.flags & PRINT_COMMS != 0 {
+                    let keyword = if branch == 0 { "if " } else { "else if " };
+                    self.trace(&[keyword, &condition.to_string(), ": taken"].concat());
+                }
                 return self.execute_statements(statements);
+            } else if self.break_flow {
+                return Condition::SigInt;
             }
         }
 
+        if self.flags & PRINT_COMMS != 0 && !failure.is_empty() {
+            self.trace("else: taken");
+        }
         self.execute_statements(failure)
     }
 
     fn execute_for(
         &mut self,
-        variable: &str,
+        variables: &[Identifier],
         values: &[String],
         statements: Vec<Statement>,
     ) -> Condition {
-        let ignore_variable = variable == "_";
-        match ForExpression::new(values, self) {
-            ForExpression::Multiple(ref values) if ignore_variable => for _ in values.iter() {
-                match self.execute_statements(statements.clone()) {
-                    Condition::Break => break,
-                    Condition::SigInt => return Condition::SigInt,
-                    _ => (),
-                }
-            },
-            ForExpression::Multiple(values) => for value in values.iter() {
-                self.set_var(variable, &value);
-                match self.execute_statements(statements.clone()) {
-                    Condition::Break => break,
-                    Condition::SigInt => return Condition::SigInt,
-                    _ => (),
-                }
-            },
-            ForExpression::Normal(ref values) if ignore_variable => for _ in values.lines() {
-                match self.execute_statements(statements.clone()) {
-                    Condition::Break => break,
-                    Condition::SigInt => return Condition::SigInt,
-                    _ => (),
-                }
-            },
-            ForExpression::Normal(values) => for value in values.lines() {
-                self.set_var(variable, &value);
-                match self.execute_statements(statements.clone()) {
-                    Condition::Break => break,
-                    Condition::SigInt => return Condition::SigInt,
-                    _ => (),
-                }
-            },
-            ForExpression::Range(start, end) if ignore_variable => for _ in start..end {
-                match self.execute_statements(statements.clone()) {
-                    Condition::Break => break,
-                    Condition::SigInt => return Condition::SigInt,
-                    _ => (),
-                }
-            },
-            ForExpression::Range(start, end) => for value in (start..end).map(|x| x.to_string()) {
-                self.set_var(variable, &value);
-                match self.execute_statements(statements.clone()) {
-                    Condition::Break => break,
-                    Condition::SigInt => return Condition::SigInt,
-                    _ => (),
+        let line = self.line;
+        let expression = ForExpression::new(values, self);
+        if self.flags & NO_UNSET != 0 {
+            let variables = variables.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+            let source = ["for ", &variables.join(" "), " in ", &values.join(" ")].concat();
+            if self.check_unbound(&source) {
+                return Condition::SigInt;
+            }
+        }
+
+        let mut values: Box<Iterator<Item = Value>> = match expression {
+            ForExpression::Multiple(values) => Box::new(values.into_iter()),
+            ForExpression::Normal(values) => {
+                Box::new(values.lines().map(Into::into).collect::<Vec<Value>>().into_iter())
+            }
+            ForExpression::Range(start, end) => Box::new((start..end).map(|x| x.to_string())),
+        };
+
+        // Each iteration assigns the next value to each of the variables in turn, where the
+        // variables of the final iteration which lack a value are assigned an empty string.
+        loop {
+            let iteration = values.by_ref().take(variables.len()).collect::<Vec<Value>>();
+            if iteration.is_empty() {
+                break;
+            }
+
+            trace_iteration(self, line, variables, &iteration);
+            let empty = Value::new();
+            let assigned = iteration.iter().chain(iter::repeat(&empty));
+            for (variable, value) in variables.iter().zip(assigned) {
+                if &**variable != "_" {
+                    self.set_var(variable, value);
                 }
-            },
+            }
+
+            match self.execute_statements(statements.clone()) {
+                Condition::Break => break,
+                Condition::SigInt => return Condition::SigInt,
+                Condition::Return => return Condition::Return,
+                _ => (),
+            }
         }
         Condition::NoOp
     }
 
     fn execute_while(&mut self, expression: Pipeline, statements: Vec<Statement>) -> Condition {
-        while self.run_pipeline(&mut expression.clone()) == Some(SUCCESS) {
+        let line = self.line;
+        while as_condition(self, |shell| {
+            shell.line = line;
+            shell.run_pipeline(&mut expression.clone())
+        }) == Some(SUCCESS)
+        {
             // Cloning is needed so the statement can be re-iterated again if needed.
             match self.execute_statements(statements.clone()) {
                 Condition::Break => break,
                 Condition::SigInt => return Condition::SigInt,
+                Condition::Return => return Condition::Return,
                 _ => (),
             }
         }
@@ -426,33 +547,42 @@ impl FlowLogic for Shell {
     where
         I: Iterator<Item = Statement>,
     {
+        debug(self, &statement);
         match statement {
             Statement::Error(number) => self.previous_status = number,
-            Statement::Let(action) => {
-                self.previous_status = self.local(action);
-            }
-            Statement::Export(action) => {
-                self.previous_status = self.export(action);
+            Statement::Line(line) => self.line = line,
+            statement @ Statement::Let(_) | statement @ Statement::Export(_) => {
+                execute_assignment(self, statement)
             }
             Statement::While {
                 expression,
                 mut statements,
             } => {
                 self.flow_control.level += 1;
-                collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level);
-                if let Condition::SigInt = self.execute_while(expression, statements) {
-                    return Condition::SigInt;
+                let redirection =
+                    collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level);
+                match self.execute_redirected(redirection, |shell| {
+                    shell.execute_while(expression, statements)
+                }) {
+                    Condition::SigInt => return Condition::SigInt,
+                    Condition::Return => return Condition::Return,
+                    _ => (),
                 }
             }
             Statement::For {
-                variable,
+                variables,
                 values,
                 mut statements,
             } => {
                 self.flow_control.level += 1;
-                collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level);
-                if let Condition::SigInt = self.execute_for(&variable, &values, statements) {
-                    return Condition::SigInt;
+                let redirection =
+                    collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level);
+                match self.execute_redirected(redirection, |shell| {
+                    shell.execute_for(&variables, &values, statements)
+                }) {
+                    Condition::SigInt => return Condition::SigInt,
+                    Condition::Return => return Condition::Return,
+                    _ => (),
                 }
             }
             Statement::If {
@@ -462,7 +592,7 @@ impl FlowLogic for Shell {
                 mut failure,
             } => {
                 self.flow_control.level += 1;
-                if let Err(why) = collect_if(
+                let redirection = match collect_if(
                     &mut iterator,
                     &mut success,
                     &mut else_if,
@@ -470,17 +600,23 @@ impl FlowLogic for Shell {
                     &mut self.flow_control.level,
                     0,
                 ) {
-                    eprintln!("{}", why);
-                    self.flow_control.level = 0;
-                    self.flow_control.current_if_mode = 0;
-                    return Condition::Break;
-                }
+                    Ok((_, redirection)) => redirection,
+                    Err(why) => {
+                        eprintln!("{}", why);
+                        self.flow_control.level = 0;
+                        self.flow_control.current_if_mode = 0;
+                        return Condition::Break;
+                    }
+                };
 
-                match self.execute_if(expression, success, else_if, failure) {
+                match self.execute_redirected(redirection, |shell| {
+                    shell.execute_if(expression, success, else_if, failure)
+                }) {
                     Condition::Break => return Condition::Break,
                     Condition::Continue => return Condition::Continue,
                     Condition::NoOp => (),
                     Condition::SigInt => return Condition::SigInt,
+                    Condition::Return => return Condition::Return,
                 }
             }
             Statement::Function {
@@ -490,18 +626,24 @@ impl FlowLogic for Shell {
                 description,
             } => {
                 self.flow_control.level += 1;
-                collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level);
+                if collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level)
+                    .is_some()
+                {
+                    eprintln!("ion: redirections are not supported on function definitions");
+                }
                 self.functions.insert(
                     name.clone(),
-                    Function::new(description, name, args, statements),
+                    Function::new(description, name, args, statements, self.script.clone()),
                 );
             }
-            Statement::Pipeline(mut pipeline) => {
-                self.run_pipeline(&mut pipeline);
-                if self.flags & ERR_EXIT != 0 && self.previous_status != SUCCESS {
-                    let status = self.previous_status;
-                    self.exit(status);
-                }
+            Statement::Pipeline(pipeline) => execute_pipeline_statement(self, pipeline),
+            Statement::Coproc { name, pipeline } => {
+                self.previous_status = match pipeline {
+                    Some(pipeline) => coprocess::spawn(self, &name, pipeline),
+                    None => coprocess::close_input(self, &name),
+                };
+                let status = self.previous_status.to_string();
+                self.set_var("?", &status);
             }
             Statement::Time(box_statement) => {
                 let time = ::std::time::Instant::now();
@@ -530,6 +672,7 @@ impl FlowLogic for Shell {
                     Condition::Continue => return Condition::Continue,
                     Condition::NoOp => (),
                     Condition::SigInt => return Condition::SigInt,
+                    Condition::Return => return Condition::Return,
                 }
             }
             Statement::And(box_statement) => {
@@ -543,6 +686,7 @@ impl FlowLogic for Shell {
                     Condition::Continue => return Condition::Continue,
                     Condition::NoOp => (),
                     Condition::SigInt => return Condition::SigInt,
+                    Condition::Return => return Condition::Return,
                 }
             }
             Statement::Or(box_statement) => {
@@ -556,11 +700,13 @@ impl FlowLogic for Shell {
                     Condition::Continue => return Condition::Continue,
                     Condition::NoOp => (),
                     Condition::SigInt => return Condition::SigInt,
+                    Condition::Return => return Condition::Return,
                 }
             }
             Statement::Not(box_statement) => {
                 // NOTE: Should the condition be used?
-                let _condition = self.execute_statement(iterator, *box_statement);
+                let _condition =
+                    as_condition(self, |shell| shell.execute_statement(iterator, *box_statement));
                 match self.previous_status {
                     FAILURE => self.previous_status = SUCCESS,
                     SUCCESS => self.previous_status = FAILURE,
@@ -571,24 +717,56 @@ impl FlowLogic for Shell {
             }
             Statement::Break => return Condition::Break,
             Statement::Continue => return Condition::Continue,
+            Statement::Return(expression) => if self.variables.scope_depth() == 0 {
+                eprintln!("ion: return: not within a function");
+                self.previous_status = FAILURE;
+                self.set_var("?", &FAILURE.to_string());
+            } else {
+                if let Some(expression) = expression {
+                    let status = expand_string(&expression, self, false).join(" ");
+                    if self.flags & NO_UNSET != 0
+                        && self.check_unbound(&["return ", &expression].concat())
+                    {
+                        return Condition::SigInt;
+                    }
+                    self.previous_status = match status.parse::<i32>() {
+                        Ok(status) => status,
+                        Err(_) => {
+                            eprintln!("ion: return: numeric argument required: '{}'", status);
+                            FAILURE
+                        }
+                    };
+                }
+                let status = self.previous_status.to_string();
+                if self.flags & PRINT_COMMS != 0 {
+                    self.trace(&["return ", &status].concat());
+                }
+                self.set_var("?", &status);
+                return Condition::Return;
+            },
             Statement::Match {
                 expression,
                 mut cases,
             } => {
                 self.flow_control.level += 1;
-                if let Err(why) =
-                    collect_cases(&mut iterator, &mut cases, &mut self.flow_control.level)
-                {
-                    eprintln!("{}", why);
-                    self.flow_control.level = 0;
-                    self.flow_control.current_if_mode = 0;
-                    return Condition::Break;
-                }
-                match self.execute_match(expression, cases) {
+                let redirection =
+                    match collect_cases(&mut iterator, &mut cases, &mut self.flow_control.level) {
+                        Ok(redirection) => redirection,
+                        Err(why) => {
+                            eprintln!("{}", why);
+                            self.flow_control.level = 0;
+                            self.flow_control.current_if_mode = 0;
+                            return Condition::Break;
+                        }
+                    };
+                match self.execute_redirected(redirection, |shell| {
+                    shell.execute_match(expression, cases)
+                }) {
                     Condition::Break => return Condition::Break,
                     Condition::Continue => return Condition::Continue,
                     Condition::NoOp => (),
                     Condition::SigInt => return Condition::SigInt,
+                    Condition::Return => return Condition::Return,
                 }
             }
             _ => {}
@@ -607,9 +785,22 @@ impl FlowLogic for Shell {
     }
 
     fn execute_statements(&mut self, mut statements: Vec<Statement>) -> Condition {
-        let mut iterator = statements.drain(..);
+        let mut iterator = statements.drain(..).peekable();
         while let Some(statement) = iterator.next() {
-            match self.execute_statement(&mut iterator, statement) {
+            // Process substitutions within the words of a statement are held open until it has
+            // completed, including those within the values of a loop.
+            let condition = profile(self, statement, |shell, statement| {
+                shell.finishing_substitutions(|shell| {
+                    if is_chained(&mut iterator) {
+                        as_condition(shell, |shell| {
+                            shell.execute_statement(&mut iterator, statement)
+                        })
+                    } else {
+                        shell.execute_statement(&mut iterator, statement)
+                    }
+                })
+            });
+            match condition {
                 Condition::NoOp => {}
                 cond => return cond,
             }
@@ -636,6 +827,9 @@ impl FlowLogic for Shell {
 
         let is_array = is_array(&expression);
         let value = expand_string(&expression, self, false);
+        if self.flags & NO_UNSET != 0 && self.check_unbound(&["match ", &expression].concat()) {
+            return Condition::SigInt;
+        }
         let mut condition = Condition::NoOp;
         for case in cases {
             // let pattern_is_array = is_array(&value);
@@ -663,6 +857,9 @@ impl FlowLogic for Shell {
                         }
                     }
 
+                    if self.flags & PRINT_COMMS != 0 {
+                        self.trace(&["match ", &value.join(" "), ": case _"].concat());
+                    }
                     condition = self.execute_statements(case.statements);
 
                     if let Some(ref bind) = case.binding {
@@ -672,6 +869,7 @@ impl FlowLogic for Shell {
                                 ReturnValue::Vector(values) => {
                                     self.variables.set_array(bind, values)
                                 }
+                                ReturnValue::Map(_) | ReturnValue::Nested(_) => unreachable!(),
                             }
                         }
                     }
@@ -700,6 +898,10 @@ impl FlowLogic for Shell {
                         }
                     }
 
+                    if self.flags & PRINT_COMMS != 0 {
+                        let case = ["match ", &value.join(" "), ": case ", &v.join(" ")].concat();
+                        self.trace(&case);
+                    }
                     condition = self.execute_statements(case.statements);
 
                     if let Some(ref bind) = case.binding {
@@ -709,6 +911,7 @@ impl FlowLogic for Shell {
                                 ReturnValue::Vector(values) => {
                                     self.variables.set_array(bind, values)
                                 }
+                                ReturnValue::Map(_) | ReturnValue::Nested(_) => unreachable!(),
                             }
                         }
                     }
@@ -723,8 +926,21 @@ impl FlowLogic for Shell {
 
     fn on_command(&mut self, command_string: &str) {
         self.break_flow = false;
-        let mut iterator =
-            StatementSplitter::new(command_string).map(parse_and_validate);
+        self.unbound_variable.borrow_mut().take();
+        // Syntax errors are reported as statements are parsed, along with where they occurred.
+        let script = self.script.clone();
+        let line = self.line;
+        let syntax_errors = self.syntax_errors.clone();
+        let statements = StatementSplitter::new(command_string).map(|statement| {
+            parse_and_validate(statement).unwrap_or_else(|error| {
+                let script = script.as_ref().map_or("ion", String::as_str);
+                eprintln!("ion: {}", error.diagnostic(command_string, script, line));
+                syntax_errors.set(syntax_errors.get() + 1);
+                Statement::Error(-1)
+            })
+        });
+        // Statements are preceded by their line, which is retained by the blocks they belong to.
+        let mut iterator = iter::once(Statement::Line(self.line)).chain(statements).peekable();
 
         // If the value is set to `0`, this means that we don't need to append to an
         // existing partial statement block in memory, but can read and execute
@@ -734,12 +950,36 @@ impl FlowLogic for Shell {
                 // Executes all statements that it can, and stores the last remaining partial
                 // statement in memory if needed. We can tell if there is a partial statement
                 // later if the value of `level` is not set to `0`.
-                if let Err(why) = self.execute_toplevel(&mut iterator, statement) {
+                self.flow_control.line = self.line;
+                let result = profile(self, statement, |shell, statement| {
+                    shell.finishing_substitutions(|shell| {
+                        if is_chained(&mut iterator) {
+                            as_condition(shell, |shell| {
+                                shell.execute_toplevel(&mut iterator, statement)
+                            })
+                        } else {
+                            shell.execute_toplevel(&mut iterator, statement)
+                        }
+                    })
+                });
+                if let Err(why) = result {
                     eprintln!("{}", why);
                     self.flow_control.level = 0;
                     self.flow_control.current_if_mode = 0;
                     return;
                 }
+
+                // Give any traps a chance to handle signals received by top-level statements.
+                if let Some(signal) = self.next_signal() {
+                    if self.handle_signal(signal) {
+                        self.exit(get_signal_code(signal));
+                    }
+                }
+
+                // An aborted statement also aborts the statements which follow it on this line.
+                if self.break_flow {
+                    return;
+                }
             }
         } else {
             fn append_new_commands<I: Iterator<Item = Statement>>(
@@ -747,6 +987,7 @@ impl FlowLogic for Shell {
                 current_statement: &mut Statement,
                 level: &mut usize,
                 current_if_mode: &mut u8,
+                redirection: &mut Option<Pipeline>,
             ) {
                 match current_statement {
                     &mut Statement::While {
@@ -754,11 +995,17 @@ impl FlowLogic for Shell {
                     }
                     | &mut Statement::For {
                         ref mut statements, ..
+                    } => {
+                        *redirection = collect_loops(&mut iterator, statements, level);
                     }
-                    | &mut Statement::Function {
+                    &mut Statement::Function {
                         ref mut statements, ..
                     } => {
-                        collect_loops(&mut iterator, statements, level);
+                        if collect_loops(&mut iterator, statements, level).is_some() {
+                            eprintln!(
+                                "ion: redirections are not supported on function definitions"
+                            );
+                        }
                     }
                     &mut Statement::If {
                         ref mut success,
@@ -774,7 +1021,10 @@ impl FlowLogic for Shell {
                             level,
                             *current_if_mode,
                         ) {
-                            Ok(mode) => mode,
+                            Ok((mode, block_redirection)) => {
+                                *redirection = block_redirection;
+                                mode
+                            }
                             Err(why) => {
                                 eprintln!("{}", why);
                                 4
@@ -782,21 +1032,46 @@ impl FlowLogic for Shell {
                         };
                     }
                     &mut Statement::Match { ref mut cases, .. } => {
-                        if let Err(why) = collect_cases(&mut iterator, cases, level) {
-                            eprintln!("{}", why);
+                        match collect_cases(&mut iterator, cases, level) {
+                            Ok(block_redirection) => *redirection = block_redirection,
+                            Err(why) => eprintln!("{}", why),
                         }
                     }
                     &mut Statement::Time(ref mut box_stmt) => {
-                        append_new_commands(iterator, box_stmt.as_mut(), level, current_if_mode);
+                        append_new_commands(
+                            iterator,
+                            box_stmt.as_mut(),
+                            level,
+                            current_if_mode,
+                            redirection,
+                        );
                     }
                     &mut Statement::And(ref mut box_stmt) => {
-                        append_new_commands(iterator, box_stmt.as_mut(), level, current_if_mode);
+                        append_new_commands(
+                            iterator,
+                            box_stmt.as_mut(),
+                            level,
+                            current_if_mode,
+                            redirection,
+                        );
                     }
                     &mut Statement::Or(ref mut box_stmt) => {
-                        append_new_commands(iterator, box_stmt.as_mut(), level, current_if_mode);
+                        append_new_commands(
+                            iterator,
+                            box_stmt.as_mut(),
+                            level,
+                            current_if_mode,
+                            redirection,
+                        );
                     }
                     &mut Statement::Not(ref mut box_stmt) => {
-                        append_new_commands(iterator, box_stmt.as_mut(), level, current_if_mode);
+                        append_new_commands(
+                            iterator,
+                            box_stmt.as_mut(),
+                            level,
+                            current_if_mode,
+                            redirection,
+                        );
                     }
                     _ => (),
                 }
@@ -807,6 +1082,7 @@ impl FlowLogic for Shell {
                 &mut self.flow_control.current_statement,
                 &mut self.flow_control.level,
                 &mut self.flow_control.current_if_mode,
+                &mut self.flow_control.redirection,
             );
 
             // If this is true, an error occurred during the if statement
@@ -814,6 +1090,7 @@ impl FlowLogic for Shell {
                 self.flow_control.level = 0;
                 self.flow_control.current_if_mode = 0;
                 self.flow_control.current_statement = Statement::Default;
+                self.flow_control.redirection = None;
                 return;
             }
 
@@ -828,11 +1105,8 @@ impl FlowLogic for Shell {
                 fn execute_final(shell: &mut Shell, statement: Statement) -> Condition {
                     match statement {
                         Statement::Error(number) => shell.previous_status = number,
-                        Statement::Let(action) => {
-                            shell.previous_status = shell.local(action);
-                        }
-                        Statement::Export(action) => {
-                            shell.previous_status = shell.export(action);
+                        statement @ Statement::Let(_) | statement @ Statement::Export(_) => {
+                            execute_assignment(shell, statement)
                         }
                         Statement::While {
                             expression,
@@ -843,12 +1117,12 @@ impl FlowLogic for Shell {
                             }
                         }
                         Statement::For {
-                            variable,
+                            variables,
                             values,
                             statements,
                         } => {
                             if let Condition::SigInt =
-                                shell.execute_for(&variable, &values, statements)
+                                shell.execute_for(&variables, &values, statements)
                             {
                                 return Condition::SigInt;
                             }
@@ -859,9 +1133,10 @@ impl FlowLogic for Shell {
                             statements,
                             description,
                         } => {
+                            let script = shell.script.clone();
                             shell.functions.insert(
                                 name.clone(),
-                                Function::new(description, name, args, statements),
+                                Function::new(description, name, args, statements, script),
                             );
                         }
                         Statement::If {
@@ -927,13 +1202,33 @@ impl FlowLogic for Shell {
                     Condition::NoOp
                 }
 
-                if let Condition::SigInt = execute_final(self, replacement) {
+                let redirection = self.flow_control.redirection.take();
+                self.line = self.flow_control.line;
+                let condition = profile(self, replacement, |shell, statement| {
+                    shell.finishing_substitutions(|shell| {
+                        shell.execute_redirected(redirection, |shell| {
+                            execute_final(shell, statement)
+                        })
+                    })
+                });
+                if let Condition::SigInt = condition {
                     return;
                 }
 
                 // Capture any leftover statements.
                 while let Some(statement) = iterator.next() {
-                    if let Err(why) = self.execute_toplevel(&mut iterator, statement) {
+                    let result = profile(self, statement, |shell, statement| {
+                        shell.finishing_substitutions(|shell| {
+                            if is_chained(&mut iterator) {
+                                as_condition(shell, |shell| {
+                                    shell.execute_toplevel(&mut iterator, statement)
+                                })
+                            } else {
+                                shell.execute_toplevel(&mut iterator, statement)
+                            }
+                        })
+                    });
+                    if let Err(why) = result {
                         eprintln!("{}", why);
                         self.flow_control.level = 0;
                         self.flow_control.current_if_mode = 0;
@@ -943,4 +1238,175 @@ impl FlowLogic for Shell {
             }
         }
     }
+
+    fn execute_redirected<F>(&mut self, redirection: Option<Pipeline>, block: F) -> Condition
+    where
+        F: FnOnce(&mut Self) -> Condition,
+    {
+        fn failed(shell: &mut Shell) -> Condition {
+            shell.previous_status = FAILURE;
+            shell.set_var("?", &FAILURE.to_string());
+            Condition::NoOp
+        }
+
+        let mut pipeline = match redirection {
+            Some(pipeline) => pipeline,
+            None => return block(self),
+        };
+
+        // The first item of the pipeline represents the block itself.
+        let mut item = pipeline.items.remove(0);
+        item.expand(self);
+
+        let from = match item.job.kind {
+            JobKind::Pipe(from) => from,
+            _ => {
+                return match redirect_shell(&item) {
+                    Some(backup) => {
+                        let condition = block(self);
+                        backup.restore();
+                        condition
+                    }
+                    None => failed(self),
+                };
+            }
+        };
+
+        let (reader, writer) = match sys::pipe2(sys::O_CLOEXEC) {
+            Ok(pipe) => pipe,
+            Err(why) => {
+                eprintln!("ion: failed to create pipe: {}", why);
+                return failed(self);
+            }
+        };
+
+        let _ = stdout().flush();
+        let _ = stderr().flush();
+        match unsafe { sys::fork() } {
+            Ok(0) => {
+                // The child is set up like any other element of a pipeline, in a process
+                // group of its own.
+                prepare_child(false, 0);
+                let _ = sys::close(reader);
+                if from != RedirectFrom::Stderr {
+                    redir(writer, sys::STDOUT_FILENO);
+                }
+                if from != RedirectFrom::Stdout {
+                    redir(writer, sys::STDERR_FILENO);
+                }
+                let _ = sys::close(writer);
+                self.is_background_shell = true;
+                self.traps.clear();
+                let status = match redirect_shell(&item) {
+                    Some(_) => {
+                        block(self);
+                        self.previous_status
+                    }
+                    None => FAILURE,
+                };
+                let _ = stdout().flush();
+                let _ = stderr().flush();
+                sys::fork_exit(status);
+            }
+            Ok(pid) => {
+                let _ = sys::close(writer);
+                match duplicate_streams(&[]) {
+                    Ok((stdin, stdout, stderr)) => {
+                        redir(reader, sys::STDIN_FILENO);
+                        let _ = sys::close(reader);
+                        self.run_pipeline(&mut pipeline);
+                        redirect_streams(stdin, stdout, stderr);
+                    }
+                    Err(why) => {
+                        eprintln!("ion: failed to `dup` STDOUT, STDIN, or STDERR: {}", why);
+                        let _ = sys::close(reader);
+                        failed(self);
+                    }
+                }
+                let _ = sys::wait_for_child(pid);
+            }
+            Err(why) => {
+                eprintln!("ion: failed to fork: {}", why);
+                let _ = sys::close(reader);
+                let _ = sys::close(writer);
+                failed(self);
+            }
+        }
+        Condition::NoOp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::assignments::Operator;
+    use shell::{flow_control::LocalAction, Binary, ShellBuilder};
+    use std::{fs::File, io::Read, os::unix::io::FromRawFd};
+
+    #[test]
+    fn chains() {
+        let chained =
+            |statements: Vec<Statement>| is_chained(&mut statements.into_iter().peekable());
+        assert!(chained(vec![Statement::And(Box::new(Statement::Break))]));
+        assert!(chained(vec![Statement::Or(Box::new(Statement::Break)), Statement::Break]));
+        assert!(!chained(vec![Statement::Break, Statement::And(Box::new(Statement::Break))]));
+        assert!(!chained(Vec::new()));
+    }
+
+    #[test]
+    fn conditions() {
+        let mut shell = ShellBuilder::new().as_library();
+        shell.flags |= ERR_EXIT;
+        let depth = as_condition(&mut shell, |shell| {
+            as_condition(shell, |shell| shell.condition_depth) + shell.condition_depth
+        });
+        assert_eq!(3, depth);
+        assert_eq!(0, shell.condition_depth);
+
+        // A failed assignment within a condition neither exits nor stops the condition.
+        let action = LocalAction::Assign("x:int".into(), Operator::Equal, "abc".into());
+        let statement = Statement::Let(action);
+        as_condition(&mut shell, |shell| execute_assignment(shell, statement));
+        assert_eq!(FAILURE, shell.previous_status);
+        assert_eq!(None, shell.variables.get_var("x"));
+    }
+
+    #[test]
+    fn return_outside_of_function() {
+        const SCRIPT: &str = "return 2\nlet top = $?\nif true\n    return 3\nend\nlet block = $?";
+        let mut shell = ShellBuilder::new().as_library();
+        shell.terminate_script_quotes(SCRIPT.lines().map(String::from));
+        assert_eq!(Some("1".into()), shell.variables.get_var("top"));
+        assert_eq!(Some("1".into()), shell.variables.get_var("block"));
+    }
+
+    #[test]
+    fn tracing() {
+        const SCRIPT: &str = "set -x\ntrue\nlet name = b\nfor i in 1 2\n    test $i = 2 && \
+                              echo $i >/dev/null\nend\nfn f arg\n    return $arg\nend\nf 0\nset +x";
+        let (read, write) = sys::pipe2(sys::O_CLOEXEC).unwrap();
+        let mut shell = ShellBuilder::new().as_library();
+        shell.script = Some("test.ion".into());
+        shell.variables.set_var("TRACE_FD", &write.to_string());
+        shell.variables.set_var("TRACE_PROMPT", "[$name] ");
+        shell.variables.set_var("name", "a");
+        shell.terminate_script_quotes(SCRIPT.lines().map(String::from));
+        let _ = sys::close(write);
+
+        // The prompt is written as it is, and is followed by the script, line and, within a
+        // function, the depth of the function calls.
+        let mut trace = String::new();
+        unsafe { File::from_raw_fd(read) }.read_to_string(&mut trace).unwrap();
+        let expected = "[$name] test.ion:2: true
+[$name] test.ion:4: for i in 1
+[$name] test.ion:5: test 1 = 2
+[$name] test.ion:4: for i in 2
+[$name] test.ion:5: test 2 = 2
+[$name] test.ion:5: echo 2 > /dev/null
+[$name] test.ion:10: f 0
+[$name] test.ion:8 (depth 1): return 0
+[$name] test.ion:11: set +x
+";
+        assert_eq!(expected, trace);
+    }
 }
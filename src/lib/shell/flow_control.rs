@@ -1,8 +1,7 @@
-use super::{flow::FlowLogic, Shell};
-use fnv::*;
+use super::{flags::{NO_UNSET, PRINT_COMMS}, flow::FlowLogic, Shell};
 use parser::{assignments::*, pipelines::Pipeline};
-use std::fmt::{self, Display, Formatter};
-use types::{Identifier, *};
+use std::{fmt::{self, Display, Formatter}, mem};
+use types::Identifier;
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct ElseIf {
@@ -74,7 +73,7 @@ pub(crate) enum Statement {
         statements:  Vec<Statement>,
     },
     For {
-        variable:   Identifier,
+        variables:  Vec<Identifier>,
         values:     Vec<String>,
         statements: Vec<Statement>,
     },
@@ -87,15 +86,26 @@ pub(crate) enum Statement {
         cases:      Vec<Case>,
     },
     Else,
-    End,
+    /// The end of a block, which may carry redirections and pipes that apply to the entire
+    /// block. The first item of the pipeline represents the block itself.
+    End(Option<Pipeline>),
     Error(i32),
     Break,
     Continue,
+    Return(Option<String>),
     Pipeline(Pipeline),
+    /// Starts a pipeline as a coprocess, whose standard input and output are connected to the
+    /// shell through pipes. Without a pipeline, the input of the named coprocess is closed.
+    Coproc {
+        name:     Identifier,
+        pipeline: Option<Pipeline>,
+    },
     Time(Box<Statement>),
     And(Box<Statement>),
     Or(Box<Statement>),
     Not(Box<Statement>),
+    /// Marks the line of the script on which the statements that follow it were written.
+    Line(usize),
     Default,
 }
 
@@ -112,24 +122,119 @@ impl Statement {
             Statement::While { .. } => "While { .. }",
             Statement::Match { .. } => "Match { .. }",
             Statement::Else => "Else",
-            Statement::End => "End",
+            Statement::End(_) => "End { .. }",
             Statement::Error(_) => "Error { .. }",
             Statement::Break => "Break",
             Statement::Continue => "Continue",
+            Statement::Return(_) => "Return { .. }",
             Statement::Pipeline(_) => "Pipeline { .. }",
+            Statement::Coproc { .. } => "Coproc { .. }",
             Statement::Time(_) => "Time { .. }",
             Statement::And(_) => "And { .. }",
             Statement::Or(_) => "Or { .. }",
             Statement::Not(_) => "Not { .. }",
+            Statement::Line(_) => "Line { .. }",
             Statement::Default => "Default",
         }
     }
 }
 
+/// Writes the statement as it would be written on a line of a script. The statements within
+/// the body of a block are not written, as each is written on a line of its own.
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Statement::Let(LocalAction::List) => write!(f, "let"),
+            Statement::Let(LocalAction::Assign(ref keys, operator, ref values)) => {
+                write!(f, "let {} {} {}", keys, operator, values)
+            }
+            Statement::Export(ExportAction::List) => write!(f, "export"),
+            Statement::Export(ExportAction::LocalExport(ref key)) => write!(f, "export {}", key),
+            Statement::Export(ExportAction::Assign(ref keys, operator, ref values)) => {
+                write!(f, "export {} {} {}", keys, operator, values)
+            }
+            Statement::If { ref expression, .. } => write!(f, "if {}", expression),
+            Statement::ElseIf(ref else_if) => write!(f, "else if {}", else_if.expression),
+            Statement::Else => write!(f, "else"),
+            // The first item of the pipeline is the `end` keyword itself.
+            Statement::End(Some(ref redirection)) => write!(f, "{}", redirection),
+            Statement::End(None) => write!(f, "end"),
+            Statement::Function {
+                ref name,
+                ref description,
+                ref args,
+                ..
+            } => {
+                write!(f, "fn {}", name)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                match *description {
+                    Some(ref description) => write!(f, " -- {}", description),
+                    None => Ok(()),
+                }
+            }
+            Statement::For {
+                ref variables,
+                ref values,
+                ..
+            } => {
+                let variables = variables.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+                write!(f, "for {} in {}", variables.join(" "), values.join(" "))
+            }
+            Statement::While { ref expression, .. } => write!(f, "while {}", expression),
+            Statement::Match { ref expression, .. } => write!(f, "match {}", expression),
+            Statement::Case(ref case) => {
+                write!(f, "case {}", case.value.as_ref().map_or("_", String::as_str))?;
+                if let Some(ref binding) = case.binding {
+                    write!(f, " @ {}", binding)?;
+                }
+                match case.conditional {
+                    Some(ref conditional) => write!(f, " if {}", conditional),
+                    None => Ok(()),
+                }
+            }
+            Statement::Break => write!(f, "break"),
+            Statement::Continue => write!(f, "continue"),
+            Statement::Return(Some(ref status)) => write!(f, "return {}", status),
+            Statement::Return(None) => write!(f, "return"),
+            Statement::Pipeline(ref pipeline) => write!(f, "{}", pipeline),
+            Statement::Coproc {
+                ref name,
+                pipeline: None,
+            } => write!(f, "coproc -c {}", name),
+            Statement::Coproc {
+                ref name,
+                pipeline: Some(ref pipeline),
+            } => if name.as_ref() as &str == "COPROC" {
+                write!(f, "coproc {}", pipeline)
+            } else {
+                write!(f, "coproc {} {}", name, pipeline)
+            },
+            Statement::Time(ref statement) => prefixed(f, "time", statement),
+            Statement::And(ref statement) => prefixed(f, "and", statement),
+            Statement::Or(ref statement) => prefixed(f, "or", statement),
+            Statement::Not(ref statement) => prefixed(f, "not", statement),
+            Statement::Error(_) | Statement::Line(_) | Statement::Default => Ok(()),
+        }
+    }
+}
+
+/// Writes a keyword that applies to the statement which follows it, if there is one.
+fn prefixed(f: &mut Formatter, keyword: &str, statement: &Statement) -> fmt::Result {
+    match *statement {
+        Statement::Default => write!(f, "{}", keyword),
+        ref statement => write!(f, "{} {}", keyword, statement),
+    }
+}
+
 pub(crate) struct FlowControl {
     pub level:             usize,
     pub current_statement: Statement,
     pub current_if_mode:   u8, // { 0 = SUCCESS; 1 = FAILURE }
+    pub redirection:       Option<Pipeline>,
+    /// The line on which the statement that is being collected begins.
+    pub line:              usize,
 }
 
 impl Default for FlowControl {
@@ -138,6 +243,8 @@ impl Default for FlowControl {
             level:             0,
             current_statement: Statement::Default,
             current_if_mode:   0,
+            redirection:       None,
+            line:              0,
         }
     }
 }
@@ -148,6 +255,8 @@ pub struct Function {
     name:        Identifier,
     args:        Vec<KeyBuf>,
     statements:  Vec<Statement>,
+    /// The script in which the function was defined.
+    script:      Option<String>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -161,7 +270,9 @@ impl Display for FunctionError {
         use self::FunctionError::*;
         match *self {
             InvalidArgumentCount => write!(fmt, "invalid number of arguments"),
-            InvalidArgumentType(t, ref value) => write!(fmt, "{} is not of type {}", value, t),
+            InvalidArgumentType(ref t, ref value) => {
+                write!(fmt, "{} is not of type {}", value, t)
+            }
         }
     }
 }
@@ -172,56 +283,80 @@ impl Function {
             return Err(FunctionError::InvalidArgumentCount);
         }
 
-        let mut variables_backup: FnvHashMap<&str, Option<Value>> =
-            FnvHashMap::with_capacity_and_hasher(64, Default::default());
-
-        let mut arrays_backup: FnvHashMap<&str, Option<Array>> =
-            FnvHashMap::with_capacity_and_hasher(64, Default::default());
-
+        let mut values = Vec::with_capacity(self.args.len());
         for (type_, value) in self.args.iter().zip(args.iter().skip(1)) {
-            let value = match value_check(shell, value.as_ref(), type_.kind) {
-                Ok(value) => value,
+            match value_check(shell, value.as_ref(), &type_.kind) {
+                Ok(value) => values.push((&type_.name, value)),
                 Err(_) => {
                     return Err(FunctionError::InvalidArgumentType(
-                        type_.kind,
+                        type_.kind.clone(),
                         value.as_ref().into(),
                     ))
                 }
-            };
-
-            match value {
-                ReturnValue::Vector(vector) => {
-                    let array = shell.variables.get_array(&type_.name).cloned();
-                    arrays_backup.insert(&type_.name, array);
-                    shell.variables.set_array(&type_.name, vector);
-                }
-                ReturnValue::Str(string) => {
-                    variables_backup.insert(&type_.name, shell.get_var(&type_.name));
-                    shell.set_var(&type_.name, &string);
-                }
             }
         }
 
-        shell.execute_statements(self.statements);
+        if shell.flags & NO_UNSET != 0 {
+            let call = args.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(" ");
+            if shell.check_unbound(&call) {
+                return Ok(());
+            }
+        }
 
-        for (name, value_option) in &variables_backup {
-            match *value_option {
-                Some(ref value) => shell.set_var(name, value),
-                None => {
-                    shell.variables.unset_var(name);
+        if shell.flags & PRINT_COMMS != 0 {
+            let mut call = self.name.to_string();
+            for &(_, ref value) in &values {
+                call.push(' ');
+                match *value {
+                    ReturnValue::Vector(ref vector) => {
+                        call.push('[');
+                        call.push_str(&vector.join(" "));
+                        call.push(']');
+                    }
+                    ReturnValue::Str(ref string) => call.push_str(string),
+                    ReturnValue::Map(ref map) => call.push_str(&map.to_string()),
+                    ReturnValue::Nested(ref nested) => call.push_str(&nested.to_string()),
                 }
             }
+            shell.trace(&call);
         }
 
-        for (name, value_option) in arrays_backup {
-            match value_option {
-                Some(value) => shell.variables.set_array(name, value),
-                None => {
-                    shell.variables.unset_array(name);
-                }
+        // Parameters, and any variables assigned within the body of the function, are
+        // stored within a new scope which is discarded once the function returns.
+        shell.variables.new_scope();
+
+        for (name, value) in values {
+            match value {
+                ReturnValue::Vector(vector) => shell.variables.set_array(name, vector),
+                ReturnValue::Str(string) => shell.set_var(name, &string),
+                ReturnValue::Map(map) => shell.variables.set_map(name, map),
+                ReturnValue::Nested(nested) => shell.variables.set_nested(name, nested),
             }
         }
 
+        if let Some(ref mut debugger) = shell.debugger {
+            debugger.enter(&self.name, shell.script.clone(), shell.line);
+        }
+        if let Some(ref profiler) = shell.profiler {
+            profiler.borrow_mut().enter(&self.name);
+        }
+
+        // The lines of the body refer to the script in which the function was defined.
+        let script = mem::replace(&mut shell.script, self.script);
+        let line = shell.line;
+        shell.execute_statements(self.statements);
+        shell.script = script;
+        shell.line = line;
+
+        if let Some(ref mut debugger) = shell.debugger {
+            debugger.leave();
+        }
+        if let Some(ref profiler) = shell.profiler {
+            profiler.borrow_mut().leave();
+        }
+
+        shell.variables.pop_scope();
+
         Ok(())
     }
 
@@ -232,24 +367,30 @@ impl Function {
         name: Identifier,
         args: Vec<KeyBuf>,
         statements: Vec<Statement>,
+        script: Option<String>,
     ) -> Function {
         Function {
             description,
             name,
             args,
             statements,
+            script,
         }
     }
 }
 
+/// Collects the cases of a match block, returning the redirection of the block once it has
+/// been ended.
 pub(crate) fn collect_cases<I>(
     iterator: &mut I,
     cases: &mut Vec<Case>,
     level: &mut usize,
-) -> Result<(), String>
+) -> Result<Option<Pipeline>, String>
 where
     I: Iterator<Item = Statement>,
 {
+    // A line is held until the statement which follows it, as that may begin a new case.
+    let mut line = None;
     macro_rules! add_to_case {
         ($statement:expr) => {
             match cases.last_mut() {
@@ -261,28 +402,34 @@ where
                         " outside of `case ...` block",
                     ].concat())
                 }
-                Some(ref mut case) => case.statements.push($statement),
+                Some(ref mut case) => {
+                    case.statements.extend(line.take().map(Statement::Line));
+                    case.statements.push($statement)
+                }
             }
         };
     }
 
     while let Some(statement) = iterator.next() {
         match statement {
-            Statement::Case(case) => {
+            Statement::Line(number) => line = Some(number),
+            Statement::Case(mut case) => {
                 if *level == 1 {
                     // If the level is 1, then we are at a top-level case
                     // statement for this match block and should push this case
+                    case.statements.extend(line.take().map(Statement::Line));
                     cases.push(case);
                 } else {
                     // This is just part of the current case block
                     add_to_case!(Statement::Case(case));
                 }
             }
-            Statement::End => {
+            Statement::End(redirection) => {
                 *level -= 1;
                 if *level == 0 {
-                    return Ok(());
+                    return Ok(redirection);
                 }
+                add_to_case!(Statement::End(redirection));
             }
             Statement::While { .. }
             | Statement::For { .. }
@@ -298,8 +445,10 @@ where
             | Statement::Error(_)
             | Statement::Export(_)
             | Statement::Continue
+            | Statement::Return(_)
             | Statement::Let { .. }
             | Statement::Pipeline(_)
+            | Statement::Coproc { .. }
             | Statement::Time(_)
             | Statement::And(_)
             | Statement::Or(_)
@@ -310,16 +459,18 @@ where
             }
         }
     }
-    return Ok(());
+    return Ok(None);
 }
 
+/// Collects the statements of a loop or function, returning the redirection of the block once
+/// it has been ended.
 pub(crate) fn collect_loops<I: Iterator<Item = Statement>>(
     iterator: &mut I,
     statements: &mut Vec<Statement>,
     level: &mut usize,
-) {
+) -> Option<Pipeline> {
     #[allow(while_let_on_iterator)]
-    while let Some(statement) = iterator.next() {
+    while let Some(mut statement) = iterator.next() {
         match statement {
             Statement::While { .. }
             | Statement::For { .. }
@@ -332,11 +483,11 @@ pub(crate) fn collect_loops<I: Iterator<Item = Statement>>(
                 | &Statement::If { .. }
                 | &Statement::Function { .. }
                 | &Statement::Match { .. } => *level += 1,
-                &Statement::End if *level == 1 => {
+                &Statement::End(_) if *level == 1 => {
                     *level = 0;
                     break;
                 }
-                &Statement::End => *level -= 1,
+                &Statement::End(_) => *level -= 1,
                 _ => (),
             },
             Statement::And(ref box_stmt) => match box_stmt.as_ref() {
@@ -345,11 +496,11 @@ pub(crate) fn collect_loops<I: Iterator<Item = Statement>>(
                 | &Statement::If { .. }
                 | &Statement::Function { .. }
                 | &Statement::Match { .. } => *level += 1,
-                &Statement::End if *level == 1 => {
+                &Statement::End(_) if *level == 1 => {
                     *level = 0;
                     break;
                 }
-                &Statement::End => *level -= 1,
+                &Statement::End(_) => *level -= 1,
                 _ => (),
             },
             Statement::Or(ref box_stmt) => match box_stmt.as_ref() {
@@ -358,11 +509,11 @@ pub(crate) fn collect_loops<I: Iterator<Item = Statement>>(
                 | &Statement::If { .. }
                 | &Statement::Function { .. }
                 | &Statement::Match { .. } => *level += 1,
-                &Statement::End if *level == 1 => {
+                &Statement::End(_) if *level == 1 => {
                     *level = 0;
                     break;
                 }
-                &Statement::End => *level -= 1,
+                &Statement::End(_) => *level -= 1,
                 _ => (),
             },
             Statement::Not(ref box_stmt) => match box_stmt.as_ref() {
@@ -371,24 +522,27 @@ pub(crate) fn collect_loops<I: Iterator<Item = Statement>>(
                 | &Statement::If { .. }
                 | &Statement::Function { .. }
                 | &Statement::Match { .. } => *level += 1,
-                &Statement::End if *level == 1 => {
+                &Statement::End(_) if *level == 1 => {
                     *level = 0;
                     break;
                 }
-                &Statement::End => *level -= 1,
+                &Statement::End(_) => *level -= 1,
                 _ => (),
             },
-            Statement::End if *level == 1 => {
+            Statement::End(ref mut redirection) if *level == 1 => {
                 *level = 0;
-                break;
+                return redirection.take();
             }
-            Statement::End => *level -= 1,
+            Statement::End(_) => *level -= 1,
             _ => (),
         }
         statements.push(statement);
     }
+    None
 }
 
+/// Collects the branches of an if block, returning the branch that was being collected, and
+/// the redirection of the block once it has been ended.
 pub(crate) fn collect_if<I>(
     iterator: &mut I,
     success: &mut Vec<Statement>,
@@ -396,12 +550,12 @@ pub(crate) fn collect_if<I>(
     failure: &mut Vec<Statement>,
     level: &mut usize,
     mut current_block: u8,
-) -> Result<u8, &'static str>
+) -> Result<(u8, Option<Pipeline>), &'static str>
 where
     I: Iterator<Item = Statement>,
 {
     #[allow(while_let_on_iterator)]
-    while let Some(statement) = iterator.next() {
+    while let Some(mut statement) = iterator.next() {
         match statement {
             Statement::While { .. }
             | Statement::For { .. }
@@ -422,11 +576,11 @@ where
             Statement::Else if *level == 1 && current_block == 1 => {
                 return Err("ion: syntax error: else block already given")
             }
-            Statement::End if *level == 1 => {
+            Statement::End(ref mut redirection) if *level == 1 => {
                 *level = 0;
-                break;
+                return Ok((current_block, redirection.take()));
             }
-            Statement::End => *level -= 1,
+            Statement::End(_) => *level -= 1,
             _ => (),
         }
 
@@ -441,5 +595,5 @@ where
         }
     }
 
-    Ok(current_block)
+    Ok((current_block, None))
 }
@@ -111,6 +111,7 @@ impl<'a> Fork<'a> {
                 let mut shell: Shell = unsafe { (self.shell as *const Shell).read() };
                 shell.set_var("PID", &sys::getpid().unwrap_or(0).to_string());
                 let _ = shell.context.take();
+                shell.traps.clear();
 
                 // Execute the given closure within the child's shell.
                 child_func(&mut shell);
@@ -1,9 +1,11 @@
 use super::Shell;
 use builtins::{BuiltinFunction, BUILTINS};
-use parser::{expand_string, pipelines::RedirectFrom};
+use parser::{
+    expand_string, pipelines::{FdRedirection, RedirectFrom},
+};
 use shell::pipe_exec::PipelineExecution;
 use smallstring::SmallString;
-use std::{fmt, fs::File, str};
+use std::{fmt, fs::File, os::unix::io::RawFd, str};
 use types::*;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -20,18 +22,40 @@ pub(crate) struct Job {
     pub args:    Array,
     pub kind:    JobKind,
     pub builtin: Option<BuiltinFunction>,
+    /// Variables that are assigned only for the duration of this command.
+    pub env:     Vec<(Identifier, Value)>,
 }
 
 impl Job {
     /// Takes the current job's arguments and expands them, one argument at a
     /// time, returning a new `Job` with the expanded arguments.
     pub(crate) fn expand(&mut self, shell: &Shell) {
+        // The body of a block is expanded as its statements are executed.
+        if self.block().is_some() {
+            return;
+        }
         let mut expanded = Array::new();
         expanded.grow(self.args.len());
         expanded.extend(self.args.drain().flat_map(|arg| expand_arg(&arg, shell)));
         self.args = expanded;
     }
 
+    /// Expands the values of the variable assignments that precede the command.
+    pub(crate) fn expand_environment(&mut self, shell: &Shell) {
+        for &mut (_, ref mut value) in self.env.iter_mut() {
+            *value = expand_string(value, shell, false).join(" ");
+        }
+    }
+
+    /// Returns the body of a subshell or group, along with whether it is a subshell.
+    pub(crate) fn block(&self) -> Option<(&str, bool)> {
+        match (self.command.as_ref(), self.args.len()) {
+            ("(", 3) => Some((&self.args[1], true)),
+            ("{", 3) => Some((&self.args[1], false)),
+            _ => None,
+        }
+    }
+
     pub(crate) fn new(args: Array, kind: JobKind) -> Self {
         let command = SmallString::from_str(&args[0]);
         let builtin = BUILTINS.get(command.as_ref()).map(|b| b.main);
@@ -40,13 +64,17 @@ impl Job {
             args,
             kind,
             builtin,
+            env: Vec::new(),
         }
     }
 }
 
 impl PartialEq for Job {
     fn eq(&self, other: &Job) -> bool {
-        self.command == other.command && self.args == other.args && self.kind == other.kind
+        self.command == other.command
+            && self.args == other.args
+            && self.kind == other.kind
+            && self.env == other.env
     }
 }
 
@@ -54,8 +82,8 @@ impl fmt::Debug for Job {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Job {{ command: {}, args: {:?}, kind: {:?} }}",
-            self.command, self.args, self.kind
+            "Job {{ command: {}, args: {:?}, kind: {:?}, env: {:?} }}",
+            self.command, self.args, self.kind, self.env
         )
     }
 }
@@ -75,27 +103,43 @@ fn expand_arg(arg: &str, shell: &Shell) -> Array {
 pub(crate) enum RefinedJob {
     /// An external program that is executed by this shell
     External {
-        name:   Identifier,
-        args:   Array,
-        stdin:  Option<File>,
-        stdout: Option<File>,
-        stderr: Option<File>,
+        name:        Identifier,
+        args:        Array,
+        stdin:       Option<File>,
+        stdout:      Option<File>,
+        stderr:      Option<File>,
+        descriptors: Vec<FdRedirection>,
+        env:         Vec<(Identifier, Value)>,
     },
     /// A procedure embedded into Ion
     Builtin {
-        main:   BuiltinFunction,
-        args:   Array,
-        stdin:  Option<File>,
-        stdout: Option<File>,
-        stderr: Option<File>,
+        main:        BuiltinFunction,
+        args:        Array,
+        stdin:       Option<File>,
+        stdout:      Option<File>,
+        stderr:      Option<File>,
+        descriptors: Vec<FdRedirection>,
+        env:         Vec<(Identifier, Value)>,
     },
     /// Functions can act as commands too!
     Function {
-        name:   Identifier,
-        args:   Array,
-        stdin:  Option<File>,
-        stdout: Option<File>,
-        stderr: Option<File>,
+        name:        Identifier,
+        args:        Array,
+        stdin:       Option<File>,
+        stdout:      Option<File>,
+        stderr:      Option<File>,
+        descriptors: Vec<FdRedirection>,
+        env:         Vec<(Identifier, Value)>,
+    },
+    /// A subshell or group, whose body is executed by a shell
+    Block {
+        body:        String,
+        subshell:    bool,
+        stdin:       Option<File>,
+        stdout:      Option<File>,
+        stderr:      Option<File>,
+        descriptors: Vec<FdRedirection>,
+        env:         Vec<(Identifier, Value)>,
     },
     /// Represents redirection into stdin from more than one source
     Cat {
@@ -182,6 +226,7 @@ macro_rules! set_field {
             RefinedJob::External { ref mut $field, .. }
             | RefinedJob::Builtin { ref mut $field, .. }
             | RefinedJob::Function { ref mut $field, .. }
+            | RefinedJob::Block { ref mut $field, .. }
             | RefinedJob::Tee { ref mut $field, .. } => {
                 *$field = Some($arg);
             }
@@ -198,6 +243,8 @@ impl RefinedJob {
             RefinedJob::External { ref args, .. }
             | RefinedJob::Builtin { ref args, .. }
             | RefinedJob::Function { ref args, .. } => format!("{}", args.join(" ")),
+            RefinedJob::Block { ref body, subshell: true, .. } => format!("({})", body),
+            RefinedJob::Block { ref body, .. } => format!("{{{}}}", body),
             // TODO: Figure out real printing
             RefinedJob::Cat { .. } | RefinedJob::Tee { .. } => "".into(),
         }
@@ -211,6 +258,8 @@ impl RefinedJob {
             RefinedJob::Function { ref name, .. } | RefinedJob::External { ref name, .. } => {
                 name.to_string()
             }
+            RefinedJob::Block { subshell: true, .. } => "subshell".into(),
+            RefinedJob::Block { .. } => "group".into(),
             // TODO: Print for real
             RefinedJob::Cat { .. } => "multi-input".into(),
             RefinedJob::Tee { .. } => "multi-output".into(),
@@ -225,31 +274,86 @@ impl RefinedJob {
                 ref stdin,
                 ref stdout,
                 ref stderr,
-            } => {
-                shell.exec_external(&name, &args[1..], stdin, stdout, stderr)
-            }
+                ref descriptors,
+                ..
+            } => shell.exec_external(&name, &args[1..], stdin, stdout, stderr, descriptors),
             RefinedJob::Builtin {
                 main,
                 ref args,
                 ref stdin,
                 ref stdout,
                 ref stderr,
-            } => {
-                shell.exec_builtin(main, args, stdout, stderr, stdin)
-            }
+                ref descriptors,
+                ..
+            } => shell.exec_builtin(main, args, stdout, stderr, stdin, descriptors),
             RefinedJob::Function {
                 ref name,
                 ref args,
                 ref stdin,
                 ref stdout,
                 ref stderr,
-            } => {
-                shell.exec_function(name, args, stdout, stderr, stdin)
-            }
+                ref descriptors,
+                ..
+            } => shell.exec_function(name, args, stdout, stderr, stdin, descriptors),
+            RefinedJob::Block {
+                ref body,
+                subshell,
+                ref stdin,
+                ref stdout,
+                ref stderr,
+                ref descriptors,
+                ..
+            } => shell.exec_block(body, subshell, stdout, stderr, stdin, descriptors),
             _ => panic!("exec job should not be able to be called on Cat or Tee jobs"),
         }
     }
 
+    /// Sets the numbered descriptor redirections of a command; other jobs have none.
+    pub(crate) fn descriptors(&mut self, redirections: Vec<FdRedirection>) {
+        match *self {
+            RefinedJob::External { ref mut descriptors, .. }
+            | RefinedJob::Builtin { ref mut descriptors, .. }
+            | RefinedJob::Function { ref mut descriptors, .. }
+            | RefinedJob::Block { ref mut descriptors, .. } => *descriptors = redirections,
+            _ => {}
+        }
+    }
+
+    /// Returns the descriptors that will be modified by the numbered descriptor redirections.
+    pub(crate) fn redirected_descriptors(&self) -> Vec<RawFd> {
+        match *self {
+            RefinedJob::External { ref descriptors, .. }
+            | RefinedJob::Builtin { ref descriptors, .. }
+            | RefinedJob::Function { ref descriptors, .. }
+            | RefinedJob::Block { ref descriptors, .. } => {
+                descriptors.iter().map(FdRedirection::fd).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Sets the variables that are assigned for the duration of a command.
+    pub(crate) fn environment(&mut self, assignments: Vec<(Identifier, Value)>) {
+        match *self {
+            RefinedJob::External { ref mut env, .. }
+            | RefinedJob::Builtin { ref mut env, .. }
+            | RefinedJob::Function { ref mut env, .. }
+            | RefinedJob::Block { ref mut env, .. } => *env = assignments,
+            _ => {}
+        }
+    }
+
+    /// Returns the variables that are assigned for the duration of a command.
+    pub(crate) fn assignments(&self) -> &[(Identifier, Value)] {
+        match *self {
+            RefinedJob::External { ref env, .. }
+            | RefinedJob::Builtin { ref env, .. }
+            | RefinedJob::Function { ref env, .. }
+            | RefinedJob::Block { ref env, .. } => env,
+            _ => &[],
+        }
+    }
+
     pub(crate) fn stderr(&mut self, file: File) {
         set_field!(self, stderr, file);
     }
@@ -294,6 +398,20 @@ impl RefinedJob {
             stdin: None,
             stdout: None,
             stderr: None,
+            descriptors: Vec::new(),
+            env: Vec::new(),
+        }
+    }
+
+    pub(crate) fn block(body: String, subshell: bool) -> Self {
+        RefinedJob::Block {
+            body,
+            subshell,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            descriptors: Vec::new(),
+            env: Vec::new(),
         }
     }
 
@@ -304,6 +422,8 @@ impl RefinedJob {
             stdin: None,
             stdout: None,
             stderr: None,
+            descriptors: Vec::new(),
+            env: Vec::new(),
         }
     }
 
@@ -314,6 +434,8 @@ impl RefinedJob {
             stdin: None,
             stdout: None,
             stderr: None,
+            descriptors: Vec::new(),
+            env: Vec::new(),
         }
     }
 }
@@ -2,6 +2,7 @@ mod assignments;
 pub(crate) mod binary;
 pub(crate) mod colors;
 mod completer;
+pub(crate) mod debugger;
 pub(crate) mod directory_stack;
 pub mod flags;
 mod flow;
@@ -12,6 +13,7 @@ mod history;
 mod job;
 pub(crate) mod pipe_exec;
 pub(crate) mod plugins;
+mod profiler;
 pub(crate) mod signals;
 pub mod status;
 pub mod variables;
@@ -25,19 +27,27 @@ pub(crate) use self::{
 };
 
 use self::{
-    directory_stack::DirectoryStack, flags::*,
+    debugger::Debugger, directory_stack::DirectoryStack, flags::*,
     flow_control::{FlowControl, Function, FunctionError}, foreground::ForegroundSignals,
-    job_control::{BackgroundProcess, JobControl}, pipe_exec::PipelineExecution, status::*,
-    variables::Variables,
+    job_control::{BackgroundProcess, JobControl},
+    pipe_exec::{
+        coprocess::{self, Coprocess}, pipe_status,
+        substitution::{finish_substitutions, Substitution}, PipelineExecution,
+    },
+    profiler::Profiler,
+    signals::Trap, status::*, variables::Variables,
 };
 use builtins::{BuiltinMap, BUILTINS};
 use fnv::FnvHashMap;
 use liner::Context;
-use parser::{pipelines::Pipeline, ArgumentSplitter, Expander, Select, Terminator};
+use parser::{
+    pipelines::Pipeline, ArgumentSplitter, Expander, Index, Select, SelectWithSize, Terminator,
+};
 use smallvec::SmallVec;
 use std::{
-    fs::File, io::{self, Read, Write}, iter::FromIterator, ops::Deref, path::Path, process,
-    sync::{atomic::Ordering, Arc, Mutex}, time::SystemTime,
+    cell::{Cell, RefCell}, fs::File, io::{self, Read, Write}, iter::FromIterator, mem, ops::Deref,
+    os::unix::io::{FromRawFd, IntoRawFd, RawFd}, path::Path, process, rc::Rc,
+    sync::{atomic::Ordering, Arc, Mutex}, time::{Instant, SystemTime},
 };
 use sys;
 use types::*;
@@ -97,6 +107,33 @@ pub struct Shell {
     /// Stores the patterns used to determine whether a command should be saved in the history
     /// or not
     ignore_setting: IgnoreSetting,
+    /// Commands that have been bound to signals, and the `EXIT` and `ERR` conditions, with
+    /// the `trap` builtin.
+    pub(crate) traps: FnvHashMap<Trap, String>,
+    /// Set while a trap is executing, to prevent traps from triggering within traps.
+    in_trap: bool,
+    /// Process substitutions which have been spawned by the pipelines that are executing.
+    pub(crate) substitutions: Mutex<Vec<Substitution>>,
+    /// The descriptors of the shell's ends of the pipes of each named coprocess.
+    pub(crate) coprocesses: FnvHashMap<Identifier, Coprocess>,
+    /// The first unbound variable that was referenced by an expansion, when `nounset` is set.
+    unbound_variable: RefCell<Option<Identifier>>,
+    /// The number of conditions which are being evaluated, such as that of an `if` statement.
+    /// Commands which fail within a condition do not cause `set -e` to exit the shell.
+    pub(crate) condition_depth: usize,
+    /// The path of the script which is being executed, if any.
+    pub(crate) script: Option<String>,
+    /// The line of the script, or the number of the command in an interactive session, which
+    /// is being executed.
+    pub(crate) line: usize,
+    /// The number of syntax errors which have been reported. The count is shared with the
+    /// statements that are being parsed, which report their own errors.
+    pub(crate) syntax_errors: Rc<Cell<usize>>,
+    /// Pauses the execution of statements, when the shell was started with `--debug`.
+    pub(crate) debugger: Option<Box<Debugger>>,
+    /// Records the time spent by statements, functions and commands, when the shell was
+    /// started with `--profile`. Command substitutions are recorded while the shell is borrowed.
+    pub(crate) profiler: Option<RefCell<Profiler>>,
 }
 
 pub struct ShellBuilder;
@@ -189,13 +226,18 @@ impl<'a> Shell {
     /// method will attempt to execute that file as a script, and then returns the final exit
     /// status of the evaluated script.
     pub fn execute_script<SCRIPT: AsRef<Path>>(&mut self, script: SCRIPT) -> io::Result<i32> {
+        let path = script.as_ref().to_string_lossy().into_owned();
         let mut script = File::open(script.as_ref())?;
         let capacity = script.metadata().ok().map_or(0, |x| x.len());
         let mut command_list = String::with_capacity(capacity as usize);
         let _ = script.read_to_string(&mut command_list)?;
+        let previous_script = mem::replace(&mut self.script, Some(path));
+        let previous_line = self.line;
         if FAILURE == self.terminate_script_quotes(command_list.lines().map(|x| x.to_owned())) {
             self.previous_status = FAILURE;
         }
+        self.script = previous_script;
+        self.line = previous_line;
         Ok(self.previous_status)
     }
 
@@ -236,11 +278,8 @@ impl<'a> Shell {
     /// variable map.
     pub fn set_var(&mut self, name: &str, value: &str) { self.variables.set_var(name, value); }
 
-    /// Executes a pipeline and returns the final exit status of the pipeline.
-    pub(crate) fn run_pipeline(&mut self, pipeline: &mut Pipeline) -> Option<i32> {
-        let command_start_time = SystemTime::now();
-
-        // Expand any aliases found
+    /// Expands the aliases which are found at the beginning of each command of the pipeline.
+    pub(crate) fn expand_aliases(&self, pipeline: &mut Pipeline) {
         for item in pipeline.items.iter_mut() {
             let mut last_command = String::with_capacity(32);
             loop {
@@ -268,30 +307,65 @@ impl<'a> Shell {
                 }
             }
         }
+    }
+
+    /// Executes a statement, and then finishes the process substitutions which were spawned by
+    /// the words that it expanded outside of a pipeline, such as those of assignments and loops.
+    pub(crate) fn finishing_substitutions<T, F>(&mut self, execute: F) -> T
+    where
+        F: FnOnce(&mut Shell) -> T,
+    {
+        let substitutions = self.substitutions.lock().unwrap().len();
+        let result = execute(self);
+        finish_substitutions(self, substitutions, true);
+        result
+    }
+
+    /// Executes a pipeline and returns the final exit status of the pipeline.
+    pub(crate) fn run_pipeline(&mut self, pipeline: &mut Pipeline) -> Option<i32> {
+        let command_start_time = SystemTime::now();
+        let substitutions = self.substitutions.lock().unwrap().len();
+
+        self.expand_aliases(pipeline);
+        // The pipeline is reported as it was written if it references an unbound variable.
+        let source = if self.flags & NO_UNSET != 0 { pipeline.to_string() } else { String::new() };
 
         // Branch if -> input == shell command i.e. echo
         let exit_status = if let Some(main) = pipeline.items[0].job.builtin {
             pipeline.expand(self);
             // Run the 'main' of the command and set exit_status
-            if !pipeline.requires_piping() {
+            if self.check_unbound(&source) {
+                Some(FAILURE)
+            } else {
                 if self.flags & PRINT_COMMS != 0 {
-                    eprintln!("> {}", pipeline.to_string());
+                    self.trace(&pipeline.to_string());
                 }
-                if self.flags & NO_EXEC != 0 {
+                if pipeline.requires_piping() {
+                    Some(self.execute_pipeline(pipeline))
+                } else if self.flags & NO_EXEC != 0 {
                     Some(SUCCESS)
                 } else {
-                    let borrowed = &pipeline.items[0].job.args;
-                    Some(main(&borrowed, self))
+                    let job = &pipeline.items[0].job;
+                    let assignments = self.variables.assign_temporarily(&job.env);
+                    let status = main(&job.args, self);
+                    self.variables.restore(assignments);
+                    Some(pipe_status(self, &[status]))
                 }
-            } else {
-                Some(self.execute_pipeline(pipeline))
             }
         // Branch else if -> input == shell function and set the exit_status
         } else if let Some(function) = self.functions.get(&pipeline.items[0].job.command).cloned() {
             if !pipeline.requires_piping() {
-                let args: &[String] = pipeline.items[0].job.args.deref();
-                match function.execute(self, args) {
-                    Ok(()) => None,
+                // Unbound variables are checked for once the arguments have been expanded.
+                pipeline.items[0].job.expand_environment(self);
+                let job = &pipeline.items[0].job;
+                let assignments = self.variables.assign_temporarily(&job.env);
+                let result = function.execute(self, job.args.deref());
+                self.variables.restore(assignments);
+                match result {
+                    Ok(()) => {
+                        let status = self.previous_status;
+                        Some(pipe_status(self, &[status]))
+                    }
                     Err(FunctionError::InvalidArgumentCount) => {
                         eprintln!("ion: invalid number of function arguments supplied");
                         Some(FAILURE)
@@ -306,12 +380,34 @@ impl<'a> Shell {
                     }
                 }
             } else {
-                Some(self.execute_pipeline(pipeline))
+                pipeline.expand(self);
+                if self.check_unbound(&source) {
+                    Some(FAILURE)
+                } else {
+                    if self.flags & PRINT_COMMS != 0 {
+                        self.trace(&pipeline.to_string());
+                    }
+                    Some(self.execute_pipeline(pipeline))
+                }
             }
         } else {
             pipeline.expand(self);
-            Some(self.execute_pipeline(pipeline))
+            if self.check_unbound(&source) {
+                Some(FAILURE)
+            } else {
+                if self.flags & PRINT_COMMS != 0 {
+                    self.trace(&pipeline.to_string());
+                }
+                Some(self.execute_pipeline(pipeline))
+            }
+        };
+
+        // Release the process substitutions that were spawned by this pipeline.
+        let background = match pipeline.items.last().map(|item| item.job.kind) {
+            Some(JobKind::Background) | Some(JobKind::Disown) => true,
+            _ => false,
         };
+        finish_substitutions(self, substitutions, !background);
 
         // If `RECORD_SUMMARY` is set to "1" (True, Yes), then write a summary of the
         // pipline just executed to the the file and context histories. At the
@@ -340,6 +436,59 @@ impl<'a> Shell {
         exit_status
     }
 
+    /// Reports the first reference to an unbound variable that was made by the expansions of
+    /// `statement`, if the `nounset` option is set. The statement should then be aborted, which
+    /// also aborts any statements that enclose it, and exits the shell if `set -e` is in effect.
+    pub(crate) fn check_unbound(&mut self, statement: &str) -> bool {
+        let name = match self.unbound_variable.borrow_mut().take() {
+            Some(name) => name,
+            None => return false,
+        };
+        eprintln!(
+            "ion: {}:{}: {}: unbound variable, referenced by `{}`",
+            self.script.as_ref().map_or("ion", String::as_str),
+            self.line,
+            name,
+            statement.trim()
+        );
+        if self.flags & ERR_EXIT != 0 {
+            self.exit(FAILURE);
+        }
+        self.previous_status = FAILURE;
+        self.set_var("?", "1");
+        self.break_flow = true;
+        true
+    }
+
+    /// Writes a line of the trace that is produced by `set -x`. Each line is preceded by the
+    /// value of `TRACE_PROMPT`, which is not expanded, and the location of the statement that
+    /// is being executed: the script, line and, within a function, the depth of the function
+    /// calls. The trace is written to standard error, or to the descriptor given by `TRACE_FD`.
+    pub(crate) fn trace(&self, text: &str) {
+        let prompt = self.variables.get_var("TRACE_PROMPT").unwrap_or_default();
+        let script = self.script.as_ref().map_or("ion", String::as_str);
+        let line = match self.variables.scope_depth() {
+            0 => format!("{}{}:{}: {}\n", prompt, script, self.line, text),
+            depth => format!("{}{}:{} (depth {}): {}\n", prompt, script, self.line, depth, text),
+        };
+
+        let descriptor = self.variables.get_var("TRACE_FD").and_then(|fd| fd.parse::<RawFd>().ok());
+        match descriptor {
+            Some(fd) => {
+                // The descriptor is borrowed, and therefore must not be closed afterwards.
+                let mut file = unsafe { File::from_raw_fd(fd) };
+                if let Err(why) = file.write_all(line.as_bytes()) {
+                    eprintln!("ion: failed to write the trace to descriptor {}: {}", fd, why);
+                }
+                let _ = file.into_raw_fd();
+            }
+            None => {
+                let stderr = io::stderr();
+                let _ = stderr.lock().write_all(line.as_bytes());
+            }
+        }
+    }
+
     /// Evaluates the source init file in the user's home directory.
     pub fn evaluate_init_file(&mut self) {
         let base_dirs = match BaseDirectories::with_prefix("ion") {
@@ -370,6 +519,18 @@ impl<'a> Shell {
     }
 
     pub(crate) fn prep_for_exit(&mut self) {
+        // The exit trap is removed before it is executed, so that an `exit` within the
+        // handler will not execute it a second time.
+        if let Some(command) = self.traps.remove(&Trap::Exit) {
+            self.on_command(&command);
+        }
+
+        if let Some(profiler) = self.profiler.take() {
+            profiler.into_inner().finish();
+        }
+
+        coprocess::close_all(self);
+
         // The context has two purposes: if it exists, this is an interactive shell; and the
         // context will also be sent a signal to commit all changes to the history file,
         // and waiting for the history thread in the background to finish.
@@ -383,14 +544,44 @@ impl<'a> Shell {
         }
     }
 
-    pub(crate) fn next_signal(&self) -> Option<i32> {
-        match signals::PENDING.swap(0, Ordering::SeqCst) {
-            0 => None,
-            signals::SIGINT => Some(sys::SIGINT),
-            signals::SIGHUP => Some(sys::SIGHUP),
-            signals::SIGTERM => Some(sys::SIGTERM),
+    /// Obtains the last signal that was received by the shell, if any. If the user has set a
+    /// trap for that signal, the trap will be executed, and the signal will be consumed.
+    pub(crate) fn next_signal(&mut self) -> Option<i32> {
+        let signal = match signals::PENDING.swap(0, Ordering::SeqCst) {
+            0 => return None,
+            signals::SIGINT => sys::SIGINT,
+            signals::SIGHUP => sys::SIGHUP,
+            signals::SIGTERM => sys::SIGTERM,
             _ => unreachable!(),
+        };
+
+        if self.run_trap(Trap::Signal(signal)) {
+            None
+        } else {
+            Some(signal)
+        }
+    }
+
+    /// Executes the command that was bound to the given condition, if one exists, and returns
+    /// whether a trap was executed. The exit status of the shell is preserved across the trap.
+    pub(crate) fn run_trap(&mut self, trap: Trap) -> bool {
+        // Traps are not triggered by commands within a trap, so that they may not recurse.
+        if self.in_trap {
+            return false;
         }
+
+        let command = match self.traps.get(&trap) {
+            Some(command) => command.clone(),
+            None => return false,
+        };
+
+        let previous_status = self.previous_status;
+        self.in_trap = true;
+        self.on_command(&command);
+        self.in_trap = false;
+        self.previous_status = previous_status;
+        self.set_var("?", &previous_status.to_string());
+        true
     }
 
     pub(crate) fn new(is_library: bool) -> Shell {
@@ -410,6 +601,17 @@ impl<'a> Shell {
             break_flow: false,
             foreground_signals: Arc::new(ForegroundSignals::new()),
             ignore_setting: IgnoreSetting::default(),
+            traps: FnvHashMap::default(),
+            in_trap: false,
+            substitutions: Mutex::new(Vec::new()),
+            coprocesses: FnvHashMap::default(),
+            unbound_variable: RefCell::new(None),
+            condition_depth: 0,
+            script: None,
+            line: 1,
+            syntax_errors: Rc::new(Cell::new(0)),
+            debugger: None,
+            profiler: None,
         }
     }
 }
@@ -417,6 +619,7 @@ impl<'a> Shell {
 impl<'a> Expander for Shell {
     /// Uses a subshell to expand a given command.
     fn command(&self, command: &str) -> Option<Value> {
+        let start = Instant::now();
         let mut output = None;
         match self.fork(Capture::StdoutThenIgnoreStderr, move |shell| {
             shell.on_command(command)
@@ -437,9 +640,25 @@ impl<'a> Expander for Shell {
 
         // Ensure that the parent retains ownership of the terminal before exiting.
         let _ = sys::tcsetpgrp(sys::STDIN_FILENO, process::id());
+        profiler::substitution(self, command, start);
         output
     }
 
+    /// Spawns a process substitution, and expands to the path of its pipe.
+    fn substitution(&self, command: &str, writable: bool) -> Option<Value> {
+        match Substitution::spawn(self, command, writable) {
+            Ok(substitution) => {
+                let path = substitution.path();
+                self.substitutions.lock().unwrap().push(substitution);
+                Some(path)
+            }
+            Err(why) => {
+                eprintln!("ion: process substitution error: {}", why);
+                None
+            }
+        }
+    }
+
     /// Expand a string variable given if its quoted / unquoted
     fn variable(&self, variable: &str, quoted: bool) -> Option<Value> {
         use ascii_helpers::AsciiReplace;
@@ -482,7 +701,7 @@ impl<'a> Expander for Shell {
             None => None,
         };
         if found.is_none() {
-            found = match self.variables.get_map(array) {
+            found = match self.variables.get_any_map(array) {
                 Some(map) => match selection {
                     Select::All => Some(
                         map.iter()
@@ -497,10 +716,57 @@ impl<'a> Expander for Shell {
                 None => None,
             }
         }
+        if found.is_none() {
+            found = self.nested(array, &[selection]);
+        }
         found
     }
 
+    fn map(&self, map: &str) -> Option<Map> {
+        self.variables.get_any_map(map).map(|map| map.to_map())
+    }
+
+    /// Expand a nested variable, where every selection of a single element descends into that
+    /// element. The elements of the value which is finally reached are then expanded, unless
+    /// the last selection was that of a range, or of every element.
+    fn nested(&self, name: &str, selections: &[Select]) -> Option<Array> {
+        let mut value = self.variables.get_nested(name)?;
+        for (id, selection) in selections.iter().enumerate() {
+            value = match (value, selection) {
+                (&Nested::Array(ref elements), &Select::Index(ref index)) => {
+                    elements.get(index.resolve(elements.len())?)?
+                }
+                (&Nested::Map(ref map), &Select::Key(ref key)) => map.get(key.get())?,
+                (&Nested::Map(ref map), &Select::Index(Index::Forward(key))) => {
+                    map.get(&key.to_string())?
+                }
+                (_, selection) if id + 1 == selections.len() => {
+                    let elements = value.elements();
+                    let length = elements.len();
+                    return Some(elements.into_iter().select(selection.clone(), length));
+                }
+                _ => return None,
+            };
+        }
+        Some(value.elements())
+    }
+
     fn tilde(&self, input: &str) -> Option<String> {
         self.variables.tilde_expansion(input, &self.directory_stack)
     }
+
+    /// Records the reference when `nounset` is set, if the variable is undefined, rather than
+    /// an index into an array which is out of bounds.
+    fn unbound(&self, name: &str) {
+        if self.flags & NO_UNSET == 0 || self.unbound_variable.borrow().is_some() {
+            return;
+        }
+        let defined = self.variables.get_array(name).is_some()
+            || self.variables.get_any_map(name).is_some()
+            || self.variables.get_nested(name).is_some()
+            || !name.contains("::") && self.variables.get_var(name).is_some();
+        if !defined {
+            *self.unbound_variable.borrow_mut() = Some(name.into());
+        }
+    }
 }
@@ -0,0 +1,174 @@
+//! Coprocesses execute a pipeline in the background, with its standard input and output
+//! connected to the shell through a pair of pipes. The descriptors of the shell's ends of those
+//! pipes are stored within a global array variable, so that commands may be redirected to
+//! them, and are closed once the coprocess is replaced or the shell exits.
+
+use super::{
+    super::{flags::{NO_EXEC, NO_UNSET, PRINT_COMMS}, status::*, JobKind, Shell},
+    do_redirection, fork::{create_process_group, prepare_background},
+    gen_background_string, job_control::{JobControl, ProcessState}, pipe, PipelineExecution,
+};
+use parser::pipelines::Pipeline;
+use std::os::unix::io::RawFd;
+use sys;
+use types::Array;
+
+/// The shell's ends of the pipes that are connected to a coprocess.
+#[derive(Debug)]
+pub(crate) struct Coprocess {
+    /// The descriptor from which the output of the coprocess is read.
+    output: RawFd,
+    /// The descriptor to which the input of the coprocess is written, until it is closed.
+    input: Option<RawFd>,
+}
+
+impl Coprocess {
+    fn close(self) {
+        let _ = sys::close(self.output);
+        if let Some(input) = self.input {
+            let _ = sys::close(input);
+        }
+    }
+}
+
+/// Starts the pipeline as a coprocess. The array `name` is assigned the descriptor from which
+/// the output of the coprocess may be read, followed by the descriptor to which its input may
+/// be written, and `name_PID` is assigned the process ID of the coprocess. Both are global,
+/// as the descriptors remain open after the scope that spawned the coprocess has ended.
+pub(crate) fn spawn(shell: &mut Shell, name: &str, mut pipeline: Pipeline) -> i32 {
+    pipeline.items.last_mut().unwrap().job.kind = JobKind::Background;
+    shell.expand_aliases(&mut pipeline);
+    let source = if shell.flags & NO_UNSET != 0 {
+        ["coproc ", name, " ", &pipeline.to_string()].concat()
+    } else {
+        String::new()
+    };
+    pipeline.expand(shell);
+    if shell.check_unbound(&source) {
+        return FAILURE;
+    }
+
+    if shell.flags & PRINT_COMMS != 0 {
+        shell.trace(&["coproc ", name, " ", &pipeline.to_string()].concat());
+    }
+
+    let (command, _) = gen_background_string(&pipeline).unwrap();
+    let commands = match shell.generate_commands(&mut pipeline) {
+        Ok(commands) => commands,
+        Err(status) => return status,
+    };
+    if shell.flags & NO_EXEC != 0 {
+        return SUCCESS;
+    }
+    let commands = match do_redirection(commands) {
+        Some(commands) => commands,
+        None => return COULD_NOT_EXEC,
+    };
+
+    // Each end is closed upon exec, so that the programs executed by the shell don't hold them.
+    let (input, output) = match sys::pipe2(sys::O_CLOEXEC) {
+        Ok(input) => match sys::pipe2(sys::O_CLOEXEC) {
+            Ok(output) => (input, output),
+            Err(why) => {
+                close(&[input.0, input.1]);
+                eprintln!("ion: coproc: failed to create pipe: {}", why);
+                return FAILURE;
+            }
+        },
+        Err(why) => {
+            eprintln!("ion: coproc: failed to create pipe: {}", why);
+            return FAILURE;
+        }
+    };
+
+    match unsafe { sys::fork() } {
+        Ok(0) => {
+            prepare_background(shell);
+            // The child must not hold the shell's ends of the pipes, or else the coprocess
+            // would never receive the end of its input.
+            let _ = sys::dup2(input.0, sys::STDIN_FILENO);
+            let _ = sys::dup2(output.1, sys::STDOUT_FILENO);
+            close(&[input.0, input.1, output.0, output.1]);
+            create_process_group(0);
+            sys::fork_exit(pipe(shell, commands, false));
+        }
+        Ok(pid) => {
+            close(&[input.0, output.1]);
+            // A coprocess that replaces another of the same name leaves the previous one
+            // without an input, as nothing else could write to it, nor read from it.
+            let coprocess = Coprocess {
+                output: output.0,
+                input:  Some(input.1),
+            };
+            if let Some(previous) = shell.coprocesses.insert(name.into(), coprocess) {
+                previous.close();
+            }
+            shell.send_to_background(pid, ProcessState::Running, command);
+            let descriptors = array![output.0.to_string(), input.1.to_string()];
+            shell.variables.set_array(&["global::", name].concat(), descriptors);
+            shell.set_var(&["global::", name, "_PID"].concat(), &pid.to_string());
+            SUCCESS
+        }
+        Err(why) => {
+            close(&[input.0, input.1, output.0, output.1]);
+            eprintln!("ion: coproc: failed to fork: {}", why);
+            FAILURE
+        }
+    }
+}
+
+/// Closes the shell's end of the input of the coprocess `name`, so that it may observe the
+/// end of its input. The output of the coprocess remains readable.
+pub(crate) fn close_input(shell: &mut Shell, name: &str) -> i32 {
+    let input = match remove_input(shell, name) {
+        Some(input) => input,
+        None => {
+            eprintln!("ion: coproc: {} does not have an input to close", name);
+            return FAILURE;
+        }
+    };
+
+    match sys::close(input) {
+        Ok(()) => SUCCESS,
+        Err(why) => {
+            eprintln!("ion: coproc: failed to close the input of {}: {}", name, why);
+            FAILURE
+        }
+    }
+}
+
+/// Closes the inputs of every coprocess, which would otherwise never exit while the shell
+/// waits for its background jobs to finish.
+pub(crate) fn close_inputs(shell: &mut Shell) {
+    let names = shell.coprocesses.keys().cloned().collect::<Vec<_>>();
+    for name in names {
+        if let Some(input) = remove_input(shell, &name) {
+            let _ = sys::close(input);
+        }
+    }
+}
+
+/// Closes both ends of every coprocess, once the shell is exiting.
+pub(crate) fn close_all(shell: &mut Shell) {
+    let coprocesses = shell.coprocesses.drain().collect::<Vec<_>>();
+    for (name, coprocess) in coprocesses {
+        shell.variables.unset_array(&["global::", &name].concat());
+        coprocess.close();
+    }
+}
+
+/// Takes the input of the coprocess, leaving only the output descriptor within its array.
+fn remove_input(shell: &mut Shell, name: &str) -> Option<RawFd> {
+    let (output, input) = match shell.coprocesses.get_mut(name) {
+        Some(coprocess) => (coprocess.output, coprocess.input.take()?),
+        None => return None,
+    };
+    shell.variables.set_array(&["global::", name].concat(), array![output.to_string()]);
+    Some(input)
+}
+
+fn close(fds: &[RawFd]) {
+    for &fd in fds {
+        let _ = sys::close(fd);
+    }
+}
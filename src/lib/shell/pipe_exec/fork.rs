@@ -8,9 +8,19 @@ use super::{
     super::{
         job::{JobKind, RefinedJob}, status::*, Shell,
     },
-    job_control::{JobControl, ProcessState}, pipe,
+    job_control::{JobControl, ProcessState}, pipe, streams::redir,
 };
-use std::process::exit;
+use std::{
+    fs::File, os::unix::io::AsRawFd, process::exit,
+};
+
+/// Prepares a child fork of the shell to execute commands in the background.
+pub(crate) fn prepare_background(shell: &mut Shell) {
+    shell.is_background_shell = true;
+    let _ = sys::reset_signal(sys::SIGINT);
+    let _ = sys::reset_signal(sys::SIGHUP);
+    let _ = sys::reset_signal(sys::SIGTERM);
+}
 
 /// Forks the shell, adding the child to the parent's background list, and executing
 /// the given commands in the child fork.
@@ -22,11 +32,15 @@ pub(crate) fn fork_pipe(
 ) -> i32 {
     match unsafe { sys::fork() } {
         Ok(0) => {
-            shell.is_background_shell = true;
-            let _ = sys::reset_signal(sys::SIGINT);
-            let _ = sys::reset_signal(sys::SIGHUP);
-            let _ = sys::reset_signal(sys::SIGTERM);
-            let _ = sys::close(sys::STDIN_FILENO);
+            prepare_background(shell);
+            // Background jobs read from the null device, rather than from a closed stdin, so
+            // that builtins, functions and blocks may still back up the standard streams.
+            match File::open(sys::NULL_PATH) {
+                Ok(null) => redir(null.as_raw_fd(), sys::STDIN_FILENO),
+                Err(_) => {
+                    let _ = sys::close(sys::STDIN_FILENO);
+                }
+            }
 
             // This ensures that the child fork has a unique PGID.
             create_process_group(0);
@@ -1,5 +1,5 @@
 use super::{
-    super::{signals, status::*, Shell}, foreground::BackgroundResult,
+    super::{signals, status::*, Shell}, coprocess, foreground::BackgroundResult,
 };
 use std::{
     fmt, process, sync::{Arc, Mutex}, thread::{sleep, spawn}, time::Duration,
@@ -29,7 +29,15 @@ pub trait JobControl {
     fn resume_stopped(&mut self);
     fn handle_signal(&self, signal: i32) -> bool;
     fn background_send(&self, signal: i32);
-    fn watch_foreground(&mut self, pid: i32, command: &str) -> i32;
+    /// Waits for the foreground process group `pid` to exit or stop. The exit status of each
+    /// process is recorded within `statuses`, which pairs the processes of a pipeline with
+    /// their statuses.
+    fn watch_foreground(
+        &mut self,
+        pid: i32,
+        command: &str,
+        statuses: &mut [(u32, Option<i32>)],
+    ) -> i32;
     fn send_to_background(&mut self, child: u32, state: ProcessState, command: String);
 }
 
@@ -51,6 +59,13 @@ impl fmt::Display for ProcessState {
     }
 }
 
+/// Records the exit status of the process `pid`, if it is an element of a pipeline.
+pub(crate) fn record_status(statuses: &mut [(u32, Option<i32>)], pid: u32, status: i32) {
+    if let Some(entry) = statuses.iter_mut().find(|entry| entry.0 == pid) {
+        entry.1 = Some(status);
+    }
+}
+
 pub(crate) fn add_to_background(
     processes: Arc<Mutex<Vec<BackgroundProcess>>>,
     pid: u32,
@@ -154,29 +169,39 @@ impl JobControl for Shell {
         }
     }
 
-    fn watch_foreground(&mut self, pid: i32, command: &str) -> i32 {
-        self_sys::watch_foreground(self, pid, command)
+    fn watch_foreground(
+        &mut self,
+        pid: i32,
+        command: &str,
+        statuses: &mut [(u32, Option<i32>)],
+    ) -> i32 {
+        self_sys::watch_foreground(self, pid, command, statuses)
     }
 
     /// Waits until all running background tasks have completed, and listens for signals in the
-    /// event that a signal is sent to kill the running tasks.
+    /// event that a signal is sent to kill the running tasks. The inputs of coprocesses are
+    /// closed beforehand, so that they may finish.
     fn wait_for_background(&mut self) {
+        coprocess::close_inputs(self);
         let sigcode;
         'event: loop {
-            for process in self.background.lock().unwrap().iter() {
-                if let ProcessState::Running = process.state {
-                    while let Some(signal) = self.next_signal() {
-                        if signal != sys::SIGTSTP {
-                            self.background_send(signal);
-                            sigcode = get_signal_code(signal);
-                            break 'event;
-                        }
-                    }
-                    sleep(Duration::from_millis(100));
-                    continue 'event;
+            let running = self.background
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|process| process.state == ProcessState::Running);
+            if !running {
+                return;
+            }
+
+            while let Some(signal) = self.next_signal() {
+                if signal != sys::SIGTSTP {
+                    self.background_send(signal);
+                    sigcode = get_signal_code(signal);
+                    break 'event;
                 }
             }
-            return;
+            sleep(Duration::from_millis(100));
         }
         self.exit(sigcode);
     }
@@ -206,3 +231,17 @@ impl JobControl for Shell {
         status
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses() {
+        let mut statuses = [(10, None), (11, None), (0, Some(NO_SUCH_COMMAND))];
+        record_status(&mut statuses, 11, FAILURE);
+        // Processes which aren't elements of the pipeline are ignored.
+        record_status(&mut statuses, 12, SUCCESS);
+        assert_eq!([(10, None), (11, Some(FAILURE)), (0, Some(NO_SUCH_COMMAND))], statuses);
+    }
+}
@@ -5,21 +5,27 @@
 //! the background, handling pipeline and conditional operators, and
 //! std{in,out,err} redirections.
 
+pub(crate) mod coprocess;
 pub mod foreground;
 mod fork;
 pub mod job_control;
 pub mod streams;
+pub(crate) mod substitution;
 
 use self::{
     fork::fork_pipe, job_control::{JobControl, ProcessState},
-    streams::{duplicate_streams, redir, redirect_streams},
+    streams::{
+        backup_descriptors, duplicate_streams, redir, redirect_descriptors, redirect_streams,
+        restore_descriptors,
+    },
 };
 use super::{
-    flags::*, flow_control::FunctionError, fork_function::command_not_found,
-    job::{RefinedJob, TeeItem}, signals::{self, SignalHandler}, status::*, JobKind, Shell,
+    flags::*, flow_control::FunctionError, fork::Capture, fork_function::command_not_found,
+    job::{RefinedJob, TeeItem}, profiler, signals::{self, SignalHandler}, status::*, FlowLogic,
+    JobKind, Shell,
 };
 use builtins::{self, BuiltinFunction};
-use parser::pipelines::{Input, PipeItem, Pipeline, RedirectFrom, Redirection};
+use parser::pipelines::{FdRedirection, Input, PipeItem, Pipeline, RedirectFrom, Redirection};
 use smallvec::SmallVec;
 use std::{
     fs::{File, OpenOptions}, io::{self, Error, Write}, iter,
@@ -27,7 +33,7 @@ use std::{
 };
 use sys;
 
-type RefinedItem = (RefinedJob, JobKind, Vec<Redirection>, Vec<Input>);
+type RefinedItem = (RefinedJob, JobKind, Vec<Redirection>, Vec<Input>, Vec<FdRedirection>);
 
 /// Create an OS pipe and write the contents of a byte slice to one end
 /// such that reading from this pipe will produce the byte slice. Return
@@ -49,17 +55,10 @@ pub unsafe fn stdin_of<T: AsRef<[u8]>>(input: T) -> Result<RawFd, Error> {
 /// 1. If the result is `Some`, then we will fork the pipeline executing into the background.
 /// 2. The value stored within `Some` will be that background job's command name.
 /// 3. If `set -x` was set, print the command.
-fn gen_background_string(pipeline: &Pipeline, print_comm: bool) -> Option<(String, bool)> {
+fn gen_background_string(pipeline: &Pipeline) -> Option<(String, bool)> {
     let last = &pipeline.items[pipeline.items.len() - 1];
     if last.job.kind == JobKind::Background || last.job.kind == JobKind::Disown {
-        let command = pipeline.to_string();
-        if print_comm {
-            eprintln!("> {}", command);
-        }
-        Some((command, last.job.kind == JobKind::Disown))
-    } else if print_comm {
-        eprintln!("> {}", pipeline.to_string());
-        None
+        Some((pipeline.to_string(), last.job.kind == JobKind::Disown))
     } else {
         None
     }
@@ -81,6 +80,18 @@ fn is_implicit_cd(argument: &str) -> bool {
 fn do_redirection(
     piped_commands: SmallVec<[RefinedItem; 16]>,
 ) -> Option<SmallVec<[(RefinedJob, JobKind); 16]>> {
+    macro_rules! here_string {
+        ($string:expr) => {
+            match unsafe { stdin_of(&$string) } {
+                Ok(stdio) => Some(unsafe { File::from_raw_fd(stdio) }),
+                Err(e) => {
+                    eprintln!("ion: failed to redirect herestring '{}' to stdin: {}", $string, e);
+                    None
+                }
+            }
+        };
+    }
+
     macro_rules! get_infile {
         ($input:expr) => {
             match $input {
@@ -95,17 +106,10 @@ fn do_redirection(
                     if !string.ends_with('\n') {
                         string.push('\n');
                     }
-                    match unsafe { stdin_of(&string) } {
-                        Ok(stdio) => Some(unsafe { File::from_raw_fd(stdio) }),
-                        Err(e) => {
-                            eprintln!(
-                                "ion: failed to redirect herestring '{}' to stdin: {}",
-                                string, e
-                            );
-                            None
-                        }
-                    }
+                    here_string!(string)
                 }
+                // The bodies of heredocs are already terminated, unless they are empty.
+                Input::HereDoc { ref body, .. } => here_string!(body),
             }
         };
     }
@@ -239,7 +243,9 @@ fn do_redirection(
     // Real logic begins here
     let mut new_commands = SmallVec::new();
     let mut prev_kind = JobKind::Last;
-    for (mut job, kind, outputs, mut inputs) in piped_commands {
+    for (mut job, kind, outputs, mut inputs, descriptors) in piped_commands {
+        // Numbered descriptors are redirected after the standard streams have been.
+        job.descriptors(descriptors);
         match (inputs.len(), prev_kind) {
             (0, _) => {}
             (1, JobKind::Pipe(_)) => {
@@ -362,8 +368,14 @@ pub(crate) trait PipelineExecution {
     ) -> Result<SmallVec<[RefinedItem; 16]>, i32>;
 
     /// Waits for all of the children of the assigned pgid to finish executing, returning the
-    /// exit status of the last process in the queue.
-    fn wait(&mut self, pgid: u32, commands: SmallVec<[RefinedJob; 16]>) -> i32;
+    /// exit status of the last process in the queue. The status of each child is recorded
+    /// within `statuses`.
+    fn wait(
+        &mut self,
+        pgid: u32,
+        commands: SmallVec<[RefinedJob; 16]>,
+        statuses: &mut [(u32, Option<i32>)],
+    ) -> i32;
 
     /// Executes a `RefinedJob` that was created in the `generate_commands` method.
     ///
@@ -377,6 +389,7 @@ pub(crate) trait PipelineExecution {
     /// * `name`: Name of the builtin to execute.
     /// * `stdin`, `stdout`, `stderr`: File descriptors that will replace the
     ///    respective standard streams if they are not `None`
+    /// * `descriptors`: Redirections of numbered descriptors, applied after the standard streams
     /// # Preconditions
     /// * `shell.builtins.contains_key(name)`; otherwise this function will panic
     fn exec_builtin(
@@ -386,6 +399,7 @@ pub(crate) trait PipelineExecution {
         stdout: &Option<File>,
         stderr: &Option<File>,
         stdin: &Option<File>,
+        descriptors: &[FdRedirection],
     ) -> i32;
 
     fn exec_external<'a, S: AsRef<str>>(
@@ -395,6 +409,7 @@ pub(crate) trait PipelineExecution {
         stdout: &Option<File>,
         stderr: &Option<File>,
         stdin: &Option<File>,
+        descriptors: &[FdRedirection],
     ) -> i32;
 
     fn exec_function<S: AsRef<str>>(
@@ -404,6 +419,19 @@ pub(crate) trait PipelineExecution {
         stdout: &Option<File>,
         stderr: &Option<File>,
         stdin: &Option<File>,
+        descriptors: &[FdRedirection],
+    ) -> i32;
+
+    /// Executes the body of a subshell, within a fork of the shell, or of a group, within the
+    /// shell itself.
+    fn exec_block(
+        &mut self,
+        body: &str,
+        subshell: bool,
+        stdout: &Option<File>,
+        stderr: &Option<File>,
+        stdin: &Option<File>,
+        descriptors: &[FdRedirection],
     ) -> i32;
 
     /// For cat jobs
@@ -433,6 +461,7 @@ impl PipelineExecution for Shell {
         stdin: &Option<File>,
         stdout: &Option<File>,
         stderr: &Option<File>,
+        descriptors: &[FdRedirection],
     ) -> i32 {
         let result = sys::fork_and_exec(
             name,
@@ -453,7 +482,12 @@ impl PipelineExecution for Shell {
                 None
             },
             false,
-            || prepare_child(true, 0),
+            || {
+                prepare_child(true, 0);
+                if !redirect_descriptors(descriptors) {
+                    sys::fork_exit(FAILURE);
+                }
+            },
         );
 
         match result {
@@ -462,7 +496,7 @@ impl PipelineExecution for Shell {
                 let _ = sys::tcsetpgrp(0, pid);
                 let _ = sys::wait_for_interrupt(pid);
                 let _ = sys::kill(pid, sys::SIGCONT);
-                self.watch_foreground(-(pid as i32), "")
+                self.watch_foreground(-(pid as i32), "", &mut [])
             }
             Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
                 if !command_not_found(self, &name) {
@@ -573,6 +607,7 @@ impl PipelineExecution for Shell {
         stdout: &Option<File>,
         stderr: &Option<File>,
         stdin: &Option<File>,
+        descriptors: &[FdRedirection],
     ) -> i32 {
         if let Some(ref file) = *stdin {
             redir(file.as_raw_fd(), sys::STDIN_FILENO);
@@ -583,6 +618,9 @@ impl PipelineExecution for Shell {
         if let Some(ref file) = *stderr {
             redir(file.as_raw_fd(), sys::STDERR_FILENO);
         }
+        if !redirect_descriptors(descriptors) {
+            return FAILURE;
+        }
 
         let function = self.functions.get(name).cloned().unwrap();
         match function.execute(self, args) {
@@ -601,6 +639,48 @@ impl PipelineExecution for Shell {
         }
     }
 
+    fn exec_block(
+        &mut self,
+        body: &str,
+        subshell: bool,
+        stdout: &Option<File>,
+        stderr: &Option<File>,
+        stdin: &Option<File>,
+        descriptors: &[FdRedirection],
+    ) -> i32 {
+        if let Some(ref file) = *stdin {
+            redir(file.as_raw_fd(), sys::STDIN_FILENO);
+        }
+        if let Some(ref file) = *stdout {
+            redir(file.as_raw_fd(), sys::STDOUT_FILENO);
+        }
+        if let Some(ref file) = *stderr {
+            redir(file.as_raw_fd(), sys::STDERR_FILENO);
+        }
+        if !redirect_descriptors(descriptors) {
+            return FAILURE;
+        }
+
+        if !subshell {
+            self.on_command(body);
+            return self.previous_status;
+        }
+
+        let status = match self.fork(Capture::None, |shell| shell.on_command(body)) {
+            Ok(result) => result.status as i32,
+            Err(why) => {
+                eprintln!("ion: subshell error: {}", why);
+                FAILURE
+            }
+        };
+        // Ensure that the shell retains ownership of the terminal after the subshell exits,
+        // unless the shell is in the background or is a library, and so never held it.
+        if !self.is_background_shell && !self.is_library {
+            let _ = sys::tcsetpgrp(sys::STDIN_FILENO, process::id());
+        }
+        status
+    }
+
     fn exec_builtin(
         &mut self,
         main: BuiltinFunction,
@@ -608,6 +688,7 @@ impl PipelineExecution for Shell {
         stdout: &Option<File>,
         stderr: &Option<File>,
         stdin: &Option<File>,
+        descriptors: &[FdRedirection],
     ) -> i32 {
         if let Some(ref file) = *stdin {
             redir(file.as_raw_fd(), sys::STDIN_FILENO);
@@ -618,15 +699,20 @@ impl PipelineExecution for Shell {
         if let Some(ref file) = *stderr {
             redir(file.as_raw_fd(), sys::STDERR_FILENO);
         }
+        if !redirect_descriptors(descriptors) {
+            return FAILURE;
+        }
 
         main(args, self)
     }
 
     fn exec_job(&mut self, job: &mut RefinedJob, _foreground: bool) -> i32 {
         // Duplicate file descriptors, execute command, and redirect back.
-        fn duplicate<F: FnMut() -> i32>(long: &str, mut func: F) -> i32 {
-            if let Ok((stdin_bk, stdout_bk, stderr_bk)) = duplicate_streams() {
+        fn duplicate<F: FnMut() -> i32>(long: &str, fds: &[RawFd], mut func: F) -> i32 {
+            if let Ok((stdin_bk, stdout_bk, stderr_bk)) = duplicate_streams(fds) {
+                let backups = backup_descriptors(fds);
                 let code = func();
+                restore_descriptors(backups);
                 redirect_streams(stdin_bk, stdout_bk, stderr_bk);
                 return code;
             }
@@ -639,10 +725,19 @@ impl PipelineExecution for Shell {
             COULD_NOT_EXEC
         }
 
-        duplicate(&job.long(), move || job.exec(self))
+        let fds = job.redirected_descriptors();
+        let assignments = self.variables.assign_temporarily(job.assignments());
+        let status = duplicate(&job.long(), &fds, || job.exec(self));
+        self.variables.restore(assignments);
+        status
     }
 
-    fn wait(&mut self, pgid: u32, commands: SmallVec<[RefinedJob; 16]>) -> i32 {
+    fn wait(
+        &mut self,
+        pgid: u32,
+        commands: SmallVec<[RefinedJob; 16]>,
+        statuses: &mut [(u32, Option<i32>)],
+    ) -> i32 {
         // TODO: Find a way to only do this when absolutely necessary.
         let as_string = commands
             .iter()
@@ -651,7 +746,7 @@ impl PipelineExecution for Shell {
             .join(" | ");
 
         // Watch the foreground group, dropping all commands that exit as they exit.
-        self.watch_foreground(-(pgid as i32), &as_string)
+        self.watch_foreground(-(pgid as i32), &as_string, statuses)
     }
 
     fn generate_commands(
@@ -664,9 +759,12 @@ impl PipelineExecution for Shell {
                 mut job,
                 outputs,
                 inputs,
+                descriptors,
             } = item;
-            let refined = {
-                if is_implicit_cd(&job.args[0]) {
+            let mut refined = {
+                if let Some((body, subshell)) = job.block() {
+                    RefinedJob::block(body.into(), subshell)
+                } else if is_implicit_cd(&job.args[0]) {
                     RefinedJob::builtin(
                         builtins::builtin_cd,
                         iter::once("cd".into()).chain(job.args.drain()).collect(),
@@ -679,7 +777,8 @@ impl PipelineExecution for Shell {
                     RefinedJob::external(job.args[0].clone().into(), job.args.drain().collect())
                 }
             };
-            results.push((refined, job.kind, outputs, inputs));
+            refined.environment(job.env);
+            results.push((refined, job.kind, outputs, inputs, descriptors));
         }
 
         Ok(results)
@@ -688,8 +787,7 @@ impl PipelineExecution for Shell {
     fn execute_pipeline(&mut self, pipeline: &mut Pipeline) -> i32 {
         // If the supplied pipeline is a background, a string representing the command
         // and a boolean representing whether it should be disowned is stored here.
-        let possible_background_name =
-            gen_background_string(&pipeline, self.flags & PRINT_COMMS != 0);
+        let possible_background_name = gen_background_string(&pipeline);
         // Generates commands for execution, differentiating between external and
         // builtin commands.
         let piped_commands = match self.generate_commands(pipeline) {
@@ -723,8 +821,10 @@ impl PipelineExecution for Shell {
             // While active, the SIGTTOU signal will be ignored.
             let _sig_ignore = SignalHandler::new();
             let foreground = !self.is_background_shell;
+            let timer = profiler::start_pipeline(self, &piped_commands);
             // Execute each command in the pipeline, giving each command the foreground.
             let exit_status = pipe(self, piped_commands, foreground);
+            profiler::end_pipeline(self, timer);
             // Set the shell as the foreground process again to regain the TTY.
             if foreground && !self.is_library {
                 let _ = sys::tcsetpgrp(0, process::id());
@@ -753,6 +853,7 @@ pub(crate) fn pipe(
                     // We need to remember the commands as they own the file
                     // descriptors that are created by sys::pipe.
                     let remember: SmallVec<[RefinedJob; 16]> = SmallVec::new();
+                    let mut statuses: SmallVec<[(u32, Option<i32>); 16]> = SmallVec::new();
                     let mut block_child = true;
                     let (mut pgid, mut last_pid, mut current_pid) = (0, 0, 0);
 
@@ -841,6 +942,7 @@ pub(crate) fn pipe(
                             }
                         }
 
+                        let previous_pid = current_pid;
                         match spawn_proc(
                             shell,
                             parent,
@@ -853,10 +955,13 @@ pub(crate) fn pipe(
                             SUCCESS => (),
                             error_code => return error_code,
                         }
+                        statuses.push(pipe_element(previous_pid, current_pid));
 
                         possible_external_stdio_pipes = None;
 
-                        if set_process_group(&mut pgid, current_pid)
+                        // The group is led by the first element that could be spawned.
+                        if current_pid != previous_pid
+                            && set_process_group(&mut pgid, current_pid)
                             && foreground
                             && !shell.is_library
                         {
@@ -871,6 +976,7 @@ pub(crate) fn pipe(
                         } else {
                             kind = ckind;
                             block_child = false;
+                            let previous_pid = current_pid;
                             match spawn_proc(
                                 shell,
                                 child,
@@ -883,13 +989,23 @@ pub(crate) fn pipe(
                                 SUCCESS => (),
                                 error_code => return error_code,
                             }
+                            statuses.push(pipe_element(previous_pid, current_pid));
+                            if pgid == 0 {
+                                // The last element was placed within its own group, as none of
+                                // the elements before it could be spawned.
+                                pgid = current_pid;
+                            }
 
                             resume_prior_process(&mut last_pid, current_pid);
                             break;
                         }
                     }
 
-                    previous_status = shell.wait(pgid, remember);
+                    previous_status = if pgid == 0 {
+                        NO_SUCH_COMMAND
+                    } else {
+                        shell.wait(pgid, remember, &mut statuses)
+                    };
                     let _ = io::stdout().flush();
                     let _ = io::stderr().flush();
                     if previous_status == TERMINATED {
@@ -898,9 +1014,18 @@ pub(crate) fn pipe(
                         }
                         return previous_status;
                     }
+
+                    // Elements which have not exited, as the pipeline was stopped, are given
+                    // the status that the pipeline was stopped with.
+                    let statuses = statuses
+                        .iter()
+                        .map(|&(_, status)| status.unwrap_or(previous_status))
+                        .collect::<SmallVec<[i32; 16]>>();
+                    previous_status = pipe_status(shell, &statuses);
                 }
                 _ => {
                     previous_status = shell.exec_job(&mut parent, foreground);
+                    pipe_status(shell, &[previous_status]);
                     let _ = io::stdout().flush();
                     let _ = io::stderr().flush();
                 }
@@ -912,6 +1037,30 @@ pub(crate) fn pipe(
     previous_status
 }
 
+/// Assigns the status of each element of a pipeline to the `PIPESTATUS` array, and returns the
+/// status of the pipeline, which is that of its last element. With `pipefail` set, the status
+/// of the pipeline is instead that of the last element to have failed, if any did.
+pub(crate) fn pipe_status(shell: &mut Shell, statuses: &[i32]) -> i32 {
+    let array = statuses.iter().map(|status| status.to_string()).collect();
+    shell.variables.set_array("PIPESTATUS", array);
+    if shell.flags & PIPE_FAIL != 0 {
+        statuses.iter().rev().find(|&&status| status != SUCCESS).cloned().unwrap_or(SUCCESS)
+    } else {
+        statuses.last().cloned().unwrap_or(SUCCESS)
+    }
+}
+
+/// Pairs the process that was spawned for an element of a pipeline with its exit status, which
+/// is unknown until it has been waited upon. If `spawn_proc` did not yield a new process, the
+/// element could not be executed.
+fn pipe_element(previous_pid: u32, pid: u32) -> (u32, Option<i32>) {
+    if pid == previous_pid {
+        (0, Some(NO_SUCH_COMMAND))
+    } else {
+        (pid, None)
+    }
+}
+
 fn spawn_proc(
     shell: &mut Shell,
     mut cmd: RefinedJob,
@@ -922,6 +1071,8 @@ fn spawn_proc(
     pgid: u32,
 ) -> i32 {
     let short = cmd.short();
+    // Children inherit the assignments, which the shell then reverts once they are spawned.
+    let assignments = shell.variables.assign_temporarily(cmd.assignments());
     match cmd {
         RefinedJob::External {
             ref name,
@@ -929,6 +1080,8 @@ fn spawn_proc(
             ref stdout,
             ref stderr,
             ref stdin,
+            ref descriptors,
+            ..
         } => {
             let args: Vec<&str> = args.iter().skip(1).map(|x| x as &str).collect();
             let result = sys::fork_and_exec(
@@ -950,7 +1103,12 @@ fn spawn_proc(
                     None
                 },
                 false,
-                || prepare_child(block_child, pgid),
+                || {
+                    prepare_child(block_child, pgid);
+                    if !redirect_descriptors(descriptors) {
+                        sys::fork_exit(FAILURE);
+                    }
+                },
             );
 
             match result {
@@ -974,11 +1132,14 @@ fn spawn_proc(
             ref stdout,
             ref stderr,
             ref stdin,
+            ref descriptors,
+            ..
         } => {
             match unsafe { sys::fork() } {
                 Ok(0) => {
                     prepare_child(block_child, pgid);
-                    let ret = shell.exec_builtin(main, &args, stdout, stderr, stdin);
+                    let ret =
+                        shell.exec_builtin(main, &args, stdout, stderr, stdin, descriptors);
                     close(stdout);
                     close(stderr);
                     close(stdin);
@@ -1002,11 +1163,44 @@ fn spawn_proc(
             ref stdout,
             ref stderr,
             ref stdin,
+            ref descriptors,
+            ..
         } => {
             match unsafe { sys::fork() } {
                 Ok(0) => {
                     prepare_child(block_child, pgid);
-                    let ret = shell.exec_function(name, &args, stdout, stderr, stdin);
+                    let ret =
+                        shell.exec_function(name, &args, stdout, stderr, stdin, descriptors);
+                    close(stdout);
+                    close(stderr);
+                    close(stdin);
+                    exit(ret)
+                }
+                Ok(pid) => {
+                    close(stdin);
+                    close(stdout);
+                    close(stderr);
+                    *last_pid = *current_pid;
+                    *current_pid = pid;
+                }
+                Err(e) => {
+                    eprintln!("ion: failed to fork {}: {}", short, e);
+                }
+            }
+        }
+        RefinedJob::Block {
+            ref body,
+            ref stdout,
+            ref stderr,
+            ref stdin,
+            ref descriptors,
+            ..
+        } => {
+            match unsafe { sys::fork() } {
+                Ok(0) => {
+                    prepare_child(block_child, pgid);
+                    // As the child is already a fork of the shell, a subshell need not fork again.
+                    let ret = shell.exec_block(body, false, stdout, stderr, stdin, descriptors);
                     close(stdout);
                     close(stderr);
                     close(stdin);
@@ -1070,6 +1264,7 @@ fn spawn_proc(
             Err(e) => eprintln!("ion: failed to fork {}: {}", short, e),
         },
     }
+    shell.variables.restore(assignments);
     SUCCESS
 }
 
@@ -1082,7 +1277,9 @@ fn close(file: &Option<File>) {
     }
 }
 
-fn prepare_child(block_child: bool, pgid: u32) {
+/// Prepares a forked child of the shell to execute an element of a pipeline. The signals which
+/// the shell blocked are unblocked, and the child joins the given process group.
+pub(crate) fn prepare_child(block_child: bool, pgid: u32) {
     signals::unblock();
     let _ = sys::reset_signal(sys::SIGINT);
     let _ = sys::reset_signal(sys::SIGHUP);
@@ -1115,3 +1312,27 @@ fn set_process_group(pgid: &mut u32, pid: u32) -> bool {
     let _ = sys::setpgid(pid, *pgid);
     pgid_set
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell::ShellBuilder;
+    use types::Array;
+
+    #[test]
+    fn pipeline_statuses() {
+        let mut shell = ShellBuilder::new().as_library();
+        assert_eq!(SUCCESS, pipe_status(&mut shell, &[2, FAILURE, SUCCESS]));
+        assert_eq!(Some(&array!["2", "1", "0"]), shell.variables.get_array("PIPESTATUS"));
+
+        // With pipefail, the last element to have failed determines the status.
+        shell.flags |= PIPE_FAIL;
+        assert_eq!(FAILURE, pipe_status(&mut shell, &[2, FAILURE, SUCCESS]));
+        assert_eq!(SUCCESS, pipe_status(&mut shell, &[SUCCESS, SUCCESS]));
+        assert_eq!(Some(&array!["0", "0"]), shell.variables.get_array("PIPESTATUS"));
+
+        // An element which could not be spawned did not yield a process.
+        assert_eq!((0, Some(NO_SUCH_COMMAND)), pipe_element(10, 10));
+        assert_eq!((11, None), pipe_element(10, 11));
+    }
+}
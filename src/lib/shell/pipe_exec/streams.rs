@@ -1,5 +1,8 @@
+use super::stdin_of;
+use parser::pipelines::{FdRedirection, FileMode, Input, PipeItem};
 use std::{
-    fs::File, io, os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    fs::{File, OpenOptions}, io::{self, Write},
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
 };
 use sys;
 
@@ -10,22 +13,38 @@ pub(crate) fn redir(old: RawFd, new: RawFd) {
     }
 }
 
+/// Backups of descriptors are placed at or above this descriptor, as is the case with other
+/// shells, so that the descriptors which scripts tend to use remain free.
+const BACKUP_FLOOR: RawFd = 10;
+
+/// Duplicates `fd` onto a descriptor above `BACKUP_FLOOR` which is not among those that are
+/// `reserved`, so that the duplicate will not be clobbered when those are redirected.
+fn dup_unreserved(fd: RawFd, reserved: &[RawFd]) -> io::Result<RawFd> {
+    let mut rejected = Vec::new();
+    let result = loop {
+        match sys::dup(fd) {
+            Ok(duplicate) if duplicate < BACKUP_FLOOR || reserved.contains(&duplicate) => {
+                rejected.push(duplicate)
+            }
+            result => break result,
+        }
+    };
+    for fd in rejected {
+        let _ = sys::close(fd);
+    }
+    result
+}
+
 /// Duplicates STDIN, STDOUT, and STDERR; in that order; and returns them as `File`s.
 /// Why, you ask? A simple safety mechanism to ensure that the duplicated FDs are closed
-/// when dropped.
-pub(crate) fn duplicate_streams() -> io::Result<(File, File, File)> {
-    // Duplicates STDIN and converts it into a `File`.
-    sys::dup(sys::STDIN_FILENO).map(|fd| unsafe { File::from_raw_fd(fd) })
-        // Do the same for stdout, and then meld the result with stdin
-        .and_then(|stdin| sys::dup(sys::STDOUT_FILENO)
-            .map(|fd| unsafe { File::from_raw_fd(fd) })
-            .map(|stdout| (stdin, stdout))
-        )
-        // And then meld stderr alongside stdin and stdout
-        .and_then(|(stdin, stdout)| sys::dup(sys::STDERR_FILENO)
-            .map(|fd| unsafe { File::from_raw_fd(fd) })
-            .map(|stderr| (stdin, stdout, stderr))
-        )
+/// when dropped. None of the duplicates will be placed upon a `reserved` descriptor.
+pub(crate) fn duplicate_streams(reserved: &[RawFd]) -> io::Result<(File, File, File)> {
+    let duplicate = |fd| dup_unreserved(fd, reserved).map(|fd| unsafe { File::from_raw_fd(fd) });
+    Ok((
+        duplicate(sys::STDIN_FILENO)?,
+        duplicate(sys::STDOUT_FILENO)?,
+        duplicate(sys::STDERR_FILENO)?,
+    ))
 }
 
 pub(crate) fn redirect_streams(inp: File, out: File, err: File) {
@@ -33,3 +52,155 @@ pub(crate) fn redirect_streams(inp: File, out: File, err: File) {
     redir(out.as_raw_fd(), sys::STDOUT_FILENO);
     redir(err.as_raw_fd(), sys::STDERR_FILENO);
 }
+
+/// Backs up each of the given descriptors that lie above the standard streams, so that they
+/// may be restored by `restore_descriptors`. Descriptors which were not open are recorded as
+/// `None`, and will be closed once restored.
+pub(crate) fn backup_descriptors(fds: &[RawFd]) -> Vec<(RawFd, Option<File>)> {
+    let mut backups: Vec<(RawFd, Option<File>)> = Vec::new();
+    for &fd in fds {
+        if fd > sys::STDERR_FILENO && !backups.iter().any(|&(backup, _)| backup == fd) {
+            let file = dup_unreserved(fd, fds).ok().map(|fd| unsafe { File::from_raw_fd(fd) });
+            backups.push((fd, file));
+        }
+    }
+    backups
+}
+
+pub(crate) fn restore_descriptors(backups: Vec<(RawFd, Option<File>)>) {
+    for (fd, backup) in backups {
+        match backup {
+            Some(file) => redir(file.as_raw_fd(), fd),
+            None => {
+                let _ = sys::close(fd);
+            }
+        }
+    }
+}
+
+/// Applies each of the numbered descriptor redirections, in order, to this process. Returns
+/// `false` if any of the redirections failed.
+pub(crate) fn redirect_descriptors(redirections: &[FdRedirection]) -> bool {
+    for redirection in redirections {
+        let result = match *redirection {
+            FdRedirection::Open { fd, ref file, mode } => open(file, mode).and_then(|opened| {
+                let opened = opened.into_raw_fd();
+                // Files are opened with close-on-exec set, which a duplicate will not inherit.
+                let source = if opened == fd { sys::dup(fd)? } else { opened };
+                let result = sys::dup2(source, fd);
+                let _ = sys::close(source);
+                result
+            }),
+            FdRedirection::Duplicate { fd, source } => sys::dup2(source, fd),
+            FdRedirection::DuplicateWord { fd, ref source } => match source.parse::<RawFd>() {
+                Ok(source) => sys::dup2(source, fd),
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("'{}' is not a file descriptor", source),
+                )),
+            },
+            FdRedirection::Close(fd) => {
+                let _ = sys::close(fd);
+                Ok(fd)
+            }
+        };
+
+        if let Err(why) = result {
+            match *redirection {
+                FdRedirection::Open { fd, ref file, .. } => {
+                    eprintln!("ion: failed to redirect '{}' to descriptor {}: {}", file, fd, why)
+                }
+                _ => eprintln!("ion: failed to redirect descriptor {}: {}", redirection.fd(), why),
+            }
+            return false;
+        }
+    }
+    true
+}
+
+/// The streams and descriptors of the shell which were replaced by `redirect_shell`.
+pub(crate) struct Backup {
+    streams:     (File, File, File),
+    descriptors: Vec<(RawFd, Option<File>)>,
+}
+
+impl Backup {
+    pub(crate) fn restore(self) {
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        restore_descriptors(self.descriptors);
+        let (stdin, stdout, stderr) = self.streams;
+        redirect_streams(stdin, stdout, stderr);
+    }
+}
+
+/// Applies the redirections of an item to the shell itself, such as those which follow the
+/// `end` of a block, so that every command executed by the shell will inherit them. Unlike the
+/// redirections of a command, these are applied in order, and are never teed.
+pub(crate) fn redirect_shell(item: &PipeItem) -> Option<Backup> {
+    let mut redirections = Vec::new();
+    let mut temporaries = Vec::new();
+    for input in &item.inputs {
+        let body = match *input {
+            Input::File(ref file) => {
+                redirections.push(FdRedirection::Open {
+                    fd:   sys::STDIN_FILENO,
+                    file: file.clone(),
+                    mode: FileMode::Read,
+                });
+                continue;
+            }
+            Input::HereString(ref string) if !string.ends_with('\n') => [string, "\n"].concat(),
+            Input::HereString(ref body) | Input::HereDoc { ref body, .. } => body.clone(),
+        };
+        match unsafe { stdin_of(&body) } {
+            Ok(source) => {
+                temporaries.push(source);
+                redirections.push(FdRedirection::Duplicate { fd: sys::STDIN_FILENO, source });
+            }
+            Err(why) => {
+                eprintln!("ion: failed to redirect herestring '{}' to stdin: {}", body, why);
+                temporaries.into_iter().for_each(|fd| drop(sys::close(fd)));
+                return None;
+            }
+        }
+    }
+    for output in &item.outputs {
+        redirections.extend(output.descriptors());
+    }
+    redirections.extend(item.descriptors.iter().cloned());
+
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    let reserved = redirections.iter().map(FdRedirection::fd).collect::<Vec<RawFd>>();
+    let backup = duplicate_streams(&reserved).map(|streams| Backup {
+        streams,
+        descriptors: backup_descriptors(&reserved),
+    });
+    let redirected = backup.is_ok() && redirect_descriptors(&redirections);
+    temporaries.into_iter().for_each(|fd| drop(sys::close(fd)));
+
+    match backup {
+        Ok(backup) => if redirected {
+            Some(backup)
+        } else {
+            backup.restore();
+            None
+        },
+        Err(why) => {
+            eprintln!("ion: failed to `dup` STDOUT, STDIN, or STDERR: {}", why);
+            None
+        }
+    }
+}
+
+fn open(file: &str, mode: FileMode) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    match mode {
+        FileMode::Read => options.read(true),
+        FileMode::Write => options.write(true).create(true).truncate(true),
+        FileMode::Append => options.append(true).create(true),
+        FileMode::ReadWrite => options.read(true).write(true).create(true),
+    };
+    options.open(file)
+}
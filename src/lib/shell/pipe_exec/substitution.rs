@@ -0,0 +1,89 @@
+//! Process substitutions, such as `<(cmd)` and `>(cmd)`, execute a command within a child of the
+//! shell that is connected to a pipe, and the path of that pipe is substituted in its place.
+
+use super::super::{FlowLogic, Shell};
+use std::{io, os::unix::io::RawFd, thread};
+use sys;
+
+/// A child process that was spawned by a process substitution, along with the end of its pipe
+/// which the shell holds open until the enclosing pipeline has completed.
+#[derive(Debug)]
+pub(crate) struct Substitution {
+    pid: u32,
+    fd:  RawFd,
+}
+
+impl Substitution {
+    /// Forks a child to execute the given command. If `writable` is set, the child will read
+    /// from the substituted path; otherwise, the child will write to it.
+    pub(crate) fn spawn(shell: &Shell, command: &str, writable: bool) -> io::Result<Substitution> {
+        let (read, write) = sys::pipe2(0)?;
+        let (parent, child, target) = if writable {
+            (write, read, sys::STDIN_FILENO)
+        } else {
+            (read, write, sys::STDOUT_FILENO)
+        };
+
+        match unsafe { sys::fork() } {
+            Ok(0) => {
+                sys::signals::unblock();
+                // The pipe may be closed before the child has written to it, once the statement
+                // which spawned it has completed, and so the child exits quietly by default.
+                let _ = sys::reset_signal(sys::SIGPIPE);
+
+                // The pipes of any sibling substitutions must not be held open by this child.
+                let _ = sys::close(parent);
+                for sibling in shell.substitutions.lock().unwrap().iter() {
+                    let _ = sys::close(sibling.fd);
+                }
+                let _ = sys::dup2(child, target);
+                let _ = sys::close(child);
+
+                let mut shell: Shell = unsafe { (shell as *const Shell).read() };
+                let _ = shell.context.take();
+                shell.traps.clear();
+                shell.on_command(command);
+                sys::fork_exit(shell.previous_status);
+            }
+            Ok(pid) => {
+                let _ = sys::close(child);
+                Ok(Substitution { pid, fd: parent })
+            }
+            Err(why) => {
+                let _ = sys::close(parent);
+                let _ = sys::close(child);
+                Err(why)
+            }
+        }
+    }
+
+    /// The path that is substituted in place of the process.
+    pub(crate) fn path(&self) -> String { format!("/dev/fd/{}", self.fd) }
+
+    /// Closes the shell's end of the pipe, and waits for the child to exit if `wait` is set.
+    /// Otherwise, the child is reaped by a background thread once it exits.
+    fn finish(self, wait: bool) {
+        let _ = sys::close(self.fd);
+        let pid = self.pid;
+        if wait {
+            let _ = sys::wait_for_child(pid);
+        } else {
+            let _ = thread::spawn(move || sys::wait_for_child(pid));
+        }
+    }
+}
+
+/// Finishes every substitution that was spawned after the first `from` substitutions, which
+/// is called by the shell once the pipeline or statement that spawned them has completed. A
+/// pipeline in the background will not be waited on.
+pub(crate) fn finish_substitutions(shell: &Shell, from: usize, wait: bool) {
+    let finished = shell
+        .substitutions
+        .lock()
+        .unwrap()
+        .drain(from..)
+        .collect::<Vec<Substitution>>();
+    for substitution in finished {
+        substitution.finish(wait);
+    }
+}
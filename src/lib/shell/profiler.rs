@@ -0,0 +1,361 @@
+//! A profiler for scripts, which records where the time of the shell was spent.
+
+use super::{flow_control::Statement, job::RefinedJob, status::*, JobKind, Shell};
+use fnv::FnvHashMap;
+use std::{
+    fs::File, io::{self, Write}, process, time::{Duration, Instant},
+};
+
+/// The number of times that something was executed, and the time that it took altogether.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Timing {
+    calls: usize,
+    time:  Duration,
+}
+
+impl Timing {
+    fn record(&mut self, time: Duration) {
+        self.calls += 1;
+        self.time += time;
+    }
+}
+
+/// A function which is being called, along with the time spent by what it has called so far.
+struct Frame {
+    name:     String,
+    start:    Instant,
+    children: Duration,
+}
+
+impl Frame {
+    fn new(name: &str) -> Frame {
+        Frame {
+            name:     name.to_owned(),
+            start:    Instant::now(),
+            children: Duration::default(),
+        }
+    }
+}
+
+pub(crate) struct Profiler {
+    script:        String,
+    statements:    FnvHashMap<String, Timing>,
+    functions:     FnvHashMap<String, Timing>,
+    commands:      FnvHashMap<String, Timing>,
+    substitutions: FnvHashMap<String, Timing>,
+    /// The functions which are being called, beginning with the script itself.
+    frames:        Vec<Frame>,
+    /// The time spent within each stack of frames, excluding that of the frames it called.
+    stacks:        FnvHashMap<String, Duration>,
+    /// The file to which the stacks are written, in the folded format of flamegraphs.
+    folded:        Option<String>,
+    /// The process which the profiler belongs to. Forks of the shell are timed by the process
+    /// which waits for them, and don't write reports of their own.
+    pid:           u32,
+}
+
+impl Profiler {
+    pub(crate) fn new(script: &str, folded: Option<String>) -> Profiler {
+        Profiler {
+            script: script.to_owned(),
+            statements: FnvHashMap::default(),
+            functions: FnvHashMap::default(),
+            commands: FnvHashMap::default(),
+            substitutions: FnvHashMap::default(),
+            frames: vec![Frame::new("main")],
+            stacks: FnvHashMap::default(),
+            folded,
+            pid: process::id(),
+        }
+    }
+
+    /// Records that a function is being called.
+    pub(crate) fn enter(&mut self, function: &str) { self.frames.push(Frame::new(function)); }
+
+    /// Records that the function which was called last has returned.
+    pub(crate) fn leave(&mut self) {
+        let elapsed = self.frames.last().map_or(Duration::default(), |f| f.start.elapsed());
+        self.leave_after(elapsed);
+    }
+
+    fn leave_after(&mut self, elapsed: Duration) {
+        let stack = self.stack();
+        let frame = match self.frames.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let own = elapsed.checked_sub(frame.children).unwrap_or_default();
+        *self.stacks.entry(stack).or_insert_with(Duration::default) += own;
+        // The frame of the script itself isn't a function.
+        if let Some(caller) = self.frames.last_mut() {
+            caller.children += elapsed;
+            self.functions.entry(frame.name).or_insert_with(Timing::default).record(elapsed);
+        }
+    }
+
+    /// The names of the frames which are being executed, as they are written to folded stacks.
+    fn stack(&self) -> String {
+        let names = self.frames.iter().map(|frame| frame.name.as_str()).collect::<Vec<&str>>();
+        names.join(";")
+    }
+
+    /// Records time that was spent outside of the shell, by the frame which is being executed.
+    fn leaf(&mut self, name: &str, elapsed: Duration) {
+        // Semicolons separate the frames of a stack, and lines separate the stacks.
+        let name = name.replace(';', ",").replace('\n', " ");
+        let stack = [self.stack(), name].join(";");
+        *self.stacks.entry(stack).or_insert_with(Duration::default) += elapsed;
+        if let Some(frame) = self.frames.last_mut() {
+            frame.children += elapsed;
+        }
+    }
+
+    fn statement(&mut self, statement: String, elapsed: Duration) {
+        self.statements.entry(statement).or_insert_with(Timing::default).record(elapsed);
+    }
+
+    /// Records the time taken by a pipeline, which is shared by each of its external commands.
+    fn pipeline(&mut self, commands: &[String], elapsed: Duration) {
+        for command in commands {
+            self.commands.entry(command.clone()).or_insert_with(Timing::default).record(elapsed);
+        }
+        self.leaf(&commands.join(" | "), elapsed);
+    }
+
+    fn substitution(&mut self, command: &str, elapsed: Duration) {
+        let name = command.trim();
+        self.substitutions.entry(name.into()).or_insert_with(Timing::default).record(elapsed);
+        self.leaf(&["$(", name, ")"].concat(), elapsed);
+    }
+
+    /// Writes the timings that were recorded, with the most time consuming of each kind first.
+    fn report<W: Write>(&self, output: &mut W, total: Duration) -> io::Result<()> {
+        writeln!(output, "ion: profile of {}: {} ms", self.script, milliseconds(total))?;
+        let sections = [
+            ("statement", &self.statements),
+            ("function", &self.functions),
+            ("command", &self.commands),
+            ("command substitution", &self.substitutions),
+        ];
+        for &(kind, timings) in &sections {
+            if timings.is_empty() {
+                continue;
+            }
+            let mut timings = timings.iter().collect::<Vec<(&String, &Timing)>>();
+            timings.sort_by(|a, b| b.1.time.cmp(&a.1.time).then_with(|| a.0.cmp(b.0)));
+            writeln!(output, "\n{:>12} {:>9}  {}", "total ms", "calls", kind)?;
+            for (name, timing) in timings {
+                let time = milliseconds(timing.time);
+                writeln!(output, "{:>12} {:>9}  {}", time, timing.calls, name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes each stack of frames with the microseconds that were spent within it.
+    fn write_stacks<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        let mut stacks = self.stacks.iter().collect::<Vec<(&String, &Duration)>>();
+        stacks.sort();
+        for (stack, time) in stacks {
+            let microseconds = time.as_secs() * 1_000_000 + u64::from(time.subsec_nanos()) / 1000;
+            if microseconds != 0 {
+                writeln!(output, "{} {}", stack, microseconds)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the functions which are still being called, and writes the report to the standard
+    /// error, along with the folded stacks if a file was given for them.
+    pub(crate) fn finish(mut self) {
+        if self.pid != process::id() {
+            return;
+        }
+        let total = self.frames[0].start.elapsed();
+        while self.frames.len() > 1 {
+            self.leave();
+        }
+        self.leave_after(total);
+
+        let stderr = io::stderr();
+        let _ = self.report(&mut stderr.lock(), total);
+        if let Some(ref path) = self.folded {
+            if let Err(why) = File::create(path).and_then(|mut file| self.write_stacks(&mut file)) {
+                eprintln!("ion: failed to write the folded stacks to {}: {}", path, why);
+            }
+        }
+    }
+}
+
+fn milliseconds(time: Duration) -> String {
+    let milliseconds = time.as_secs() as f64 * 1000.0 + f64::from(time.subsec_nanos()) / 1e6;
+    format!("{:.3}", milliseconds)
+}
+
+/// Describes a statement which is about to be executed, or returns `None` if it won't be,
+/// either because it continues a block, or because it depends upon a status that wasn't set.
+fn describe(shell: &Shell, statement: &Statement) -> Option<String> {
+    let text = match *statement {
+        Statement::And(ref statement) if shell.previous_status == SUCCESS => statement.to_string(),
+        Statement::Or(ref statement) if shell.previous_status == FAILURE => statement.to_string(),
+        Statement::Not(ref statement) | Statement::Time(ref statement) => statement.to_string(),
+        Statement::Let(_)
+        | Statement::Export(_)
+        | Statement::If { .. }
+        | Statement::For { .. }
+        | Statement::While { .. }
+        | Statement::Match { .. }
+        | Statement::Break
+        | Statement::Continue
+        | Statement::Return(_)
+        | Statement::Pipeline(_)
+        | Statement::Coproc { .. } => statement.to_string(),
+        _ => return None,
+    };
+    let script = shell.script.as_ref().map_or("ion", String::as_str);
+    Some(format!("{}:{}: {}", script, shell.line, text))
+}
+
+/// Executes a statement, recording the time that it took if the shell is being profiled. The
+/// time of a block includes that of the statements within it, and blocks are only recorded once
+/// they have been executed, rather than when they are started.
+pub(crate) fn profile<T, F>(shell: &mut Shell, statement: Statement, execute: F) -> T
+where
+    F: FnOnce(&mut Shell, Statement) -> T,
+{
+    let description = match shell.profiler {
+        Some(_) => describe(shell, &statement),
+        None => None,
+    };
+    let description = match description {
+        Some(description) => description,
+        None => return execute(shell, statement),
+    };
+
+    let start = Instant::now();
+    let result = execute(shell, statement);
+    if shell.flow_control.level == 0 {
+        if let Some(ref profiler) = shell.profiler {
+            profiler.borrow_mut().statement(description, start.elapsed());
+        }
+    }
+    result
+}
+
+/// Starts timing a pipeline, if the shell is being profiled, returning the names of the
+/// external commands within it and the time at which it started.
+pub(crate) fn start_pipeline(
+    shell: &Shell,
+    commands: &[(RefinedJob, JobKind)],
+) -> Option<(Vec<String>, Instant)> {
+    shell.profiler.as_ref()?;
+    let names = commands
+        .iter()
+        .filter_map(|&(ref job, _)| match *job {
+            RefinedJob::External { ref name, .. } => Some(name.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    if names.is_empty() {
+        None
+    } else {
+        Some((names, Instant::now()))
+    }
+}
+
+/// Records the time taken by a pipeline which was started with `start_pipeline`.
+pub(crate) fn end_pipeline(shell: &Shell, timer: Option<(Vec<String>, Instant)>) {
+    if let (Some(profiler), Some((names, start))) = (shell.profiler.as_ref(), timer) {
+        profiler.borrow_mut().pipeline(&names, start.elapsed());
+    }
+}
+
+/// Records the time taken by a command substitution, including the fork of the shell.
+pub(crate) fn substitution(shell: &Shell, command: &str, start: Instant) {
+    if let Some(ref profiler) = shell.profiler {
+        profiler.borrow_mut().substitution(command, start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell::{Binary, ShellBuilder};
+    use std::cell::RefCell;
+
+    #[test]
+    fn counts() {
+        let script = "fn inc\n    let count += 1\nend\nlet count = 0\nfor x in 1..4\n    inc\n    \
+                      false || inc\nend\ntrue && true";
+        let mut shell = ShellBuilder::new().as_library();
+        shell.profiler = Some(RefCell::new(Profiler::new("test.ion", None)));
+        shell.script = Some("test.ion".into());
+        shell.terminate_script_quotes(script.lines().map(String::from));
+
+        let profiler = shell.profiler.take().unwrap().into_inner();
+        let mut statements = profiler
+            .statements
+            .iter()
+            .map(|(statement, timing)| (statement.as_str(), timing.calls))
+            .collect::<Vec<(&str, usize)>>();
+        statements.sort();
+        let expected = vec![
+            ("test.ion:2: let count += 1", 6),
+            ("test.ion:4: let count = 0", 1),
+            ("test.ion:5: for x in 1..4", 1),
+            ("test.ion:6: inc", 3),
+            ("test.ion:7: false", 3),
+            ("test.ion:7: inc", 3),
+            ("test.ion:9: true", 2),
+        ];
+        assert_eq!(expected, statements);
+        assert_eq!(6, profiler.functions["inc"].calls);
+    }
+
+    #[test]
+    fn reports() {
+        let mut profiler = Profiler::new("test.ion", None);
+        profiler.enter("outer");
+        profiler.enter("inner");
+        profiler.pipeline(&["sleep".into()], Duration::from_millis(5));
+        profiler.leave_after(Duration::from_millis(7));
+        profiler.substitution("date; true\n", Duration::from_millis(3));
+        profiler.leave_after(Duration::from_millis(20));
+        profiler.statement("test.ion:3: outer".into(), Duration::from_millis(20));
+        profiler.statement("test.ion:4: sleep 1".into(), Duration::from_millis(30));
+        profiler.pipeline(&["sleep".into(), "cat".into()], Duration::from_millis(30));
+        profiler.leave_after(Duration::from_millis(60));
+
+        let mut stacks = Vec::new();
+        profiler.write_stacks(&mut stacks).unwrap();
+        let expected = "main 10000
+main;outer 10000
+main;outer;$(date, true) 3000
+main;outer;inner 2000
+main;outer;inner;sleep 5000
+main;sleep | cat 30000
+";
+        assert_eq!(expected, String::from_utf8(stacks).unwrap());
+
+        let mut report = Vec::new();
+        profiler.report(&mut report, Duration::from_millis(60)).unwrap();
+        let expected = "ion: profile of test.ion: 60.000 ms
+
+    total ms     calls  statement
+      30.000         1  test.ion:4: sleep 1
+      20.000         1  test.ion:3: outer
+
+    total ms     calls  function
+      20.000         1  outer
+       7.000         1  inner
+
+    total ms     calls  command
+      35.000         2  sleep
+      30.000         1  cat
+
+    total ms     calls  command substitution
+       3.000         1  date; true
+";
+        assert_eq!(expected, String::from_utf8(report).unwrap());
+    }
+}
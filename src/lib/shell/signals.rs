@@ -34,3 +34,64 @@ impl SignalHandler {
 impl Drop for SignalHandler {
     fn drop(&mut self) { unblock(); }
 }
+
+/// A condition that a user-defined handler may be attached to with the `trap` builtin.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) enum Trap {
+    /// Triggered just before the shell exits.
+    Exit,
+    /// Triggered whenever a pipeline returns a non-zero exit status.
+    Err,
+    /// Triggered when the shell receives the given signal.
+    Signal(i32),
+}
+
+impl Trap {
+    /// Parses a trap condition from a signal name (with or without the `SIG` prefix), a signal
+    /// number, or one of the `EXIT` and `ERR` pseudo-signals.
+    pub(crate) fn parse(name: &str) -> Option<Trap> {
+        let name = name.trim_left_matches("SIG");
+        match name {
+            "EXIT" | "0" => Some(Trap::Exit),
+            "ERR" => Some(Trap::Err),
+            "HUP" => Some(Trap::Signal(sys::SIGHUP)),
+            "INT" => Some(Trap::Signal(sys::SIGINT)),
+            "TERM" => Some(Trap::Signal(sys::SIGTERM)),
+            _ => match name.parse::<i32>() {
+                Ok(signal) if signal == sys::SIGHUP => Some(Trap::Signal(sys::SIGHUP)),
+                Ok(signal) if signal == sys::SIGINT => Some(Trap::Signal(sys::SIGINT)),
+                Ok(signal) if signal == sys::SIGTERM => Some(Trap::Signal(sys::SIGTERM)),
+                _ => None,
+            },
+        }
+    }
+
+    /// The name of the condition, as it is displayed when listing traps.
+    pub(crate) fn name(&self) -> &'static str {
+        match *self {
+            Trap::Exit => "EXIT",
+            Trap::Err => "ERR",
+            Trap::Signal(sys::SIGHUP) => "SIGHUP",
+            Trap::Signal(sys::SIGINT) => "SIGINT",
+            Trap::Signal(sys::SIGTERM) => "SIGTERM",
+            Trap::Signal(_) => "UNKNOWN",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trap_parsing() {
+        assert_eq!(Trap::parse("EXIT"), Some(Trap::Exit));
+        assert_eq!(Trap::parse("0"), Some(Trap::Exit));
+        assert_eq!(Trap::parse("ERR"), Some(Trap::Err));
+        assert_eq!(Trap::parse("INT"), Some(Trap::Signal(sys::SIGINT)));
+        assert_eq!(Trap::parse("SIGTERM"), Some(Trap::Signal(sys::SIGTERM)));
+        assert_eq!(Trap::parse(&sys::SIGHUP.to_string()), Some(Trap::Signal(sys::SIGHUP)));
+        assert_eq!(Trap::parse("SIGKILL"), None);
+        assert_eq!(Trap::parse("FOO"), None);
+    }
+}
@@ -5,12 +5,12 @@ use super::{
 use fnv::FnvHashMap;
 use liner::Context;
 use std::{
-    env, io::{self, BufRead},
+    env, ffi::OsString, io::{self, BufRead}, iter,
 };
 use sys::{self, geteuid, getpid, getuid, is_root, variables as self_sys};
 use types::{
-    Array, ArrayVariableContext, HashMap, HashMapVariableContext, Identifier, Key, Value,
-    VariableContext,
+    Array, ArrayVariableContext, HashMap, HashMapVariableContext, Identifier, Key, Map, MapRef,
+    MapVariableContext, Nested, NestedVariableContext, Value, VariableContext,
 };
 use unicode_segmentation::UnicodeSegmentation;
 use xdg::BaseDirectories;
@@ -19,12 +19,76 @@ lazy_static! {
     static ref STRING_NAMESPACES: FnvHashMap<Identifier, StringNamespace> = namespaces::collect();
 }
 
+/// Variables which are maintained by the shell itself, and therefore always reside in the
+/// global scope, regardless of the scope that they are assigned from.
+const SHELL_VARIABLES: &[&str] = &["?", "PIPESTATUS", "PWD", "OLDPWD", "PID", "NS_PLUGINS"];
+
+/// A frame of variables that is created for each function invocation. Assignments made within
+/// the body of a function are stored here, and are discarded when the function returns.
+#[derive(Clone, Debug, Default)]
+pub struct Scope {
+    pub nested:    NestedVariableContext,
+    pub hashmaps:  HashMapVariableContext,
+    pub maps:      MapVariableContext,
+    pub arrays:    ArrayVariableContext,
+    pub variables: VariableContext,
+}
+
+/// The prior values of variables that were temporarily assigned for the duration of a single
+/// command, as with `NAME=value command`.
+#[derive(Debug, Default)]
+pub(crate) struct TemporaryAssignments {
+    previous: Vec<(Identifier, Option<Value>, Option<OsString>)>,
+}
+
+/// Designates which scope a variable reference should be resolved against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Namespace {
+    /// The innermost scope, falling back to the global scope.
+    Current,
+    /// The scope that is `n` frames above the current scope (`super::`).
+    Super(usize),
+    /// The top-level scope of the shell (`global::`).
+    Global,
+}
+
+impl Namespace {
+    /// Strips any `global::` or `super::` prefixes from the given name.
+    fn parse(name: &str) -> (Namespace, &str) {
+        if name.starts_with("global::") {
+            return (Namespace::Global, &name[8..]);
+        }
+
+        let mut name = name;
+        let mut levels = 0;
+        while name.starts_with("super::") {
+            name = &name[7..];
+            levels += 1;
+        }
+
+        if levels == 0 {
+            (Namespace::Current, name)
+        } else {
+            (Namespace::Super(levels), name)
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Variables {
+    /// Nested arrays and maps within the global scope.
+    pub nested:    NestedVariableContext,
+    /// Hash maps within the global scope.
     pub hashmaps:  HashMapVariableContext,
+    /// Sorted and insertion-ordered maps within the global scope.
+    pub maps:      MapVariableContext,
+    /// Arrays within the global scope.
     pub arrays:    ArrayVariableContext,
+    /// Strings within the global scope.
     pub variables: VariableContext,
     pub aliases:   VariableContext,
+    /// Function scopes, with the innermost scope being the last element.
+    scopes:        Vec<Scope>,
     flags:         u8,
 }
 
@@ -42,6 +106,8 @@ impl Default for Variables {
                 .into(),
         );
 
+        map.insert("TRACE_PROMPT".into(), "+ ".into());
+
         // Set the PID, UID, and EUID variables.
         map.insert(
             "PID".into(),
@@ -76,10 +142,13 @@ impl Default for Variables {
             |path| env::set_var("HOME", path.to_str().unwrap_or("?")),
         );
         Variables {
+            nested:    FnvHashMap::default(),
             hashmaps:  FnvHashMap::with_capacity_and_hasher(64, Default::default()),
+            maps:      FnvHashMap::default(),
             arrays:    FnvHashMap::with_capacity_and_hasher(64, Default::default()),
             variables: map,
             aliases:   FnvHashMap::with_capacity_and_hasher(64, Default::default()),
+            scopes:    Vec::new(),
             flags:     0,
         }
     }
@@ -88,6 +157,175 @@ impl Default for Variables {
 const PLUGIN: u8 = 1;
 
 impl Variables {
+    /// Pushes a new scope for a function invocation.
+    pub(crate) fn new_scope(&mut self) { self.scopes.push(Scope::default()); }
+
+    /// Discards the innermost scope, along with every variable that was assigned within it.
+    pub(crate) fn pop_scope(&mut self) { self.scopes.pop(); }
+
+    /// The number of function scopes that are currently active.
+    pub(crate) fn scope_depth(&self) -> usize { self.scopes.len() }
+
+    /// Obtains the index of the frame that assignments to `name` within `namespace` should
+    /// be written to, where `0` is the global scope.
+    fn assignment_frame(&self, namespace: Namespace, name: &str) -> usize {
+        match namespace {
+            Namespace::Global => 0,
+            _ if SHELL_VARIABLES.contains(&name) => 0,
+            Namespace::Current => self.scopes.len(),
+            Namespace::Super(levels) => self.scopes.len().saturating_sub(levels),
+        }
+    }
+
+    /// Obtains the frames that a lookup of a variable within `namespace` should search, in
+    /// order. A function may only see its own scope and the global scope, unless the scope
+    /// of its caller is explicitly requested with `super::`.
+    fn lookup_frames(&self, namespace: Namespace) -> impl Iterator<Item = usize> {
+        let frame = match namespace {
+            Namespace::Global => 0,
+            Namespace::Current => self.scopes.len(),
+            Namespace::Super(levels) => self.scopes.len().saturating_sub(levels),
+        };
+        iter::once(frame).chain(if frame == 0 { None } else { Some(0) })
+    }
+
+    fn strings_in(&self, frame: usize) -> &VariableContext {
+        match frame {
+            0 => &self.variables,
+            _ => &self.scopes[frame - 1].variables,
+        }
+    }
+
+    fn strings_in_mut(&mut self, frame: usize) -> &mut VariableContext {
+        match frame {
+            0 => &mut self.variables,
+            _ => &mut self.scopes[frame - 1].variables,
+        }
+    }
+
+    fn arrays_in(&self, frame: usize) -> &ArrayVariableContext {
+        match frame {
+            0 => &self.arrays,
+            _ => &self.scopes[frame - 1].arrays,
+        }
+    }
+
+    fn arrays_in_mut(&mut self, frame: usize) -> &mut ArrayVariableContext {
+        match frame {
+            0 => &mut self.arrays,
+            _ => &mut self.scopes[frame - 1].arrays,
+        }
+    }
+
+    fn hashmaps_in(&self, frame: usize) -> &HashMapVariableContext {
+        match frame {
+            0 => &self.hashmaps,
+            _ => &self.scopes[frame - 1].hashmaps,
+        }
+    }
+
+    fn hashmaps_in_mut(&mut self, frame: usize) -> &mut HashMapVariableContext {
+        match frame {
+            0 => &mut self.hashmaps,
+            _ => &mut self.scopes[frame - 1].hashmaps,
+        }
+    }
+
+    fn maps_in(&self, frame: usize) -> &MapVariableContext {
+        match frame {
+            0 => &self.maps,
+            _ => &self.scopes[frame - 1].maps,
+        }
+    }
+
+    fn maps_in_mut(&mut self, frame: usize) -> &mut MapVariableContext {
+        match frame {
+            0 => &mut self.maps,
+            _ => &mut self.scopes[frame - 1].maps,
+        }
+    }
+
+    fn nested_in(&self, frame: usize) -> &NestedVariableContext {
+        match frame {
+            0 => &self.nested,
+            _ => &self.scopes[frame - 1].nested,
+        }
+    }
+
+    fn nested_in_mut(&mut self, frame: usize) -> &mut NestedVariableContext {
+        match frame {
+            0 => &mut self.nested,
+            _ => &mut self.scopes[frame - 1].nested,
+        }
+    }
+
+    /// Removes the arrays, maps and nested values with the given name from the frame, so that
+    /// a collection which is being assigned will not be shadowed by a stale collection of
+    /// another kind. Strings are expanded with `$` rather than `@`, and so are kept apart.
+    fn clear_collections(&mut self, frame: usize, name: &str) {
+        self.arrays_in_mut(frame).remove(name);
+        self.hashmaps_in_mut(frame).remove(name);
+        self.maps_in_mut(frame).remove(name);
+        self.nested_in_mut(frame).remove(name);
+    }
+
+    /// Iterates over every string variable that is visible from the current scope, with
+    /// local variables shadowing global variables of the same name.
+    pub(crate) fn visible_strings<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a Identifier, &'a Value)> + 'a {
+        let local = self.scopes.last();
+        local.into_iter().flat_map(|scope| scope.variables.iter()).chain(
+            self.variables
+                .iter()
+                .filter(move |&(key, _)| local.map_or(true, |s| !s.variables.contains_key(key))),
+        )
+    }
+
+    /// Iterates over every array variable that is visible from the current scope, with
+    /// local variables shadowing global variables of the same name.
+    pub(crate) fn visible_arrays<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a Identifier, &'a Array)> + 'a {
+        let local = self.scopes.last();
+        local.into_iter().flat_map(|scope| scope.arrays.iter()).chain(
+            self.arrays
+                .iter()
+                .filter(move |&(key, _)| local.map_or(true, |s| !s.arrays.contains_key(key))),
+        )
+    }
+
+    /// Iterates over every map variable that is visible from the current scope, with local
+    /// variables shadowing global variables of the same name.
+    pub(crate) fn visible_maps<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a Identifier, MapRef<'a>)> + 'a {
+        let maps = |scope: &'a Scope| {
+            let hashmaps = scope.hashmaps.iter().map(|(key, map)| (key, MapRef::Hash(map)));
+            hashmaps.chain(scope.maps.iter().map(|(key, map)| (key, MapRef::Other(map))))
+        };
+        let local = self.scopes.last();
+        let global = self.hashmaps.iter().map(|(key, map)| (key, MapRef::Hash(map))).chain(
+            self.maps.iter().map(|(key, map)| (key, MapRef::Other(map))),
+        );
+        local.into_iter().flat_map(maps).chain(global.filter(move |&(key, _)| {
+            local.map_or(true, |s| !s.hashmaps.contains_key(key) && !s.maps.contains_key(key))
+        }))
+    }
+
+    /// Iterates over every nested variable that is visible from the current scope, with local
+    /// variables shadowing global variables of the same name.
+    pub(crate) fn visible_nested<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a Identifier, &'a Nested)> + 'a {
+        let local = self.scopes.last();
+        local.into_iter().flat_map(|scope| scope.nested.iter()).chain(
+            self.nested
+                .iter()
+                .filter(move |&(key, _)| local.map_or(true, |s| !s.nested.contains_key(key))),
+        )
+    }
+
     #[allow(dead_code)]
     pub(crate) fn is_hashmap_reference(key: &str) -> Option<(Identifier, Key)> {
         let mut key_iter = key.split('[');
@@ -183,13 +421,27 @@ impl Variables {
     }
 
     pub fn strings<'a>(&'a self) -> impl Iterator<Item = Identifier> + 'a {
-        self.variables
-            .keys()
-            .cloned()
+        self.visible_strings()
+            .map(|(key, _)| key.clone())
             .chain(env::vars().map(|(k, _)| k.into()))
     }
 
-    pub fn unset_var(&mut self, name: &str) -> Option<Value> { self.variables.remove(name) }
+    /// Removes a string variable from the frame that an assignment to it would be written to,
+    /// so that the variables of outer scopes are only removed by `super::` or `global::`.
+    pub fn unset_var(&mut self, name: &str) -> Option<Value> {
+        let (namespace, name) = Namespace::parse(name);
+        let frame = self.assignment_frame(namespace, name);
+        self.strings_in_mut(frame).remove(name)
+    }
+
+    /// Obtains a reference to a string variable that is visible from the current scope,
+    /// without consulting the process environment or any namespaces.
+    pub(crate) fn get_str(&self, name: &str) -> Option<&Value> {
+        let (namespace, name) = Namespace::parse(name);
+        self.lookup_frames(namespace)
+            .filter_map(|frame| self.strings_in(frame).get(name))
+            .next()
+    }
 
     pub fn get_var_or_empty(&self, name: &str) -> Value { self.get_var(name).unwrap_or_default() }
 
@@ -199,6 +451,7 @@ impl Variables {
             "SWD" => return Some(self.get_simplified_directory()),
             _ => (),
         }
+        let (namespace, name) = Namespace::parse(name);
         if let Some((name, variable)) = name.find("::").map(|pos| (&name[..pos], &name[pos + 2..]))
         {
             // If the parsed name contains the '::' pattern, then a namespace was
@@ -247,8 +500,9 @@ impl Variables {
             }
         } else {
             // Otherwise, it's just a simple variable name.
-            self.variables
-                .get(name)
+            self.lookup_frames(namespace)
+                .filter_map(|frame| self.strings_in(frame).get(name))
+                .next()
                 .cloned()
                 .or_else(|| env::var(name).map(Into::into).ok())
         }
@@ -299,53 +553,145 @@ impl Variables {
             .replace(&self.get_var("HOME").unwrap(), "~")
     }
 
-    pub fn unset_array(&mut self, name: &str) -> Option<Array> { self.arrays.remove(name) }
+    pub fn unset_array(&mut self, name: &str) -> Option<Array> {
+        let (namespace, name) = Namespace::parse(name);
+        let frame = self.assignment_frame(namespace, name);
+        self.arrays_in_mut(frame).remove(name)
+    }
 
-    pub fn get_array(&self, name: &str) -> Option<&Array> { self.arrays.get(name) }
+    /// Removes the string and every collection with the given name, like `unset_array`.
+    pub fn unset_all(&mut self, name: &str) {
+        let (namespace, name) = Namespace::parse(name);
+        let frame = self.assignment_frame(namespace, name);
+        self.strings_in_mut(frame).remove(name);
+        self.clear_collections(frame, name);
+    }
 
-    pub fn get_map(&self, name: &str) -> Option<&HashMap> { self.hashmaps.get(name) }
+    /// Removes a map of any kind, like `unset_array`.
+    pub fn unset_map(&mut self, name: &str) -> Option<Map> {
+        let (namespace, name) = Namespace::parse(name);
+        let frame = self.assignment_frame(namespace, name);
+        match self.hashmaps_in_mut(frame).remove(name) {
+            Some(map) => Some(Map::Hash(map)),
+            None => self.maps_in_mut(frame).remove(name),
+        }
+    }
+
+    /// Removes a nested variable, like `unset_array`, if the predicate accepts its value.
+    pub fn unset_nested_if<F>(&mut self, name: &str, predicate: F) -> Option<Nested>
+    where
+        F: FnOnce(&Nested) -> bool,
+    {
+        let (namespace, name) = Namespace::parse(name);
+        let frame = self.assignment_frame(namespace, name);
+        let nested = self.nested_in_mut(frame);
+        if nested.get(name).map_or(false, predicate) {
+            nested.remove(name)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_array(&self, name: &str) -> Option<&Array> {
+        let (namespace, name) = Namespace::parse(name);
+        self.lookup_frames(namespace)
+            .filter_map(|frame| self.arrays_in(frame).get(name))
+            .next()
+    }
+
+    /// Obtains a hash map, which is the kind of map that is declared with `hmap`. Maps of the
+    /// other kinds are obtained with `get_any_map`.
+    pub fn get_map(&self, name: &str) -> Option<&HashMap> {
+        match self.get_any_map(name)? {
+            MapRef::Hash(map) => Some(map),
+            MapRef::Other(_) => None,
+        }
+    }
+
+    /// Obtains a map of any kind.
+    pub fn get_any_map(&self, name: &str) -> Option<MapRef> {
+        let (namespace, name) = Namespace::parse(name);
+        self.lookup_frames(namespace)
+            .filter_map(|frame| match self.hashmaps_in(frame).get(name) {
+                Some(map) => Some(MapRef::Hash(map)),
+                None => self.maps_in(frame).get(name).map(MapRef::Other),
+            })
+            .next()
+    }
+
+    pub fn get_nested(&self, name: &str) -> Option<&Nested> {
+        let (namespace, name) = Namespace::parse(name);
+        self.lookup_frames(namespace)
+            .filter_map(|frame| self.nested_in(frame).get(name))
+            .next()
+    }
 
     #[allow(dead_code)]
     pub(crate) fn set_hashmap_value(&mut self, name: &str, key: &str, value: &str) {
+        let (namespace, name) = Namespace::parse(name);
         if !name.is_empty() {
-            if let Some(map) = self.hashmaps.get_mut(name) {
+            let frame = self.assignment_frame(namespace, name);
+            if let Some(map) = self.maps_in_mut(frame).get_mut(name) {
                 map.insert(key.into(), value.into());
                 return;
             }
 
-            let mut map = HashMap::with_capacity_and_hasher(4, Default::default());
-            map.insert(key.into(), value.into());
-            self.hashmaps.insert(name.into(), map);
+            self.hashmaps_in_mut(frame)
+                .entry(name.into())
+                .or_insert_with(|| HashMap::with_capacity_and_hasher(4, Default::default()))
+                .insert(key.into(), value.into());
         }
     }
 
-    pub fn set_array(&mut self, name: &str, value: Array) {
+    pub fn set_map(&mut self, name: &str, value: Map) {
+        let (namespace, name) = Namespace::parse(name);
         if !name.is_empty() {
-            if value.is_empty() {
-                self.arrays.remove(name);
-            } else {
-                self.arrays.insert(name.into(), value);
+            let frame = self.assignment_frame(namespace, name);
+            self.clear_collections(frame, name);
+            match value {
+                Map::Hash(map) => {
+                    self.hashmaps_in_mut(frame).insert(name.into(), map);
+                }
+                map => {
+                    self.maps_in_mut(frame).insert(name.into(), map);
+                }
             }
         }
     }
 
+    pub fn set_nested(&mut self, name: &str, value: Nested) {
+        let (namespace, name) = Namespace::parse(name);
+        if !name.is_empty() {
+            let frame = self.assignment_frame(namespace, name);
+            self.clear_collections(frame, name);
+            self.nested_in_mut(frame).insert(name.into(), value);
+        }
+    }
+
+    pub fn set_array(&mut self, name: &str, value: Array) {
+        let (namespace, name) = Namespace::parse(name);
+        if !name.is_empty() {
+            let frame = self.assignment_frame(namespace, name);
+            self.clear_collections(frame, name);
+            self.arrays_in_mut(frame).insert(name.into(), value);
+        }
+    }
+
     pub fn set_var(&mut self, name: &str, value: &str) {
+        let (namespace, name) = Namespace::parse(name);
         if !name.is_empty() {
-            if value.is_empty() {
-                self.variables.remove(name);
-            } else {
-                if name == "NS_PLUGINS" {
-                    match value {
-                        "0" => self.disable_plugins(),
-                        "1" => self.enable_plugins(),
-                        _ => eprintln!(
-                            "ion: unsupported value for NS_PLUGINS. Value must be either 0 or 1."
-                        ),
-                    }
-                    return;
+            if name == "NS_PLUGINS" {
+                match value {
+                    "0" => self.disable_plugins(),
+                    "1" => self.enable_plugins(),
+                    _ => eprintln!(
+                        "ion: unsupported value for NS_PLUGINS. Value must be either 0 or 1."
+                    ),
                 }
-                self.variables.insert(name.into(), value.into());
+                return;
             }
+            let frame = self.assignment_frame(namespace, name);
+            self.strings_in_mut(frame).insert(name.into(), value.into());
         }
     }
 
@@ -366,14 +712,50 @@ impl Variables {
             let handle = stdin.lock();
             let mut lines = handle.lines();
             for arg in args.into_iter().skip(1) {
-                if let Some(Ok(line)) = lines.next() {
-                    self.set_var(arg.as_ref(), line.trim());
+                match lines.next() {
+                    Some(Ok(line)) => self.set_var(arg.as_ref(), line.trim()),
+                    // Signals the end of input, so that `while read` loops may terminate.
+                    _ => return FAILURE,
                 }
             }
         }
         SUCCESS
     }
 
+    /// Assigns each variable within the current scope and the process environment, so that
+    /// they are visible to the command which is about to be executed, whether that command
+    /// is a builtin, a function or an external program. The returned values are to be handed
+    /// back to `restore` once the command has completed.
+    pub(crate) fn assign_temporarily(
+        &mut self,
+        assignments: &[(Identifier, Value)],
+    ) -> TemporaryAssignments {
+        let mut previous = Vec::with_capacity(assignments.len());
+        for &(ref name, ref value) in assignments {
+            let frame = self.assignment_frame(Namespace::Current, name);
+            let string = self.strings_in_mut(frame).insert(name.clone(), value.clone());
+            previous.push((name.clone(), string, env::var_os(&**name)));
+            env::set_var(&**name, value);
+        }
+        TemporaryAssignments { previous }
+    }
+
+    /// Reverts the variables that were assigned by `assign_temporarily` to their prior values.
+    pub(crate) fn restore(&mut self, assignments: TemporaryAssignments) {
+        // Restoring in reverse ensures that the original value wins if a name was repeated.
+        for (name, string, variable) in assignments.previous.into_iter().rev() {
+            let frame = self.assignment_frame(Namespace::Current, &name);
+            match string {
+                Some(value) => self.strings_in_mut(frame).insert(name.clone(), value),
+                None => self.strings_in_mut(frame).remove(&name),
+            };
+            match variable {
+                Some(value) => env::set_var(&*name, value),
+                None => env::remove_var(&*name),
+            }
+        }
+    }
+
     pub(crate) fn disable_plugins(&mut self) { self.flags &= 255 ^ PLUGIN; }
 
     pub(crate) fn enable_plugins(&mut self) { self.flags |= PLUGIN; }
@@ -385,6 +767,7 @@ impl Variables {
 mod tests {
     use super::*;
     use parser::{expand_string, Expander};
+    use types::{BTreeMap, OrderedMap};
 
     struct VariableExpander(pub Variables);
 
@@ -417,6 +800,152 @@ mod tests {
         }
     }
 
+    #[test]
+    fn function_scopes_shadow_and_discard_variables() {
+        let mut variables = Variables::default();
+        variables.set_var("FOO", "global");
+        variables.new_scope();
+        assert_eq!(Some("global".into()), variables.get_var("FOO"));
+        variables.set_var("FOO", "local");
+        variables.set_var("BAR", "local");
+        variables.set_array("BAZ", array!["local"]);
+        assert_eq!(Some("local".into()), variables.get_var("FOO"));
+        assert_eq!(Some("global".into()), variables.get_var("global::FOO"));
+        variables.pop_scope();
+        assert_eq!(Some("global".into()), variables.get_var("FOO"));
+        assert_eq!(None, variables.get_var("BAR"));
+        assert_eq!(None, variables.get_array("BAZ"));
+    }
+
+    #[test]
+    fn functions_cannot_see_caller_variables_without_super() {
+        let mut variables = Variables::default();
+        variables.new_scope();
+        variables.set_var("FOO", "caller");
+        variables.new_scope();
+        assert_eq!(None, variables.get_var("FOO"));
+        assert_eq!(Some("caller".into()), variables.get_var("super::FOO"));
+        variables.set_var("super::FOO", "callee");
+        variables.set_var("global::BAR", "callee");
+        variables.pop_scope();
+        assert_eq!(Some("callee".into()), variables.get_var("FOO"));
+        variables.pop_scope();
+        assert_eq!(None, variables.get_var("FOO"));
+        assert_eq!(Some("callee".into()), variables.get_var("BAR"));
+    }
+
+    #[test]
+    fn unset_only_removes_outer_variables_with_a_namespace() {
+        let mut variables = Variables::default();
+        variables.set_var("FOO", "global");
+        variables.set_array("BAR", array!["global"]);
+        variables.new_scope();
+        variables.set_var("FOO", "caller");
+        variables.new_scope();
+        assert_eq!(None, variables.unset_var("FOO"));
+        assert_eq!(None, variables.unset_array("BAR"));
+        assert_eq!(Some("caller".into()), variables.unset_var("super::FOO"));
+        assert_eq!(Some("global".into()), variables.unset_var("global::FOO"));
+        assert_eq!(Some(array!["global"]), variables.unset_array("global::BAR"));
+        variables.set_var("FOO", "local");
+        assert_eq!(Some("local".into()), variables.unset_var("FOO"));
+        assert_eq!(None, variables.get_var("FOO"));
+    }
+
+    #[test]
+    fn hash_maps_are_stored_apart_from_other_kinds() {
+        let mut variables = Variables::default();
+        variables.set_hashmap_value("map", "key", "value");
+        assert_eq!(Some(&"value".into()), variables.get_map("map").and_then(|m| m.get("key")));
+        assert!(variables.hashmaps.contains_key("map"));
+
+        // A map of another kind replaces the hash map, and is only found by `get_any_map`.
+        let mut sorted = BTreeMap::new();
+        sorted.insert("b".into(), "2".into());
+        sorted.insert("a".into(), "1".into());
+        variables.set_map("map", Map::BTree(sorted));
+        assert_eq!(None, variables.get_map("map"));
+        assert!(!variables.hashmaps.contains_key("map"));
+        let map = variables.get_any_map("map").unwrap();
+        assert_eq!("[a=1 b=2]", map.to_string());
+        variables.set_hashmap_value("map", "c", "3");
+        assert_eq!(Some(&"3".into()), variables.get_any_map("map").unwrap().get("c"));
+    }
+
+    #[test]
+    fn collections_replace_collections_of_other_kinds() {
+        let mut variables = Variables::default();
+        variables.set_var("name", "string");
+        variables.set_array("name", array!["1", "2"]);
+        let mut map = HashMap::default();
+        map.insert("a".into(), "b".into());
+        variables.set_map("name", Map::Hash(map));
+        assert_eq!(None, variables.get_array("name"));
+        assert_eq!("[a=b]", variables.get_any_map("name").unwrap().to_string());
+
+        variables.set_array("name", array!["arr"]);
+        let mut ordered = OrderedMap::new();
+        ordered.insert("b".into(), "2".into());
+        variables.set_map("name", Map::Ordered(ordered));
+        assert_eq!(None, variables.get_array("name"));
+        assert_eq!("[b=2]", variables.get_any_map("name").unwrap().to_string());
+
+        variables.set_array("name", array!["arr"]);
+        assert!(variables.get_any_map("name").is_none());
+        assert_eq!(Some(&array!["arr"]), variables.get_array("name"));
+        // A string is expanded with `$` rather than `@`, and so is unaffected.
+        assert_eq!(Some("string".into()), variables.get_var("name"));
+    }
+
+    #[test]
+    fn nested_values_replace_flat_collections() {
+        let mut variables = Variables::default();
+        variables.set_array("name", array!["1", "2"]);
+        let nested = Nested::Array(vec![
+            Nested::Array(vec![Nested::Str("3".into())]),
+            Nested::Array(vec![Nested::Str("4".into())]),
+        ]);
+        variables.set_nested("name", nested);
+        assert_eq!(None, variables.get_array("name"));
+        assert_eq!("[[3] [4]]", variables.get_nested("name").unwrap().to_string());
+
+        variables.set_map("name", Map::Ordered(OrderedMap::new()));
+        assert!(variables.get_nested("name").is_none());
+        variables.set_nested("name", Nested::Array(Vec::new()));
+        assert!(variables.get_any_map("name").is_none());
+        variables.set_array("name", array!["5"]);
+        assert!(variables.get_nested("name").is_none());
+    }
+
+    #[test]
+    fn ordered_map_values_are_replaced_in_place() {
+        let mut variables = Variables::default();
+        let mut ordered = OrderedMap::new();
+        ordered.insert("b".into(), "1".into());
+        ordered.insert("a".into(), "2".into());
+        variables.set_map("map", Map::Ordered(ordered));
+        variables.set_hashmap_value("map", "b", "3");
+        variables.set_hashmap_value("map", "c", "4");
+        assert_eq!("[b=3 a=2 c=4]", variables.get_any_map("map").unwrap().to_string());
+    }
+
+    #[test]
+    fn temporary_assignments_are_restored() {
+        let mut variables = Variables::default();
+        variables.set_var("ION_TEMPORARY_A", "original");
+        let assignments = [
+            ("ION_TEMPORARY_A".into(), "first".into()),
+            ("ION_TEMPORARY_B".into(), "second".into()),
+        ];
+        let previous = variables.assign_temporarily(&assignments);
+        assert_eq!(Some("first".into()), variables.get_var("ION_TEMPORARY_A"));
+        assert_eq!(Ok("second".into()), env::var("ION_TEMPORARY_B"));
+        variables.restore(previous);
+        assert_eq!(Some("original".into()), variables.get_var("ION_TEMPORARY_A"));
+        assert_eq!(None, variables.get_var("ION_TEMPORARY_B"));
+        assert!(env::var("ION_TEMPORARY_A").is_err());
+    }
+
     #[test]
     fn minimal_directory_var_should_compact_path() {
         let mut variables = Variables::default();
@@ -88,7 +88,12 @@ pub(crate) fn watch_background(
     }
 }
 
-pub(crate) fn watch_foreground(shell: &mut Shell, pid: i32, command: &str) -> i32 {
+pub(crate) fn watch_foreground(
+    shell: &mut Shell,
+    pid: i32,
+    command: &str,
+    statuses: &mut [(u32, Option<i32>)],
+) -> i32 {
     let mut signaled = 0;
     let mut exit_status = 0;
     let mut status;
@@ -106,9 +111,13 @@ pub(crate) fn watch_foreground(shell: &mut Shell, pid: i32, command: &str) -> i3
                     }
                 },
                 Ok(0) => (),
-                Ok(_pid) if wifexited(status) => exit_status = wexitstatus(status) as i32,
+                Ok(pid) if wifexited(status) => {
+                    exit_status = wexitstatus(status) as i32;
+                    record_status(statuses, pid as u32, exit_status);
+                }
                 Ok(pid) if wifsignaled(status) => {
                     let signal = wtermsig(status);
+                    record_status(statuses, pid as u32, 128 + signal as i32);
                     if signal == SIGPIPE {
                         continue;
                     } else if wcoredump(status) {
@@ -48,6 +48,7 @@ pub fn wait_for_interrupt(pid: u32) -> io::Result<()> {
 
 pub fn wait_for_child(pid: u32) -> io::Result<u8> {
     let mut status;
+    let mut exit_status = 0;
     use syscall::{waitpid, ECHILD};
 
     loop {
@@ -55,11 +56,12 @@ pub fn wait_for_child(pid: u32) -> io::Result<u8> {
         match waitpid(pid as usize, &mut status, WUNTRACED) {
             Err(ref error) if error.errno == ECHILD => break,
             Err(error) => return Err(io::Error::from_raw_os_error(error.errno)),
-            _ => (),
+            // The status must be kept from the call which reaped the child.
+            _ => exit_status = status,
         }
     }
 
-    let status = ExitStatus::from_raw(status as i32);
+    let status = ExitStatus::from_raw(exit_status as i32);
     Ok(status.code().unwrap_or(0) as u8)
 }
 
@@ -85,7 +85,12 @@ pub(crate) fn watch_background(
     }
 }
 
-pub(crate) fn watch_foreground(shell: &mut Shell, pid: i32, command: &str) -> i32 {
+pub(crate) fn watch_foreground(
+    shell: &mut Shell,
+    pid: i32,
+    command: &str,
+    statuses: &mut [(u32, Option<i32>)],
+) -> i32 {
     let mut signaled = 0;
     let mut exit_status = 0;
     let mut status;
@@ -103,9 +108,13 @@ pub(crate) fn watch_foreground(shell: &mut Shell, pid: i32, command: &str) -> i3
                     }
                 },
                 0 => (),
-                _pid if WIFEXITED(status) => exit_status = WEXITSTATUS(status),
+                pid if WIFEXITED(status) => {
+                    exit_status = WEXITSTATUS(status);
+                    record_status(statuses, pid as u32, exit_status);
+                }
                 pid if WIFSIGNALED(status) => {
                     let signal = WTERMSIG(status);
+                    record_status(statuses, pid as u32, 128 + signal as i32);
                     if signal == SIGPIPE {
                         continue;
                     } else if WCOREDUMP(status) {
@@ -4,7 +4,8 @@ pub mod job_control;
 pub mod signals;
 
 use libc::{
-    c_char, c_int, pid_t, sighandler_t, strerror, waitpid, ECHILD, EINTR, WEXITSTATUS, WUNTRACED,
+    c_char, c_int, pid_t, sighandler_t, strerror, waitpid, ECHILD, EINTR, WEXITSTATUS, WIFEXITED,
+    WIFSIGNALED, WTERMSIG, WUNTRACED,
 };
 use std::{
     env, ffi::{CStr, CString}, io::{self, Write}, os::unix::io::RawFd, ptr,
@@ -71,17 +72,22 @@ pub fn wait_for_interrupt(pid: u32) -> io::Result<()> {
 
 pub fn wait_for_child(pid: u32) -> io::Result<u8> {
     let mut status;
-    let mut result;
+    let mut exit_status = 0;
 
     loop {
         status = 0;
-        result = unsafe { waitpid(pid as i32, &mut status, WUNTRACED) };
-        if result == -1 {
-            break if errno() == ECHILD {
-                Ok(unsafe { WEXITSTATUS(status) as u8 })
-            } else {
-                Err(io::Error::from_raw_os_error(errno()))
-            };
+        match unsafe { waitpid(pid as i32, &mut status, WUNTRACED) } {
+            -1 if errno() == EINTR => continue,
+            -1 if errno() == ECHILD => break Ok(exit_status),
+            -1 => break Err(io::Error::from_raw_os_error(errno())),
+            // The status must be kept from the call which reaped the child.
+            _ if unsafe { WIFEXITED(status) } => {
+                exit_status = unsafe { WEXITSTATUS(status) as u8 }
+            }
+            _ if unsafe { WIFSIGNALED(status) } => {
+                exit_status = 128 + unsafe { WTERMSIG(status) as u8 }
+            }
+            _ => (),
         }
     }
 }
@@ -1,15 +1,210 @@
 use fnv::FnvHashMap;
 use smallstring::SmallString;
 use smallvec::SmallVec;
+use std::{collections::BTreeMap as StdBTreeMap, fmt, iter};
 
 pub type Array = SmallVec<[Value; 4]>;
-pub type HashMap = FnvHashMap<Key, Value>;
+pub type HashMap<V = Value> = FnvHashMap<Key, V>;
+pub type BTreeMap<V = Value> = StdBTreeMap<Key, V>;
 pub type Identifier = SmallString;
 pub type Key = SmallString;
 pub type Value = String;
 pub type VariableContext = FnvHashMap<Identifier, Value>;
 pub type ArrayVariableContext = FnvHashMap<Identifier, Array>;
 pub type HashMapVariableContext = FnvHashMap<Identifier, HashMap>;
+pub type MapVariableContext = FnvHashMap<Identifier, Map>;
+pub type NestedVariableContext = FnvHashMap<Identifier, Nested>;
+
+/// A map which iterates over its pairs in the order in which their keys were first inserted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderedMap<V = Value> {
+    pairs:   Vec<(Key, V)>,
+    indices: FnvHashMap<Key, usize>,
+}
+
+impl<V> OrderedMap<V> {
+    pub fn new() -> OrderedMap<V> {
+        OrderedMap {
+            pairs:   Vec::new(),
+            indices: FnvHashMap::default(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.indices.get(key).map(|&index| &self.pairs[index].1)
+    }
+
+    /// Replacing the value of an existing key retains the position of that key.
+    pub fn insert(&mut self, key: Key, value: V) {
+        match self.indices.get(&key) {
+            Some(&index) => self.pairs[index].1 = value,
+            None => {
+                self.indices.insert(key.clone(), self.pairs.len());
+                self.pairs.push((key, value));
+            }
+        }
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a Key, &'a V)> + 'a {
+        self.pairs.iter().map(|&(ref key, ref value)| (key, value))
+    }
+
+    pub fn len(&self) -> usize { self.pairs.len() }
+
+    pub fn is_empty(&self) -> bool { self.pairs.is_empty() }
+}
+
+/// A map variable, which is declared as either a `hmap`, which has no defined order, a `bmap`
+/// whose keys are kept in sorted order, or an `omap` which retains the order of insertion.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Map<V = Value> {
+    Hash(HashMap<V>),
+    BTree(BTreeMap<V>),
+    Ordered(OrderedMap<V>),
+}
+
+impl<V> Map<V> {
+    pub fn get(&self, key: &str) -> Option<&V> {
+        match *self {
+            Map::Hash(ref map) => map.get(key),
+            Map::BTree(ref map) => map.get(key),
+            Map::Ordered(ref map) => map.get(key),
+        }
+    }
+
+    pub fn insert(&mut self, key: Key, value: V) {
+        match *self {
+            Map::Hash(ref mut map) => {
+                map.insert(key, value);
+            }
+            Map::BTree(ref mut map) => {
+                map.insert(key, value);
+            }
+            Map::Ordered(ref mut map) => map.insert(key, value),
+        }
+    }
+
+    /// Iterates over the pairs of the map, in the order which is defined by its kind.
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = (&'a Key, &'a V)> + 'a> {
+        match *self {
+            Map::Hash(ref map) => Box::new(map.iter()),
+            Map::BTree(ref map) => Box::new(map.iter()),
+            Map::Ordered(ref map) => Box::new(map.iter()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match *self {
+            Map::Hash(ref map) => map.len(),
+            Map::BTree(ref map) => map.len(),
+            Map::Ordered(ref map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+/// Maps are displayed in the same `[key=value ...]` form in which they are declared.
+impl<V: fmt::Display> fmt::Display for Map<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_pairs(f, self.iter()) }
+}
+
+fn write_pairs<'a, V, I>(f: &mut fmt::Formatter, pairs: I) -> fmt::Result
+where
+    V: fmt::Display + 'a,
+    I: Iterator<Item = (&'a Key, &'a V)>,
+{
+    write!(f, "[")?;
+    for (id, (key, value)) in pairs.enumerate() {
+        let separator = if id == 0 { "" } else { " " };
+        write!(f, "{}{}={}", separator, key, value)?;
+    }
+    write!(f, "]")
+}
+
+/// A reference to a map variable of any kind. Hash maps are stored apart from the maps of
+/// other kinds, so that they remain accessible as a `HashMap`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MapRef<'a> {
+    Hash(&'a HashMap),
+    Other(&'a Map),
+}
+
+impl<'a> MapRef<'a> {
+    pub fn get(&self, key: &str) -> Option<&'a Value> {
+        match *self {
+            MapRef::Hash(map) => map.get(key),
+            MapRef::Other(map) => map.get(key),
+        }
+    }
+
+    /// Iterates over the pairs of the map, in the order which is defined by its kind.
+    pub fn iter(&self) -> Box<Iterator<Item = (&'a Key, &'a Value)> + 'a> {
+        match *self {
+            MapRef::Hash(map) => Box::new(map.iter()),
+            MapRef::Other(map) => map.iter(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match *self {
+            MapRef::Hash(map) => map.len(),
+            MapRef::Other(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn to_map(&self) -> Map {
+        match *self {
+            MapRef::Hash(map) => Map::Hash(map.clone()),
+            MapRef::Other(map) => map.clone(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for MapRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_pairs(f, self.iter()) }
+}
+
+/// A value which may contain other values, such as an array of maps or a map of arrays. Only
+/// variables which are declared with such a type are stored as nested values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Nested {
+    Str(Value),
+    Array(Vec<Nested>),
+    Map(Map<Nested>),
+}
+
+impl Nested {
+    /// The elements of an array, or the values of a map, with each collection that they
+    /// contain being displayed in its literal form.
+    pub fn elements(&self) -> Array {
+        match *self {
+            Nested::Str(ref value) => iter::once(value.clone()).collect(),
+            Nested::Array(ref elements) => elements.iter().map(ToString::to_string).collect(),
+            Nested::Map(ref map) => map.iter().map(|(_, value)| value.to_string()).collect(),
+        }
+    }
+}
+
+/// Nested values are displayed in the same form in which they are declared.
+impl fmt::Display for Nested {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Nested::Str(ref value) => write!(f, "{}", value),
+            Nested::Array(ref elements) => {
+                write!(f, "[")?;
+                for (id, element) in elements.iter().enumerate() {
+                    let separator = if id == 0 { "" } else { " " };
+                    write!(f, "{}{}", separator, element)?;
+                }
+                write!(f, "]")
+            }
+            Nested::Map(ref map) => write!(f, "{}", map),
+        }
+    }
+}
 
 /// Construct a new Array containing the given arguments
 ///
@@ -36,3 +231,30 @@ macro_rules! array [
         _arr
     })
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs<'a, I: Iterator<Item = (&'a Key, &'a Value)>>(iter: I) -> Vec<String> {
+        iter.map(|(key, value)| format!("{}={}", key, value)).collect()
+    }
+
+    #[test]
+    fn reinserted_keys_keep_their_position() {
+        let mut map = OrderedMap::new();
+        map.insert("b".into(), "1".into());
+        map.insert("a".into(), "2".into());
+        map.insert("c".into(), "3".into());
+        map.insert("b".into(), "4".into());
+        assert_eq!(pairs(map.iter()), vec!["b=4", "a=2", "c=3"]);
+        assert_eq!(map.get("b").map(String::as_str), Some("4"));
+        assert_eq!(map.len(), 3);
+
+        let mut map = Map::Ordered(map);
+        map.insert("a".into(), "5".into());
+        map.insert("d".into(), "6".into());
+        assert_eq!(map.to_string(), "[b=4 a=5 c=3 d=6]");
+        assert_eq!(MapRef::Other(&map).to_string(), "[b=4 a=5 c=3 d=6]");
+    }
+}
@@ -27,6 +27,20 @@ fn main() {
                 continue;
             }
             "-c" => shell.execute_arguments(args),
+            "--lint" => {
+                let status = shell.lint_scripts(args);
+                shell.exit(status);
+            }
+            "--fmt" => {
+                let status = shell.format_scripts(args);
+                shell.exit(status);
+            }
+            "--lsp" => {
+                let status = shell.language_server();
+                shell.exit(status);
+            }
+            "--debug" => shell.debug_script(args),
+            "--profile" => shell.profile_script(args),
             "-v" | "--version" => shell.display_version(),
             "-h" | "--help" => {
                 let stdout = stdout();